@@ -0,0 +1,80 @@
+//! Lookups and loading, run with `cargo bench`.
+//!
+//! `retort` against `exact_index` is what the exact index is for, `map_*`
+//! compares the hashers behind it and the dataset on the insults themselves.
+//! They use the built-in benchmark harness, criterion doesn't build with
+//! this compiler.
+
+#![feature(slicing_syntax)]
+
+extern crate test;
+extern crate mi_insults;
+
+use std::collections::HashMap;
+use std::io::File;
+use test::Bencher;
+
+use mi_insults::Insults;
+use mi_insults::index::fast_map;
+
+static INSULT: &'static str = "You fight like a dairy farmer.";
+
+#[bench]
+fn retort(b: &mut Bencher) {
+    let mi = Insults::new("insults.json");
+    b.iter(|| mi.retort(INSULT));
+}
+
+#[bench]
+fn exact_index(b: &mut Bencher) {
+    let mi = Insults::new("insults.json");
+    let index = mi.exact_index();
+    b.iter(|| index.retort(INSULT));
+}
+
+#[bench]
+fn normalized_index(b: &mut Bencher) {
+    let mi = Insults::new("insults.json");
+    let index = mi.normalized_index();
+    b.iter(|| index.retort("  you fight like a DAIRY farmer. "));
+}
+
+#[bench]
+fn fuzzy_index(b: &mut Bencher) {
+    let mi = Insults::new("insults.json");
+    let index = mi.fuzzy_index();
+    b.iter(|| index.retort("you fihgt like a dairy farmer"));
+}
+
+#[bench]
+fn map_sip(b: &mut Bencher) {
+    let mi = Insults::new("insults.json");
+    let mut map = HashMap::new();
+    for insult in mi.insults().into_iter() {
+        map.insert(insult.clone(), ());
+    }
+    let keys: Vec<String> = mi.insults().into_iter().map(|x| x.clone()).collect();
+    b.iter(|| keys.iter().filter(|x| map.contains_key(*x)).count());
+}
+
+#[bench]
+fn map_fnv(b: &mut Bencher) {
+    let mi = Insults::new("insults.json");
+    let mut map = fast_map();
+    for insult in mi.insults().into_iter() {
+        map.insert(insult.clone(), ());
+    }
+    let keys: Vec<String> = mi.insults().into_iter().map(|x| x.clone()).collect();
+    b.iter(|| keys.iter().filter(|x| map.contains_key(*x)).count());
+}
+
+#[bench]
+fn load_path(b: &mut Bencher) {
+    b.iter(|| Insults::try_from_path("insults.json"));
+}
+
+#[bench]
+fn load_str(b: &mut Bencher) {
+    let data = File::open(&Path::new("insults.json")).read_to_string().unwrap();
+    b.iter(|| Insults::try_from_str(data[]));
+}
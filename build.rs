@@ -0,0 +1,114 @@
+//! Compile the built-in dataset into a perfect hash table for the
+//! `static-data` feature.
+
+extern crate serialize;
+
+use std::io::File;
+use std::os;
+use serialize::json;
+
+#[path = "src/static_hash.rs"]
+mod static_hash;
+
+static GAMES: [&'static str, ..5] = ["monkey_island1", "sword_master", "monkey_island3",
+                                     "captain_rottingham", "monkey_island4"];
+
+fn main() {
+    if os::getenv("CARGO_FEATURE_STATIC_DATA").is_none() {
+        return;
+    }
+
+    let data = File::open(&Path::new("insults.json")).read_to_string().unwrap();
+    let data = json::from_str(data[]).unwrap();
+
+    let mut entries = Vec::new();
+    for game in GAMES.iter() {
+        let pairs = match data.find(&game.to_string()) {
+            Some(&json::Object(ref x)) => x,
+            _ => panic!("insults.json: {} is not an object", game),
+        };
+        for (insult, value) in pairs.iter() {
+            // Both the flat and the versioned layout
+            let retort = match *value {
+                json::String(ref x) => x[],
+                ref x => x.find(&"retort".to_string()).and_then(|r| r.as_string())
+                    .expect("insults.json: retort is not a string"),
+            };
+            entries.push((game.to_string(), insult.clone(), retort.to_string()));
+        }
+    }
+    let (displacements, slots) = build(entries[]);
+
+    let mut out = String::new();
+    let failed = match data.find(&"failed_retorts".to_string()) {
+        Some(&json::List(ref x)) => x,
+        _ => panic!("insults.json: failed_retorts is not a list"),
+    };
+    out.push_str(format!("static FAILED_RETORTS: [&'static str, ..{}] = [\n", failed.len())[]);
+    for retort in failed.iter() {
+        let retort = retort.as_string().expect("insults.json: failed retort is not a string");
+        out.push_str(format!("    \"{}\",\n", retort.escape_default())[]);
+    }
+    out.push_str("];\n\n");
+    out.push_str(format!("static DISPLACEMENTS: [u64, ..{}] = [\n", displacements.len())[]);
+    for d in displacements.iter() {
+        out.push_str(format!("    {},\n", d)[]);
+    }
+    out.push_str("];\n\n");
+    out.push_str(format!("static ENTRIES: [(&'static str, &'static str, &'static str), ..{}] = [\n",
+                         slots.len())[]);
+    for &i in slots.iter() {
+        let (ref game, ref insult, ref retort) = entries[i];
+        out.push_str(format!("    (\"{}\", \"{}\", \"{}\"),\n",
+                             game.escape_default(), insult.escape_default(),
+                             retort.escape_default())[]);
+    }
+    out.push_str("];\n");
+
+    let dst = Path::new(os::getenv("OUT_DIR").unwrap()).join("static_data.rs");
+    File::create(&dst).write_str(out[]).unwrap();
+}
+
+/// Find a displacement for every bucket so that all entries end up in
+/// distinct slots, returning the displacements and the entry of every slot.
+fn build(entries: &[(String, String, String)]) -> (Vec<u64>, Vec<uint>) {
+    let n = entries.len();
+    let slot = |seed: u64, i: uint| {
+        let (ref game, ref insult, _) = entries[i];
+        (static_hash::hash(seed, game[], insult[]) % n as u64) as uint
+    };
+
+    let len = (n + 3) / 4;
+    let mut buckets: Vec<Vec<uint>> = Vec::from_fn(len, |_| Vec::new());
+    for (i, &(ref game, ref insult, _)) in entries.iter().enumerate() {
+        buckets[(static_hash::hash(0, game[], insult[]) % len as u64) as uint].push(i);
+    }
+    // The largest buckets are the hardest to place
+    let mut order: Vec<uint> = range(0, len).collect();
+    order.sort_by(|a, b| buckets[*b].len().cmp(&buckets[*a].len()));
+
+    let mut displacements = Vec::from_elem(len, 0u64);
+    let mut slots: Vec<Option<uint>> = Vec::from_elem(n, None);
+    for &b in order.iter() {
+        if buckets[b].is_empty() {
+            continue;
+        }
+        let mut d = 1u64;
+        loop {
+            let taken: Vec<uint> = buckets[b].iter().map(|&i| slot(d, i)).collect();
+            let free = taken.iter().enumerate().all(|(j, &s)| {
+                slots[s].is_none() && !taken[..j].contains(&s)
+            });
+            if free {
+                for (&i, &s) in buckets[b].iter().zip(taken.iter()) {
+                    slots[s] = Some(i);
+                }
+                displacements[b] = d;
+                break;
+            }
+            d += 1;
+        }
+    }
+
+    (displacements, slots.into_iter().map(|x| x.unwrap()).collect())
+}
@@ -0,0 +1,65 @@
+/* C interface of mi_insults, built with the ffi feature. */
+
+#ifndef MI_INSULTS_H
+#define MI_INSULTS_H
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* A loaded dataset. */
+typedef struct MiInsults MiInsults;
+
+/* Load the dataset at path, NULL if it can't be loaded. */
+MiInsults *mi_insults_new(const char *path);
+
+/* Load the dataset from a JSON string, NULL if it's invalid. */
+MiInsults *mi_insults_from_json(const char *json);
+
+void mi_insults_free(MiInsults *mi);
+
+/* The retort to insult, NULL if there is none. Valid until mi is freed. */
+const char *mi_insults_retort(const MiInsults *mi, const char *insult);
+
+/* 1 if retort is the retort to insult, otherwise 0. */
+int mi_insults_is_retort(const MiInsults *mi, const char *insult, const char *retort);
+
+/* A random insult, NULL if there are none. */
+const char *mi_insults_rand_insult(const MiInsults *mi);
+
+/* A random failed retort, NULL if there are none. */
+const char *mi_insults_rand_failed_retort(const MiInsults *mi);
+
+/* A duel played on a dataset. */
+typedef struct MiDuel MiDuel;
+
+/* Start a duel won by the first to first_to points. Free it before mi. */
+MiDuel *mi_duel_new(const MiInsults *mi, unsigned int first_to);
+
+void mi_duel_free(MiDuel *duel);
+
+/*
+ * Results of an exchange: 0 and 1 are points to the player and the opponent,
+ * 2 and 3 their serve, 4 a retry with a hint. -1 if it couldn't be played.
+ */
+int mi_duel_exchange(MiDuel *duel, const char *insult, const char *retort);
+int mi_duel_retry(MiDuel *duel, const char *retort);
+int mi_duel_give_up(MiDuel *duel);
+
+/* The hint of the last retry, NULL if there is none. */
+const char *mi_duel_hint(const MiDuel *duel);
+
+/* Points of the player (0) or the opponent (1). */
+unsigned int mi_duel_score(const MiDuel *duel, int side);
+
+/* Who throws the next insult, 0 for the player and 1 for the opponent. */
+int mi_duel_attacker(const MiDuel *duel);
+
+/* 0 for the player, 1 for the opponent and -1 if undecided. */
+int mi_duel_winner(const MiDuel *duel);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
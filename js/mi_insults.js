@@ -0,0 +1,68 @@
+// JavaScript wrapper of the wasm feature, see src/wasm.rs.
+//
+// Expects the Emscripten compiled crate as `Module`.
+
+function JsInsults(json) {
+    this.ptr = Module.ccall('js_insults_from_json', 'number', ['string'], [json]);
+    if (this.ptr === 0) {
+        throw new Error('invalid insults');
+    }
+}
+
+// A string result, null for a null pointer.
+JsInsults.prototype.string = function(ptr) {
+    return ptr === 0 ? null : Module.Pointer_stringify(ptr);
+};
+
+JsInsults.prototype.free = function() {
+    Module.ccall('js_insults_free', null, ['number'], [this.ptr]);
+    this.ptr = 0;
+};
+
+JsInsults.prototype.retort = function(insult) {
+    return this.string(Module.ccall('js_insults_retort', 'number',
+                                    ['number', 'string'], [this.ptr, insult]));
+};
+
+JsInsults.prototype.randInsult = function() {
+    return this.string(Module.ccall('js_insults_rand_insult', 'number', ['number'], [this.ptr]));
+};
+
+JsInsults.prototype.startDuel = function(firstTo) {
+    Module.ccall('js_insults_start_duel', null, ['number', 'number'], [this.ptr, firstTo]);
+};
+
+// The outcome of an exchange, like {point: "player"}, {serve: "opponent"} or {hint: "How ..."}.
+JsInsults.prototype.outcome = function(code) {
+    var sides = ['player', 'opponent'];
+    if (code < 0) {
+        throw new Error('no exchange to play');
+    }
+    if (code < 2) {
+        return {point: sides[code]};
+    }
+    if (code < 4) {
+        return {serve: sides[code - 2]};
+    }
+    return {hint: this.string(Module.ccall('js_insults_result', 'number', ['number'], [this.ptr]))};
+};
+
+JsInsults.prototype.exchange = function(insult, retort) {
+    return this.outcome(Module.ccall('js_insults_exchange', 'number',
+                                     ['number', 'string', 'string'], [this.ptr, insult, retort]));
+};
+
+JsInsults.prototype.retry = function(retort) {
+    return this.outcome(Module.ccall('js_insults_retry', 'number',
+                                     ['number', 'string'], [this.ptr, retort]));
+};
+
+JsInsults.prototype.score = function(side) {
+    return Module.ccall('js_insults_score', 'number', ['number', 'number'],
+                        [this.ptr, side === 'player' ? 0 : 1]);
+};
+
+JsInsults.prototype.winner = function() {
+    var code = Module.ccall('js_insults_winner', 'number', ['number'], [this.ptr]);
+    return code < 0 ? null : ['player', 'opponent'][code];
+};
@@ -0,0 +1,27 @@
+// Type declarations for the mi-insults Node.js bindings.
+
+export declare const PLAYER: number;
+export declare const OPPONENT: number;
+
+export type Exchange = { point: number } | { serve: number } | { hint: string };
+
+export declare class Insults {
+    constructor(path?: string);
+    static fromJson(json: string): Insults;
+    free(): void;
+    retort(insult: string): string | null;
+    isRetort(insult: string, retort: string): boolean;
+    randInsult(): string | null;
+    randFailedRetort(): string | null;
+    duel(firstTo?: number): Duel;
+}
+
+export declare class Duel {
+    exchange(insult: string, retort: string): Exchange;
+    retry(retort: string): Exchange;
+    giveUp(): Exchange;
+    score(side: number): number;
+    attacker(): number;
+    winner(): number | null;
+    free(): void;
+}
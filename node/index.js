@@ -0,0 +1,135 @@
+// Node.js bindings for mi_insults.
+//
+// Build the shared library with the node feature and point MI_INSULTS_LIB
+// to it if it isn't on the library path:
+//
+//     var Insults = require('mi-insults').Insults;
+//     var mi = new Insults('insults.json');
+//     mi.retort('You fight like a dairy farmer.');
+//     var duel = mi.duel(3);
+//     duel.exchange(mi.randInsult(), 'How appropriate. You fight like a cow.');
+//     duel.free();
+//     mi.free();
+
+var ffi = require('ffi');
+var ref = require('ref');
+
+var ptr = ref.refType(ref.types.void);
+var str = 'string';
+
+var lib = ffi.Library(process.env.MI_INSULTS_LIB || 'libmi_insults', {
+    mi_insults_new: [ptr, [str]],
+    mi_insults_from_json: [ptr, [str]],
+    mi_insults_free: ['void', [ptr]],
+    mi_insults_retort: [str, [ptr, str]],
+    mi_insults_is_retort: ['int', [ptr, str, str]],
+    mi_insults_rand_insult: [str, [ptr]],
+    mi_insults_rand_failed_retort: [str, [ptr]],
+    mi_duel_new: [ptr, [ptr, 'uint']],
+    mi_duel_free: ['void', [ptr]],
+    mi_duel_exchange: ['int', [ptr, str, str]],
+    mi_duel_retry: ['int', [ptr, str]],
+    mi_duel_give_up: ['int', [ptr]],
+    mi_duel_hint: [str, [ptr]],
+    mi_duel_score: ['uint', [ptr, 'int']],
+    mi_duel_attacker: ['int', [ptr]],
+    mi_duel_winner: ['int', [ptr]]
+});
+
+var PLAYER = 0;
+var OPPONENT = 1;
+
+// A dataset loaded from a file, or from a JSON string with fromJson.
+function Insults(path, handle) {
+    this.handle = handle || lib.mi_insults_new(path || 'insults.json');
+    if (this.handle.isNull()) {
+        throw new Error("can't load " + path);
+    }
+}
+
+Insults.fromJson = function (json) {
+    var handle = lib.mi_insults_from_json(json);
+    if (handle.isNull()) {
+        throw new Error('invalid insults JSON');
+    }
+    return new Insults(null, handle);
+};
+
+Insults.prototype.free = function () {
+    lib.mi_insults_free(this.handle);
+    this.handle = ref.NULL;
+};
+
+// The retort to insult, null if there is none.
+Insults.prototype.retort = function (insult) {
+    return lib.mi_insults_retort(this.handle, insult);
+};
+
+Insults.prototype.isRetort = function (insult, retort) {
+    return lib.mi_insults_is_retort(this.handle, insult, retort) === 1;
+};
+
+Insults.prototype.randInsult = function () {
+    return lib.mi_insults_rand_insult(this.handle);
+};
+
+Insults.prototype.randFailedRetort = function () {
+    return lib.mi_insults_rand_failed_retort(this.handle);
+};
+
+// Start a duel won by the first to firstTo points, free it before the dataset.
+Insults.prototype.duel = function (firstTo) {
+    return new Duel(lib.mi_duel_new(this.handle, firstTo === undefined ? 3 : firstTo));
+};
+
+function Duel(handle) {
+    this.handle = handle;
+}
+
+// Results are {point: side}, {serve: side} or {hint: retort} to retry.
+Duel.prototype.result = function (code) {
+    switch (code) {
+    case 0: case 1: return {point: code};
+    case 2: case 3: return {serve: code - 2};
+    case 4: return {hint: lib.mi_duel_hint(this.handle)};
+    default: throw new Error("exchange can't be played");
+    }
+};
+
+Duel.prototype.exchange = function (insult, retort) {
+    return this.result(lib.mi_duel_exchange(this.handle, insult, retort));
+};
+
+Duel.prototype.retry = function (retort) {
+    return this.result(lib.mi_duel_retry(this.handle, retort));
+};
+
+Duel.prototype.giveUp = function () {
+    return this.result(lib.mi_duel_give_up(this.handle));
+};
+
+Duel.prototype.score = function (side) {
+    return lib.mi_duel_score(this.handle, side);
+};
+
+Duel.prototype.attacker = function () {
+    return lib.mi_duel_attacker(this.handle);
+};
+
+// PLAYER, OPPONENT or null if undecided.
+Duel.prototype.winner = function () {
+    var winner = lib.mi_duel_winner(this.handle);
+    return winner < 0 ? null : winner;
+};
+
+Duel.prototype.free = function () {
+    lib.mi_duel_free(this.handle);
+    this.handle = ref.NULL;
+};
+
+module.exports = {
+    Insults: Insults,
+    Duel: Duel,
+    PLAYER: PLAYER,
+    OPPONENT: OPPONENT
+};
@@ -0,0 +1,162 @@
+"""Python bindings for mi_insults.
+
+Build the shared library with the python feature and point MI_INSULTS_LIB
+to it if it isn't next to this file or on the library path:
+
+    from mi_insults import Insults
+
+    mi = Insults("insults.json")
+    mi.retort("You fight like a dairy farmer.")
+    duel = mi.duel(3)
+    duel.exchange(mi.rand_insult(), "How appropriate. You fight like a cow.")
+"""
+
+import ctypes
+import ctypes.util
+import os
+
+PLAYER, OPPONENT = 0, 1
+
+
+def _load():
+    path = os.environ.get("MI_INSULTS_LIB")
+    if path is None:
+        here = os.path.dirname(os.path.abspath(__file__))
+        for name in ("libmi_insults.so", "libmi_insults.dylib", "mi_insults.dll"):
+            if os.path.exists(os.path.join(here, name)):
+                path = os.path.join(here, name)
+                break
+    if path is None:
+        path = ctypes.util.find_library("mi_insults")
+    if path is None:
+        raise ImportError("libmi_insults not found, set MI_INSULTS_LIB")
+
+    lib = ctypes.CDLL(path)
+    p, s, i, u = ctypes.c_void_p, ctypes.c_char_p, ctypes.c_int, ctypes.c_uint
+    for name, res, args in [
+        ("mi_insults_new", p, [s]),
+        ("mi_insults_from_json", p, [s]),
+        ("mi_insults_free", None, [p]),
+        ("mi_insults_retort", s, [p, s]),
+        ("mi_insults_is_retort", i, [p, s, s]),
+        ("mi_insults_rand_insult", s, [p]),
+        ("mi_insults_rand_failed_retort", s, [p]),
+        ("mi_duel_new", p, [p, u]),
+        ("mi_duel_free", None, [p]),
+        ("mi_duel_exchange", i, [p, s, s]),
+        ("mi_duel_retry", i, [p, s]),
+        ("mi_duel_give_up", i, [p]),
+        ("mi_duel_hint", s, [p]),
+        ("mi_duel_score", u, [p, i]),
+        ("mi_duel_attacker", i, [p]),
+        ("mi_duel_winner", i, [p]),
+    ]:
+        f = getattr(lib, name)
+        f.restype = res
+        f.argtypes = args
+    return lib
+
+
+_lib = _load()
+
+
+def _encode(s):
+    return s.encode("utf-8")
+
+
+def _decode(s):
+    return None if s is None else s.decode("utf-8")
+
+
+class Insults(object):
+    """A dataset loaded from a file, or from a JSON string with `from_json`."""
+
+    def __init__(self, path="insults.json", _ptr=None):
+        self._ptr = _ptr or _lib.mi_insults_new(_encode(path))
+        if not self._ptr:
+            raise ValueError("can't load %s" % path)
+
+    @classmethod
+    def from_json(cls, json):
+        ptr = _lib.mi_insults_from_json(_encode(json))
+        if not ptr:
+            raise ValueError("invalid insults JSON")
+        return cls(_ptr=ptr)
+
+    def __del__(self):
+        if getattr(self, "_ptr", None):
+            _lib.mi_insults_free(self._ptr)
+            self._ptr = None
+
+    def retort(self, insult):
+        """The retort to insult, None if there is none."""
+        return _decode(_lib.mi_insults_retort(self._ptr, _encode(insult)))
+
+    def is_retort(self, insult, retort):
+        return _lib.mi_insults_is_retort(self._ptr, _encode(insult), _encode(retort)) == 1
+
+    def rand_insult(self):
+        return _decode(_lib.mi_insults_rand_insult(self._ptr))
+
+    def rand_failed_retort(self):
+        return _decode(_lib.mi_insults_rand_failed_retort(self._ptr))
+
+    def duel(self, first_to=3):
+        """Start a duel won by the first to first_to points."""
+        return Duel(self, first_to)
+
+
+class Exchange(object):
+    """The result of an exchange, like the Rust `Exchange`."""
+
+    def __init__(self, code, hint):
+        if code < 0:
+            raise ValueError("exchange can't be played")
+        self.point = {0: PLAYER, 1: OPPONENT}.get(code)
+        self.serve = {2: PLAYER, 3: OPPONENT}.get(code)
+        self.hint = hint if code == 4 else None
+
+    def __repr__(self):
+        if self.point is not None:
+            return "Exchange(point=%d)" % self.point
+        if self.serve is not None:
+            return "Exchange(serve=%d)" % self.serve
+        return "Exchange(hint=%r)" % self.hint
+
+
+class Duel(object):
+    """A duel, keeping its dataset alive."""
+
+    def __init__(self, insults, first_to):
+        self._insults = insults
+        self._ptr = _lib.mi_duel_new(insults._ptr, first_to)
+
+    def __del__(self):
+        if getattr(self, "_ptr", None):
+            _lib.mi_duel_free(self._ptr)
+            self._ptr = None
+
+    def _result(self, code):
+        return Exchange(code, _decode(_lib.mi_duel_hint(self._ptr)))
+
+    def exchange(self, insult, retort):
+        return self._result(_lib.mi_duel_exchange(self._ptr, _encode(insult), _encode(retort)))
+
+    def retry(self, retort):
+        return self._result(_lib.mi_duel_retry(self._ptr, _encode(retort)))
+
+    def give_up(self):
+        return self._result(_lib.mi_duel_give_up(self._ptr))
+
+    def score(self, side):
+        return _lib.mi_duel_score(self._ptr, side)
+
+    @property
+    def attacker(self):
+        return _lib.mi_duel_attacker(self._ptr)
+
+    @property
+    def winner(self):
+        """PLAYER, OPPONENT or None if undecided."""
+        winner = _lib.mi_duel_winner(self._ptr)
+        return None if winner < 0 else winner
@@ -0,0 +1,260 @@
+//! Achievements unlocked by playing duels.
+//!
+//! A `Tracker` keeps a `Record` of what the player has done from the events
+//! of their duels, and unlocks the achievements of its `Registry` whose
+//! condition the record meets. The record and the unlocked achievements
+//! encode to JSON, to be saved with the rest of the player's progress.
+
+use std::collections::{TreeMap, TreeSet};
+
+use duel::{DuelEvent, InsultThrown, RetortSucceeded, RetortFailed, GaveUp, DuelWon, Player, Opponent};
+use game::{Game, MonkeyIsland1, SwordMaster, MonkeyIsland3};
+use insults::Insults;
+
+/// What the player has done, for judging achievements.
+#[deriving(Clone, PartialEq, Show, Encodable, Decodable)]
+pub struct Record {
+    pub duels_won: uint,
+    /// Duels won by game key of the opponent, like `sword_master`.
+    pub wins_against: TreeMap<String, uint>,
+    /// Insults answered correctly in a row.
+    pub streak: uint,
+    pub best_streak: uint,
+    /// Insults thrown at the player.
+    pub heard: TreeSet<String>,
+}
+
+impl Record {
+    pub fn new() -> Record {
+        Record {
+            duels_won: 0,
+            wins_against: TreeMap::new(),
+            streak: 0,
+            best_streak: 0,
+            heard: TreeSet::new(),
+        }
+    }
+
+    pub fn wins_against(&self, game: Game) -> uint {
+        self.wins_against.get(&game.key().to_string()).map(|x| *x).unwrap_or(0)
+    }
+
+    /// Whether every insult of `game` has been heard.
+    pub fn heard_all(&self, insults: &Insults, game: Game) -> bool {
+        let all = insults.entries(game);
+        !all.is_empty() && all.iter().all(|&(insult, _)| self.heard.contains(insult))
+    }
+}
+
+/// A milestone to unlock.
+pub struct Achievement {
+    /// Stable id, used in the saved state.
+    pub id: String,
+    pub name: String,
+    pub description: String,
+    /// Whether a record unlocks the achievement.
+    pub condition: fn(&Record, &Insults) -> bool,
+}
+
+impl Achievement {
+    pub fn new(id: &str, name: &str, description: &str,
+               condition: fn(&Record, &Insults) -> bool) -> Achievement {
+        Achievement {
+            id: id.to_string(),
+            name: name.to_string(),
+            description: description.to_string(),
+            condition: condition,
+        }
+    }
+}
+
+fn won_duel(record: &Record, _: &Insults) -> bool {
+    record.duels_won >= 1
+}
+
+fn beat_sword_master(record: &Record, _: &Insults) -> bool {
+    record.wins_against(SwordMaster) >= 1
+}
+
+fn fifty_in_a_row(record: &Record, _: &Insults) -> bool {
+    record.best_streak >= 50
+}
+
+fn heard_mi1(record: &Record, insults: &Insults) -> bool {
+    record.heard_all(insults, MonkeyIsland1)
+}
+
+fn heard_mi3(record: &Record, insults: &Insults) -> bool {
+    record.heard_all(insults, MonkeyIsland3)
+}
+
+/// The achievements a tracker can unlock.
+pub struct Registry {
+    achievements: Vec<Achievement>,
+}
+
+impl Registry {
+    /// A registry without achievements.
+    pub fn new() -> Registry {
+        Registry { achievements: Vec::new() }
+    }
+
+    /// The achievements built into the crate.
+    pub fn builtin() -> Registry {
+        let mut res = Registry::new();
+        res.register(Achievement::new("first_blood", "First blood", "Won a duel", won_duel));
+        res.register(Achievement::new("sword_master", "Master of the sword",
+                                      "Defeated the Sword Master", beat_sword_master));
+        res.register(Achievement::new("unflappable", "Unflappable",
+                                      "Answered 50 insults without a fail", fifty_in_a_row));
+        res.register(Achievement::new("heard_mi1", "Seasoned pirate",
+                                      "Heard every insult of The Secret of Monkey Island", heard_mi1));
+        res.register(Achievement::new("heard_mi3", "Cursed ears",
+                                      "Heard every insult of The Curse of Monkey Island", heard_mi3));
+        res
+    }
+
+    /// Add an achievement, replacing any with the same id.
+    pub fn register(&mut self, achievement: Achievement) {
+        self.achievements.retain(|x| x.id != achievement.id);
+        self.achievements.push(achievement);
+    }
+
+    pub fn get<'a>(&'a self, id: &str) -> Option<&'a Achievement> {
+        self.achievements.iter().find(|x| x.id[] == id)
+    }
+
+    pub fn achievements<'a>(&'a self) -> &'a [Achievement] {
+        self.achievements[]
+    }
+}
+
+/// Everything a tracker saves.
+#[deriving(Clone, PartialEq, Show, Encodable, Decodable)]
+pub struct Unlocks {
+    pub record: Record,
+    /// Ids of the unlocked achievements.
+    pub unlocked: TreeSet<String>,
+}
+
+impl Unlocks {
+    pub fn new() -> Unlocks {
+        Unlocks { record: Record::new(), unlocked: TreeSet::new() }
+    }
+}
+
+/// Follows the duels of a player and unlocks achievements.
+pub struct Tracker<'a> {
+    insults: &'a Insults,
+    registry: Registry,
+    unlocks: Unlocks,
+    /// The game of the current opponent.
+    opponent: Option<Game>,
+}
+
+impl<'a> Tracker<'a> {
+    pub fn new(insults: &'a Insults, registry: Registry) -> Tracker<'a> {
+        Tracker { insults: insults, registry: registry, unlocks: Unlocks::new(), opponent: None }
+    }
+
+    /// Continue from saved unlocks.
+    pub fn with_unlocks(mut self, unlocks: Unlocks) -> Tracker<'a> {
+        self.unlocks = unlocks;
+        self
+    }
+
+    pub fn unlocks(&self) -> &Unlocks {
+        &self.unlocks
+    }
+
+    /// Set the game of the opponent of the next duels, like `SwordMaster`.
+    pub fn against(&mut self, opponent: Option<Game>) {
+        self.opponent = opponent;
+    }
+
+    /// The unlocked achievements, in registry order.
+    pub fn unlocked<'b>(&'b self) -> Vec<&'b Achievement> {
+        self.registry.achievements.iter().filter(|x| self.unlocks.unlocked.contains(&x.id)).collect()
+    }
+
+    /// Record an event of a duel of the player, returning the achievements it unlocked.
+    pub fn on_event<'b>(&'b mut self, event: &DuelEvent) -> Vec<&'b Achievement> {
+        {
+            let record = &mut self.unlocks.record;
+            match *event {
+                InsultThrown(Opponent, ref insult) => {
+                    record.heard.insert(insult.clone());
+                },
+                RetortSucceeded(Player, _) => {
+                    record.streak += 1;
+                    record.best_streak = record.best_streak.max(record.streak);
+                },
+                RetortFailed(Player, _) | GaveUp(Player) => record.streak = 0,
+                DuelWon(Player) => {
+                    record.duels_won += 1;
+                    if let Some(game) = self.opponent {
+                        let n = record.wins_against(game);
+                        record.wins_against.insert(game.key().to_string(), n + 1);
+                    }
+                },
+                _ => (),
+            }
+        }
+
+        let mut res = Vec::new();
+        for achievement in self.registry.achievements.iter() {
+            if !self.unlocks.unlocked.contains(&achievement.id) &&
+                    (achievement.condition)(&self.unlocks.record, self.insults) {
+                self.unlocks.unlocked.insert(achievement.id.clone());
+                res.push(achievement);
+            }
+        }
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use duel::{InsultThrown, RetortSucceeded, RetortFailed, DuelWon, Player, Opponent};
+    use game::{SwordMaster, MonkeyIsland3};
+    use insults::Insults;
+    use super::*;
+
+    fn ten_wins(record: &Record, _: &Insults) -> bool {
+        record.duels_won >= 10
+    }
+
+    fn ids(achievements: Vec<&Achievement>) -> Vec<String> {
+        achievements.iter().map(|x| x.id.clone()).collect()
+    }
+
+    #[test]
+    fn test_tracker() {
+        let mi = Insults::new("insults.json");
+        let mut registry = Registry::builtin();
+        registry.register(Achievement::new("veteran", "Veteran", "Won 10 duels", ten_wins));
+        let mut tracker = Tracker::new(&mi, registry);
+
+        for _ in range(0u, 49) {
+            assert!(tracker.on_event(&RetortSucceeded(Player, "Arr".to_string())).is_empty());
+        }
+        tracker.on_event(&RetortFailed(Player, "Arr".to_string()));
+        for _ in range(0u, 49) {
+            tracker.on_event(&RetortSucceeded(Player, "Arr".to_string()));
+        }
+        assert_eq!(ids(tracker.on_event(&RetortSucceeded(Player, "Arr".to_string()))), vec!["unflappable".to_string()]);
+
+        tracker.against(Some(SwordMaster));
+        assert_eq!(ids(tracker.on_event(&DuelWon(Player))),
+                   vec!["first_blood".to_string(), "sword_master".to_string()]);
+        tracker.on_event(&DuelWon(Opponent));
+
+        let mut last = Vec::new();
+        for &(insult, _) in mi.entries(MonkeyIsland3).iter() {
+            last = ids(tracker.on_event(&InsultThrown(Opponent, insult.clone())));
+        }
+        assert_eq!(last, vec!["heard_mi3".to_string()]);
+        assert_eq!(tracker.unlocks().record.wins_against(SwordMaster), 1);
+        assert_eq!(tracker.unlocked().len(), 4);
+    }
+}
@@ -0,0 +1,141 @@
+//! Discord bots, independent of the Discord library.
+//!
+//! Convert the library's message events into a `Message`, pass it to
+//! `DiscordBot::handle` and send the reply to the channel. The commands are
+//! those of `BotSession`.
+
+use bots::{Answer, BotSession, Incoming, Reply, Comeback, Started, YourServe, Thrown, Answered,
+           Scored, NotAnInsult, Over, NoDuel, RandomInsult, Usage, Top, Rank,
+           format_top, format_rank};
+use duel::{Score, Side, Player, Opponent, Point, Serve, Retry};
+use insults::Insults;
+
+/// A message received in a channel.
+#[deriving(Clone, PartialEq, Show)]
+pub struct Message {
+    pub channel_id: u64,
+    pub author_id: u64,
+    /// Whether the author is a bot, bots are never answered.
+    pub author_is_bot: bool,
+    pub content: String,
+}
+
+impl Message {
+    pub fn incoming(&self) -> Incoming {
+        Incoming {
+            channel: self.channel_id.to_string(),
+            user: self.author_id.to_string(),
+            text: self.content.clone(),
+        }
+    }
+}
+
+/// Answers messages and runs a duel per channel.
+pub struct DiscordBot<'a> {
+    session: BotSession<'a>,
+}
+
+impl<'a> DiscordBot<'a> {
+    pub fn new(insults: &'a Insults) -> DiscordBot<'a> {
+        DiscordBot::with_session(BotSession::new(insults))
+    }
+
+    pub fn with_session(session: BotSession<'a>) -> DiscordBot<'a> {
+        DiscordBot { session: session }
+    }
+
+    pub fn session(&mut self) -> &mut BotSession<'a> {
+        &mut self.session
+    }
+
+    /// The reply to `msg`, if any.
+    pub fn handle(&mut self, msg: &Message) -> Option<String> {
+        if msg.author_is_bot {
+            return None;
+        }
+        self.session.handle(&msg.incoming()).map(|x| format_reply(&x))
+    }
+}
+
+/// A reply as a Discord message, mentioning the user it's for.
+pub fn format_reply(reply: &Reply) -> String {
+    let lines: Vec<String> = reply.answers.iter().map(|x| format_answer(x)).collect();
+    if reply.is_personal() {
+        format!("<@{}> {}", reply.user, lines.connect("\n"))
+    } else {
+        lines.connect("\n")
+    }
+}
+
+/// Escape Discord markdown in `text`.
+pub fn escape(text: &str) -> String {
+    let mut res = String::with_capacity(text.len());
+    for c in text.chars() {
+        if "\\*_~`|>".contains_char(c) {
+            res.push('\\');
+        }
+        res.push(c);
+    }
+    res
+}
+
+fn score(score: &Score) -> String {
+    format!("**{}** - **{}**", score.player, score.opponent)
+}
+
+fn side(side: Side) -> &'static str {
+    match side {
+        Player => "you",
+        Opponent => "me",
+    }
+}
+
+/// An answer as a line of Discord markdown.
+pub fn format_answer(answer: &Answer) -> String {
+    match *answer {
+        Comeback(retort) => format!("**{}**", escape(retort)),
+        Started(n) => format!("En garde! First to {} points wins.", n),
+        YourServe => "Your serve, throw an insult.".to_string(),
+        Thrown(insult) => format!("*{}*", escape(insult)),
+        Answered(retort) => format!("**{}**", escape(retort)),
+        Scored(Point(x), ref s) => format!("Point to {}, {}", side(x), score(s)),
+        Scored(Serve(x), ref s) => format!("The serve goes to {}, {}", side(x), score(s)),
+        Scored(Retry(ref hint), _) => format!("Not quite, try again: ||{}||", escape(hint[])),
+        NotAnInsult => "That's no insult I know, try another.".to_string(),
+        Over(Player, ref s) => format!("You win the duel, {}!", score(s)),
+        Over(Opponent, ref s) => format!("I win the duel, {}!", score(s)),
+        NoDuel => "There's no duel to give up.".to_string(),
+        RandomInsult(insult) => format!("*{}*", escape(insult)),
+        Usage(ref syntax) => format!("Usage: `{}`", syntax),
+        Top(ref top) => format_top(top[], |name| format!("**{}**", escape(name))),
+        Rank(ref rank) => format_rank(rank),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use insults::Insults;
+    use super::*;
+
+    #[test]
+    fn test_handle() {
+        let mi = Insults::new("insults.json");
+        let mut bot = DiscordBot::new(&mi);
+        let msg = |content: &str| Message {
+            channel_id: 1,
+            author_id: 42,
+            author_is_bot: false,
+            content: content.to_string(),
+        };
+
+        assert_eq!(bot.handle(&msg("You fight like a dairy farmer.")),
+                   Some("**How appropriate. You fight like a cow.**".to_string()));
+        assert_eq!(bot.handle(&msg("Ahoy!")), None);
+        assert_eq!(bot.handle(&Message { author_is_bot: true, ..msg("!duel") }), None);
+        assert_eq!(bot.handle(&msg("!duel 2")),
+                   Some("<@42> En garde! First to 2 points wins.\nYour serve, throw an insult.".to_string()));
+        assert_eq!(bot.handle(&msg("!giveup")),
+                   Some("<@42> I win the duel, **0** - **0**!".to_string()));
+        assert_eq!(escape("*_wow_*"), "\\*\\_wow\\_\\*".to_string());
+    }
+}
@@ -0,0 +1,238 @@
+//! IRC bots, speaking the protocol over any stream.
+//!
+//! `IrcFrontend` reads lines from the server, answering pings itself, and
+//! sends replies as `PRIVMSG` lines wrapped to fit the line length limit.
+//! Give the `BotSession` a cooldown to keep busy channels readable:
+//!
+//! ```no_run
+//! use std::io::{BufferedReader, TcpStream};
+//! use std::rand;
+//! use std::time::Duration;
+//! use mi_insults::Insults;
+//! use mi_insults::bots::{mod, BotSession};
+//! use mi_insults::bots::irc::IrcFrontend;
+//!
+//! let mi = Insults::new("insults.json");
+//! let stream = TcpStream::connect("irc.libera.chat", 6667).unwrap();
+//! let mut irc = IrcFrontend::new(BufferedReader::new(stream.clone()), stream, "murray");
+//! irc.login(&["#scumm"]).unwrap();
+//! let mut session = BotSession::new(&mi).with_cooldown(Duration::seconds(10));
+//! bots::run(&mut irc, &mut session, &mut rand::task_rng()).unwrap();
+//! ```
+
+use std::io::{IoResult, EndOfFile};
+
+use bots::{Answer, ChatFrontend, Incoming, Reply, Comeback, Started, YourServe, Thrown, Answered,
+           Scored, NotAnInsult, Over, NoDuel, RandomInsult, Usage, Top, Rank,
+           format_top, format_rank};
+use duel::{Score, Side, Player, Opponent, Point, Serve, Retry};
+
+/// Longest line servers accept, with the trailing CRLF.
+pub const MAX_LINE: uint = 512;
+/// Room left for the `:nick!user@host ` prefix the server adds when relaying.
+const RELAY_PREFIX: uint = 100;
+
+const BOLD: &'static str = "\x02";
+const ITALIC: &'static str = "\x1d";
+
+/// A `PRIVMSG` received.
+#[deriving(Clone, PartialEq, Show)]
+pub struct Privmsg {
+    pub nick: String,
+    /// The channel, or our nick for private messages.
+    pub target: String,
+    pub text: String,
+}
+
+impl Privmsg {
+    /// Parse a raw `:nick!user@host PRIVMSG target :text` line.
+    pub fn parse(line: &str) -> Option<Privmsg> {
+        let line = line.trim_right_chars(['\r', '\n'][]);
+        if !line.starts_with(":") {
+            return None;
+        }
+        let (prefix, rest) = match line.find(' ') {
+            Some(i) => (line[1..i], line[i + 1..]),
+            None => return None,
+        };
+        if !rest.starts_with("PRIVMSG ") {
+            return None;
+        }
+        let rest = rest["PRIVMSG ".len()..];
+        let (target, text) = match rest.find_str(" :") {
+            Some(i) => (rest[..i], rest[i + 2..]),
+            None => return None,
+        };
+        let nick = prefix.split('!').next().unwrap_or(prefix);
+        Some(Privmsg { nick: nick.to_string(), target: target.to_string(), text: text.to_string() })
+    }
+
+    /// Where to reply, the channel or the sender of a private message.
+    pub fn reply_to<'a>(&'a self) -> &'a str {
+        if self.target[].starts_with("#") || self.target[].starts_with("&") {
+            self.target[]
+        } else {
+            self.nick[]
+        }
+    }
+}
+
+/// A connection to an IRC server as `nick`.
+pub struct IrcFrontend<R, W> {
+    reader: R,
+    writer: W,
+    nick: String,
+}
+
+impl<R: Buffer, W: Writer> IrcFrontend<R, W> {
+    pub fn new(reader: R, writer: W, nick: &str) -> IrcFrontend<R, W> {
+        IrcFrontend { reader: reader, writer: writer, nick: nick.to_string() }
+    }
+
+    /// Register the nick and join `channels`.
+    pub fn login(&mut self, channels: &[&str]) -> IoResult<()> {
+        try!(write!(self.writer, "NICK {}\r\nUSER {} 0 * :{}\r\n", self.nick, self.nick, self.nick));
+        for channel in channels.iter() {
+            try!(write!(self.writer, "JOIN {}\r\n", channel));
+        }
+        self.writer.flush()
+    }
+}
+
+impl<R: Buffer, W: Writer> ChatFrontend for IrcFrontend<R, W> {
+    fn receive(&mut self) -> IoResult<Option<Incoming>> {
+        loop {
+            let line = match self.reader.read_line() {
+                Ok(x) => x,
+                Err(ref e) if e.kind == EndOfFile => return Ok(None),
+                Err(e) => return Err(e),
+            };
+            if line[].starts_with("PING ") {
+                try!(write!(self.writer, "PONG {}\r\n", line[5..].trim_right()));
+                try!(self.writer.flush());
+            } else if let Some(msg) = Privmsg::parse(line[]) {
+                let channel = msg.reply_to().to_string();
+                return Ok(Some(Incoming { channel: channel, user: msg.nick, text: msg.text }));
+            }
+        }
+    }
+
+    fn send(&mut self, reply: &Reply) -> IoResult<()> {
+        for line in format_reply(reply).iter() {
+            try!(write!(self.writer, "{}\r\n", line));
+        }
+        self.writer.flush()
+    }
+
+    fn own_user(&self) -> &str {
+        self.nick[]
+    }
+}
+
+/// A reply as `PRIVMSG` lines, without CRLF, wrapped to fit.
+pub fn format_reply(reply: &Reply) -> Vec<String> {
+    let lines: Vec<String> = reply.answers.iter().map(|x| format_answer(x)).collect();
+    let text = if reply.is_personal() {
+        format!("{}: {}", reply.user, lines.connect(" "))
+    } else {
+        lines.connect(" ")
+    };
+    let target = reply.channel[];
+    let width = MAX_LINE - RELAY_PREFIX - "PRIVMSG  :\r\n".len() - target.len();
+    wrap(text[], width).into_iter().map(|x| format!("PRIVMSG {} :{}", target, x)).collect()
+}
+
+/// Split `text` into lines of at most `width` bytes, breaking between words when possible.
+pub fn wrap(text: &str, width: uint) -> Vec<String> {
+    let mut res = Vec::new();
+    let mut line = String::new();
+    for word in text.split(' ').filter(|x| !x.is_empty()) {
+        if !line.is_empty() && line.len() + 1 + word.len() > width {
+            res.push(line);
+            line = String::new();
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        let mut word = word;
+        while line.len() + word.len() > width {
+            // Longer than a line, split at a character boundary
+            let mut i = width - line.len();
+            while !word.is_char_boundary(i) {
+                i -= 1;
+            }
+            line.push_str(word[..i]);
+            res.push(line);
+            line = String::new();
+            word = word[i..];
+        }
+        line.push_str(word);
+    }
+    if !line.is_empty() {
+        res.push(line);
+    }
+    res
+}
+
+fn score(score: &Score) -> String {
+    format!("{}{} - {}{}", BOLD, score.player, score.opponent, BOLD)
+}
+
+fn side(side: Side) -> &'static str {
+    match side {
+        Player => "you",
+        Opponent => "me",
+    }
+}
+
+/// An answer with IRC formatting codes.
+pub fn format_answer(answer: &Answer) -> String {
+    match *answer {
+        Comeback(retort) => format!("{}{}{}", BOLD, retort, BOLD),
+        Started(n) => format!("En garde! First to {} points wins.", n),
+        YourServe => "Your serve, throw an insult.".to_string(),
+        Thrown(insult) => format!("{}{}{}", ITALIC, insult, ITALIC),
+        Answered(retort) => format!("{}{}{}", BOLD, retort, BOLD),
+        Scored(Point(x), ref s) => format!("Point to {}, {}.", side(x), score(s)),
+        Scored(Serve(x), ref s) => format!("The serve goes to {}, {}.", side(x), score(s)),
+        Scored(Retry(ref hint), _) => format!("Not quite, try again: {}", hint),
+        NotAnInsult => "That's no insult I know, try another.".to_string(),
+        Over(Player, ref s) => format!("You win the duel, {}!", score(s)),
+        Over(Opponent, ref s) => format!("I win the duel, {}!", score(s)),
+        NoDuel => "There's no duel to give up.".to_string(),
+        RandomInsult(insult) => format!("{}{}{}", ITALIC, insult, ITALIC),
+        Usage(ref syntax) => format!("usage: {}", syntax),
+        Top(ref top) => format_top(top[], |name| format!("{}{}{}", BOLD, name, BOLD)),
+        Rank(ref rank) => format_rank(rank),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufReader, MemWriter};
+    use std::rand;
+
+    use bots::{mod, BotSession};
+    use insults::Insults;
+    use super::*;
+
+    #[test]
+    fn test_irc() {
+        let mi = Insults::new("insults.json");
+        let mut session = BotSession::new(&mi);
+        let input = b"PING :irc.example.org\r\n\
+                      :murray!m@skull.island PRIVMSG #scumm :You fight like a dairy farmer.\r\n\
+                      :guybrush!g@melee.island PRIVMSG #scumm :You fight like a dairy farmer.\r\n\
+                      :guybrush!g@melee.island PRIVMSG murray :!duel 0\r\n";
+        let mut irc = IrcFrontend::new(BufReader::new(input), MemWriter::new(), "murray");
+        bots::run(&mut irc, &mut session, &mut rand::task_rng()).unwrap();
+        assert_eq!(String::from_utf8(irc.writer.unwrap()).unwrap()[],
+                   "PONG :irc.example.org\r\n\
+                    PRIVMSG #scumm :\x02How appropriate. You fight like a cow.\x02\r\n\
+                    PRIVMSG guybrush :guybrush: usage: !duel [points]\r\n");
+
+        assert_eq!(wrap("En garde, you scurvy dog", 10),
+                   vec!["En garde,".to_string(), "you scurvy".to_string(), "dog".to_string()]);
+        assert_eq!(wrap("Thwack", 4), vec!["Thwa".to_string(), "ck".to_string()]);
+    }
+}
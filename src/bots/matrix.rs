@@ -0,0 +1,188 @@
+//! Matrix bots, over the JSON events of the client-server API.
+//!
+//! Pass the response of every `/sync` to `MatrixBot::handle_sync` and send
+//! the returned contents as `m.room.message` events to their rooms. Each
+//! room has its own duel, the commands are those of `BotSession`. Replies
+//! carry both a plain `body` and an HTML `formatted_body`.
+
+use std::collections::TreeMap;
+use serialize::json::{mod, Json, ToJson};
+
+use bots::{Answer, BotSession, Incoming, Reply, Comeback, Started, YourServe, Thrown, Answered,
+           Scored, NotAnInsult, Over, NoDuel, RandomInsult, Usage, Top, Rank,
+           format_top, format_rank};
+use duel::{Score, Side, Player, Opponent, Point, Serve, Retry};
+use insults::Insults;
+
+/// Answers room messages and runs a duel per room.
+pub struct MatrixBot<'a> {
+    session: BotSession<'a>,
+    /// Our own user id, our messages are never answered.
+    user_id: String,
+}
+
+impl<'a> MatrixBot<'a> {
+    pub fn new(insults: &'a Insults, user_id: &str) -> MatrixBot<'a> {
+        MatrixBot::with_session(BotSession::new(insults), user_id)
+    }
+
+    pub fn with_session(session: BotSession<'a>, user_id: &str) -> MatrixBot<'a> {
+        MatrixBot { session: session, user_id: user_id.to_string() }
+    }
+
+    pub fn session(&mut self) -> &mut BotSession<'a> {
+        &mut self.session
+    }
+
+    /// Replies to the timelines of joined rooms in a `/sync` response, as room ids and contents.
+    pub fn handle_sync(&mut self, sync: &Json) -> Vec<(String, Json)> {
+        let mut res = Vec::new();
+        let rooms = match sync.find_path(&[&"rooms".to_string(), &"join".to_string()]) {
+            Some(&json::Object(ref rooms)) => rooms,
+            _ => return res,
+        };
+        for (room_id, room) in rooms.iter() {
+            let events = match room.find_path(&[&"timeline".to_string(), &"events".to_string()]) {
+                Some(&json::List(ref events)) => events,
+                _ => continue,
+            };
+            for event in events.iter() {
+                if let Some(content) = self.handle_event(room_id[], event) {
+                    res.push((room_id.clone(), content));
+                }
+            }
+        }
+        res
+    }
+
+    /// The content of the reply to a room event, if any.
+    pub fn handle_event(&mut self, room_id: &str, event: &Json) -> Option<Json> {
+        if string(event, "type") != Some("m.room.message") {
+            return None;
+        }
+        let sender = match string(event, "sender") {
+            Some(x) if x != self.user_id[] => x,
+            _ => return None,
+        };
+        let body = match event.find(&"content".to_string()).and_then(|x| string(x, "body")) {
+            Some(x) => x,
+            None => return None,
+        };
+        let msg = Incoming { channel: room_id.to_string(), user: sender.to_string(), text: body.to_string() };
+        self.session.handle(&msg).map(|x| format_reply(&x))
+    }
+}
+
+/// A reply as `m.room.message` content, mentioning the user it's for.
+pub fn format_reply(reply: &Reply) -> Json {
+    let (mut plain, mut html) = (Vec::new(), Vec::new());
+    for answer in reply.answers.iter() {
+        let (p, h) = format_answer(answer);
+        plain.push(p);
+        html.push(h);
+    }
+    if reply.is_personal() {
+        content(format!("{}: {}", reply.user, plain.connect("\n")),
+                format!("{}: {}", escape(reply.user[]), html.connect("<br>")))
+    } else {
+        content(plain.connect("\n"), html.connect("<br>"))
+    }
+}
+
+fn string<'a>(data: &'a Json, key: &str) -> Option<&'a str> {
+    data.find(&key.to_string()).and_then(|x| x.as_string())
+}
+
+/// An `m.room.message` content with a plain and an HTML body.
+pub fn content(body: String, html: String) -> Json {
+    let mut res = TreeMap::new();
+    res.insert("msgtype".to_string(), "m.text".to_json());
+    res.insert("body".to_string(), body.to_json());
+    res.insert("format".to_string(), "org.matrix.custom.html".to_json());
+    res.insert("formatted_body".to_string(), html.to_json());
+    json::Object(res)
+}
+
+/// Escape `text` for HTML.
+pub fn escape(text: &str) -> String {
+    text.replace("&", "&amp;").replace("<", "&lt;").replace(">", "&gt;").replace("\"", "&quot;")
+}
+
+fn score(score: &Score) -> (String, String) {
+    (format!("{} - {}", score.player, score.opponent),
+     format!("<strong>{} - {}</strong>", score.player, score.opponent))
+}
+
+fn side(side: Side) -> &'static str {
+    match side {
+        Player => "you",
+        Opponent => "me",
+    }
+}
+
+/// An answer as plain text and HTML.
+pub fn format_answer(answer: &Answer) -> (String, String) {
+    let same = |x: String| (x.clone(), escape(x[]));
+    match *answer {
+        Comeback(retort) | Answered(retort) =>
+            (retort.to_string(), format!("<strong>{}</strong>", escape(retort))),
+        Started(n) => same(format!("En garde! First to {} points wins.", n)),
+        YourServe => same("Your serve, throw an insult.".to_string()),
+        Thrown(insult) => (insult.to_string(), format!("<em>{}</em>", escape(insult))),
+        Scored(Point(x), ref s) => {
+            let (plain, html) = score(s);
+            (format!("Point to {}, {}.", side(x), plain), format!("Point to {}, {}.", side(x), html))
+        },
+        Scored(Serve(x), ref s) => {
+            let (plain, html) = score(s);
+            (format!("The serve goes to {}, {}.", side(x), plain),
+             format!("The serve goes to {}, {}.", side(x), html))
+        },
+        Scored(Retry(ref hint), _) =>
+            (format!("Not quite, try again: {}", hint),
+             format!("Not quite, try again: <span data-mx-spoiler>{}</span>", escape(hint[]))),
+        NotAnInsult => same("That's no insult I know, try another.".to_string()),
+        Over(winner, ref s) => {
+            let who = match winner {
+                Player => "You win",
+                Opponent => "I win",
+            };
+            let (plain, html) = score(s);
+            (format!("{} the duel, {}!", who, plain), format!("{} the duel, {}!", who, html))
+        },
+        NoDuel => same("There's no duel to give up.".to_string()),
+        RandomInsult(insult) => (insult.to_string(), format!("<em>{}</em>", escape(insult))),
+        Usage(ref syntax) =>
+            (format!("Usage: {}", syntax), format!("Usage: <code>{}</code>", escape(syntax[]))),
+        Top(ref top) => (format_top(top[], |name| name.to_string()),
+                         format_top(top[], |name| format!("<strong>{}</strong>", escape(name)))),
+        Rank(ref rank) => same(format_rank(rank)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serialize::json;
+
+    use insults::Insults;
+    use super::*;
+
+    #[test]
+    fn test_handle_sync() {
+        let mi = Insults::new("insults.json");
+        let mut bot = MatrixBot::new(&mi, "@murray:skull.island");
+        let sync = json::from_str(r#"{"rooms": {"join": {"!scumm:melee.island": {"timeline": {"events": [
+            {"type": "m.room.message", "sender": "@guybrush:melee.island",
+             "content": {"msgtype": "m.text", "body": "You fight like a dairy farmer."}},
+            {"type": "m.room.message", "sender": "@murray:skull.island",
+             "content": {"msgtype": "m.text", "body": "You fight like a dairy farmer."}},
+            {"type": "m.room.member", "sender": "@lechuck:melee.island", "content": {}}
+        ]}}}}}"#).unwrap();
+
+        let res = bot.handle_sync(&sync);
+        assert_eq!(res, vec![("!scumm:melee.island".to_string(),
+                              content("How appropriate. You fight like a cow.".to_string(),
+                                      "<strong>How appropriate. You fight like a cow.</strong>".to_string()))]);
+        assert_eq!(escape("<b>&"), "&lt;b&gt;&amp;".to_string());
+    }
+}
@@ -0,0 +1,502 @@
+//! Chat bots answering insults and running duels in channels.
+//!
+//! `BotSession` is the whole bot apart from the chat service: it parses the
+//! commands
+//!
+//! * `!duel [points]` to duel the bot, first to 3 points by default
+//! * `!giveup` to give up the exchange, or the duel on your serve
+//! * `!insult` for a random insult
+//! * `!top` and `!rank` for the leaderboard, given one
+//!
+//! retorts to insults however they're typed and plays one duel against the
+//! computer per channel through `Sessions`. Finished duels are recorded on
+//! the leaderboard of the session. A chat service implements
+//! `ChatFrontend` to receive messages and format the `Answer`s, `run` then
+//! connects the two. The adapters in the submodules are such frontends.
+
+use std::collections::HashMap;
+use std::io::IoResult;
+use std::rand::{mod, Rng};
+use std::time::Duration;
+use time;
+
+use duel::{Ai, Duel, Exchange, Rules, Score, Side, Player, Opponent, FirstTo, Retry};
+use insults::Insults;
+use leaderboard::{Leaderboard, Standing};
+use normalize::{normalize, NormalizedIndex};
+
+pub mod discord;
+pub mod irc;
+#[cfg(feature = "matrix")]
+pub mod matrix;
+
+/// Points to win a duel started without any.
+pub const DEFAULT_POINTS: uint = 3;
+
+/// Players listed by the top command.
+pub const TOP_PLAYERS: uint = 5;
+
+/// What the bot answers, for an adapter to format.
+#[deriving(Clone, PartialEq, Show)]
+pub enum Answer<'a> {
+    /// The retort to an insult typed outside a duel.
+    Comeback(&'a str),
+    /// A duel to this many points started, the player serves.
+    Started(uint),
+    YourServe,
+    /// The bot throws an insult.
+    Thrown(&'a str),
+    /// The bot's retort to the player's insult.
+    Answered(&'a str),
+    /// The outcome of an exchange and the score after it.
+    Scored(Exchange, Score),
+    /// The player served something that isn't an insult.
+    NotAnInsult,
+    Over(Side, Score),
+    /// Giving up without a duel in the channel.
+    NoDuel,
+    /// The reply to the insult command.
+    RandomInsult(&'a str),
+    /// The syntax of a command used wrongly.
+    Usage(String),
+    /// The best players of the leaderboard.
+    Top(Vec<Standing>),
+    /// The rank and standing of the player, None if they haven't finished a duel.
+    Rank(Option<(uint, Standing)>),
+}
+
+/// A message received in a channel.
+#[deriving(Clone, PartialEq, Show)]
+pub struct Incoming {
+    /// The channel, or the user for private messages.
+    pub channel: String,
+    pub user: String,
+    pub text: String,
+}
+
+/// The answers to a message.
+#[deriving(Clone, PartialEq, Show)]
+pub struct Reply<'a> {
+    pub channel: String,
+    /// The user who sent the message.
+    pub user: String,
+    pub answers: Vec<Answer<'a>>,
+}
+
+impl<'a> Reply<'a> {
+    /// Whether the reply is meant for the user, rather than the whole channel.
+    pub fn is_personal(&self) -> bool {
+        match self.answers[0] {
+            Comeback(_) | RandomInsult(_) | Top(_) => false,
+            _ => true,
+        }
+    }
+}
+
+/// The top of the leaderboard as `1. guybrush 3-1, 2. ...`, with names formatted by `name`.
+pub fn format_top(top: &[Standing], name: |&str| -> String) -> String {
+    if top.is_empty() {
+        return "Nobody has finished a duel yet.".to_string();
+    }
+    let mut players = Vec::new();
+    for (i, x) in top.iter().enumerate() {
+        players.push(format!("{}. {} {}-{}", i + 1, name(x.player[]), x.wins, x.losses));
+    }
+    format!("Top duelists: {}", players.connect(", "))
+}
+
+/// The player's rank in plain text.
+pub fn format_rank(rank: &Option<(uint, Standing)>) -> String {
+    match *rank {
+        Some((n, ref x)) => format!("You're number {} with {} wins and {} losses, {} in a row.",
+                                    n, x.wins, x.losses, x.streak),
+        None => "You haven't finished a duel yet.".to_string(),
+    }
+}
+
+/// A chat service a bot talks through.
+pub trait ChatFrontend {
+    /// The next message, None when the service disconnects.
+    fn receive(&mut self) -> IoResult<Option<Incoming>>;
+
+    /// Send `reply` to its channel, formatted for the service.
+    fn send(&mut self, reply: &Reply) -> IoResult<()>;
+
+    /// The bot's own user, its messages are never answered.
+    fn own_user(&self) -> &str;
+}
+
+/// Answer messages from `frontend` with `session` until it disconnects.
+pub fn run<F: ChatFrontend, R: Rng>(frontend: &mut F, session: &mut BotSession, rng: &mut R)
+        -> IoResult<()> {
+    loop {
+        let msg = match try!(frontend.receive()) {
+            Some(x) => x,
+            None => return Ok(()),
+        };
+        if msg.user[] == frontend.own_user() {
+            continue;
+        }
+        if let Some(reply) = session.handle_at(&msg, time::get_time().sec, rng) {
+            try!(frontend.send(&reply));
+        }
+    }
+}
+
+/// A bot independent of the chat service.
+pub struct BotSession<'a> {
+    sessions: Sessions<'a>,
+    prefix: String,
+    cooldown: Duration,
+    /// When each channel was last answered outside a duel, in seconds.
+    last_reply: HashMap<String, i64>,
+    leaderboard: Option<Leaderboard>,
+}
+
+impl<'a> BotSession<'a> {
+    pub fn new(insults: &'a Insults) -> BotSession<'a> {
+        BotSession::with_sessions(Sessions::new(insults))
+    }
+
+    pub fn with_sessions(sessions: Sessions<'a>) -> BotSession<'a> {
+        BotSession {
+            sessions: sessions,
+            prefix: "!".to_string(),
+            cooldown: Duration::zero(),
+            last_reply: HashMap::new(),
+            leaderboard: None,
+        }
+    }
+
+    /// Start commands with `prefix` instead of `!`.
+    pub fn with_prefix(mut self, prefix: &str) -> BotSession<'a> {
+        self.prefix = prefix.to_string();
+        self
+    }
+
+    /// Hold back retorts and random insults for `cooldown` after answering a channel.
+    ///
+    /// Duels are never held back.
+    pub fn with_cooldown(mut self, cooldown: Duration) -> BotSession<'a> {
+        self.cooldown = cooldown;
+        self
+    }
+
+    /// Record finished duels on `leaderboard` and answer the leaderboard commands.
+    pub fn with_leaderboard(mut self, leaderboard: Leaderboard) -> BotSession<'a> {
+        self.leaderboard = Some(leaderboard);
+        self
+    }
+
+    pub fn leaderboard(&mut self) -> Option<&mut Leaderboard> {
+        self.leaderboard.as_mut()
+    }
+
+    pub fn sessions(&mut self) -> &mut Sessions<'a> {
+        &mut self.sessions
+    }
+
+    /// The reply to `msg`, if any.
+    pub fn handle(&mut self, msg: &Incoming) -> Option<Reply<'a>> {
+        self.handle_at(msg, time::get_time().sec, &mut rand::task_rng())
+    }
+
+    /// Like `handle` at the time `now`, choosing the bot's moves with `rng`.
+    pub fn handle_at<R: Rng>(&mut self, msg: &Incoming, now: i64, rng: &mut R) -> Option<Reply<'a>> {
+        let (channel, user) = (msg.channel[], msg.user[]);
+        let text = msg.text[].trim();
+
+        let answers = if text.starts_with(self.prefix[]) {
+            let mut words = text[self.prefix.len()..].words();
+            match (words.next(), words.next(), words.next()) {
+                (Some("duel"), None, _) =>
+                    self.sessions.start(channel, user, Rules::first_to(DEFAULT_POINTS)),
+                (Some("duel"), Some(n), None) => match from_str::<uint>(n) {
+                    Some(n) if n > 0 => self.sessions.start(channel, user, Rules::first_to(n)),
+                    _ => vec![Usage(format!("{}duel [points]", self.prefix))],
+                },
+                (Some("giveup"), None, _) => self.sessions.give_up(channel, user, rng),
+                (Some("insult"), None, _) if !self.cooling_down(channel, now) => {
+                    self.last_reply.insert(channel.to_string(), now);
+                    vec![RandomInsult(self.sessions.insults().rand_insult(rng)[])]
+                },
+                // A failing store leaves the commands unanswered
+                (Some("top"), None, _) => match self.leaderboard {
+                    Some(ref board) => match board.top(TOP_PLAYERS) {
+                        Ok(top) => vec![Top(top)],
+                        Err(_) => return None,
+                    },
+                    None => return None,
+                },
+                (Some("rank"), None, _) => match self.leaderboard {
+                    Some(ref board) => match (board.rank_of(user), board.standing(user)) {
+                        (Ok(Some(rank)), Ok(Some(standing))) => vec![Rank(Some((rank, standing)))],
+                        (Ok(_), Ok(_)) => vec![Rank(None)],
+                        _ => return None,
+                    },
+                    None => return None,
+                },
+                _ => return None,
+            }
+        } else if self.sessions.dueling(channel) == Some(user) || !self.cooling_down(channel, now) {
+            let res = self.sessions.message(channel, user, text, rng);
+            if let Some(&Comeback(_)) = res.get(0) {
+                self.last_reply.insert(channel.to_string(), now);
+            }
+            res
+        } else {
+            return None;
+        };
+
+        if answers.is_empty() {
+            return None;
+        }
+        if let (Some(&Over(winner, _)), Some(board)) = (answers.last(), self.leaderboard.as_mut()) {
+            // The duel is over either way, losing the record is better than stalling
+            let _ = board.record(user, winner == Player);
+        }
+        Some(Reply { channel: channel.to_string(), user: user.to_string(), answers: answers })
+    }
+
+    fn cooling_down(&self, channel: &str, now: i64) -> bool {
+        match self.last_reply.get(&channel.to_string()) {
+            Some(&last) => now - last < self.cooldown.num_seconds(),
+            None => false,
+        }
+    }
+}
+
+struct ChannelDuel<'a> {
+    user: String,
+    duel: Duel<'a>,
+    /// The bot's insult waiting for the player's retort.
+    pending: Option<&'a str>,
+}
+
+/// Duels against the computer, one per channel.
+pub struct Sessions<'a> {
+    insults: &'a Insults,
+    index: NormalizedIndex<'a>,
+    ai: Ai,
+    duels: HashMap<String, ChannelDuel<'a>>,
+}
+
+impl<'a> Sessions<'a> {
+    pub fn new(insults: &'a Insults) -> Sessions<'a> {
+        Sessions {
+            insults: insults,
+            index: insults.normalized_index(),
+            ai: Ai::default(),
+            duels: HashMap::new(),
+        }
+    }
+
+    /// Duel against `ai` instead of the default opponent.
+    pub fn with_ai(mut self, ai: Ai) -> Sessions<'a> {
+        self.ai = ai;
+        self
+    }
+
+    pub fn insults(&self) -> &'a Insults {
+        self.insults
+    }
+
+    /// The user dueling in `channel`, if there's a duel.
+    pub fn dueling<'b>(&'b self, channel: &str) -> Option<&'b str> {
+        self.duels.get(&channel.to_string()).map(|x| x.user[])
+    }
+
+    /// Start a duel by `rules` for `user` in `channel`, replacing any duel there.
+    pub fn start(&mut self, channel: &str, user: &str, rules: Rules) -> Vec<Answer<'a>> {
+        let first_to = match rules.win_condition {
+            FirstTo(n) => n,
+            _ => 1,
+        };
+        let duel = ChannelDuel { user: user.to_string(), duel: Duel::new(self.insults, rules), pending: None };
+        self.duels.insert(channel.to_string(), duel);
+        vec![Started(first_to), YourServe]
+    }
+
+    /// `user` gives up the exchange, or the duel if it's their serve.
+    pub fn give_up<R: Rng>(&mut self, channel: &str, user: &str, rng: &mut R) -> Vec<Answer<'a>> {
+        let key = channel.to_string();
+        let res = match self.duels.get_mut(&key) {
+            Some(x) if x.user[] == user => match x.pending.take() {
+                Some(_) => vec![Scored(x.duel.give_up(), x.duel.score().clone())],
+                None => vec![Over(Opponent, x.duel.score().clone())],
+            },
+            _ => return vec![NoDuel],
+        };
+        if let Some(&Over(..)) = res.last() {
+            self.duels.remove(&key);
+            return res;
+        }
+        self.next_turn(&key, res, rng)
+    }
+
+    /// Answer a message of `user` in `channel`.
+    ///
+    /// The player of the duel in the channel plays their move, anyone else
+    /// gets the retort if the message is an insult.
+    pub fn message<R: Rng>(&mut self, channel: &str, user: &str, text: &str, rng: &mut R)
+            -> Vec<Answer<'a>> {
+        if self.dueling(channel) != Some(user) {
+            return match self.index.retort(text) {
+                Some(found) => vec![Comeback(found.retort)],
+                None => Vec::new(),
+            };
+        }
+
+        let key = channel.to_string();
+        let res = {
+            let x = self.duels.get_mut(&key).unwrap();
+            let pending = x.pending;
+            match pending {
+                Some(insult) => {
+                    // Typing the retort loosely still counts
+                    let retort = match self.insults.retort(insult) {
+                        Some(r) if normalize(r) == normalize(text) => r,
+                        _ => text,
+                    };
+                    let exchange = if x.duel.retrying().is_some() {
+                        x.duel.retry(retort)
+                    } else {
+                        x.duel.exchange(insult, retort)
+                    };
+                    if let Retry(_) = exchange {} else {
+                        x.pending = None;
+                    }
+                    vec![Scored(exchange, x.duel.score().clone())]
+                },
+                None => match self.index.insult(text) {
+                    Some(insult) => {
+                        let retort = self.ai.retort(self.insults, insult, rng);
+                        let mut exchange = x.duel.exchange(insult, retort);
+                        if let Retry(_) = exchange {
+                            // The computer doesn't take hints
+                            exchange = x.duel.give_up();
+                        }
+                        vec![Answered(retort), Scored(exchange, x.duel.score().clone())]
+                    },
+                    None => return vec![NotAnInsult],
+                },
+            }
+        };
+        self.next_turn(&key, res, rng)
+    }
+
+    /// End the duel in `key` if it's over, otherwise prompt the next exchange.
+    fn next_turn<R: Rng>(&mut self, key: &String, mut res: Vec<Answer<'a>>, rng: &mut R)
+            -> Vec<Answer<'a>> {
+        let over = {
+            let x = self.duels.get_mut(key).unwrap();
+            match x.duel.winner() {
+                Some(winner) => Some(Over(winner, x.duel.score().clone())),
+                // Retrying the same insult
+                None if x.pending.is_some() => None,
+                None if x.duel.attacker() == Opponent => {
+                    let insult = self.ai.insult(self.insults, rng);
+                    x.pending = Some(insult);
+                    res.push(Thrown(insult));
+                    None
+                },
+                None => {
+                    res.push(YourServe);
+                    None
+                },
+            }
+        };
+        if let Some(over) = over {
+            self.duels.remove(key);
+            res.push(over);
+        }
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::IoResult;
+    use std::rand;
+    use std::time::Duration;
+
+    use duel::{Ai, Rules, Score, Point, Player, Opponent};
+    use insults::Insults;
+    use leaderboard::Leaderboard;
+    use super::*;
+
+    #[test]
+    fn test_sessions() {
+        let mi = Insults::new("insults.json");
+        let mut sessions = Sessions::new(&mi).with_ai(Ai::new(0.0));
+        let mut rng = rand::task_rng();
+
+        assert_eq!(sessions.message("#scumm", "guybrush", "you fight like a  DAIRY farmer.", &mut rng),
+                   vec![Comeback("How appropriate. You fight like a cow.")]);
+        assert_eq!(sessions.give_up("#scumm", "guybrush", &mut rng), vec![NoDuel]);
+
+        assert_eq!(sessions.start("#scumm", "guybrush", Rules::first_to(1)), vec![Started(1), YourServe]);
+        assert_eq!(sessions.dueling("#scumm"), Some("guybrush"));
+        assert_eq!(sessions.message("#scumm", "guybrush", "Arr!", &mut rng), vec![NotAnInsult]);
+        let res = sessions.message("#scumm", "guybrush", "You fight like a dairy farmer.", &mut rng);
+        assert_eq!(res[1..], [Scored(Point(Player), Score { player: 1, opponent: 0 }),
+                              Over(Player, Score { player: 1, opponent: 0 })][]);
+        assert_eq!(sessions.dueling("#scumm"), None);
+
+        sessions.start("#scumm", "guybrush", Rules::first_to(3));
+        assert_eq!(sessions.give_up("#scumm", "guybrush", &mut rng), vec![Over(Opponent, Score { player: 0, opponent: 0 })]);
+        assert_eq!(sessions.dueling("#scumm"), None);
+    }
+
+    struct Script {
+        incoming: Vec<Incoming>,
+        sent: Vec<String>,
+    }
+
+    impl ChatFrontend for Script {
+        fn receive(&mut self) -> IoResult<Option<Incoming>> {
+            Ok(self.incoming.remove(0))
+        }
+
+        fn send(&mut self, reply: &Reply) -> IoResult<()> {
+            self.sent.push(format!("{} {}", reply.channel, reply.answers));
+            Ok(())
+        }
+
+        fn own_user(&self) -> &str {
+            "murray"
+        }
+    }
+
+    #[test]
+    fn test_run() {
+        let mi = Insults::new("insults.json");
+        let mut session = BotSession::new(&mi).with_prefix("?").with_cooldown(Duration::seconds(60))
+            .with_leaderboard(Leaderboard::in_memory());
+        let msg = |user: &str, text: &str| Incoming {
+            channel: "#scumm".to_string(),
+            user: user.to_string(),
+            text: text.to_string(),
+        };
+        let mut script = Script {
+            incoming: vec![msg("murray", "You fight like a dairy farmer."),
+                           msg("guybrush", "You fight like a dairy farmer."),
+                           msg("guybrush", "You fight like a dairy farmer."),
+                           msg("guybrush", "?duel none"),
+                           msg("guybrush", "?duel"),
+                           msg("guybrush", "?giveup"),
+                           msg("guybrush", "?top")],
+            sent: Vec::new(),
+        };
+
+        run(&mut script, &mut session, &mut rand::task_rng()).unwrap();
+        assert_eq!(script.sent, vec![
+            r#"#scumm [Comeback(How appropriate. You fight like a cow.)]"#.to_string(),
+            r#"#scumm [Usage(?duel [points])]"#.to_string(),
+            r#"#scumm [Started(3), YourServe]"#.to_string(),
+            r#"#scumm [Over(Opponent, Score { player: 0, opponent: 0 })]"#.to_string(),
+            r#"#scumm [Top([Standing { player: guybrush, wins: 0, losses: 1, streak: 0, best_streak: 0 }])]"#.to_string(),
+        ]);
+        assert_eq!(session.leaderboard().unwrap().rank_of("guybrush").unwrap(), Some(1));
+    }
+}
@@ -0,0 +1,152 @@
+//! Indices of a dataset built the first time they're asked for.
+//!
+//! Building every index up front is wasted on callers only using one.
+//! `IndexCache` builds each index on first use and keeps it, `warm_up`
+//! builds them all at once, for servers wanting the first request as quick
+//! as the rest.
+//!
+//! The indices borrow the dataset, so the cache isn't shared between
+//! threads, give every thread a cache of its own.
+
+use std::cell::UnsafeCell;
+
+use fuzzy::FuzzyIndex;
+use index::{ExactIndex, ReverseIndex};
+use insults::Insults;
+use normalize::NormalizedIndex;
+use partial::PartialIndex;
+use phonetic::PhoneticIndex;
+
+/// A value built on first use.
+struct Lazy<T> {
+    value: UnsafeCell<Option<Box<T>>>,
+}
+
+impl<T> Lazy<T> {
+    fn new() -> Lazy<T> {
+        Lazy { value: UnsafeCell::new(None) }
+    }
+
+    fn get<'b>(&'b self, init: || -> T) -> &'b T {
+        // The value is set once and never moved or dropped before the cell,
+        // the cell isn't Sync so nothing builds it concurrently
+        unsafe {
+            let value = &mut *self.value.get();
+            if value.is_none() {
+                *value = Some(box init());
+            }
+            &**value.as_ref().unwrap()
+        }
+    }
+
+    fn is_built(&self) -> bool {
+        unsafe { (*self.value.get()).is_some() }
+    }
+}
+
+/// The indices of a dataset, each built on first use.
+pub struct IndexCache<'a> {
+    insults: &'a Insults,
+    exact: Lazy<ExactIndex<'a>>,
+    normalized: Lazy<NormalizedIndex<'a>>,
+    fuzzy: Lazy<FuzzyIndex<'a>>,
+    phonetic: Lazy<PhoneticIndex<'a>>,
+    partial: Lazy<PartialIndex<'a>>,
+    reverse: Lazy<ReverseIndex<'a>>,
+}
+
+impl<'a> IndexCache<'a> {
+    /// A cache for `insults`, building nothing yet.
+    pub fn new(insults: &'a Insults) -> IndexCache<'a> {
+        IndexCache {
+            insults: insults,
+            exact: Lazy::new(),
+            normalized: Lazy::new(),
+            fuzzy: Lazy::new(),
+            phonetic: Lazy::new(),
+            partial: Lazy::new(),
+            reverse: Lazy::new(),
+        }
+    }
+
+    pub fn insults(&self) -> &'a Insults {
+        self.insults
+    }
+
+    pub fn exact<'b>(&'b self) -> &'b ExactIndex<'a> {
+        let insults = self.insults;
+        self.exact.get(|| ExactIndex::new(insults))
+    }
+
+    /// The normalized index, with stemming for locales with a stemmer.
+    pub fn normalized<'b>(&'b self) -> &'b NormalizedIndex<'a> {
+        let insults = self.insults;
+        self.normalized.get(|| NormalizedIndex::new(insults).with_stemming())
+    }
+
+    pub fn fuzzy<'b>(&'b self) -> &'b FuzzyIndex<'a> {
+        let insults = self.insults;
+        self.fuzzy.get(|| FuzzyIndex::new(insults))
+    }
+
+    pub fn phonetic<'b>(&'b self) -> &'b PhoneticIndex<'a> {
+        let insults = self.insults;
+        self.phonetic.get(|| PhoneticIndex::new(insults))
+    }
+
+    /// The index of the words of the insults.
+    pub fn partial<'b>(&'b self) -> &'b PartialIndex<'a> {
+        let insults = self.insults;
+        self.partial.get(|| PartialIndex::new(insults))
+    }
+
+    /// The index of insults by retort.
+    pub fn reverse<'b>(&'b self) -> &'b ReverseIndex<'a> {
+        let insults = self.insults;
+        self.reverse.get(|| ReverseIndex::new(insults))
+    }
+
+    /// Build every index not built yet.
+    pub fn warm_up(&self) {
+        self.exact();
+        self.normalized();
+        self.fuzzy();
+        self.phonetic();
+        self.partial();
+        self.reverse();
+    }
+
+    /// How many indices have been built.
+    pub fn built(&self) -> uint {
+        [self.exact.is_built(), self.normalized.is_built(), self.fuzzy.is_built(),
+         self.phonetic.is_built(), self.partial.is_built(), self.reverse.is_built()]
+            .iter().filter(|x| **x).count()
+    }
+}
+
+impl Insults {
+    /// A cache of the indices of the dataset, built as they're used.
+    pub fn index_cache<'a>(&'a self) -> IndexCache<'a> {
+        IndexCache::new(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use insults::Insults;
+
+    #[test]
+    fn test_index_cache() {
+        let mi = Insults::new("insults.json");
+        let cache = mi.index_cache();
+        assert_eq!(cache.built(), 0);
+        let insult = "You fight like a dairy farmer.";
+        assert_eq!(cache.fuzzy().retort("you fihgt like a dairy farmer").map(|x| x.insult_matched), Some(insult));
+        assert_eq!(cache.exact().retort(insult).map(|x| x.retort), mi.retort(insult));
+        assert_eq!(cache.built(), 2);
+        let fuzzy = cache.fuzzy() as *const _;
+        assert!(cache.fuzzy() as *const _ == fuzzy);
+        cache.warm_up();
+        assert_eq!(cache.built(), 6);
+    }
+}
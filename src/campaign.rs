@@ -0,0 +1,236 @@
+//! A string of duels against ever better opponents, ending with a boss.
+//!
+//! The opponents of a campaign know a growing share of the insults, set by
+//! a difficulty curve, and the last one knows them all. Every opponent
+//! knows what the ones before knew. What the player learns from a fight is
+//! kept in their `Knowledge` for the next.
+
+use std::rand::{Isaac64Rng, Rng, SeedableRng};
+
+use duel::{Duel, DuelEvent, Rules, Side, Player, Opponent};
+use duel::{InsultThrown, RetortSucceeded};
+use insults::Insults;
+use profile::Knowledge;
+
+/// How the share of insults known grows from one opponent to the next.
+#[deriving(Clone, PartialEq, Show)]
+pub enum Curve {
+    /// By the same share for every opponent.
+    Linear,
+    /// Slowly at first, then faster, the larger the base the steeper.
+    Exponential(f64),
+    /// The share known by opponent `i`, the last share for opponents past the end.
+    Custom(Vec<f64>),
+}
+
+impl Curve {
+    /// The share of insults opponent `stage` of `stages` knows, between 0 and 1.
+    pub fn at(&self, stage: uint, stages: uint) -> f64 {
+        if stages == 0 {
+            return 1.0;
+        }
+        let x = (stage + 1) as f64 / stages as f64;
+        let res = match *self {
+            Linear => x,
+            Exponential(base) if base > 1.0 => (base.powf(x) - 1.0) / (base - 1.0),
+            Exponential(_) => x,
+            Custom(ref shares) => match shares.get(stage).or(shares.last()) {
+                Some(x) => *x,
+                None => x,
+            },
+        };
+        res.max(0.0).min(1.0)
+    }
+}
+
+/// An opponent of the campaign.
+#[deriving(Clone, PartialEq, Show)]
+pub struct Rival {
+    /// The insults the opponent can throw and retort to.
+    pub known: Vec<String>,
+    pub boss: bool,
+}
+
+impl Rival {
+    pub fn knows(&self, insult: &str) -> bool {
+        self.known.iter().any(|x| x[] == insult)
+    }
+
+    /// A random insult the opponent knows.
+    pub fn insult<'a, R: Rng>(&'a self, rng: &mut R) -> &'a str {
+        rng.choose(self.known[]).expect("the opponent knows no insults")[]
+    }
+
+    /// The retort to `insult` if the opponent knows it, otherwise one of its wrong answers.
+    pub fn retort<'a, R: Rng>(&self, insults: &'a Insults, insult: &str, rng: &mut R) -> &'a str {
+        match insults.retort(insult) {
+            Some(x) if self.knows(insult) => x,
+            _ => insults.rand_opponent_failed_retort(rng),
+        }
+    }
+}
+
+/// Opponents fought in order, a lost duel is fought again.
+pub struct Campaign {
+    stages: uint,
+    curve: Curve,
+    rules: Rules,
+    boss_rules: Rules,
+    /// Every insult, in the order the opponents learn them.
+    order: Vec<String>,
+    stage: uint,
+    duels: uint,
+    /// What the player has learned so far.
+    pub knowledge: Knowledge,
+}
+
+impl Campaign {
+    /// A campaign of `stages` opponents over `insults`, the same for the same seed.
+    pub fn new(insults: &Insults, stages: uint, curve: Curve, seed: u64) -> Campaign {
+        let mut order: Vec<String> = insults.insults().into_iter().map(|x| x.clone()).collect();
+        order.sort();
+        order.dedup();
+        let mut rng: Isaac64Rng = SeedableRng::from_seed([seed][]);
+        rng.shuffle(order.as_mut_slice());
+        Campaign {
+            stages: stages,
+            curve: curve,
+            rules: Rules::default(),
+            boss_rules: Rules::default(),
+            order: order,
+            stage: 0,
+            duels: 0,
+            knowledge: Knowledge::new(),
+        }
+    }
+
+    /// Fight the opponents by `rules`, the boss included unless `with_boss_rules` says otherwise.
+    pub fn with_rules(mut self, rules: Rules) -> Campaign {
+        self.rules = rules.clone();
+        self.boss_rules = rules;
+        self
+    }
+
+    /// Fight the boss by `rules`.
+    pub fn with_boss_rules(mut self, rules: Rules) -> Campaign {
+        self.boss_rules = rules;
+        self
+    }
+
+    /// Start with what the player learned before.
+    pub fn with_knowledge(mut self, knowledge: Knowledge) -> Campaign {
+        self.knowledge = knowledge;
+        self
+    }
+
+    pub fn stages(&self) -> uint {
+        self.stages
+    }
+
+    /// The opponent to beat next, None once the boss is beaten.
+    pub fn current_stage(&self) -> Option<uint> {
+        if self.stage < self.stages {
+            Some(self.stage)
+        } else {
+            None
+        }
+    }
+
+    pub fn is_boss(&self, stage: uint) -> bool {
+        stage + 1 == self.stages
+    }
+
+    /// The share of insults opponent `stage` knows, all of them for the boss.
+    pub fn difficulty(&self, stage: uint) -> f64 {
+        if self.is_boss(stage) {
+            1.0
+        } else {
+            self.curve.at(stage, self.stages)
+        }
+    }
+
+    /// Opponent `stage`, knowing at least one insult.
+    pub fn rival(&self, stage: uint) -> Rival {
+        let n = (self.difficulty(stage) * self.order.len() as f64).ceil() as uint;
+        let n = n.max(1).min(self.order.len());
+        Rival { known: self.order[..n].to_vec(), boss: self.is_boss(stage) }
+    }
+
+    /// The duel against the current opponent.
+    pub fn next_duel<'a>(&self, insults: &'a Insults) -> Option<Duel<'a>> {
+        self.current_stage().map(|stage| {
+            let rules = if self.is_boss(stage) { &self.boss_rules } else { &self.rules };
+            Duel::new(insults, rules.clone())
+        })
+    }
+
+    /// Record a finished duel against the current opponent from its events.
+    ///
+    /// The player learns the insults the opponent threw and the ones the
+    /// opponent retorted to, and moves on to the next opponent after a win.
+    pub fn record(&mut self, winner: Side, events: &[DuelEvent]) {
+        assert!(self.current_stage().is_some(), "the campaign is over");
+        let mut thrown_by_player: Option<&str> = None;
+        for event in events.iter() {
+            match *event {
+                InsultThrown(Opponent, ref x) => self.knowledge.learn(x[]),
+                InsultThrown(Player, ref x) => {
+                    thrown_by_player = Some(x[]);
+                    continue;
+                },
+                RetortSucceeded(Opponent, _) => if let Some(x) = thrown_by_player {
+                    self.knowledge.learn(x);
+                },
+                _ => (),
+            }
+            thrown_by_player = None;
+        }
+        self.duels += 1;
+        if winner == Player {
+            self.stage += 1;
+        }
+    }
+
+    /// Duels fought, lost ones included.
+    pub fn duels(&self) -> uint {
+        self.duels
+    }
+
+    pub fn is_over(&self) -> bool {
+        self.current_stage().is_none()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use duel::{Player, Opponent, InsultThrown, RetortSucceeded};
+    use insults::Insults;
+    use super::*;
+
+    #[test]
+    fn test_campaign() {
+        assert_eq!(Linear.at(1, 4), 0.5);
+        assert!(Exponential(10.0).at(1, 4) < 0.5);
+        assert_eq!(Custom(vec![0.2, 0.3]).at(3, 4), 0.3);
+
+        let mi = Insults::new("insults.json");
+        let mut campaign = Campaign::new(&mi, 3, Linear, 7);
+        let (first, boss) = (campaign.rival(0), campaign.rival(2));
+        assert!(boss.boss && boss.known.len() > first.known.len());
+        assert!(first.known.iter().all(|x| boss.knows(x[])));
+
+        let insult = "You fight like a dairy farmer.";
+        campaign.record(Opponent, [InsultThrown(Opponent, insult.to_string())][]);
+        assert_eq!(campaign.current_stage(), Some(0));
+        assert!(campaign.knowledge.knows(insult));
+
+        let retort = mi.retort(insult).unwrap().to_string();
+        campaign.record(Player, [InsultThrown(Player, "Oh yeah?".to_string()),
+                                 RetortSucceeded(Opponent, retort)][]);
+        assert!(campaign.knowledge.knows("Oh yeah?"));
+        campaign.record(Player, &[]);
+        campaign.record(Player, &[]);
+        assert!(campaign.is_over());
+        assert_eq!(campaign.duels(), 4);
+    }
+}
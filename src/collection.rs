@@ -0,0 +1,161 @@
+//! Insults and retorts the player has come across, for completionists.
+//!
+//! Both are kept by canonical id, so an insult heard in a translation
+//! counts for every language.
+
+use std::collections::TreeSet;
+use std::io::{File, IoResult};
+use serialize::json;
+
+use duel::{DuelEvent, InsultThrown, RetortSucceeded};
+use game::Game;
+use insults::Insults;
+use schema::{LoadError, Io, Schema};
+
+/// How much of a game has been collected.
+#[deriving(Clone, PartialEq, Eq, Show)]
+pub struct Tally {
+    pub insults: uint,
+    pub retorts: uint,
+    /// Insults in the game, each with one retort.
+    pub total: uint,
+}
+
+/// The insults heard and the retorts seen to them, by canonical id.
+#[deriving(Clone, PartialEq, Show, Encodable, Decodable)]
+pub struct Collection {
+    pub insults: TreeSet<String>,
+    /// The insults whose retort has been seen.
+    pub retorts: TreeSet<String>,
+}
+
+fn id_of<'a>(insults: &'a Insults, insult: &'a str) -> &'a str {
+    insults.canonical_id(insult).unwrap_or(insult)
+}
+
+impl Collection {
+    pub fn new() -> Collection {
+        Collection { insults: TreeSet::new(), retorts: TreeSet::new() }
+    }
+
+    /// Collect `insult`, true if it's new.
+    pub fn encounter_insult(&mut self, insults: &Insults, insult: &str) -> bool {
+        self.insults.insert(id_of(insults, insult).to_string())
+    }
+
+    /// Collect the retort to `insult`, true if it's new.
+    pub fn encounter_retort(&mut self, insults: &Insults, insult: &str) -> bool {
+        self.retorts.insert(id_of(insults, insult).to_string())
+    }
+
+    /// Collect the insults thrown in a duel and the retorts answering them correctly.
+    pub fn record(&mut self, insults: &Insults, events: &[DuelEvent]) {
+        let mut thrown: Option<&str> = None;
+        for event in events.iter() {
+            match *event {
+                InsultThrown(_, ref x) => {
+                    self.encounter_insult(insults, x[]);
+                    thrown = Some(x[]);
+                },
+                RetortSucceeded(..) => if let Some(x) = thrown.take() {
+                    self.encounter_retort(insults, x);
+                },
+                _ => (),
+            }
+        }
+    }
+
+    pub fn has_insult(&self, insults: &Insults, insult: &str) -> bool {
+        self.insults.contains(&id_of(insults, insult).to_string())
+    }
+
+    pub fn has_retort(&self, insults: &Insults, insult: &str) -> bool {
+        self.retorts.contains(&id_of(insults, insult).to_string())
+    }
+
+    /// What has been collected of `game`.
+    pub fn tally(&self, insults: &Insults, game: Game) -> Tally {
+        let mut res = Tally { insults: 0, retorts: 0, total: 0 };
+        for (insult, _) in insults.entries(game).into_iter() {
+            res.total += 1;
+            if self.has_insult(insults, insult[]) {
+                res.insults += 1;
+            }
+            if self.has_retort(insults, insult[]) {
+                res.retorts += 1;
+            }
+        }
+        res
+    }
+
+    /// The share of insults and retorts collected over every game, in percent.
+    pub fn percent_complete(&self, insults: &Insults) -> f64 {
+        let (mut found, mut total) = (0u, 0u);
+        for game in Game::all().iter() {
+            let tally = self.tally(insults, *game);
+            found += tally.insults + tally.retorts;
+            total += 2 * tally.total;
+        }
+        if total == 0 {
+            100.0
+        } else {
+            100.0 * found as f64 / total as f64
+        }
+    }
+
+    /// The insults of `game` still missing, or whose retort is, ordered by insult.
+    pub fn missing_in<'a>(&self, insults: &'a Insults, game: Game) -> Vec<&'a str> {
+        insults.entries(game).into_iter()
+            .map(|(insult, _)| insult[])
+            .filter(|x| !self.has_insult(insults, *x) || !self.has_retort(insults, *x))
+            .collect()
+    }
+
+    /// Read a collection saved with `save`.
+    pub fn open(path: &Path) -> Result<Collection, LoadError> {
+        let data = match File::open(path).read_to_string() {
+            Ok(x) => x,
+            Err(e) => return Err(Io(e)),
+        };
+        match json::decode(data[]) {
+            Ok(x) => Ok(x),
+            Err(e) => Err(Schema(vec![path.display().to_string()], e.to_string())),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> IoResult<()> {
+        File::create(path).write_str(json::encode(self)[])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::TempDir;
+
+    use duel::{Player, Opponent, InsultThrown, RetortSucceeded};
+    use game::MonkeyIsland1;
+    use insults::Insults;
+    use super::*;
+
+    #[test]
+    fn test_collection() {
+        let mi = Insults::new("insults.json");
+        let insult = "You fight like a dairy farmer.";
+        let mut collection = Collection::new();
+        assert_eq!(collection.percent_complete(&mi), 0.0);
+
+        collection.record(&mi, &[InsultThrown(Player, insult.to_string()),
+                                 RetortSucceeded(Opponent, mi.retort(insult).unwrap().to_string())]);
+        assert!(collection.has_insult(&mi, insult) && collection.has_retort(&mi, insult));
+        let tally = collection.tally(&mi, MonkeyIsland1);
+        assert_eq!((tally.insults, tally.retorts), (1, 1));
+        assert_eq!(collection.missing_in(&mi, MonkeyIsland1).len(), tally.total - 1);
+        assert!(!collection.missing_in(&mi, MonkeyIsland1).contains(&insult));
+        assert!(collection.percent_complete(&mi) > 0.0);
+
+        let dir = TempDir::new("collection").unwrap();
+        let path = dir.path().join("collection.json");
+        collection.save(&path).unwrap();
+        assert_eq!(Collection::open(&path), Ok(collection));
+    }
+}
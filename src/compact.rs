@@ -0,0 +1,313 @@
+//! Datasets packed into a single string, for consumers going over every insult.
+//!
+//! A loaded dataset holds every insult and retort in a `String` of its own.
+//! `CompactInsults` keeps them in one buffer, its entries only ranges into
+//! it, so iterating reads memory in order and a dataset costs a handful of
+//! allocations rather than thousands. `CompactInsults::from_reader` packs
+//! the entries as the stream loader parses them, without ever building the
+//! `Insults` tables.
+
+use std::io::{BufferedReader, Reader};
+use std::slice;
+
+use formats::stream;
+use game::{Game, MonkeyIsland1, MonkeyIsland3, SwordMaster, CaptainRottingham};
+use game::{NOT_FAIR_SWORD_MASTER, NOT_FAIR_ROTTINGHAM};
+use insults::Insults;
+use normalize::nfc;
+use schema::LoadError;
+
+/// A range of the buffer.
+#[deriving(Clone)]
+struct Span {
+    start: u32,
+    len: u32,
+}
+
+#[deriving(Clone)]
+struct Row {
+    game: Game,
+    insult: Span,
+    retort: Span,
+}
+
+/// What `Insults::retort` answers to an insult.
+enum Answer {
+    /// The retort of one of its rows.
+    Packed(Span),
+    /// A retort of no row, like the Sword Master's.
+    Custom(&'static str),
+}
+
+/// An entry of a `CompactInsults`.
+#[deriving(Clone, PartialEq, Show)]
+pub struct CompactEntry<'a> {
+    pub game: Game,
+    pub insult: &'a str,
+    pub retort: &'a str,
+}
+
+/// Append `s` to `text`, returning where it went.
+fn push(text: &mut String, s: &str) -> Span {
+    let span = Span { start: text.len() as u32, len: s.len() as u32 };
+    text.push_str(s);
+    span
+}
+
+/// Append `s` composed to NFC to `text`, returning where it went.
+fn push_nfc(text: &mut String, s: &str) -> Span {
+    if s.bytes().all(|b| b < 0x80) {
+        push(text, s)
+    } else {
+        push(text, nfc(s)[])
+    }
+}
+
+fn text_of<'a>(text: &'a str, span: &Span) -> &'a str {
+    text[span.start as uint..(span.start + span.len) as uint]
+}
+
+/// The answer to the insult having the rows `group`, with the rules of
+/// `Insults::retort`.
+fn answer(rows: &[Row], group: &[uint], priority: &[Game]) -> Option<Answer> {
+    let has = |game: Game| group.iter().find(|&&i| rows[i].game == game).map(|&i| rows[i].retort.clone());
+    for game in priority.iter() {
+        let found = match *game {
+            MonkeyIsland1 if has(SwordMaster).is_some() => Some(Custom(NOT_FAIR_SWORD_MASTER)),
+            MonkeyIsland3 if has(CaptainRottingham).is_some() => Some(Custom(NOT_FAIR_ROTTINGHAM)),
+            game => has(game).map(Packed),
+        };
+        if found.is_some() {
+            return found;
+        }
+    }
+    None
+}
+
+/// The insults, retorts and failed retorts of a dataset in one buffer.
+pub struct CompactInsults {
+    text: String,
+    /// Ordered by game in `Game::all` order, then by insult.
+    rows: Vec<Row>,
+    /// Every insult once with its answer from `Insults::retort`, ordered by insult.
+    answers: Vec<(Span, Span)>,
+    failed_retorts: Vec<Span>,
+}
+
+impl CompactInsults {
+    /// Pack the entries of `insults`, without its fallbacks.
+    pub fn new(insults: &Insults) -> CompactInsults {
+        let mut size = insults.failed_retorts().iter().map(|x| x.len()).fold(0, |a, b| a + b);
+        let mut count = 0;
+        for game in Game::all().iter() {
+            for (insult, entry) in insults.entries(*game).into_iter() {
+                size += insult.len() + entry.retort.len();
+                count += 1;
+            }
+        }
+
+        let mut text = String::with_capacity(size);
+        let mut rows = Vec::with_capacity(count);
+        for game in Game::all().iter() {
+            for (insult, entry) in insults.entries(*game).into_iter() {
+                let insult = push(&mut text, insult[]);
+                let retort = push(&mut text, entry.retort[]);
+                rows.push(Row { game: *game, insult: insult, retort: retort });
+            }
+        }
+        CompactInsults::pack(text, rows, insults)
+    }
+
+    /// Pack the dataset in the JSON of `reader` as it's parsed, like
+    /// `Insults::try_from_reader` does for uncompressed JSON.
+    pub fn from_reader<R: Reader>(reader: R) -> Result<CompactInsults, LoadError> {
+        let mut text = String::new();
+        let mut rows = Vec::new();
+        let sections = try!(stream::load_entries(BufferedReader::new(reader), |game, insult, entry| {
+            let insult = push_nfc(&mut text, insult[]);
+            let retort = push_nfc(&mut text, entry.retort[]);
+            rows.push(Row { game: game, insult: insult, retort: retort });
+        }));
+        Ok(CompactInsults::pack(text, rows, &sections))
+    }
+
+    /// Order `rows`, keeping the last of an insult repeated in a game, and
+    /// answer every insult. The failed retorts and the lookup priority come
+    /// from `sections`.
+    fn pack(text: String, rows: Vec<Row>, sections: &Insults) -> CompactInsults {
+        let mut text = text;
+        let mut order: Vec<uint> = range(0, rows.len()).collect();
+        order.sort_by(|&a, &b| {
+            let (x, y) = (&rows[a], &rows[b]);
+            (x.game, text_of(text[], &x.insult)).cmp(&(y.game, text_of(text[], &y.insult)))
+        });
+        let mut kept = Vec::with_capacity(rows.len());
+        for (n, &i) in order.iter().enumerate() {
+            let row = &rows[i];
+            let replaced = order.get(n + 1).map_or(false, |&j| {
+                rows[j].game == row.game && text_of(text[], &rows[j].insult) == text_of(text[], &row.insult)
+            });
+            if !replaced {
+                kept.push(row.clone());
+            }
+        }
+        let rows = kept;
+
+        let mut failed_retorts = Vec::new();
+        for x in sections.failed_retorts().iter() {
+            failed_retorts.push(push(&mut text, x[]));
+        }
+
+        let mut order: Vec<uint> = range(0, rows.len()).collect();
+        order.sort_by(|&a, &b| text_of(text[], &rows[a].insult).cmp(text_of(text[], &rows[b].insult)));
+        let mut answers = Vec::new();
+        // Custom retorts, packed once each
+        let mut custom: Vec<(&'static str, Span)> = Vec::new();
+        let mut start = 0;
+        while start < order.len() {
+            let games = order[start..].iter().take_while(|&&i|
+                text_of(text[], &rows[i].insult) == text_of(text[], &rows[order[start]].insult)).count();
+            let retort = match answer(rows[], order[start..start + games], sections.game_priority()) {
+                Some(Packed(x)) => Some(x),
+                Some(Custom(s)) => match custom.iter().position(|&(x, _)| x == s) {
+                    Some(n) => {
+                        let (_, ref x) = custom[n];
+                        Some(x.clone())
+                    },
+                    None => {
+                        let span = push(&mut text, s);
+                        custom.push((s, span.clone()));
+                        Some(span)
+                    },
+                },
+                None => None,
+            };
+            if let Some(retort) = retort {
+                answers.push((rows[order[start]].insult.clone(), retort));
+            }
+            start += games;
+        }
+
+        CompactInsults { text: text, rows: rows, answers: answers, failed_retorts: failed_retorts }
+    }
+
+    pub fn len(&self) -> uint {
+        self.rows.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    /// Every entry, ordered by game and then by insult.
+    pub fn iter<'a>(&'a self) -> Entries<'a> {
+        Entries { text: self.text[], rows: self.rows.iter() }
+    }
+
+    /// The entries of `game`, ordered by insult.
+    pub fn entries<'a>(&'a self, game: Game) -> Entries<'a> {
+        let start = self.rows.iter().position(|x| x.game == game).unwrap_or(self.rows.len());
+        let len = self.rows[start..].iter().take_while(|x| x.game == game).count();
+        Entries { text: self.text[], rows: self.rows[start..start + len].iter() }
+    }
+
+    /// The retort to `insult`, like `Insults::retort` on the entries packed.
+    pub fn retort<'a>(&'a self, insult: &str) -> Option<&'a str> {
+        let insult = nfc(insult);
+        let (mut lo, mut hi) = (0, self.answers.len());
+        while lo < hi {
+            let mid = (lo + hi) / 2;
+            let (ref x, _) = self.answers[mid];
+            if text_of(self.text[], x) < insult[] {
+                lo = mid + 1;
+            } else {
+                hi = mid;
+            }
+        }
+        match self.answers.get(lo) {
+            Some(&(ref x, ref retort)) if text_of(self.text[], x) == insult[] =>
+                Some(text_of(self.text[], retort)),
+            _ => None,
+        }
+    }
+
+    pub fn failed_retorts<'a>(&'a self) -> Vec<&'a str> {
+        self.failed_retorts.iter().map(|x| text_of(self.text[], x)).collect()
+    }
+
+    /// Bytes taken by the strings.
+    pub fn text_len(&self) -> uint {
+        self.text.len()
+    }
+}
+
+/// The entries of a `CompactInsults`.
+pub struct Entries<'a> {
+    text: &'a str,
+    rows: slice::Items<'a, Row>,
+}
+
+impl<'a> Iterator<CompactEntry<'a>> for Entries<'a> {
+    fn next(&mut self) -> Option<CompactEntry<'a>> {
+        let text = self.text;
+        self.rows.next().map(|row| CompactEntry {
+            game: row.game,
+            insult: text_of(text, &row.insult),
+            retort: text_of(text, &row.retort),
+        })
+    }
+}
+
+impl Insults {
+    /// The dataset packed into one buffer, see the `compact` module.
+    pub fn compact(&self) -> CompactInsults {
+        CompactInsults::new(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::File;
+
+    use game::{Game, SwordMaster};
+    use insults::Insults;
+    use super::*;
+
+    #[test]
+    fn test_compact() {
+        let mi = Insults::new("insults.json");
+        let compact = mi.compact();
+        assert_eq!(compact.len(), mi.len());
+        for game in Game::all().iter() {
+            let packed: Vec<(&str, &str)> = compact.entries(*game).map(|x| (x.insult, x.retort)).collect();
+            let entries: Vec<(&str, &str)> = mi.entries(*game).into_iter()
+                .map(|(insult, entry)| (insult[], entry.retort[])).collect();
+            assert_eq!(packed, entries);
+        }
+        for insult in mi.insults().into_iter() {
+            assert_eq!(compact.retort(insult[]), mi.retort(insult[]));
+        }
+        let rottingham = mi.captain_rottingham_insults()[0];
+        assert_eq!(compact.retort(rottingham[]), Some("That's not fair, you're using Captain Rottingham's insults!"));
+        assert_eq!(compact.retort("Oh yeah?"), None);
+        assert!(compact.iter().any(|x| x.game == SwordMaster));
+        assert_eq!(compact.failed_retorts().len(), mi.failed_retorts().len());
+    }
+
+    #[test]
+    fn test_from_reader() {
+        let mi = Insults::new("insults.json");
+        let compact = CompactInsults::from_reader(File::open(&Path::new("insults.json")).unwrap()).unwrap();
+        assert_eq!(compact.len(), mi.len());
+        let packed = mi.compact();
+        let streamed: Vec<CompactEntry> = compact.iter().collect();
+        let entries: Vec<CompactEntry> = packed.iter().collect();
+        assert_eq!(streamed, entries);
+        for insult in mi.insults().into_iter() {
+            assert_eq!(compact.retort(insult[]), mi.retort(insult[]));
+        }
+        let failed_retorts: Vec<&str> = mi.failed_retorts().iter().map(|x| x[]).collect();
+        assert_eq!(compact.failed_retorts(), failed_retorts);
+        assert!(CompactInsults::from_reader(File::open(&Path::new("Cargo.toml")).unwrap()).is_err());
+    }
+}
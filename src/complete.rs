@@ -0,0 +1,81 @@
+//! Autocompletion of insults as they are typed.
+
+use insults::Insults;
+
+/// Insults sorted for prefix searches.
+pub struct Completions<'a> {
+    sorted: Vec<&'a str>,
+}
+
+impl<'a> Completions<'a> {
+    /// Index the insults of `insults` and its fallbacks.
+    pub fn new(insults: &'a Insults) -> Completions<'a> {
+        let mut sorted = Vec::new();
+        let mut current = Some(insults);
+        loop {
+            match current {
+                Some(x) => {
+                    sorted.extend(x.insults().into_iter().map(|s| s[]));
+                    current = x.fallback();
+                },
+                None => break,
+            }
+        }
+        sorted.sort();
+        sorted.dedup();
+        Completions { sorted: sorted }
+    }
+
+    /// All insults starting with `prefix`, in order.
+    pub fn complete(&self, prefix: &str) -> Vec<&'a str> {
+        let start = self.lower_bound(prefix);
+        self.sorted[start..].iter()
+            .take_while(|x| x.starts_with(prefix))
+            .map(|x| *x)
+            .collect()
+    }
+
+    /// Index of the first insult not less than `s`.
+    fn lower_bound(&self, s: &str) -> uint {
+        let (mut lo, mut hi) = (0, self.sorted.len());
+        while lo < hi {
+            let mid = (lo + hi) / 2;
+            if self.sorted[mid] < s {
+                lo = mid + 1;
+            } else {
+                hi = mid;
+            }
+        }
+        lo
+    }
+}
+
+impl Insults {
+    /// An index for completing insults, build it once and query it on every keystroke.
+    pub fn completions<'a>(&'a self) -> Completions<'a> {
+        Completions::new(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use insults::Insults;
+
+    #[test]
+    fn test_complete() {
+        let mi = Insults::new("insults.json");
+        let completions = mi.completions();
+
+        let res = completions.complete("You fight like");
+        assert!(res.contains(&"You fight like a dairy farmer."));
+        assert!(res.iter().all(|x| x.starts_with("You fight like")));
+        assert_eq!(completions.complete("You fight like a dairy farmer."),
+            vec!["You fight like a dairy farmer."]);
+        assert!(completions.complete("Zzz").is_empty());
+
+        let de = Insults::with_locale("insults.json", "de");
+        let completions = de.completions();
+        assert_eq!(completions.complete("Du k"), vec!["Du kämpfst wie ein dummer Bauer."]);
+        assert!(completions.complete("You fight like").len() > 0);
+    }
+}
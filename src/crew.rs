@@ -0,0 +1,161 @@
+//! Random pirates to fight, for filling tournaments with some variety.
+//!
+//! A `Generator` makes pirates with a name, a few quirks and the insults
+//! they know, the same crew for the same seed.
+
+use std::rand::{mod, Isaac64Rng, Rng, SeedableRng};
+
+use insults::Insults;
+
+/// Habits of a pirate.
+#[deriving(Clone, PartialEq, Eq, Show)]
+pub enum Quirk {
+    /// Throws the shorter half of the insults it knows.
+    PrefersShort,
+    /// Doesn't throw an insult twice until it has thrown all it knows.
+    NeverRepeats,
+    /// Now and then throws a line of the Sword Master.
+    Cheats,
+}
+
+static QUIRKS: [Quirk, ..3] = [PrefersShort, NeverRepeats, Cheats];
+
+/// Chance of a cheater throwing a Sword Master line.
+const CHEAT_CHANCE: f64 = 0.2;
+
+static FIRST_NAMES: &'static [&'static str] = &[
+    "Scurvy", "One-Eyed", "Mad", "Salty", "Peg-Leg", "Bloody", "Toothless", "Cap'n",
+];
+
+static LAST_NAMES: &'static [&'static str] = &[
+    "Pete", "Morgan", "McGee", "Bones", "Flint", "Silver", "Grogbeard", "LeBlanc",
+];
+
+/// A generated opponent.
+pub struct Pirate {
+    pub name: String,
+    pub quirks: Vec<Quirk>,
+    /// The insults the pirate can throw and retort to.
+    pub known: Vec<String>,
+    thrown: Vec<String>,
+    rng: Isaac64Rng,
+}
+
+impl Pirate {
+    pub fn has(&self, quirk: Quirk) -> bool {
+        self.quirks.contains(&quirk)
+    }
+
+    pub fn knows(&self, insult: &str) -> bool {
+        self.known.iter().any(|x| x[] == insult)
+    }
+
+    /// The insult to throw, by the quirks of the pirate.
+    pub fn insult<'a>(&mut self, insults: &'a Insults) -> &'a str {
+        if self.has(Cheats) && self.rng.gen::<f64>() < CHEAT_CHANCE {
+            if let Some(x) = self.rng.choose(insults.sword_master_insults()[]) {
+                return x[];
+            }
+        }
+
+        let mut candidates: Vec<&String> = insults.insults().into_iter().filter(|x| self.knows(x[])).collect();
+        if self.has(NeverRepeats) {
+            if candidates.iter().all(|x| self.thrown.contains(*x)) {
+                self.thrown.clear();
+            }
+            candidates.retain(|x| !self.thrown.contains(*x));
+        }
+        if self.has(PrefersShort) {
+            candidates.sort_by(|a, b| a.chars().count().cmp(&b.chars().count()));
+            let half = (candidates.len() + 1) / 2;
+            candidates.truncate(half);
+        }
+        let res = match self.rng.choose(candidates[]) {
+            Some(x) => x[],
+            None => insults.rand_insult(&mut self.rng)[],
+        };
+        self.thrown.push(res.to_string());
+        res
+    }
+
+    /// The retort to `insult` if the pirate knows it, otherwise one of the opponent's wrong answers.
+    pub fn retort<'a>(&mut self, insults: &'a Insults, insult: &str) -> &'a str {
+        match insults.retort(insult) {
+            Some(x) if self.knows(insult) => x,
+            _ => insults.rand_opponent_failed_retort(&mut self.rng),
+        }
+    }
+}
+
+/// Makes pirates, the same ones for the same seed.
+pub struct Generator {
+    rng: Isaac64Rng,
+    /// Least and most share of the insults a pirate knows.
+    knowledge: (f64, f64),
+    /// Chance of a pirate having each quirk.
+    quirk_chance: f64,
+}
+
+impl Generator {
+    pub fn new(seed: u64) -> Generator {
+        Generator { rng: SeedableRng::from_seed([seed][]), knowledge: (0.2, 0.8), quirk_chance: 0.3 }
+    }
+
+    /// Let pirates know between `least` and `most` of the insults, 0.2 to 0.8 by default.
+    pub fn knowledge(mut self, least: f64, most: f64) -> Generator {
+        self.knowledge = (least.max(0.0).min(1.0), most.max(least).min(1.0));
+        self
+    }
+
+    /// Give pirates each quirk with the chance `chance`, 0.3 by default.
+    pub fn quirk_chance(mut self, chance: f64) -> Generator {
+        self.quirk_chance = chance;
+        self
+    }
+
+    /// A new pirate knowing some of `insults`.
+    pub fn generate(&mut self, insults: &Insults) -> Pirate {
+        let name = format!("{} {}", self.rng.choose(FIRST_NAMES).unwrap(), self.rng.choose(LAST_NAMES).unwrap());
+        let quirks = QUIRKS.iter().filter(|_| self.rng.gen::<f64>() < self.quirk_chance).map(|x| *x).collect();
+
+        let mut all: Vec<String> = insults.insults().into_iter().map(|x| x.clone()).collect();
+        all.sort();
+        all.dedup();
+        let (least, most) = self.knowledge;
+        let share = least + (most - least) * self.rng.gen::<f64>();
+        let n = ((share * all.len() as f64).round() as uint).max(1).min(all.len());
+        let known = if all.is_empty() { Vec::new() } else { rand::sample(&mut self.rng, all.into_iter(), n) };
+
+        Pirate { name: name, quirks: quirks, known: known, thrown: Vec::new(), rng: SeedableRng::from_seed([self.rng.gen::<u64>()][]) }
+    }
+
+    /// `n` new pirates, like the opponents of a tournament.
+    pub fn crew(&mut self, insults: &Insults, n: uint) -> Vec<Pirate> {
+        Vec::from_fn(n, |_| self.generate(insults))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use insults::Insults;
+    use super::*;
+
+    #[test]
+    fn test_generator() {
+        let mi = Insults::new("insults.json");
+        let names = |seed| Generator::new(seed).crew(&mi, 3).into_iter().map(|x| x.name).collect::<Vec<String>>();
+        assert_eq!(names(7), names(7));
+
+        let mut pirate = Generator::new(7).knowledge(0.5, 0.5).quirk_chance(0.0).generate(&mi);
+        assert!(pirate.quirks.is_empty());
+        let insult = pirate.insult(&mi).to_string();
+        assert!(pirate.knows(insult[]));
+        assert_eq!(pirate.retort(&mi, insult[]), mi.retort(insult[]).unwrap());
+
+        let mut pirate = Generator::new(8).quirk_chance(0.0).generate(&mi);
+        pirate.quirks = vec![NeverRepeats];
+        pirate.known.truncate(2);
+        let first = pirate.insult(&mi).to_string();
+        assert!(pirate.insult(&mi) != first[]);
+    }
+}
@@ -0,0 +1,164 @@
+//! Banter spanning more than one insult and retort.
+//!
+//! A dialogue is a tree of lines under `dialogues` in the data file, every
+//! line answered by one of its replies:
+//!
+//! ```json
+//! "dialogues": {
+//!     "barkeep": {
+//!         "line": "You look like you've never held a sword.",
+//!         "speaker": "Barkeep",
+//!         "replies": [
+//!             {"line": "I've held plenty.", "replies": [{"line": "Butter knives don't count."}]},
+//!             {"line": "Give me a grog and we'll see."}
+//!         ]
+//!     }
+//! }
+//! ```
+//!
+//! `Dialogue` walks a tree from its first line, one chosen reply at a time.
+
+#[cfg(not(feature = "std"))]
+use prelude::*;
+#[cfg(feature = "std")]
+use std::collections::TreeMap;
+#[cfg(feature = "std")]
+use serialize::json::{mod, Json, ToJson};
+
+static NO_REPLIES: &'static [DialogueLine] = &[];
+
+/// A line of a dialogue with the lines answering it.
+#[deriving(Clone, PartialEq, Show)]
+#[cfg_attr(feature = "std", deriving(Encodable, Decodable))]
+pub struct DialogueLine {
+    pub line: String,
+    /// Who says the line.
+    pub speaker: Option<String>,
+    /// Missing for the last line of a branch.
+    pub replies: Option<Vec<DialogueLine>>,
+}
+
+impl DialogueLine {
+    pub fn new(line: &str) -> DialogueLine {
+        DialogueLine { line: String::from_str(line), speaker: None, replies: None }
+    }
+
+    /// Add `reply` to the lines answering this one.
+    pub fn reply(mut self, reply: DialogueLine) -> DialogueLine {
+        let mut replies = self.replies.take().unwrap_or(Vec::new());
+        replies.push(reply);
+        self.replies = Some(replies);
+        self
+    }
+
+    pub fn replies<'a>(&'a self) -> &'a [DialogueLine] {
+        match self.replies {
+            Some(ref x) => x[],
+            None => NO_REPLIES,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl ToJson for DialogueLine {
+    fn to_json(&self) -> Json {
+        let mut res = TreeMap::new();
+        res.insert("line".to_string(), self.line.to_json());
+        if let Some(ref x) = self.speaker {
+            res.insert("speaker".to_string(), x.to_json());
+        }
+        if let Some(ref x) = self.replies {
+            res.insert("replies".to_string(), x.to_json());
+        }
+        json::Object(res)
+    }
+}
+
+/// Walks a dialogue tree from its first line.
+#[deriving(Clone)]
+pub struct Dialogue<'a> {
+    root: &'a DialogueLine,
+    /// The lines said so far, the first line excluded.
+    path: Vec<&'a DialogueLine>,
+}
+
+impl<'a> Dialogue<'a> {
+    pub fn new(root: &'a DialogueLine) -> Dialogue<'a> {
+        Dialogue { root: root, path: Vec::new() }
+    }
+
+    /// The line just said.
+    pub fn current(&self) -> &'a DialogueLine {
+        match self.path.last() {
+            Some(x) => *x,
+            None => self.root,
+        }
+    }
+
+    /// The lines to choose from as an answer to the current one.
+    pub fn replies(&self) -> &'a [DialogueLine] {
+        self.current().replies()
+    }
+
+    /// Answer with the reply `i`, false if there's no such reply.
+    pub fn choose(&mut self, i: uint) -> bool {
+        match self.replies().get(i) {
+            Some(x) => {
+                self.path.push(x);
+                true
+            },
+            None => false,
+        }
+    }
+
+    /// Take back the last reply, false at the first line.
+    pub fn back(&mut self) -> bool {
+        self.path.pop().is_some()
+    }
+
+    /// Whether the current line has no replies.
+    pub fn is_over(&self) -> bool {
+        self.replies().is_empty()
+    }
+
+    /// Every line said so far, from the first one.
+    pub fn transcript(&self) -> Vec<&'a DialogueLine> {
+        let mut res = vec![self.root];
+        res.push_all(self.path[]);
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use insults::{Insults, fixture};
+    use super::*;
+
+    #[test]
+    fn test_dialogue() {
+        let mi = Insults::from_str(fixture(r#"{"failed_retorts": ["Oh yeah?"],
+            "dialogues": {
+                "barkeep": {
+                    "line": "You look like you've never held a sword.",
+                    "speaker": "Barkeep",
+                    "replies": [
+                        {"line": "I've held plenty.", "replies": [{"line": "Butter knives don't count."}]},
+                        {"line": "Give me a grog and we'll see."}
+                    ]
+                }
+            }}"#)[]);
+        assert_eq!(mi.dialogue_names(), vec!["barkeep"]);
+        let mut dialogue = mi.dialogue("barkeep").unwrap();
+        assert_eq!(dialogue.current().speaker, Some("Barkeep".to_string()));
+        assert_eq!(dialogue.replies().len(), 2);
+        assert!(!dialogue.choose(2) && dialogue.choose(0) && dialogue.choose(0));
+        assert!(dialogue.is_over());
+        assert_eq!(dialogue.current().line[], "Butter knives don't count.");
+        assert_eq!(dialogue.transcript().len(), 3);
+        assert!(dialogue.back() && dialogue.back() && !dialogue.back());
+
+        let tree = DialogueLine::new("Taunt").reply(DialogueLine::new("Counter").reply(DialogueLine::new("Comeback")));
+        assert_eq!(Dialogue::new(&tree).replies()[0].replies()[0].line[], "Comeback");
+        assert!(Insults::new("insults.json").dialogue("barkeep").is_none());
+    }
+}
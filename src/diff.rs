@@ -0,0 +1,105 @@
+//! What a dataset changes compared to another, like a contributed pack.
+//!
+//! Only the insults of the datasets are compared, not their fallbacks.
+
+use game::Game;
+use insults::{Insults, Entry};
+
+/// An insult whose entry differs between the datasets.
+#[deriving(Clone, PartialEq, Show)]
+pub struct Change {
+    pub insult: String,
+    pub old: Entry,
+    pub new: Entry,
+}
+
+/// The changes to the insults of a game, ordered by insult.
+#[deriving(Clone, PartialEq, Show)]
+pub struct GameDiff {
+    pub game: Game,
+    /// Insults only the other dataset has.
+    pub added: Vec<(String, Entry)>,
+    /// Insults only this dataset has.
+    pub removed: Vec<(String, Entry)>,
+    pub changed: Vec<Change>,
+}
+
+impl GameDiff {
+    /// Whether the game has the same insults in both datasets.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// The changes to every game with any, in game order.
+#[deriving(Clone, PartialEq, Show)]
+pub struct DatasetDiff {
+    pub games: Vec<GameDiff>,
+}
+
+impl DatasetDiff {
+    /// Whether the datasets have the same insults.
+    pub fn is_empty(&self) -> bool {
+        self.games.is_empty()
+    }
+
+    /// The changes to `game`, None if it has none.
+    pub fn game<'a>(&'a self, game: Game) -> Option<&'a GameDiff> {
+        self.games.iter().find(|x| x.game == game)
+    }
+}
+
+impl Insults {
+    /// What `other` adds, removes and changes compared to this dataset.
+    pub fn diff(&self, other: &Insults) -> DatasetDiff {
+        let mut games = Vec::new();
+        for game in Game::all().iter() {
+            let mut res = GameDiff { game: *game, added: Vec::new(), removed: Vec::new(), changed: Vec::new() };
+            for &(insult, old) in self.entries(*game).iter() {
+                match other.entry(*game, insult[]) {
+                    Some(new) if new == old => (),
+                    Some(new) => res.changed.push(Change {
+                        insult: insult.clone(),
+                        old: old.clone(),
+                        new: new.clone(),
+                    }),
+                    None => res.removed.push((insult.clone(), old.clone())),
+                }
+            }
+            for &(insult, new) in other.entries(*game).iter() {
+                if self.entry(*game, insult[]).is_none() {
+                    res.added.push((insult.clone(), new.clone()));
+                }
+            }
+            if !res.is_empty() {
+                games.push(res);
+            }
+        }
+        DatasetDiff { games: games }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use game::{MonkeyIsland1, SwordMaster};
+    use insults::{Insults, Entry};
+
+    #[test]
+    fn test_diff() {
+        let mi = Insults::new("insults.json");
+        assert!(mi.diff(&mi.clone()).is_empty());
+
+        let mut contribution = mi.clone();
+        contribution.insert(MonkeyIsland1, "You fight like a dairy farmer.", Entry::new("Moo."));
+        contribution.insert(MonkeyIsland1, "Your mother was a hamster!", Entry::new("Ni!"));
+        let diff = mi.diff(&contribution);
+        assert_eq!(diff.games.len(), 1);
+        let changes = diff.game(MonkeyIsland1).unwrap();
+        assert_eq!(changes.added, vec![("Your mother was a hamster!".to_string(), Entry::new("Ni!"))]);
+        assert_eq!(changes.changed[0].new.retort[], "Moo.");
+        assert!(changes.removed.is_empty() && diff.game(SwordMaster).is_none());
+
+        let reverse = contribution.diff(&mi);
+        assert_eq!(reverse.game(MonkeyIsland1).unwrap().removed.len(), 1);
+    }
+}
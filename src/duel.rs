@@ -0,0 +1,644 @@
+//! Rules engine for insult sword fighting.
+
+use std::rand::Rng;
+use std::sync::Arc;
+use std::time::Duration;
+
+use hint::{mod, FirstWord};
+use insults::Insults;
+use narrator::Narrator;
+
+/// One of the two duelists.
+#[deriving(Clone, PartialEq, Eq, Show)]
+pub enum Side {
+    Player,
+    Opponent,
+}
+
+impl Side {
+    /// The other duelist.
+    pub fn other(&self) -> Side {
+        match *self {
+            Player => Opponent,
+            Opponent => Player,
+        }
+    }
+}
+
+/// How the winner of a duel is decided.
+#[deriving(Clone, PartialEq, Show)]
+pub enum WinCondition {
+    /// The first to win this many exchanges wins the duel.
+    FirstTo(uint),
+    /// The first exchange won decides the duel, the golden insult.
+    SuddenDeath,
+    /// Most exchanges won when the clock runs out.
+    /// A tie when time is up is settled by sudden death.
+    Timed(Duration),
+}
+
+/// What happens when the defender fails to retort.
+#[deriving(Clone, PartialEq, Show)]
+pub enum FailedRetortPolicy {
+    /// The attacker scores a point and keeps the serve.
+    LosePoint,
+    /// Nobody scores, the attacker keeps the serve.
+    LoseServe,
+    /// The defender gets a hint and one more try.
+    /// A correct retry takes the serve without scoring, failing again loses the point.
+    RetryWithHint,
+}
+
+/// Who throws the next insult after an exchange.
+#[deriving(Clone, PartialEq, Show)]
+pub enum TurnPolicy {
+    /// A correct retort takes the serve, whoever wins an exchange insults next like in the games.
+    SwapOnSuccess,
+    /// A failed retort hands the serve over, whoever loses an exchange insults next.
+    SwapOnFailure,
+    /// The sides take turns insulting.
+    Alternate,
+    /// The side always insults, like bosses that never answer.
+    Fixed(Side),
+}
+
+/// What a side does in the next exchange.
+#[deriving(Clone, PartialEq, Eq, Show)]
+pub enum Role {
+    Attacker,
+    Defender,
+}
+
+/// The rules a duel is fought by.
+#[deriving(Clone, PartialEq, Show)]
+pub struct Rules {
+    pub win_condition: WinCondition,
+    pub failed_retort: FailedRetortPolicy,
+    pub turns: TurnPolicy,
+}
+
+impl Rules {
+    pub fn new(win_condition: WinCondition) -> Rules {
+        Rules {
+            win_condition: win_condition,
+            failed_retort: LosePoint,
+            turns: SwapOnSuccess,
+        }
+    }
+
+    /// Use `policy` for failed retorts.
+    pub fn with_failed_retort(mut self, policy: FailedRetortPolicy) -> Rules {
+        self.failed_retort = policy;
+        self
+    }
+
+    /// Use `policy` to decide who insults next.
+    pub fn with_turns(mut self, policy: TurnPolicy) -> Rules {
+        self.turns = policy;
+        self
+    }
+
+    /// First to win `n` exchanges.
+    pub fn first_to(n: uint) -> Rules {
+        Rules::new(FirstTo(n))
+    }
+
+    /// A single golden insult decides.
+    pub fn sudden_death() -> Rules {
+        Rules::new(SuddenDeath)
+    }
+
+    /// Most points when `time` has run out.
+    pub fn timed(time: Duration) -> Rules {
+        Rules::new(Timed(time))
+    }
+}
+
+impl Default for Rules {
+    /// First to three, like the fights around Mêlée Island.
+    fn default() -> Rules {
+        Rules::first_to(3)
+    }
+}
+
+/// Exchanges won by each side.
+#[deriving(Clone, PartialEq, Eq, Show)]
+pub struct Score {
+    pub player: uint,
+    pub opponent: uint,
+}
+
+impl Score {
+    pub fn of(&self, side: Side) -> uint {
+        match side {
+            Player => self.player,
+            Opponent => self.opponent,
+        }
+    }
+
+    fn add_point(&mut self, side: Side) {
+        match side {
+            Player => self.player += 1,
+            Opponent => self.opponent += 1,
+        }
+    }
+
+    /// The side with the most points, if any.
+    pub fn leader(&self) -> Option<Side> {
+        if self.player > self.opponent {
+            Some(Player)
+        } else if self.opponent > self.player {
+            Some(Opponent)
+        } else {
+            None
+        }
+    }
+}
+
+/// The result of an exchange.
+#[deriving(Clone, PartialEq, Show)]
+pub enum Exchange {
+    /// The side scored a point.
+    Point(Side),
+    /// The side serves next, nobody scored.
+    Serve(Side),
+    /// The defender failed but may retry, with a hint to the correct retort.
+    Retry(String),
+}
+
+/// Something happening in a duel, for UIs, loggers and sound engines.
+#[deriving(Clone, PartialEq, Show)]
+pub enum DuelEvent {
+    /// The side threw the insult.
+    InsultThrown(Side, String),
+    /// The side retorted correctly.
+    RetortSucceeded(Side, String),
+    /// The side's retort was wrong.
+    RetortFailed(Side, String),
+    /// The side didn't know any retort.
+    GaveUp(Side),
+    /// The defender gets another try with the hint.
+    HintGiven(String),
+    /// The side won the exchange and a point.
+    RoundWon(Side),
+    /// The side took the serve without scoring.
+    ServeTaken(Side),
+    /// The next exchange decides the duel.
+    SuddenDeathStarted,
+    /// The side won the duel.
+    DuelWon(Side),
+    /// A flavor line of the narrator, after the event it follows.
+    Narrated(String),
+}
+
+/// Reacts to the events of a duel.
+pub trait DuelObserver {
+    fn on_event(&mut self, event: &DuelEvent);
+}
+
+/// Sends every event down the channel, ignoring a hung up receiver.
+impl DuelObserver for Sender<DuelEvent> {
+    fn on_event(&mut self, event: &DuelEvent) {
+        let _ = self.send_opt(event.clone());
+    }
+}
+
+/// Where a duel looks up retorts.
+enum Source<'a> {
+    Borrowed(&'a Insults),
+    Shared(Arc<Insults>),
+}
+
+/// A duel in progress.
+pub struct Duel<'a> {
+    insults: Source<'a>,
+    rules: Rules,
+    score: Score,
+    elapsed: Duration,
+    sudden_death: bool,
+    winner: Option<Side>,
+    attacker: Side,
+    retrying: Option<String>,
+    observers: Vec<Box<DuelObserver + Send + 'a>>,
+    narrator: Option<Narrator>,
+}
+
+impl Duel<'static> {
+    /// A duel on a shared dataset, which can be sent to another thread.
+    pub fn shared(insults: Arc<Insults>, rules: Rules) -> Duel<'static> {
+        Duel::from_source(Shared(insults), rules)
+    }
+}
+
+impl<'a> Duel<'a> {
+    pub fn new(insults: &'a Insults, rules: Rules) -> Duel<'a> {
+        Duel::from_source(Borrowed(insults), rules)
+    }
+
+    fn from_source(insults: Source<'a>, rules: Rules) -> Duel<'a> {
+        let sudden_death = rules.win_condition == SuddenDeath;
+        Duel {
+            insults: insults,
+            rules: rules,
+            score: Score { player: 0, opponent: 0 },
+            elapsed: Duration::zero(),
+            sudden_death: sudden_death,
+            winner: None,
+            attacker: Player,
+            retrying: None,
+            observers: Vec::new(),
+            narrator: None,
+        }
+    }
+
+    /// Notify `observer` of everything that happens in the duel.
+    pub fn observe(&mut self, observer: Box<DuelObserver + Send + 'a>) {
+        self.observers.push(observer);
+    }
+
+    /// Interleave the flavor lines of `narrator` with the events.
+    pub fn with_narrator(mut self, narrator: Narrator) -> Duel<'a> {
+        self.narrator = Some(narrator);
+        self
+    }
+
+    fn emit(&mut self, event: DuelEvent) {
+        for observer in self.observers.iter_mut() {
+            observer.on_event(&event);
+        }
+        let line = self.narrator.as_mut().and_then(|x| x.narrate(&event));
+        if let Some(line) = line {
+            let narrated = Narrated(line);
+            for observer in self.observers.iter_mut() {
+                observer.on_event(&narrated);
+            }
+        }
+    }
+
+    /// Let `side` serve the first insult, the player serves by default.
+    ///
+    /// With fixed turns the fixed side serves regardless.
+    pub fn serve_first(mut self, side: Side) -> Duel<'a> {
+        self.attacker = match self.rules.turns {
+            Fixed(fixed) => fixed,
+            _ => side,
+        };
+        self
+    }
+
+    /// The dataset the duel is fought with.
+    pub fn insults(&self) -> &Insults {
+        match self.insults {
+            Borrowed(x) => x,
+            Shared(ref x) => &**x,
+        }
+    }
+
+    pub fn rules(&self) -> &Rules {
+        &self.rules
+    }
+
+    pub fn score(&self) -> &Score {
+        &self.score
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    /// The winner, once the duel is decided.
+    pub fn winner(&self) -> Option<Side> {
+        self.winner
+    }
+
+    pub fn is_over(&self) -> bool {
+        self.winner.is_some()
+    }
+
+    /// True if the next exchange decides the duel.
+    pub fn is_sudden_death(&self) -> bool {
+        self.sudden_death
+    }
+
+    /// The side throwing the next insult.
+    pub fn attacker(&self) -> Side {
+        self.attacker
+    }
+
+    /// The side answering the next insult.
+    pub fn defender(&self) -> Side {
+        self.attacker.other()
+    }
+
+    /// What `side` does in the next exchange.
+    pub fn role(&self, side: Side) -> Role {
+        if side == self.attacker { Attacker } else { Defender }
+    }
+
+    /// The insult waiting for a retry, if the defender is retrying.
+    pub fn retrying<'b>(&'b self) -> Option<&'b str> {
+        self.retrying.as_ref().map(|x| x[])
+    }
+
+    /// Play an exchange where the attacker throws `insult` and the defender answers with `retort`.
+    ///
+    /// The defender wins the exchange with the correct retort, otherwise the
+    /// failed retort policy of the rules decides.
+    pub fn exchange(&mut self, insult: &str, retort: &str) -> Exchange {
+        assert!(!self.is_over(), "the duel is already over");
+        assert!(self.retrying.is_none(), "waiting for a retry");
+
+        let attacker = self.attacker;
+        self.emit(InsultThrown(attacker, insult.to_string()));
+        if self.insults().is_retort(insult, retort) {
+            self.emit(RetortSucceeded(attacker.other(), retort.to_string()));
+            return self.point(attacker.other());
+        }
+        self.emit(RetortFailed(attacker.other(), retort.to_string()));
+        trace_event!("{} failed to retort to {}, applying {}", attacker.other(), insult,
+                     self.rules.failed_retort);
+
+        match self.rules.failed_retort {
+            LosePoint => self.point(attacker),
+            LoseServe => self.serve(false),
+            RetryWithHint => {
+                let hint = self.insults().retort(insult).map(|x| hint::reveal(x, FirstWord));
+                match hint {
+                    Some(hint) => {
+                        self.retrying = Some(insult.to_string());
+                        self.emit(HintGiven(hint.clone()));
+                        Retry(hint)
+                    },
+                    None => self.point(attacker),
+                }
+            },
+        }
+    }
+
+    /// The defender doesn't know any retort.
+    ///
+    /// Unlike a failed retort there's no retry, the attacker wins the exchange
+    /// unless the rules only take the serve.
+    pub fn give_up(&mut self) -> Exchange {
+        assert!(!self.is_over(), "the duel is already over");
+        self.retrying = None;
+
+        let attacker = self.attacker;
+        self.emit(GaveUp(attacker.other()));
+        match self.rules.failed_retort {
+            LoseServe => self.serve(false),
+            _ => self.point(attacker),
+        }
+    }
+
+    /// Retry the retort to the insult of the last exchange.
+    pub fn retry(&mut self, retort: &str) -> Exchange {
+        let insult = self.retrying.take().expect("no exchange to retry");
+
+        let attacker = self.attacker;
+        if self.insults().is_retort(insult[], retort) {
+            self.emit(RetortSucceeded(attacker.other(), retort.to_string()));
+            self.serve(true)
+        } else {
+            self.emit(RetortFailed(attacker.other(), retort.to_string()));
+            self.point(attacker)
+        }
+    }
+
+    /// Pass the serve on by the turn policy, `defended` if the defender won the exchange.
+    fn next_turn(&mut self, defended: bool) {
+        let attacker = self.attacker;
+        self.attacker = match self.rules.turns {
+            SwapOnSuccess => if defended { attacker.other() } else { attacker },
+            SwapOnFailure => if defended { attacker } else { attacker.other() },
+            Alternate => attacker.other(),
+            Fixed(side) => side,
+        };
+    }
+
+    /// End an exchange without a point.
+    fn serve(&mut self, defended: bool) -> Exchange {
+        let attacker = self.attacker;
+        self.next_turn(defended);
+        if self.attacker != attacker {
+            let serving = self.attacker;
+            self.emit(ServeTaken(serving));
+        }
+        Serve(self.attacker)
+    }
+
+    fn point(&mut self, won_by: Side) -> Exchange {
+        self.score.add_point(won_by);
+        let defended = won_by != self.attacker;
+        self.next_turn(defended);
+        self.emit(RoundWon(won_by));
+        trace_event!("point to {}, {} - {}", won_by, self.score.player, self.score.opponent);
+
+        if self.sudden_death {
+            self.winner = Some(won_by);
+        } else if let FirstTo(n) = self.rules.win_condition {
+            if self.score.of(won_by) >= n {
+                self.winner = Some(won_by);
+            }
+        }
+        if let Some(winner) = self.winner {
+            self.emit(DuelWon(winner));
+        }
+        Point(won_by)
+    }
+
+    /// Let time pass on the duel clock.
+    ///
+    /// Only timed duels care about the clock. When time is up the leader wins,
+    /// a tie goes to the golden insult.
+    pub fn elapse(&mut self, time: Duration) {
+        if self.is_over() {
+            return;
+        }
+        self.elapsed = self.elapsed + time;
+
+        if let Timed(limit) = self.rules.win_condition {
+            if self.elapsed >= limit && !self.sudden_death {
+                match self.score.leader() {
+                    Some(side) => {
+                        self.winner = Some(side);
+                        self.emit(DuelWon(side));
+                    },
+                    None => {
+                        self.sudden_death = true;
+                        self.emit(SuddenDeathStarted);
+                    },
+                }
+            }
+        }
+    }
+}
+
+/// A computer opponent.
+#[deriving(Clone, PartialEq, Show)]
+pub struct Ai {
+    /// Chance of knowing the retort to an insult, between 0 and 1.
+    pub skill: f64,
+}
+
+impl Ai {
+    pub fn new(skill: f64) -> Ai {
+        Ai { skill: skill }
+    }
+
+    /// The insult to throw.
+    pub fn insult<'a, R: Rng>(&self, insults: &'a Insults, rng: &mut R) -> &'a str {
+        insults.rand_insult(rng)[]
+    }
+
+    /// The retort to `insult`, one of the opponent's wrong answers if it doesn't know it.
+    pub fn retort<'a, R: Rng>(&self, insults: &'a Insults, insult: &str, rng: &mut R) -> &'a str {
+        match insults.retort(insult) {
+            Some(x) if rng.gen::<f64>() < self.skill => x,
+            _ => insults.rand_opponent_failed_retort(rng),
+        }
+    }
+}
+
+impl Default for Ai {
+    /// Knows half the retorts, like a pirate fresh off the boat.
+    fn default() -> Ai {
+        Ai::new(0.5)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rand;
+    use std::time::Duration;
+
+    use insults::Insults;
+    use super::*;
+
+    #[test]
+    fn test_win_conditions() {
+        let mi = Insults::new("insults.json");
+        let insult = "You fight like a dairy farmer.";
+        let retort = "How appropriate. You fight like a cow.";
+
+        let mut duel = Duel::new(&mi, Rules::first_to(2)).serve_first(Opponent);
+        assert_eq!(duel.exchange(insult, retort), Point(Player));
+        assert_eq!(duel.attacker(), Player);
+        assert_eq!(duel.exchange(insult, "Oh yeah?"), Point(Player));
+        assert_eq!(duel.winner(), Some(Player));
+
+        let mut duel = Duel::new(&mi, Rules::sudden_death());
+        assert!(duel.is_sudden_death());
+        duel.exchange(insult, "Oh yeah?");
+        assert_eq!(duel.winner(), Some(Player));
+
+        let mut duel = Duel::new(&mi, Rules::timed(Duration::seconds(60))).serve_first(Opponent);
+        duel.exchange(insult, retort);
+        duel.exchange(insult, retort);
+        duel.elapse(Duration::seconds(61));
+        assert_eq!(duel.winner(), None);
+        assert!(duel.is_sudden_death());
+        duel.exchange(insult, "Oh yeah?");
+        assert_eq!(duel.winner(), Some(Opponent));
+    }
+
+    #[test]
+    fn test_failed_retort_policy() {
+        let mi = Insults::new("insults.json");
+        let insult = "You fight like a dairy farmer.";
+        let retort = "How appropriate. You fight like a cow.";
+
+        let mut duel = Duel::new(&mi, Rules::first_to(3).with_failed_retort(LoseServe));
+        assert_eq!(duel.exchange(insult, "Oh yeah?"), Serve(Player));
+        assert_eq!(duel.score().player, 0);
+
+        let mut duel = Duel::new(&mi, Rules::first_to(3).with_failed_retort(RetryWithHint));
+        assert_eq!(duel.exchange(insult, "Oh yeah?"), Retry("How ...".to_string()));
+        assert_eq!(duel.retrying(), Some(insult));
+        assert_eq!(duel.retry(retort), Serve(Opponent));
+        assert_eq!(duel.score().opponent, 0);
+
+        assert_eq!(duel.exchange(insult, "Oh yeah?"), Retry("How ...".to_string()));
+        assert_eq!(duel.retry("Oh yeah?"), Point(Opponent));
+
+        assert_eq!(duel.give_up(), Point(Opponent));
+        assert_eq!(duel.score().opponent, 2);
+    }
+
+    #[test]
+    fn test_turn_policy() {
+        let mi = Insults::new("insults.json");
+        let insult = "You fight like a dairy farmer.";
+        let retort = "How appropriate. You fight like a cow.";
+
+        let mut duel = Duel::new(&mi, Rules::first_to(5).with_turns(SwapOnFailure));
+        duel.exchange(insult, retort);
+        assert_eq!(duel.attacker(), Player);
+        duel.exchange(insult, "Oh yeah?");
+        assert_eq!(duel.attacker(), Opponent);
+        assert_eq!((duel.role(Opponent), duel.defender()), (Attacker, Player));
+
+        let mut duel = Duel::new(&mi, Rules::first_to(5).with_turns(Alternate));
+        duel.exchange(insult, "Oh yeah?");
+        assert_eq!(duel.attacker(), Opponent);
+
+        let rules = Rules::first_to(5).with_turns(Fixed(Opponent)).with_failed_retort(RetryWithHint);
+        let mut duel = Duel::new(&mi, rules).serve_first(Player);
+        assert_eq!(duel.attacker(), Opponent);
+        duel.exchange(insult, retort);
+        duel.exchange(insult, "Oh yeah?");
+        assert_eq!(duel.retry(retort), Serve(Opponent));
+        assert_eq!(duel.role(Player), Defender);
+    }
+
+    #[test]
+    fn test_observer() {
+        let mi = Insults::new("insults.json");
+        let insult = "You fight like a dairy farmer.";
+        let (tx, rx) = channel();
+
+        let mut duel = Duel::new(&mi, Rules::first_to(1).with_failed_retort(RetryWithHint));
+        duel.observe(box tx);
+        duel.exchange(insult, "Oh yeah?");
+        duel.retry("Oh yeah?");
+        // Hangs up the channel
+        drop(duel);
+
+        let events: Vec<DuelEvent> = rx.iter().collect();
+        assert_eq!(events, vec![
+            InsultThrown(Player, insult.to_string()),
+            RetortFailed(Opponent, "Oh yeah?".to_string()),
+            HintGiven("How ...".to_string()),
+            RetortFailed(Opponent, "Oh yeah?".to_string()),
+            RoundWon(Player),
+            DuelWon(Player),
+        ]);
+    }
+
+    #[test]
+    fn test_shared() {
+        let mi = Insults::new("insults.json").into_shared();
+        let (tx, rx) = channel();
+        for _ in range(0u, 2) {
+            let (mi, tx) = (mi.clone(), tx.clone());
+            spawn(proc() {
+                let mut duel = Duel::shared(mi, Rules::sudden_death());
+                duel.exchange("You fight like a dairy farmer.", "How appropriate. You fight like a cow.");
+                tx.send(duel.winner());
+            });
+        }
+        assert_eq!((rx.recv(), rx.recv()), (Some(Opponent), Some(Opponent)));
+    }
+
+    #[test]
+    fn test_ai() {
+        let mi = Insults::new("insults.json");
+        let insult = "You fight like a dairy farmer.";
+        let mut rng = rand::task_rng();
+
+        assert_eq!(Ai::new(1.0).retort(&mi, insult, &mut rng), "How appropriate. You fight like a cow.");
+        let failed = Ai::new(0.0).retort(&mi, insult, &mut rng);
+        assert!(mi.opponent_failed_retorts().iter().any(|x| x[] == failed));
+        assert!(mi.retort(Ai::default().insult(&mi, &mut rng)).is_some());
+    }
+}
@@ -0,0 +1,295 @@
+//! C interface, for adventure game engines written in C and C++.
+//!
+//! Build with the `ffi` feature and link against the static library, the
+//! functions are declared in `include/mi_insults.h`:
+//!
+//! ```c
+//! MiInsults *mi = mi_insults_new("insults.json");
+//! const char *retort = mi_insults_retort(mi, "You fight like a dairy farmer.");
+//! mi_insults_free(mi);
+//! ```
+//!
+//! Returned strings belong to the dataset and stay valid until it's freed,
+//! except hints which stay valid until the next exchange of their duel.
+//!
+//! `python/mi_insults.py` wraps the shared library for Python and
+//! `node/index.js` for Node.js.
+
+use libc::{c_char, c_int, c_uint};
+use std::c_str::CString;
+use std::collections::HashMap;
+use std::mem;
+use std::ptr;
+use std::rand::{task_rng, Rng};
+
+use duel::{Duel, Rules, Player, Opponent, Exchange, Point, Serve, Retry};
+use insults::Insults;
+use schema::LoadError;
+
+/// A dataset with its retorts as C strings, opaque to C.
+pub struct MiInsults {
+    insults: Insults,
+    /// Retorts by insult, including those of the fallbacks.
+    retorts: HashMap<String, CString>,
+    /// Insults of the dataset with a retort, like `Insults::answered_insults`.
+    all: Vec<CString>,
+    failed_retorts: Vec<CString>,
+}
+
+impl MiInsults {
+    fn new(insults: Insults) -> MiInsults {
+        let retorts = {
+            let mut retorts = HashMap::new();
+            let mut current = Some(&insults);
+            loop {
+                match current {
+                    Some(x) => {
+                        for insult in x.insults().into_iter() {
+                            if retorts.contains_key(insult) {
+                                continue;
+                            }
+                            // Insults of games the lookup policy leaves out have no answer
+                            if let Some(retort) = insults.retort(insult[]) {
+                                retorts.insert(insult.clone(), retort.to_c_str());
+                            }
+                        }
+                        current = x.fallback();
+                    },
+                    None => break,
+                }
+            }
+            retorts
+        };
+        let all = insults.answered_insults().iter().map(|x| x.to_c_str()).collect();
+        let failed_retorts = insults.failed_retorts().iter().map(|x| x.to_c_str()).collect();
+        MiInsults {
+            insults: insults,
+            retorts: retorts,
+            all: all,
+            failed_retorts: failed_retorts,
+        }
+    }
+}
+
+unsafe fn to_str<'a>(s: &'a *const c_char) -> Option<&'a str> {
+    if s.is_null() {
+        return None;
+    }
+    mem::transmute(CString::new(*s, false).as_str())
+}
+
+unsafe fn into_ptr(insults: Result<Insults, LoadError>) -> *mut MiInsults {
+    match insults {
+        Ok(x) => mem::transmute(box MiInsults::new(x)),
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+/// Load the dataset at `path`, null if it can't be loaded.
+#[no_mangle]
+pub unsafe extern "C" fn mi_insults_new(path: *const c_char) -> *mut MiInsults {
+    match to_str(&path) {
+        Some(x) => into_ptr(Insults::try_from_path(x)),
+        None => ptr::null_mut(),
+    }
+}
+
+/// Load the dataset from a JSON string, null if it's invalid.
+#[no_mangle]
+pub unsafe extern "C" fn mi_insults_from_json(json: *const c_char) -> *mut MiInsults {
+    match to_str(&json) {
+        Some(x) => into_ptr(Insults::try_from_str(x)),
+        None => ptr::null_mut(),
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn mi_insults_free(mi: *mut MiInsults) {
+    if !mi.is_null() {
+        let _: Box<MiInsults> = mem::transmute(mi);
+    }
+}
+
+/// The retort to `insult`, null if there is none.
+#[no_mangle]
+pub unsafe extern "C" fn mi_insults_retort(mi: *const MiInsults, insult: *const c_char) -> *const c_char {
+    let retort = to_str(&insult).and_then(|x| (*mi).retorts.get(&x.to_string()));
+    match retort {
+        Some(x) => x.as_ptr(),
+        None => ptr::null(),
+    }
+}
+
+/// 1 if `retort` is the retort to `insult`, otherwise 0.
+#[no_mangle]
+pub unsafe extern "C" fn mi_insults_is_retort(mi: *const MiInsults, insult: *const c_char,
+                                              retort: *const c_char) -> c_int {
+    match (to_str(&insult), to_str(&retort)) {
+        (Some(insult), Some(retort)) if (*mi).insults.is_retort(insult, retort) => 1,
+        _ => 0,
+    }
+}
+
+/// A random insult, null if there are no insults.
+#[no_mangle]
+pub unsafe extern "C" fn mi_insults_rand_insult(mi: *const MiInsults) -> *const c_char {
+    rand_ptr((*mi).all[])
+}
+
+/// A random failed retort, null if there are none.
+#[no_mangle]
+pub unsafe extern "C" fn mi_insults_rand_failed_retort(mi: *const MiInsults) -> *const c_char {
+    rand_ptr((*mi).failed_retorts[])
+}
+
+fn rand_ptr(strings: &[CString]) -> *const c_char {
+    match task_rng().choose(strings) {
+        Some(x) => x.as_ptr(),
+        None => ptr::null(),
+    }
+}
+
+/// A duel on a dataset, opaque to C.
+pub struct MiDuel {
+    duel: Duel<'static>,
+    hint: Option<CString>,
+}
+
+/// Start a duel on `mi` won by the first to `first_to` points.
+///
+/// The duel must be freed before `mi`.
+#[no_mangle]
+pub unsafe extern "C" fn mi_duel_new(mi: *const MiInsults, first_to: c_uint) -> *mut MiDuel {
+    let insults: &'static Insults = mem::transmute(&(*mi).insults);
+    mem::transmute(box MiDuel {
+        duel: Duel::new(insults, Rules::first_to(first_to as uint)),
+        hint: None,
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn mi_duel_free(duel: *mut MiDuel) {
+    if !duel.is_null() {
+        let _: Box<MiDuel> = mem::transmute(duel);
+    }
+}
+
+/// An exchange as a code: 0 and 1 are points to the player and the opponent,
+/// 2 and 3 their serve and 4 a retry with a hint. -1 if it couldn't be played.
+fn exchange_code(duel: &mut MiDuel, exchange: Exchange) -> c_int {
+    let side = |side| match side { Player => 0, Opponent => 1 };
+    duel.hint = None;
+    match exchange {
+        Point(x) => side(x),
+        Serve(x) => 2 + side(x),
+        Retry(hint) => {
+            duel.hint = Some(hint.to_c_str());
+            4
+        },
+    }
+}
+
+/// Play an exchange, see `exchange_code` for the result.
+#[no_mangle]
+pub unsafe extern "C" fn mi_duel_exchange(duel: *mut MiDuel, insult: *const c_char,
+                                          retort: *const c_char) -> c_int {
+    let duel = &mut *duel;
+    if duel.duel.is_over() || duel.duel.retrying().is_some() {
+        return -1;
+    }
+    let exchange = match (to_str(&insult), to_str(&retort)) {
+        (Some(insult), Some(retort)) => duel.duel.exchange(insult, retort),
+        _ => return -1,
+    };
+    exchange_code(duel, exchange)
+}
+
+/// Retry the last exchange, see `exchange_code` for the result.
+#[no_mangle]
+pub unsafe extern "C" fn mi_duel_retry(duel: *mut MiDuel, retort: *const c_char) -> c_int {
+    let duel = &mut *duel;
+    if duel.duel.retrying().is_none() {
+        return -1;
+    }
+    let exchange = match to_str(&retort) {
+        Some(retort) => duel.duel.retry(retort),
+        None => return -1,
+    };
+    exchange_code(duel, exchange)
+}
+
+/// The attacker doesn't know a retort, see `exchange_code` for the result.
+#[no_mangle]
+pub unsafe extern "C" fn mi_duel_give_up(duel: *mut MiDuel) -> c_int {
+    let duel = &mut *duel;
+    if duel.duel.is_over() {
+        return -1;
+    }
+    let exchange = duel.duel.give_up();
+    exchange_code(duel, exchange)
+}
+
+/// The hint of the last retry, null if there is none.
+#[no_mangle]
+pub unsafe extern "C" fn mi_duel_hint(duel: *const MiDuel) -> *const c_char {
+    match (*duel).hint {
+        Some(ref x) => x.as_ptr(),
+        None => ptr::null(),
+    }
+}
+
+/// Points of the player (0) or the opponent (1).
+#[no_mangle]
+pub unsafe extern "C" fn mi_duel_score(duel: *const MiDuel, side: c_int) -> c_uint {
+    (*duel).duel.score().of(if side == 0 { Player } else { Opponent }) as c_uint
+}
+
+/// Who throws the next insult, 0 for the player and 1 for the opponent.
+#[no_mangle]
+pub unsafe extern "C" fn mi_duel_attacker(duel: *const MiDuel) -> c_int {
+    match (*duel).duel.attacker() {
+        Player => 0,
+        Opponent => 1,
+    }
+}
+
+/// The winner, 0 for the player, 1 for the opponent and -1 if undecided.
+#[no_mangle]
+pub unsafe extern "C" fn mi_duel_winner(duel: *const MiDuel) -> c_int {
+    match (*duel).duel.winner() {
+        Some(Player) => 0,
+        Some(Opponent) => 1,
+        None => -1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::c_str::CString;
+
+    use super::*;
+
+    #[test]
+    fn test_ffi() {
+        unsafe {
+            let mi = mi_insults_new("insults.json".to_c_str().as_ptr());
+            assert!(!mi.is_null());
+
+            let insult = "You fight like a dairy farmer.".to_c_str();
+            let retort = CString::new(mi_insults_retort(mi as *const MiInsults, insult.as_ptr()), false);
+            assert_eq!(retort.as_str(), Some("How appropriate. You fight like a cow."));
+            assert_eq!(mi_insults_is_retort(mi as *const MiInsults, insult.as_ptr(), retort.as_ptr()), 1);
+            assert!(mi_insults_retort(mi as *const MiInsults, retort.as_ptr()).is_null());
+            assert!(!mi_insults_rand_insult(mi as *const MiInsults).is_null());
+
+            let duel = mi_duel_new(mi as *const MiInsults, 1);
+            assert_eq!(mi_duel_exchange(duel, insult.as_ptr(), retort.as_ptr()), 1);
+            assert_eq!(mi_duel_winner(duel as *const MiDuel), 1);
+            assert_eq!(mi_duel_exchange(duel, insult.as_ptr(), retort.as_ptr()), -1);
+            mi_duel_free(duel);
+            mi_insults_free(mi);
+
+            assert!(mi_insults_new("missing.json".to_c_str().as_ptr()).is_null());
+        }
+    }
+}
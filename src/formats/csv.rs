@@ -0,0 +1,300 @@
+//! CSV import and export, for translators working in spreadsheets.
+//!
+//! Every row is an insult with its retort, failed retorts have `failed_retorts`
+//! or `opponent_failed_retorts` as game and no insult, banter lines their
+//! section of `banter`:
+//!
+//! ```csv
+//! game,insult,retort,locale,speaker,context,rating,audio,retort_audio,canonical
+//! failed_retorts,,Oh yeah?,en,,,,,,
+//! taunts,,Draw your blade!,en,,,,,,
+//! monkey_island1,You fight like a dairy farmer.,How appropriate. You fight like a cow.,en,Pirate,,,,,
+//! ```
+//!
+//! The metadata of an entry follows the locale, audio clips as their JSON
+//! like `{"file":"mi1_duels.ogg","start_ms":1200}`, and `canonical` is the
+//! canonical id of a translated insult. Files with only the first four
+//! columns load as well. Canonical ids of insults without a row are lost.
+//!
+//! CSV has no rows for `lookup_priority` or `dialogues`, exporting a dataset
+//! with those sections drops them.
+
+use std::collections::TreeMap;
+use serialize::json::{mod, ToJson};
+
+use game::Game;
+use insults::{Insults, AudioClip};
+
+static HEADER: [&'static str, ..10] = ["game", "insult", "retort", "locale", "speaker", "context",
+                                       "rating", "audio", "retort_audio", "canonical"];
+
+/// The columns every file has, up to the locale.
+const REQUIRED: uint = 4;
+
+/// The metadata columns holding strings.
+static TEXT_FIELDS: [&'static str, ..3] = ["speaker", "context", "rating"];
+static CLIP_FIELDS: [&'static str, ..2] = ["audio", "retort_audio"];
+
+/// The sections of `banter`.
+static BANTER: [&'static str, ..3] = ["taunts", "victory", "defeat"];
+
+impl Insults {
+    /// Load insults from CSV with the columns `game,insult,retort,locale`,
+    /// optionally followed by the metadata columns.
+    pub fn from_csv(s: &str) -> Insults {
+        let rows = match parse(s) {
+            Ok(x) => x,
+            Err(e) => panic!("csv error: {}", e),
+        };
+        let mut rows = rows.into_iter();
+
+        let columns = match rows.next() {
+            Some(ref header) if header.len() >= REQUIRED && header.len() <= HEADER.len()
+                    && header.iter().map(|x| x[]).collect::<Vec<&str>>()[] == HEADER[..header.len()] =>
+                header.len(),
+            _ => panic!("csv error: expected the header {}", HEADER[].connect(",")),
+        };
+
+        let mut failed = Vec::new();
+        let mut opponent_failed = Vec::new();
+        let mut banter = TreeMap::new();
+        let mut canonical = TreeMap::new();
+        let mut games = TreeMap::new();
+        for game in Game::all().iter() {
+            games.insert(game.key().to_string(), TreeMap::new());
+        }
+        let mut locale: Option<String> = None;
+
+        for (i, row) in rows.enumerate() {
+            let line = i + 2;
+            if row.len() != columns {
+                panic!("csv error: row {}: expected {} columns", line, columns);
+            }
+            let (game, insult, retort, row_locale) = (&row[0], &row[1], &row[2], &row[3]);
+
+            match locale {
+                Some(ref x) if x != row_locale =>
+                    panic!("csv error: row {}: mixed locales {} and {}", line, x, row_locale),
+                _ => {},
+            }
+            locale = Some(row_locale.clone());
+
+            if game[] == "failed_retorts" {
+                failed.push(json::String(retort.clone()));
+            } else if game[] == "opponent_failed_retorts" {
+                opponent_failed.push(json::String(retort.clone()));
+            } else if BANTER.contains(&game[]) {
+                if !banter.contains_key(game) {
+                    banter.insert(game.clone(), Vec::new());
+                }
+                banter.get_mut(game).unwrap().push(json::String(retort.clone()));
+            } else {
+                let mut entry = TreeMap::new();
+                for name in TEXT_FIELDS.iter() {
+                    if let Some(x) = cell(row[], *name) {
+                        entry.insert(name.to_string(), json::String(x.clone()));
+                    }
+                }
+                for name in CLIP_FIELDS.iter() {
+                    if let Some(x) = cell(row[], *name) {
+                        match json::from_str(x[]) {
+                            Ok(clip) => { entry.insert(name.to_string(), clip); },
+                            Err(e) => panic!("csv error: row {}: {}: {}", line, name, e),
+                        }
+                    }
+                }
+                if let Some(x) = cell(row[], "canonical") {
+                    canonical.insert(insult.clone(), json::String(x.clone()));
+                }
+                let entry = if entry.is_empty() {
+                    json::String(retort.clone())
+                } else {
+                    entry.insert("retort".to_string(), json::String(retort.clone()));
+                    json::Object(entry)
+                };
+                match games.get_mut(game) {
+                    Some(pairs) => { pairs.insert(insult.clone(), entry); },
+                    None => panic!("csv error: row {}: unknown game {}", line, game),
+                }
+            }
+        }
+
+        let mut res = TreeMap::new();
+        res.insert("failed_retorts".to_string(), json::List(failed));
+        if !opponent_failed.is_empty() {
+            res.insert("opponent_failed_retorts".to_string(), json::List(opponent_failed));
+        }
+        if !banter.is_empty() {
+            let sections = banter.into_iter().map(|(k, v)| (k, json::List(v))).collect();
+            res.insert("banter".to_string(), json::Object(sections));
+        }
+        for (game, pairs) in games.into_iter() {
+            res.insert(game, json::Object(pairs));
+        }
+        if !canonical.is_empty() {
+            res.insert("canonical".to_string(), json::Object(canonical));
+        }
+        match locale {
+            Some(ref x) if x[] != "en" => { res.insert("locale".to_string(), x.to_json()); },
+            _ => {},
+        }
+        Insults::from_json(json::Object(res))
+    }
+
+    /// The dataset as CSV with all the columns, see the module documentation.
+    pub fn to_csv(&self) -> String {
+        let locale = self.locale();
+        let data = self.to_json();
+
+        let mut res = String::new();
+        write_row(HEADER[], &mut res);
+        if let Some(&json::List(ref retorts)) = data.find(&"failed_retorts".to_string()) {
+            for retort in retorts.iter() {
+                write_line("failed_retorts", retort.as_string().unwrap_or(""), locale, &mut res);
+            }
+        }
+        if let Some(&json::List(ref retorts)) = data.find(&"opponent_failed_retorts".to_string()) {
+            for retort in retorts.iter() {
+                write_line("opponent_failed_retorts", retort.as_string().unwrap_or(""), locale, &mut res);
+            }
+        }
+        for section in BANTER.iter() {
+            if let Some(&json::List(ref lines)) = data.find_path(&[&"banter".to_string(), &section.to_string()]) {
+                for line in lines.iter() {
+                    write_line(*section, line.as_string().unwrap_or(""), locale, &mut res);
+                }
+            }
+        }
+        let canonical: TreeMap<&String, &String> = self.translated_insults().into_iter().collect();
+        for game in Game::all().iter() {
+            for (insult, entry) in self.entries(*game).into_iter() {
+                let (audio, retort_audio) = (clip(&entry.audio), clip(&entry.retort_audio));
+                let id = canonical.get(&insult).map_or("", |x| x[]);
+                write_row([game.key(), insult[], entry.retort[], locale, text(&entry.speaker),
+                           text(&entry.context), text(&entry.rating), audio[], retort_audio[], id], &mut res);
+            }
+        }
+        res
+    }
+}
+
+/// The cell of the column `name` in `row`, if the file has it and it isn't empty.
+fn cell<'a>(row: &'a [String], name: &str) -> Option<&'a String> {
+    match HEADER[..row.len()].iter().position(|x| *x == name) {
+        Some(i) if !row[i].is_empty() => Some(&row[i]),
+        _ => None,
+    }
+}
+
+fn text<'a>(field: &'a Option<String>) -> &'a str {
+    field.as_ref().map_or("", |x| x[])
+}
+
+fn clip(field: &Option<AudioClip>) -> String {
+    field.as_ref().map_or(String::new(), |x| x.to_json().to_string())
+}
+
+/// A row of a section without insults, with empty metadata.
+fn write_line(section: &str, line: &str, locale: &str, out: &mut String) {
+    write_row([section, "", line, locale, "", "", "", "", "", ""], out);
+}
+
+fn write_row(fields: &[&str], out: &mut String) {
+    for (i, field) in fields.iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        if field.contains_char(',') || field.contains_char('"') || field.contains_char('\n') {
+            out.push('"');
+            out.push_str(field.replace("\"", "\"\"")[]);
+            out.push('"');
+        } else {
+            out.push_str(*field);
+        }
+    }
+    out.push_str("\r\n");
+}
+
+/// Split CSV into rows of fields, following RFC 4180.
+fn parse(s: &str) -> Result<Vec<Vec<String>>, String> {
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = s.chars().peekable();
+
+    loop {
+        match chars.next() {
+            Some('"') if quoted => {
+                if chars.peek() == Some(&'"') {
+                    chars.next();
+                    field.push('"');
+                } else {
+                    quoted = false;
+                }
+            },
+            Some(c) if quoted => field.push(c),
+            Some('"') if field.is_empty() => quoted = true,
+            Some(',') => {
+                row.push(field);
+                field = String::new();
+            },
+            Some('\r') if chars.peek() == Some(&'\n') => {},
+            Some('\n') => {
+                row.push(field);
+                field = String::new();
+                rows.push(row);
+                row = Vec::new();
+            },
+            Some(c) => field.push(c),
+            None => break,
+        }
+    }
+
+    if quoted {
+        return Err(format!("unterminated quote on row {}", rows.len() + 1));
+    }
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+    Ok(rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use serialize::json::ToJson;
+
+    use insults::{Insults, fixture};
+
+    #[test]
+    fn test_csv_round_trip() {
+        let mi = Insults::new("insults.json");
+        let csv = mi.to_csv();
+        assert!(csv[].starts_with("game,insult,retort,locale,speaker,context,rating,audio,retort_audio,canonical\r\n\
+                                   failed_retorts,,"));
+        assert_eq!(Insults::from_csv(csv[]).to_json(), mi.to_json());
+
+        let de = Insults::with_locale("insults.json", "de");
+        let csv = de.to_csv();
+        let loaded = Insults::from_csv(csv[]);
+        assert_eq!(loaded.locale(), "de");
+        assert_eq!(loaded.canonical_id("Du kämpfst wie ein dummer Bauer."), Some("You fight like a dairy farmer."));
+
+        let meta = Insults::from_str(fixture(r#"{"failed_retorts": ["Oh yeah?"],
+            "monkey_island1": {"You fight like a dairy farmer.": {
+                "retort": "How appropriate, you fight like a cow.",
+                "speaker": "Pirate", "context": "Mêlée Island", "rating": "harsh",
+                "audio": {"file": "mi1_duels.ogg", "start_ms": 1200, "end_ms": 2900},
+                "retort_audio": {"file": "mi1_duels.ogg"}}}}"#)[]);
+        assert_eq!(Insults::from_csv(meta.to_csv()[]).to_json(), meta.to_json());
+
+        // The columns up to the locale are enough
+        let mi = Insults::from_csv("game,insult,retort,locale\n\
+            failed_retorts,,\"Oh, yeah?\",en\n\
+            monkey_island1,\"You fight like a \"\"dairy\"\" farmer.\",\"How appropriate.\nLike a cow.\",en\n");
+        assert_eq!(mi.failed_retorts(), ["Oh, yeah?".to_string()][]);
+        assert_eq!(mi.retort("You fight like a \"dairy\" farmer."),
+            Some("How appropriate.\nLike a cow."));
+    }
+}
@@ -0,0 +1,151 @@
+//! Gzip compressed packs, like `insults.json.gz`.
+
+use std::io::{File, IoResult};
+use serialize::json::ToJson;
+use flate;
+
+use insults::Insults;
+
+impl Insults {
+    /// Save the dataset as gzip compressed JSON.
+    pub fn save_compressed(&self, location: &str) -> IoResult<()> {
+        let data = self.to_json().to_string();
+        File::create(&Path::new(location)).write(compress(data.as_bytes())[])
+    }
+}
+
+// Header flags
+static FHCRC: u8 = 2;
+static FEXTRA: u8 = 4;
+static FNAME: u8 = 8;
+static FCOMMENT: u8 = 16;
+
+/// Gzip `data`.
+pub fn compress(data: &[u8]) -> Vec<u8> {
+    // Deflate, no flags, no modification time, unknown OS
+    let mut res = vec![0x1f, 0x8b, 8, 0, 0, 0, 0, 0, 0, 255];
+    let deflated = flate::deflate_bytes(data).expect("deflate failed");
+    res.push_all(deflated.as_slice());
+    push_le_u32(&mut res, crc32(data));
+    push_le_u32(&mut res, data.len() as u32);
+    res
+}
+
+/// Decompress gzipped `data`.
+pub fn decompress(data: &[u8]) -> Result<Vec<u8>, String> {
+    if data.len() < 18 || data[0] != 0x1f || data[1] != 0x8b {
+        return Err("not gzip data".to_string());
+    }
+    if data[2] != 8 {
+        return Err("unsupported compression method".to_string());
+    }
+
+    // The header ends before the eight bytes of the trailer
+    let flags = data[3];
+    let end = data.len() - 8;
+    let truncated = Err("truncated header".to_string());
+    let mut pos = 10;
+    if flags & FEXTRA != 0 {
+        if pos + 2 > end {
+            return truncated;
+        }
+        pos += 2 + (data[pos] as uint | data[pos + 1] as uint << 8);
+        if pos > end {
+            return truncated;
+        }
+    }
+    for flag in [FNAME, FCOMMENT].iter() {
+        if flags & *flag != 0 {
+            match skip_zero_terminated(data[pos..end]) {
+                Some(n) => pos += n,
+                None => return truncated,
+            }
+        }
+    }
+    if flags & FHCRC != 0 {
+        pos += 2;
+        if pos > end {
+            return truncated;
+        }
+    }
+
+    let res = match flate::inflate_bytes(data[pos..end]) {
+        Some(x) => x.as_slice().to_vec(),
+        None => return Err("invalid deflate stream".to_string()),
+    };
+
+    if read_le_u32(data[end..]) != crc32(res[]) {
+        return Err("checksum mismatch".to_string());
+    }
+    if read_le_u32(data[end + 4..]) != res.len() as u32 {
+        return Err("size mismatch".to_string());
+    }
+    Ok(res)
+}
+
+/// The length of the zero terminated field `data` starts with, terminator included.
+fn skip_zero_terminated(data: &[u8]) -> Option<uint> {
+    data.iter().position(|&b| b == 0).map(|i| i + 1)
+}
+
+fn push_le_u32(out: &mut Vec<u8>, x: u32) {
+    for i in range(0u, 4) {
+        out.push((x >> (8 * i)) as u8);
+    }
+}
+
+fn read_le_u32(data: &[u8]) -> u32 {
+    range(0u, 4).fold(0, |acc, i| acc | (data[i] as u32 << (8 * i)))
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xffffffffu32;
+    for &b in data.iter() {
+        crc ^= b as u32;
+        for _ in range(0u, 8) {
+            crc = if crc & 1 == 1 { (crc >> 1) ^ 0xedb88320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{File, TempDir};
+    use serialize::json::ToJson;
+
+    use insults::Insults;
+    use schema::Compression;
+    use super::*;
+
+    #[test]
+    fn test_compressed() {
+        let data = b"You fight like a dairy farmer.";
+        assert_eq!(decompress(compress(data)[]), Ok(data.to_vec()));
+
+        // An extra field longer than the file, and a name never terminated
+        let mut extra = vec![0x1f, 0x8b, 8, 4, 0, 0, 0, 0, 0, 255, 0xff, 0xff];
+        extra.push_all(&[0, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(decompress(extra[]), Err("truncated header".to_string()));
+        let mut name = vec![0x1f, 0x8b, 8, 8, 0, 0, 0, 0, 0, 255];
+        name.push_all(b"insults.json");
+        name.push_all(&[0, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(decompress(name[]), Err("truncated header".to_string()));
+
+        let tmp = TempDir::new("mi_insults").unwrap();
+        let path = tmp.path().join("insults.json.gz");
+        let location = path.as_str().unwrap();
+
+        let mi = Insults::new("insults.json");
+        mi.save_compressed(location).unwrap();
+        assert_eq!(Insults::from_path(location).to_json(), mi.to_json());
+
+        let truncated = tmp.path().join("truncated.json.gz");
+        let compressed = compress(mi.to_json().to_string().as_bytes());
+        File::create(&truncated).write(compressed[..compressed.len() / 2]).unwrap();
+        match Insults::try_from_path(truncated.as_str().unwrap()) {
+            Err(Compression(_)) => (),
+            _ => panic!("expected a gzip error"),
+        }
+    }
+}
@@ -0,0 +1,227 @@
+//! Loading packs over HTTP, for bots pulling the dataset at startup.
+//!
+//! Packs are cached together with their `ETag`, later loads only download
+//! the pack when it has changed. Only plain `http://` URLs are supported.
+
+use std::ascii::AsciiExt;
+use std::hash;
+use std::io::{mod, File, IoResult, TcpStream};
+use std::io::fs::{mod, PathExtensions};
+use std::os;
+use std::str;
+use std::sync::Future;
+
+use insults::Insults;
+use schema::{LoadError, Http, Io};
+
+impl Insults {
+    /// Load insults from `url`, cached in the temporary directory.
+    pub fn from_url(url: &str) -> Insults {
+        match Insults::try_from_url(url) {
+            Ok(x) => x,
+            Err(e) => panic!("{}: {}", url, e)
+        }
+    }
+
+    /// Load insults from `url`, like `from_url` but returning errors.
+    pub fn try_from_url(url: &str) -> Result<Insults, LoadError> {
+        Insults::try_from_url_cached(url, &os::tmpdir().join("mi_insults"))
+    }
+
+    /// Load insults from `url` on a separate task, see `from_path_async`.
+    pub fn from_url_async(url: &str) -> Future<Result<Insults, LoadError>> {
+        let url = url.to_string();
+        Future::spawn(proc() Insults::try_from_url(url[]))
+    }
+
+    /// Load insults from `url`, cached in the directory `cache`.
+    ///
+    /// The cached pack is used when the server reports it unchanged,
+    /// or when the server can't be reached.
+    pub fn try_from_url_cached(url: &str, cache: &Path) -> Result<Insults, LoadError> {
+        let (host, port, path) = match parse_url(url) {
+            Some(x) => x,
+            None => return Err(Http(format!("unsupported url {}", url)))
+        };
+
+        let key = hash::hash(&url);
+        let body_path = cache.join(format!("{:x}.json", key));
+        let etag_path = cache.join(format!("{:x}.etag", key));
+        let etag = if body_path.exists() {
+            File::open(&etag_path).read_to_string().ok()
+        } else {
+            None
+        };
+
+        let response = match get(host[], port, path[], etag) {
+            Ok(x) => x,
+            Err(_) if body_path.exists() => return cached(&body_path),
+            Err(e) => return Err(Io(e))
+        };
+        match response.status {
+            200 => {
+                let res = try!(Insults::try_from_bytes(url, response.body.clone()));
+                // A failing cache only costs a download next time
+                let _ = store(cache, &body_path, &etag_path, response.body[], response.etag);
+                Ok(res)
+            },
+            304 if body_path.exists() => cached(&body_path),
+            status => Err(Http(format!("{} responded with status {}", url, status)))
+        }
+    }
+}
+
+struct Response {
+    status: u16,
+    etag: Option<String>,
+    body: Vec<u8>,
+}
+
+fn cached(path: &Path) -> Result<Insults, LoadError> {
+    match File::open(path).read_to_end() {
+        Ok(x) => Insults::try_from_bytes(path.display().to_string()[], x),
+        Err(e) => Err(Io(e))
+    }
+}
+
+fn store(cache: &Path, body_path: &Path, etag_path: &Path, body: &[u8],
+         etag: Option<String>) -> IoResult<()> {
+    try!(fs::mkdir_recursive(cache, io::USER_RWX));
+    try!(File::create(body_path).write(body));
+    match etag {
+        Some(etag) => File::create(etag_path).write_str(etag[]),
+        None if etag_path.exists() => fs::unlink(etag_path),
+        None => Ok(()),
+    }
+}
+
+/// Split `http://host:port/path` into its parts.
+fn parse_url(url: &str) -> Option<(String, u16, String)> {
+    if !url.starts_with("http://") {
+        return None;
+    }
+    let rest = url["http://".len()..];
+    let (authority, path) = match rest.find('/') {
+        Some(i) => (rest[..i], rest[i..]),
+        None => (rest, "/"),
+    };
+    let (host, port) = match authority.find(':') {
+        Some(i) => match from_str::<u16>(authority[i + 1..]) {
+            Some(port) => (authority[..i], port),
+            None => return None,
+        },
+        None => (authority, 80),
+    };
+    if host.is_empty() {
+        return None;
+    }
+    Some((host.to_string(), port, path.to_string()))
+}
+
+fn get(host: &str, port: u16, path: &str, etag: Option<String>) -> IoResult<Response> {
+    let mut stream = try!(TcpStream::connect(host, port));
+    let mut request = format!("GET {} HTTP/1.0\r\nHost: {}\r\n", path, host);
+    if let Some(etag) = etag {
+        request.push_str(format!("If-None-Match: {}\r\n", etag)[]);
+    }
+    request.push_str("\r\n");
+    try!(stream.write_str(request[]));
+
+    let data = try!(stream.read_to_end());
+    parse_response(data[])
+}
+
+fn invalid(desc: &'static str) -> io::IoError {
+    io::IoError {
+        kind: io::InvalidInput,
+        desc: desc,
+        detail: None,
+    }
+}
+
+fn parse_response(data: &[u8]) -> IoResult<Response> {
+    let end = match range(0, data.len()).find(|&i| data[i..].starts_with(b"\r\n\r\n")) {
+        Some(i) => i,
+        None => return Err(invalid("incomplete response")),
+    };
+    let head = match str::from_utf8(data[..end]) {
+        Some(x) => x,
+        None => return Err(invalid("response headers are not utf-8")),
+    };
+
+    let mut lines = head.split_str("\r\n");
+    let status = lines.next()
+        .and_then(|line| line.split(' ').nth(1))
+        .and_then(|x| from_str::<u16>(x));
+    let status = match status {
+        Some(x) => x,
+        None => return Err(invalid("invalid status line")),
+    };
+
+    let mut etag = None;
+    for line in lines {
+        if let Some(i) = line.find(':') {
+            if line[..i].to_ascii_lower()[] == "etag" {
+                etag = Some(line[i + 1..].trim().to_string());
+            }
+        }
+    }
+
+    Ok(Response {
+        status: status,
+        etag: etag,
+        body: data[end + 4..].to_vec(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Acceptor, Listener, TcpListener, TempDir};
+    use serialize::json::ToJson;
+
+    use insults::{Insults, read_file};
+
+    #[test]
+    fn test_from_url() {
+        let mut listener = TcpListener::bind("127.0.0.1", 0).unwrap();
+        let port = listener.socket_name().unwrap().port;
+        let mut acceptor = listener.listen().unwrap();
+
+        spawn(proc() {
+            let data = read_file("insults.json");
+            for i in range(0u, 3) {
+                let mut stream = acceptor.accept().unwrap();
+                let mut request = Vec::new();
+                let mut buf = [0u8, ..1024];
+                while !request[].ends_with(b"\r\n\r\n") {
+                    let n = stream.read(buf).unwrap();
+                    request.push_all(buf[..n]);
+                }
+                let request = String::from_utf8(request).unwrap();
+
+                // The last connection is dropped without a response
+                if i == 2 {
+                    continue;
+                }
+                let response = if request[].contains("If-None-Match: \"v1\"") {
+                    "HTTP/1.0 304 Not Modified\r\n\r\n".to_string()
+                } else {
+                    format!("HTTP/1.0 200 OK\r\nETag: \"v1\"\r\n\r\n{}", data)
+                };
+                stream.write_str(response[]).unwrap();
+            }
+        });
+
+        let tmp = TempDir::new("mi_insults").unwrap();
+        let url = format!("http://127.0.0.1:{}/insults.json", port);
+        let expected = Insults::new("insults.json").to_json();
+
+        // Downloaded, unchanged, and the server is gone
+        for _ in range(0u, 3) {
+            let mi = Insults::try_from_url_cached(url[], tmp.path()).unwrap();
+            assert_eq!(mi.to_json(), expected);
+        }
+
+        assert!(Insults::try_from_url("https://example.com/insults.json").is_err());
+    }
+}
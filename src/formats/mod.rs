@@ -0,0 +1,20 @@
+//! Insult packs in other formats than JSON.
+//!
+//! Every format is converted to the layout of `insults.json` and loaded with `Insults::from_json`,
+//! except snapshots, which hold the decoded tables.
+
+mod csv;
+#[cfg(feature = "flate")]
+pub mod gzip;
+#[cfg(feature = "http")]
+mod http;
+#[cfg(feature = "msgpack")]
+mod msgpack;
+#[cfg(feature = "ron")]
+mod ron;
+mod snapshot;
+pub mod stream;
+#[cfg(feature = "toml")]
+mod toml;
+#[cfg(feature = "yaml")]
+mod yaml;
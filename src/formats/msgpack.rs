@@ -0,0 +1,204 @@
+//! MessagePack, a compact binary encoding for shipping packs to clients.
+//!
+//! The pack has the same layout as `insults.json`, a map of games.
+
+use std::collections::TreeMap;
+use std::io::{mod, IoResult, MemReader, MemWriter};
+use std::io::util::LimitReader;
+use serialize::json::{mod, Json, ToJson};
+
+use insults::Insults;
+
+impl Insults {
+    /// Load insults from MessagePack.
+    pub fn from_msgpack(data: &[u8]) -> Insults {
+        let mut r = MemReader::new(data.to_vec());
+        match read(&mut r) {
+            Ok(x) => Insults::from_json(x),
+            Err(e) => panic!("msgpack error: {}", e),
+        }
+    }
+
+    /// The dataset as MessagePack.
+    pub fn to_msgpack(&self) -> Vec<u8> {
+        let mut w = MemWriter::new();
+        // Writing to memory can't fail
+        write(&self.to_json(), &mut w).unwrap();
+        w.unwrap()
+    }
+}
+
+fn write_len<W: Writer>(len: uint, fix: u8, max_fix: uint, tag16: u8, w: &mut W) -> IoResult<()> {
+    if len <= max_fix {
+        w.write_u8(fix | len as u8)
+    } else if len <= 0xffff {
+        try!(w.write_u8(tag16));
+        w.write_be_u16(len as u16)
+    } else {
+        try!(w.write_u8(tag16 + 1));
+        w.write_be_u32(len as u32)
+    }
+}
+
+fn write_str<W: Writer>(s: &str, w: &mut W) -> IoResult<()> {
+    if s.len() > 31 && s.len() <= 0xff {
+        try!(w.write_u8(0xd9));
+        try!(w.write_u8(s.len() as u8));
+    } else {
+        try!(write_len(s.len(), 0xa0, 31, 0xda, w));
+    }
+    w.write_str(s)
+}
+
+fn write<W: Writer>(value: &Json, w: &mut W) -> IoResult<()> {
+    match *value {
+        json::Null => w.write_u8(0xc0),
+        json::Boolean(false) => w.write_u8(0xc2),
+        json::Boolean(true) => w.write_u8(0xc3),
+        json::I64(i) if i >= 0 && i < 128 => w.write_u8(i as u8),
+        json::I64(i) => {
+            try!(w.write_u8(0xd3));
+            w.write_be_i64(i)
+        },
+        json::U64(u) if u < 128 => w.write_u8(u as u8),
+        json::U64(u) => {
+            try!(w.write_u8(0xcf));
+            w.write_be_u64(u)
+        },
+        json::F64(f) => {
+            try!(w.write_u8(0xcb));
+            w.write_be_f64(f)
+        },
+        json::String(ref s) => write_str(s[], w),
+        json::List(ref xs) => {
+            try!(write_len(xs.len(), 0x90, 15, 0xdc, w));
+            for x in xs.iter() {
+                try!(write(x, w));
+            }
+            Ok(())
+        },
+        json::Object(ref m) => {
+            try!(write_len(m.len(), 0x80, 15, 0xde, w));
+            for (k, v) in m.iter() {
+                try!(write_str(k[], w));
+                try!(write(v, w));
+            }
+            Ok(())
+        },
+    }
+}
+
+fn invalid(desc: &'static str) -> io::IoError {
+    io::IoError {
+        kind: io::InvalidInput,
+        desc: desc,
+        detail: None,
+    }
+}
+
+// Lengths come from the input, so nothing is allocated for them up front.
+fn read_str<R: Reader>(len: uint, r: &mut R) -> IoResult<Json> {
+    let data = try!(LimitReader::new(r.by_ref(), len).read_to_end());
+    if data.len() < len {
+        return Err(io::standard_error(io::EndOfFile));
+    }
+    match String::from_utf8(data) {
+        Ok(s) => Ok(json::String(s)),
+        Err(_) => Err(invalid("string is not utf-8")),
+    }
+}
+
+fn read_list<R: Reader>(len: uint, r: &mut R) -> IoResult<Json> {
+    let mut xs = Vec::new();
+    for _ in range(0, len) {
+        xs.push(try!(read(r)));
+    }
+    Ok(json::List(xs))
+}
+
+fn read_map<R: Reader>(len: uint, r: &mut R) -> IoResult<Json> {
+    let mut m = TreeMap::new();
+    for _ in range(0, len) {
+        let k = match try!(read(r)) {
+            json::String(s) => s,
+            _ => return Err(invalid("map keys must be strings")),
+        };
+        m.insert(k, try!(read(r)));
+    }
+    Ok(json::Object(m))
+}
+
+fn read<R: Reader>(r: &mut R) -> IoResult<Json> {
+    let tag = try!(r.read_u8());
+    match tag {
+        0x00...0x7f => Ok(json::I64(tag as i64)),
+        0x80...0x8f => read_map((tag & 0x0f) as uint, r),
+        0x90...0x9f => read_list((tag & 0x0f) as uint, r),
+        0xa0...0xbf => read_str((tag & 0x1f) as uint, r),
+        0xc0 => Ok(json::Null),
+        0xc2 => Ok(json::Boolean(false)),
+        0xc3 => Ok(json::Boolean(true)),
+        0xca => Ok(json::F64(try!(r.read_be_f32()) as f64)),
+        0xcb => Ok(json::F64(try!(r.read_be_f64()))),
+        0xcc => Ok(json::U64(try!(r.read_u8()) as u64)),
+        0xcd => Ok(json::U64(try!(r.read_be_u16()) as u64)),
+        0xce => Ok(json::U64(try!(r.read_be_u32()) as u64)),
+        0xcf => Ok(json::U64(try!(r.read_be_u64()))),
+        0xd0 => Ok(json::I64(try!(r.read_i8()) as i64)),
+        0xd1 => Ok(json::I64(try!(r.read_be_i16()) as i64)),
+        0xd2 => Ok(json::I64(try!(r.read_be_i32()) as i64)),
+        0xd3 => Ok(json::I64(try!(r.read_be_i64()))),
+        0xd9 => {
+            let len = try!(r.read_u8()) as uint;
+            read_str(len, r)
+        },
+        0xda => {
+            let len = try!(r.read_be_u16()) as uint;
+            read_str(len, r)
+        },
+        0xdb => {
+            let len = try!(r.read_be_u32()) as uint;
+            read_str(len, r)
+        },
+        0xdc => {
+            let len = try!(r.read_be_u16()) as uint;
+            read_list(len, r)
+        },
+        0xdd => {
+            let len = try!(r.read_be_u32()) as uint;
+            read_list(len, r)
+        },
+        0xde => {
+            let len = try!(r.read_be_u16()) as uint;
+            read_map(len, r)
+        },
+        0xdf => {
+            let len = try!(r.read_be_u32()) as uint;
+            read_map(len, r)
+        },
+        0xe0...0xff => Ok(json::I64(tag as i8 as i64)),
+        _ => Err(invalid("unsupported type")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::MemReader;
+    use serialize::json::ToJson;
+
+    use insults::Insults;
+    use super::read;
+
+    #[test]
+    fn test_msgpack_round_trip() {
+        let mi = Insults::new("insults.json");
+        let data = mi.to_msgpack();
+        // A map of the version and eight sections
+        assert_eq!(data[0], 0x89);
+        assert_eq!(Insults::from_msgpack(data[]).to_json(), mi.to_json());
+
+        // A list and a string claiming four billion elements and bytes
+        assert!(read(&mut MemReader::new(vec![0xdd, 0xff, 0xff, 0xff, 0xff])).is_err());
+        assert!(read(&mut MemReader::new(vec![0xdb, 0xff, 0xff, 0xff, 0xff])).is_err());
+    }
+}
@@ -0,0 +1,390 @@
+//! RON packs, the Rusty Object Notation.
+//!
+//! A pack is a struct with the games as maps:
+//!
+//! ```ron
+//! #![enable(implicit_some)]
+//! (
+//!     failed_retorts: ["Oh yeah?"],
+//!     monkey_island1: {
+//!         "You fight like a dairy farmer.": "How appropriate. You fight like a cow.",
+//!     },
+//!     locale: "en",
+//! )
+//! ```
+
+use std::collections::TreeMap;
+use std::io::{File, IoResult};
+use serialize::json::{mod, Json, ToJson};
+
+use insults::{mod, Insults};
+
+impl Insults {
+    /// Load insults from a RON file.
+    pub fn from_ron_path(location: &str) -> Insults {
+        Insults::from_ron_str(insults::read_file(location)[])
+    }
+
+    /// Load insults from a RON string.
+    pub fn from_ron_str(s: &str) -> Insults {
+        let mut parser = Parser { s: s, pos: 0 };
+        match parser.document() {
+            Ok(x) => Insults::from_json(x),
+            Err(e) => {
+                let (line, col) = parser.linecol();
+                panic!("ron error: {}:{}: {}", line, col, e)
+            }
+        }
+    }
+
+    /// The dataset as RON.
+    pub fn to_ron(&self) -> String {
+        let mut res = "#![enable(implicit_some)]\n".to_string();
+        write_struct(&self.to_json(), &mut res);
+        res.push('\n');
+        res
+    }
+
+    /// Save the dataset as RON to `location`.
+    pub fn save_ron(&self, location: &str) -> IoResult<()> {
+        File::create(&Path::new(location)).write_str(self.to_ron()[])
+    }
+}
+
+fn write_struct(value: &Json, out: &mut String) {
+    match *value {
+        json::Object(ref fields) => {
+            out.push_str("(\n");
+            for (k, v) in fields.iter() {
+                out.push_str(format!("    {}: ", k)[]);
+                write_value(v, 1, out);
+                out.push_str(",\n");
+            }
+            out.push(')');
+        },
+        ref x => write_value(x, 0, out),
+    }
+}
+
+fn write_value(value: &Json, depth: uint, out: &mut String) {
+    let indent = String::from_char(4 * (depth + 1), ' ');
+    match *value {
+        json::Object(ref m) if m.is_empty() => out.push_str("{}"),
+        json::Object(ref m) => {
+            out.push_str("{\n");
+            for (k, v) in m.iter() {
+                out.push_str(indent[]);
+                write_str(k[], out);
+                out.push_str(": ");
+                write_value(v, depth + 1, out);
+                out.push_str(",\n");
+            }
+            out.push_str(indent[..4 * depth]);
+            out.push('}');
+        },
+        json::List(ref xs) if xs.is_empty() => out.push_str("[]"),
+        json::List(ref xs) => {
+            out.push_str("[\n");
+            for x in xs.iter() {
+                out.push_str(indent[]);
+                write_value(x, depth + 1, out);
+                out.push_str(",\n");
+            }
+            out.push_str(indent[..4 * depth]);
+            out.push(']');
+        },
+        json::String(ref s) => write_str(s[], out),
+        json::Null => out.push_str("None"),
+        ref x => out.push_str(x.to_string()[]),
+    }
+}
+
+fn write_str(s: &str, out: &mut String) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+struct Parser<'a> {
+    s: &'a str,
+    pos: uint,
+}
+
+impl<'a> Parser<'a> {
+    fn document(&mut self) -> Result<Json, String> {
+        self.skip();
+        while self.rest().starts_with("#![") {
+            match self.rest().find(']') {
+                Some(i) => self.pos += i + 1,
+                None => return Err("unterminated attribute".to_string()),
+            }
+            self.skip();
+        }
+        let res = try!(self.value());
+        self.skip();
+        if self.pos < self.s.len() {
+            return Err("trailing characters".to_string());
+        }
+        Ok(res)
+    }
+
+    fn rest(&self) -> &'a str {
+        self.s[self.pos..]
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    fn linecol(&self) -> (uint, uint) {
+        let before = self.s[..self.pos];
+        let line = before.chars().filter(|&c| c == '\n').count() + 1;
+        let col = match before.rfind('\n') {
+            Some(i) => self.pos - i,
+            None => self.pos + 1,
+        };
+        (line, col)
+    }
+
+    /// Skip whitespace and comments.
+    fn skip(&mut self) {
+        loop {
+            let rest = self.rest();
+            let trimmed = rest.trim_left();
+            self.pos += rest.len() - trimmed.len();
+
+            if trimmed.starts_with("//") {
+                self.pos += trimmed.find('\n').unwrap_or(trimmed.len());
+            } else if trimmed.starts_with("/*") {
+                self.pos += trimmed.find_str("*/").map(|i| i + 2).unwrap_or(trimmed.len());
+            } else {
+                return;
+            }
+        }
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), String> {
+        self.skip();
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(format!("expected `{}`", c))
+        }
+    }
+
+    /// Consume `c` if it's next.
+    fn eat(&mut self, c: char) -> bool {
+        self.skip();
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn value(&mut self) -> Result<Json, String> {
+        self.skip();
+        match self.peek() {
+            Some('"') => self.string().map(json::String),
+            Some('[') => self.list(),
+            Some('{') => self.map(),
+            Some('(') => self.parens(),
+            Some(c) if c.is_alphabetic() || c == '_' => {
+                let ident = self.ident();
+                match ident {
+                    "true" => Ok(json::Boolean(true)),
+                    "false" => Ok(json::Boolean(false)),
+                    "None" => Ok(json::Null),
+                    "Some" => {
+                        try!(self.expect('('));
+                        let res = try!(self.value());
+                        self.eat(',');
+                        try!(self.expect(')'));
+                        Ok(res)
+                    },
+                    _ => {
+                        self.skip();
+                        if self.peek() == Some('(') {
+                            // A named struct, the name doesn't matter
+                            self.parens()
+                        } else {
+                            Ok(json::String(ident.to_string()))
+                        }
+                    },
+                }
+            },
+            Some(c) if c.is_digit() || c == '-' || c == '+' => self.number(),
+            Some(c) => Err(format!("unexpected `{}`", c)),
+            None => Err("unexpected end of input".to_string()),
+        }
+    }
+
+    fn ident(&mut self) -> &'a str {
+        let rest = self.rest();
+        let len = rest.find(|c: char| !(c.is_alphanumeric() || c == '_')).unwrap_or(rest.len());
+        self.pos += len;
+        rest[..len]
+    }
+
+    fn number(&mut self) -> Result<Json, String> {
+        let rest = self.rest();
+        let len = rest.find(|c: char| !(c.is_digit() || "+-.eE_".contains_char(c)))
+                      .unwrap_or(rest.len());
+        let text = rest[..len].replace("_", "");
+        self.pos += len;
+
+        match from_str::<i64>(text[]) {
+            Some(i) => Ok(json::I64(i)),
+            None => match from_str::<f64>(text[]) {
+                Some(f) => Ok(json::F64(f)),
+                None => Err(format!("invalid number `{}`", text)),
+            },
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        try!(self.expect('"'));
+        let mut res = String::new();
+        let mut chars = self.rest().char_indices();
+        loop {
+            match chars.next() {
+                Some((i, '"')) => {
+                    self.pos += i + 1;
+                    return Ok(res);
+                },
+                Some((_, '\\')) => match chars.next() {
+                    Some((_, 'n')) => res.push('\n'),
+                    Some((_, 'r')) => res.push('\r'),
+                    Some((_, 't')) => res.push('\t'),
+                    Some((_, c)) => res.push(c),
+                    None => break,
+                },
+                Some((_, c)) => res.push(c),
+                None => break,
+            }
+        }
+        Err("unterminated string".to_string())
+    }
+
+    fn list(&mut self) -> Result<Json, String> {
+        try!(self.expect('['));
+        let mut res = Vec::new();
+        while !self.eat(']') {
+            res.push(try!(self.value()));
+            if !self.eat(',') {
+                try!(self.expect(']'));
+                break;
+            }
+        }
+        Ok(json::List(res))
+    }
+
+    fn map(&mut self) -> Result<Json, String> {
+        try!(self.expect('{'));
+        let mut res = TreeMap::new();
+        while !self.eat('}') {
+            let key = match try!(self.value()) {
+                json::String(s) => s,
+                _ => return Err("map keys must be strings".to_string()),
+            };
+            try!(self.expect(':'));
+            res.insert(key, try!(self.value()));
+            if !self.eat(',') {
+                try!(self.expect('}'));
+                break;
+            }
+        }
+        Ok(json::Object(res))
+    }
+
+    /// A struct with named fields, a tuple or `()`.
+    fn parens(&mut self) -> Result<Json, String> {
+        try!(self.expect('('));
+        self.skip();
+
+        // Look ahead for `field:` to tell structs from tuples
+        let start = self.pos;
+        let is_struct = match self.peek() {
+            Some(c) if c.is_alphabetic() || c == '_' => {
+                self.ident();
+                let res = self.eat(':');
+                self.pos = start;
+                res
+            },
+            _ => false,
+        };
+
+        if is_struct {
+            let mut res = TreeMap::new();
+            while !self.eat(')') {
+                self.skip();
+                let field = self.ident().to_string();
+                try!(self.expect(':'));
+                res.insert(field, try!(self.value()));
+                if !self.eat(',') {
+                    try!(self.expect(')'));
+                    break;
+                }
+            }
+            Ok(json::Object(res))
+        } else if self.eat(')') {
+            Ok(json::Null)
+        } else {
+            let mut res = Vec::new();
+            while !self.eat(')') {
+                res.push(try!(self.value()));
+                if !self.eat(',') {
+                    try!(self.expect(')'));
+                    break;
+                }
+            }
+            Ok(json::List(res))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serialize::json::ToJson;
+
+    use insults::Insults;
+
+    #[test]
+    fn test_ron_round_trip() {
+        let mi = Insults::from_ron_str(r#"
+#![enable(implicit_some)]
+Insults(
+    // Nothing fancy
+    failed_retorts: ["Oh yeah?", "I'm shaking, I'm shaking!",],
+    monkey_island1: {
+        "You fight like a dairy farmer.": "How appropriate. You fight like a cow.",
+    },
+    sword_master: {},
+    monkey_island3: {},
+    captain_rottingham: {},
+    monkey_island4: {
+        "Hey, look over there!": "Yeah, yeah I know: it's a three headed monkey."
+    },
+    locale: Some("en"),
+)
+"#);
+        assert_eq!(mi.mi1_retort("You fight like a dairy farmer."),
+            Some("How appropriate. You fight like a cow."));
+        assert_eq!(mi.mi4_retort("Hey, look over there!"),
+            Some("Yeah, yeah I know: it's a three headed monkey."));
+
+        let full = Insults::new("insults.json");
+        assert_eq!(Insults::from_ron_str(full.to_ron()[]).to_json(), full.to_json());
+    }
+}
@@ -0,0 +1,390 @@
+//! Binary snapshots of a loaded dataset for fast startup.
+//!
+//! A snapshot holds the decoded tables of a dataset together with its
+//! fallbacks, so a localized dataset starts up from a single file without
+//! parsing, migrating, validating or normalizing anything.
+//!
+//! The tables are written with their `Encodable` implementations: numbers
+//! little endian, strings, lists and maps after their length as a `u32`,
+//! options and enum variants after a tag.
+
+use std::char;
+use std::io::{mod, BufferedWriter, File, IoError, IoResult};
+use std::mem;
+use serialize::{mod, Decodable, Encodable};
+
+use insults::Insults;
+use schema::{LoadError, Io};
+
+static MAGIC: &'static [u8] = b"MIIS";
+static VERSION: u8 = 2;
+
+impl Insults {
+    /// Save the dataset and its fallbacks as a snapshot.
+    pub fn save_snapshot(&self, location: &str) -> IoResult<()> {
+        let mut w = BufferedWriter::new(try!(File::create(&Path::new(location))));
+        try!(w.write(MAGIC));
+        try!(w.write_u8(VERSION));
+        try!(self.encode(&mut SnapshotEncoder { w: &mut w }));
+        w.flush()
+    }
+
+    /// Load a snapshot saved with `save_snapshot`.
+    pub fn load_snapshot(location: &str) -> Insults {
+        match Insults::try_load_snapshot(location) {
+            Ok(x) => x,
+            Err(e) => panic!("{}: {}", location, e)
+        }
+    }
+
+    /// Load a snapshot, like `load_snapshot` but returning errors.
+    pub fn try_load_snapshot(location: &str) -> Result<Insults, LoadError> {
+        let data = match File::open(&Path::new(location)).read_to_end() {
+            Ok(x) => x,
+            Err(e) => return Err(Io(e)),
+        };
+        if data.len() < MAGIC.len() + 1 || data[..MAGIC.len()] != MAGIC {
+            return Err(Io(invalid("not a snapshot")));
+        }
+        if data[MAGIC.len()] != VERSION {
+            return Err(Io(invalid("not a snapshot of this version")));
+        }
+
+        let mut d = SnapshotDecoder { data: data[MAGIC.len() + 1..], pos: 0 };
+        match Decodable::decode(&mut d) {
+            Ok(x) => Ok(x),
+            Err(e) => Err(Io(e)),
+        }
+    }
+}
+
+fn invalid(desc: &'static str) -> IoError {
+    IoError { kind: io::InvalidInput, desc: desc, detail: None }
+}
+
+struct SnapshotEncoder<'a, W: 'a> {
+    w: &'a mut W,
+}
+
+impl<'a, W: Writer> SnapshotEncoder<'a, W> {
+    fn emit_len(&mut self, len: uint) -> IoResult<()> {
+        self.w.write_le_u32(len as u32)
+    }
+}
+
+impl<'a, W: Writer> serialize::Encoder<IoError> for SnapshotEncoder<'a, W> {
+    fn emit_nil(&mut self) -> IoResult<()> { Ok(()) }
+
+    fn emit_uint(&mut self, v: uint) -> IoResult<()> { self.w.write_le_u64(v as u64) }
+    fn emit_u64(&mut self, v: u64) -> IoResult<()> { self.w.write_le_u64(v) }
+    fn emit_u32(&mut self, v: u32) -> IoResult<()> { self.w.write_le_u32(v) }
+    fn emit_u16(&mut self, v: u16) -> IoResult<()> { self.w.write_le_u16(v) }
+    fn emit_u8(&mut self, v: u8) -> IoResult<()> { self.w.write_u8(v) }
+
+    fn emit_int(&mut self, v: int) -> IoResult<()> { self.w.write_le_i64(v as i64) }
+    fn emit_i64(&mut self, v: i64) -> IoResult<()> { self.w.write_le_i64(v) }
+    fn emit_i32(&mut self, v: i32) -> IoResult<()> { self.w.write_le_i32(v) }
+    fn emit_i16(&mut self, v: i16) -> IoResult<()> { self.w.write_le_i16(v) }
+    fn emit_i8(&mut self, v: i8) -> IoResult<()> { self.w.write_i8(v) }
+
+    fn emit_bool(&mut self, v: bool) -> IoResult<()> { self.w.write_u8(v as u8) }
+    fn emit_f64(&mut self, v: f64) -> IoResult<()> { self.w.write_le_f64(v) }
+    fn emit_f32(&mut self, v: f32) -> IoResult<()> { self.w.write_le_f32(v) }
+    fn emit_char(&mut self, v: char) -> IoResult<()> { self.w.write_le_u32(v as u32) }
+
+    fn emit_str(&mut self, v: &str) -> IoResult<()> {
+        try!(self.emit_len(v.len()));
+        self.w.write_str(v)
+    }
+
+    fn emit_enum(&mut self, _: &str, f: |&mut SnapshotEncoder<'a, W>| -> IoResult<()>) -> IoResult<()> {
+        f(self)
+    }
+
+    fn emit_enum_variant(&mut self, _: &str, id: uint, _: uint,
+                         f: |&mut SnapshotEncoder<'a, W>| -> IoResult<()>) -> IoResult<()> {
+        try!(self.emit_len(id));
+        f(self)
+    }
+
+    fn emit_enum_variant_arg(&mut self, _: uint,
+                             f: |&mut SnapshotEncoder<'a, W>| -> IoResult<()>) -> IoResult<()> {
+        f(self)
+    }
+
+    fn emit_enum_struct_variant(&mut self, name: &str, id: uint, len: uint,
+                                f: |&mut SnapshotEncoder<'a, W>| -> IoResult<()>) -> IoResult<()> {
+        self.emit_enum_variant(name, id, len, f)
+    }
+
+    fn emit_enum_struct_variant_field(&mut self, _: &str, _: uint,
+                                      f: |&mut SnapshotEncoder<'a, W>| -> IoResult<()>) -> IoResult<()> {
+        f(self)
+    }
+
+    fn emit_struct(&mut self, _: &str, _: uint,
+                   f: |&mut SnapshotEncoder<'a, W>| -> IoResult<()>) -> IoResult<()> {
+        f(self)
+    }
+
+    fn emit_struct_field(&mut self, _: &str, _: uint,
+                         f: |&mut SnapshotEncoder<'a, W>| -> IoResult<()>) -> IoResult<()> {
+        f(self)
+    }
+
+    fn emit_tuple(&mut self, len: uint, f: |&mut SnapshotEncoder<'a, W>| -> IoResult<()>) -> IoResult<()> {
+        try!(self.emit_len(len));
+        f(self)
+    }
+
+    fn emit_tuple_arg(&mut self, _: uint, f: |&mut SnapshotEncoder<'a, W>| -> IoResult<()>) -> IoResult<()> {
+        f(self)
+    }
+
+    fn emit_tuple_struct(&mut self, _: &str, len: uint,
+                         f: |&mut SnapshotEncoder<'a, W>| -> IoResult<()>) -> IoResult<()> {
+        self.emit_tuple(len, f)
+    }
+
+    fn emit_tuple_struct_arg(&mut self, _: uint,
+                             f: |&mut SnapshotEncoder<'a, W>| -> IoResult<()>) -> IoResult<()> {
+        f(self)
+    }
+
+    fn emit_option(&mut self, f: |&mut SnapshotEncoder<'a, W>| -> IoResult<()>) -> IoResult<()> {
+        f(self)
+    }
+
+    fn emit_option_none(&mut self) -> IoResult<()> { self.w.write_u8(0) }
+
+    fn emit_option_some(&mut self, f: |&mut SnapshotEncoder<'a, W>| -> IoResult<()>) -> IoResult<()> {
+        try!(self.w.write_u8(1));
+        f(self)
+    }
+
+    fn emit_seq(&mut self, len: uint, f: |&mut SnapshotEncoder<'a, W>| -> IoResult<()>) -> IoResult<()> {
+        try!(self.emit_len(len));
+        f(self)
+    }
+
+    fn emit_seq_elt(&mut self, _: uint, f: |&mut SnapshotEncoder<'a, W>| -> IoResult<()>) -> IoResult<()> {
+        f(self)
+    }
+
+    fn emit_map(&mut self, len: uint, f: |&mut SnapshotEncoder<'a, W>| -> IoResult<()>) -> IoResult<()> {
+        try!(self.emit_len(len));
+        f(self)
+    }
+
+    fn emit_map_elt_key(&mut self, _: uint, f: |&mut SnapshotEncoder<'a, W>| -> IoResult<()>) -> IoResult<()> {
+        f(self)
+    }
+
+    fn emit_map_elt_val(&mut self, _: uint, f: |&mut SnapshotEncoder<'a, W>| -> IoResult<()>) -> IoResult<()> {
+        f(self)
+    }
+}
+
+struct SnapshotDecoder<'a> {
+    data: &'a [u8],
+    pos: uint,
+}
+
+impl<'a> SnapshotDecoder<'a> {
+    fn take(&mut self, n: uint) -> IoResult<&'a [u8]> {
+        let data = self.data;
+        if n > data.len() - self.pos {
+            return Err(invalid("the snapshot is truncated"));
+        }
+        self.pos += n;
+        Ok(data[self.pos - n..self.pos])
+    }
+
+    fn read_le(&mut self, n: uint) -> IoResult<u64> {
+        let bytes = try!(self.take(n));
+        Ok(bytes.iter().rev().fold(0, |acc, &b| acc << 8 | b as u64))
+    }
+
+    /// A length, at most the bytes left since every element takes at least one.
+    ///
+    /// Decoded lists and maps allocate for their length up front, a corrupt
+    /// snapshot mustn't have them allocate more than the file could hold.
+    fn read_len(&mut self) -> IoResult<uint> {
+        let len = try!(self.read_le(4)) as uint;
+        if len > self.data.len() - self.pos {
+            return Err(invalid("the snapshot is truncated"));
+        }
+        Ok(len)
+    }
+}
+
+impl<'a> serialize::Decoder<IoError> for SnapshotDecoder<'a> {
+    fn read_nil(&mut self) -> IoResult<()> { Ok(()) }
+
+    fn read_uint(&mut self) -> IoResult<uint> { Ok(try!(self.read_le(8)) as uint) }
+    fn read_u64(&mut self) -> IoResult<u64> { self.read_le(8) }
+    fn read_u32(&mut self) -> IoResult<u32> { Ok(try!(self.read_le(4)) as u32) }
+    fn read_u16(&mut self) -> IoResult<u16> { Ok(try!(self.read_le(2)) as u16) }
+    fn read_u8(&mut self) -> IoResult<u8> { Ok(try!(self.read_le(1)) as u8) }
+
+    fn read_int(&mut self) -> IoResult<int> { Ok(try!(self.read_le(8)) as i64 as int) }
+    fn read_i64(&mut self) -> IoResult<i64> { Ok(try!(self.read_le(8)) as i64) }
+    fn read_i32(&mut self) -> IoResult<i32> { Ok(try!(self.read_le(4)) as u32 as i32) }
+    fn read_i16(&mut self) -> IoResult<i16> { Ok(try!(self.read_le(2)) as u16 as i16) }
+    fn read_i8(&mut self) -> IoResult<i8> { Ok(try!(self.read_le(1)) as u8 as i8) }
+
+    fn read_bool(&mut self) -> IoResult<bool> { Ok(try!(self.read_le(1)) != 0) }
+
+    fn read_f64(&mut self) -> IoResult<f64> {
+        let bits = try!(self.read_le(8));
+        Ok(unsafe { mem::transmute::<u64, f64>(bits) })
+    }
+
+    fn read_f32(&mut self) -> IoResult<f32> {
+        let bits = try!(self.read_le(4)) as u32;
+        Ok(unsafe { mem::transmute::<u32, f32>(bits) })
+    }
+
+    fn read_char(&mut self) -> IoResult<char> {
+        match char::from_u32(try!(self.read_le(4)) as u32) {
+            Some(c) => Ok(c),
+            None => Err(invalid("invalid char")),
+        }
+    }
+
+    fn read_str(&mut self) -> IoResult<String> {
+        let len = try!(self.read_len());
+        let bytes = try!(self.take(len));
+        match String::from_utf8(bytes.to_vec()) {
+            Ok(s) => Ok(s),
+            Err(_) => Err(invalid("string is not utf-8")),
+        }
+    }
+
+    fn read_enum<T>(&mut self, _: &str, f: |&mut SnapshotDecoder<'a>| -> IoResult<T>) -> IoResult<T> {
+        f(self)
+    }
+
+    fn read_enum_variant<T>(&mut self, names: &[&str],
+                            f: |&mut SnapshotDecoder<'a>, uint| -> IoResult<T>) -> IoResult<T> {
+        let id = try!(self.read_le(4)) as uint;
+        if id >= names.len() {
+            return Err(invalid("unknown enum variant"));
+        }
+        f(self, id)
+    }
+
+    fn read_enum_variant_arg<T>(&mut self, _: uint,
+                                f: |&mut SnapshotDecoder<'a>| -> IoResult<T>) -> IoResult<T> {
+        f(self)
+    }
+
+    fn read_enum_struct_variant<T>(&mut self, names: &[&str],
+                                   f: |&mut SnapshotDecoder<'a>, uint| -> IoResult<T>) -> IoResult<T> {
+        self.read_enum_variant(names, f)
+    }
+
+    fn read_enum_struct_variant_field<T>(&mut self, _: &str, _: uint,
+                                         f: |&mut SnapshotDecoder<'a>| -> IoResult<T>) -> IoResult<T> {
+        f(self)
+    }
+
+    fn read_struct<T>(&mut self, _: &str, _: uint,
+                      f: |&mut SnapshotDecoder<'a>| -> IoResult<T>) -> IoResult<T> {
+        f(self)
+    }
+
+    fn read_struct_field<T>(&mut self, _: &str, _: uint,
+                            f: |&mut SnapshotDecoder<'a>| -> IoResult<T>) -> IoResult<T> {
+        f(self)
+    }
+
+    fn read_tuple<T>(&mut self, f: |&mut SnapshotDecoder<'a>, uint| -> IoResult<T>) -> IoResult<T> {
+        let len = try!(self.read_len());
+        f(self, len)
+    }
+
+    fn read_tuple_arg<T>(&mut self, _: uint, f: |&mut SnapshotDecoder<'a>| -> IoResult<T>) -> IoResult<T> {
+        f(self)
+    }
+
+    fn read_tuple_struct<T>(&mut self, _: &str,
+                            f: |&mut SnapshotDecoder<'a>, uint| -> IoResult<T>) -> IoResult<T> {
+        self.read_tuple(f)
+    }
+
+    fn read_tuple_struct_arg<T>(&mut self, _: uint,
+                                f: |&mut SnapshotDecoder<'a>| -> IoResult<T>) -> IoResult<T> {
+        f(self)
+    }
+
+    fn read_option<T>(&mut self, f: |&mut SnapshotDecoder<'a>, bool| -> IoResult<T>) -> IoResult<T> {
+        match try!(self.read_le(1)) {
+            0 => f(self, false),
+            1 => f(self, true),
+            _ => Err(invalid("invalid option tag")),
+        }
+    }
+
+    fn read_seq<T>(&mut self, f: |&mut SnapshotDecoder<'a>, uint| -> IoResult<T>) -> IoResult<T> {
+        let len = try!(self.read_len());
+        f(self, len)
+    }
+
+    fn read_seq_elt<T>(&mut self, _: uint, f: |&mut SnapshotDecoder<'a>| -> IoResult<T>) -> IoResult<T> {
+        f(self)
+    }
+
+    fn read_map<T>(&mut self, f: |&mut SnapshotDecoder<'a>, uint| -> IoResult<T>) -> IoResult<T> {
+        let len = try!(self.read_len());
+        f(self, len)
+    }
+
+    fn read_map_elt_key<T>(&mut self, _: uint, f: |&mut SnapshotDecoder<'a>| -> IoResult<T>) -> IoResult<T> {
+        f(self)
+    }
+
+    fn read_map_elt_val<T>(&mut self, _: uint, f: |&mut SnapshotDecoder<'a>| -> IoResult<T>) -> IoResult<T> {
+        f(self)
+    }
+
+    fn error(&mut self, err: &str) -> IoError {
+        IoError { kind: io::InvalidInput, desc: "invalid snapshot", detail: Some(err.to_string()) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{File, TempDir};
+    use serialize::json::ToJson;
+
+    use insults::Insults;
+    use super::{MAGIC, VERSION};
+
+    #[test]
+    fn test_snapshot() {
+        let tmp = TempDir::new("mi_insults").unwrap();
+        let path = tmp.path().join("insults.snapshot");
+        let location = path.as_str().unwrap();
+
+        let de = Insults::with_locale("insults.json", "de");
+        de.save_snapshot(location).unwrap();
+
+        let loaded = Insults::load_snapshot(location);
+        assert!(loaded == de);
+        assert_eq!(loaded.to_json(), de.to_json());
+        assert_eq!(loaded.locale(), "de");
+        assert_eq!(loaded.retort("Hey, look over there!"),
+            Some("Yeah, yeah I know: it's a three headed monkey."));
+        assert_eq!(loaded.fallback().map(|x| x.to_json()), de.fallback().map(|x| x.to_json()));
+
+        // A list claiming four billion failed retorts
+        let corrupt = tmp.path().join("corrupt.snapshot");
+        let mut data = MAGIC.to_vec();
+        data.push(VERSION);
+        data.push_all(&[0xff, 0xff, 0xff, 0xff]);
+        File::create(&corrupt).write(data[]).unwrap();
+        assert!(Insults::try_load_snapshot(corrupt.as_str().unwrap()).is_err());
+        assert!(Insults::try_load_snapshot("insults.json").is_err());
+    }
+}
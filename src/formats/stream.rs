@@ -0,0 +1,243 @@
+//! JSON read a character at a time, for packs of tens of megabytes.
+//!
+//! The game sections are decoded entry by entry straight into the tables
+//! of the dataset, so neither the file nor its JSON tree is ever held in
+//! memory whole. The other sections are small and go through
+//! `Insults::try_from_json` like any other dataset.
+
+use std::collections::TreeMap;
+use std::io::{Buffer, BufferedReader, IoError, EndOfFile};
+use serialize::Decodable;
+use serialize::json::{mod, Json, Parser, JsonEvent, Key};
+use serialize::json::{ObjectStart, ObjectEnd, ListStart, ListEnd, BooleanValue, I64Value, U64Value,
+                      F64Value, StringValue, NullValue, Error};
+
+use game::Game;
+use insults::{Insults, Entry};
+use migrations;
+use normalize::nfc;
+use schema::{mod, LoadError, Io, Syntax, Schema};
+
+impl Insults {
+    /// Load insults from JSON read from `reader`.
+    pub fn from_reader<R: Reader>(reader: R) -> Insults {
+        match Insults::try_from_reader(reader) {
+            Ok(x) => x,
+            Err(e) => panic!("{}", e)
+        }
+    }
+
+    /// Load insults from JSON read from `reader`, like `from_reader` but returning errors.
+    pub fn try_from_reader<R: Reader>(reader: R) -> Result<Insults, LoadError> {
+        load(BufferedReader::new(reader))
+    }
+}
+
+/// The characters of a reader, keeping the error that ended them.
+struct Chars<B> {
+    reader: B,
+    error: Option<IoError>,
+}
+
+impl<B: Buffer> Iterator<char> for Chars<B> {
+    fn next(&mut self) -> Option<char> {
+        if self.error.is_some() {
+            return None;
+        }
+        match self.reader.read_char() {
+            Ok(c) => Some(c),
+            Err(ref e) if e.kind == EndOfFile => None,
+            Err(e) => {
+                self.error = Some(e);
+                None
+            },
+        }
+    }
+}
+
+/// Load insults from the JSON of `reader`.
+pub fn load<B: Buffer>(reader: B) -> Result<Insults, LoadError> {
+    let mut entries = Vec::new();
+    let mut res = try!(load_entries(reader, |game, insult, entry| entries.push((game, insult, entry))));
+    for (game, insult, mut entry) in entries.into_iter() {
+        entry.retort = nfc(entry.retort[]);
+        res.insert(game, insult[], entry);
+    }
+    Ok(res)
+}
+
+/// Load the JSON of `reader`, handing the entries of the games to `add`
+/// instead of the tables of the dataset returned.
+///
+/// Insults and retorts are handed over as they are in the file, not
+/// composed to NFC yet.
+pub fn load_entries<B: Buffer>(reader: B, add: |Game, String, Entry|) -> Result<Insults, LoadError> {
+    let mut add = add;
+    let mut chars = Chars { reader: reader, error: None };
+    let res = load_dataset(&mut Parser::new(chars.by_ref()), &mut add);
+    match chars.error {
+        Some(e) => Err(Io(e)),
+        None => res,
+    }
+}
+
+fn next<T: Iterator<char>>(parser: &mut Parser<T>) -> Result<JsonEvent, LoadError> {
+    match parser.next() {
+        Some(Error(e)) => Err(Syntax(e)),
+        Some(event) => Ok(event),
+        None => Err(Schema(Vec::new(), "the file ends unexpectedly".to_string())),
+    }
+}
+
+/// The key of the object member the parser is in.
+fn member_key<T: Iterator<char>>(parser: &Parser<T>) -> String {
+    match parser.stack().top() {
+        Some(Key(x)) => x.to_string(),
+        _ => panic!("the parser is not in an object"),
+    }
+}
+
+/// The value starting with `event`.
+fn value<T: Iterator<char>>(parser: &mut Parser<T>, event: JsonEvent) -> Result<Json, LoadError> {
+    Ok(match event {
+        ObjectStart => {
+            let mut res = TreeMap::new();
+            loop {
+                match try!(next(parser)) {
+                    ObjectEnd => break,
+                    event => {
+                        let key = member_key(parser);
+                        res.insert(key, try!(value(parser, event)));
+                    },
+                }
+            }
+            json::Object(res)
+        },
+        ListStart => {
+            let mut res = Vec::new();
+            loop {
+                match try!(next(parser)) {
+                    ListEnd => break,
+                    event => res.push(try!(value(parser, event))),
+                }
+            }
+            json::List(res)
+        },
+        BooleanValue(x) => json::Boolean(x),
+        I64Value(x) => json::I64(x),
+        U64Value(x) => json::U64(x),
+        F64Value(x) => json::F64(x),
+        StringValue(x) => json::String(x),
+        NullValue => json::Null,
+        ObjectEnd | ListEnd | Error(_) => unreachable!(),
+    })
+}
+
+/// Hand the entries of the game section starting with `event` to `add`.
+///
+/// Entries in the flat layout of version 1 are upgraded on the way, the
+/// error for the first one is returned for datasets claiming a later version.
+fn load_game<T: Iterator<char>>(parser: &mut Parser<T>, game: Game, event: JsonEvent,
+                                add: &mut |Game, String, Entry|) -> Result<Option<LoadError>, LoadError> {
+    if event != ObjectStart {
+        try!(value(parser, event));
+        return Err(Schema(vec![game.key().to_string()], "is not an object".to_string()));
+    }
+    let mut flat = None;
+    loop {
+        let event = try!(next(parser));
+        if event == ObjectEnd {
+            return Ok(flat);
+        }
+        let insult = member_key(parser);
+        let entry = match try!(value(parser, event)) {
+            json::String(retort) => {
+                if flat.is_none() {
+                    flat = schema::validate_entry(game, insult[], &json::String(retort.clone())).err();
+                }
+                Entry::new(retort[])
+            },
+            data => {
+                try!(schema::validate_entry(game, insult[], &data));
+                let mut decoder = json::Decoder::new(data);
+                let entry: Entry = match Decodable::decode(&mut decoder) {
+                    Ok(x) => x,
+                    Err(e) => return Err(Schema(Vec::new(), e.to_string())),
+                };
+                entry
+            },
+        };
+        (*add)(game, insult, entry);
+    }
+}
+
+fn load_dataset<T: Iterator<char>>(parser: &mut Parser<T>, add: &mut |Game, String, Entry|)
+                                   -> Result<Insults, LoadError> {
+    match try!(next(parser)) {
+        ObjectStart => (),
+        event => {
+            try!(value(parser, event));
+            return Err(Schema(Vec::new(), "the dataset is not an object".to_string()));
+        },
+    }
+
+    let mut sections = TreeMap::new();
+    let mut flat = None;
+    loop {
+        let event = try!(next(parser));
+        if event == ObjectEnd {
+            break;
+        }
+        let section = member_key(parser);
+        match Game::from_key(section[]) {
+            Some(game) => {
+                let error = try!(load_game(parser, game, event, add));
+                flat = flat.or(error);
+                // Validated and decoded empty, the entries were handed to `add`
+                sections.insert(section, json::Object(TreeMap::new()));
+            },
+            None => {
+                let data = try!(value(parser, event));
+                sections.insert(section, data);
+            },
+        }
+    }
+    if let Some(Error(e)) = parser.next() {
+        return Err(Syntax(e));
+    }
+
+    let data = json::Object(sections);
+    if let (Some(e), Some(v)) = (flat, migrations::version(&data)) {
+        if v >= 2 {
+            return Err(e);
+        }
+    }
+    Insults::try_from_json(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{File, MemReader};
+
+    use insults::{Insults, fixture, read_file};
+    use game::MonkeyIsland1;
+    use schema::Schema;
+
+    #[test]
+    fn test_from_reader() {
+        let file = File::open(&Path::new("insults.json")).unwrap();
+        let streamed = Insults::try_from_reader(file).unwrap();
+        assert!(streamed == Insults::try_from_str(read_file("insults.json")[]).unwrap());
+
+        let v1 = fixture(r#"{"version": 1,
+            "monkey_island1": {"You fight like a dairy farmer.": "How appropriate. You fight like a cow."}}"#);
+        let mi = Insults::try_from_reader(MemReader::new(v1.as_bytes().to_vec())).unwrap();
+        assert_eq!(mi.count_in(MonkeyIsland1), 1);
+
+        let bad = fixture(r#"{"monkey_island1": {"Insult": {"retort": 1}}}"#);
+        match Insults::try_from_reader(MemReader::new(bad.as_bytes().to_vec())) {
+            Err(Schema(path, _)) => assert_eq!(path[0][], "monkey_island1"),
+            x => panic!("{}", x),
+        }
+    }
+}
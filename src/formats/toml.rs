@@ -0,0 +1,141 @@
+//! TOML packs.
+//!
+//! TOML keys can't hold a whole insult so every game is an array of tables,
+//! which leaves room for comments:
+//!
+//! ```toml
+//! failed_retorts = ["Oh yeah?", "I'm shaking, I'm shaking!"]
+//!
+//! # The classic
+//! [[monkey_island1]]
+//! insult = "You fight like a dairy farmer."
+//! retort = "How appropriate. You fight like a cow."
+//! speaker = "Pirate"
+//!
+//! [monkey_island1.audio]
+//! file = "mi1_duels.ogg"
+//! start_ms = 1200
+//! ```
+//!
+//! The fields of an entry besides `insult` are those of the JSON layout.
+
+use std::collections::TreeMap;
+use serialize::json::{mod, Json};
+use toml::{mod, Parser, Value};
+
+use game::Game;
+use insults::{mod, Insults};
+
+impl Insults {
+    /// Load insults from a TOML file.
+    pub fn from_toml_path(location: &str) -> Insults {
+        Insults::from_toml_str(insults::read_file(location)[])
+    }
+
+    /// Load insults from a TOML string.
+    pub fn from_toml_str(s: &str) -> Insults {
+        let mut parser = Parser::new(s);
+        match parser.parse() {
+            Some(table) => Insults::from_json(pack_to_json(table)),
+            None => {
+                let e = &parser.errors[0];
+                let (line, col) = parser.to_linecol(e.lo);
+                panic!("toml error: {}:{}: {}", line + 1, col + 1, e.desc)
+            }
+        }
+    }
+}
+
+/// Convert a pack to the JSON layout, the arrays of insult tables of the games become objects.
+fn pack_to_json(pack: toml::Table) -> Json {
+    let mut res = TreeMap::new();
+    for (key, value) in pack.into_iter() {
+        let value = match value {
+            toml::Array(entries) if Game::from_key(key[]).is_some() =>
+                json::Object(entries.into_iter().map(to_pair).collect()),
+            x => to_json(x),
+        };
+        res.insert(key, value);
+    }
+    json::Object(res)
+}
+
+/// An insult and its entry, with every field of the table but the insult.
+fn to_pair(entry: Value) -> (String, Json) {
+    let valid = entry.lookup("retort").and_then(|x| x.as_str()).is_some();
+    let insult = entry.lookup("insult").and_then(|x| x.as_str()).map(|x| x.to_string());
+    match (entry, insult) {
+        (toml::Table(fields), Some(insult)) if valid => {
+            let fields = fields.into_iter()
+                .filter(|&(ref k, _)| k[] != "insult")
+                .map(|(k, v)| (k, to_json(v)))
+                .collect();
+            (insult, json::Object(fields))
+        },
+        (entry, _) => panic!("toml error: expected `insult` and `retort` in {}", entry),
+    }
+}
+
+fn to_json(value: Value) -> Json {
+    match value {
+        toml::String(s) => json::String(s),
+        // Like the JSON parser, which reads positive numbers as unsigned
+        toml::Integer(i) if i >= 0 => json::U64(i as u64),
+        toml::Integer(i) => json::I64(i),
+        toml::Float(f) => json::F64(f),
+        toml::Boolean(b) => json::Boolean(b),
+        toml::Datetime(s) => json::String(s),
+        toml::Array(xs) => json::List(xs.into_iter().map(to_json).collect()),
+        toml::Table(t) => json::Object(t.into_iter().map(|(k, v)| (k, to_json(v))).collect()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use game::MonkeyIsland1;
+    use insults::Insults;
+
+    #[test]
+    fn test_from_toml_str() {
+        let mi = Insults::from_toml_str(r#"
+failed_retorts = ["Oh yeah?"]
+opponent_failed_retorts = []
+lookup_priority = []
+monkey_island3 = []
+captain_rottingham = []
+monkey_island4 = []
+
+# The classic
+[[monkey_island1]]
+insult = "You fight like a dairy farmer."
+retort = "How appropriate. You fight like a cow."
+
+speaker = "Pirate"
+rating = "harsh"
+
+[monkey_island1.audio]
+file = "mi1_duels.ogg"
+start_ms = 1200
+end_ms = 2900
+
+[[sword_master]]
+insult = "I will milk every drop of blood from your body!"
+retort = "How appropriate. You fight like a cow."
+"#);
+
+        assert_eq!(mi.failed_retorts(), ["Oh yeah?".to_string()][]);
+        assert_eq!(mi.mi1_retort("You fight like a dairy farmer."),
+            Some("How appropriate. You fight like a cow."));
+        assert_eq!(mi.sword_master_retort("I will milk every drop of blood from your body!"),
+            Some("How appropriate. You fight like a cow."));
+        assert_eq!(mi.insults().len(), 2);
+
+        let entry = mi.entry(MonkeyIsland1, "You fight like a dairy farmer.").unwrap();
+        assert_eq!(entry.speaker, Some("Pirate".to_string()));
+        assert!(entry.is_harsh());
+        let clip = mi.audio_for("You fight like a dairy farmer.").unwrap();
+        assert_eq!((clip.file[], clip.start_ms, clip.end_ms), ("mi1_duels.ogg", Some(1200), Some(2900)));
+        assert_eq!(mi.opponent_failed_retorts(), mi.failed_retorts());
+        assert!(mi.game_priority().is_empty());
+    }
+}
@@ -0,0 +1,255 @@
+//! YAML packs.
+//!
+//! Only the subset of YAML used by translation files is understood: block
+//! mappings and sequences, plain and quoted scalars, empty `{}` and `[]`, and comments.
+//! The items of a sequence may be indented as deep as its key or deeper.
+//!
+//! ```yaml
+//! failed_retorts:
+//!   - Oh yeah?
+//!
+//! monkey_island1:
+//!   # The classic
+//!   "You fight like a dairy farmer.": "How appropriate. You fight like a cow."
+//! sword_master: {}
+//! ```
+
+use std::collections::TreeMap;
+use serialize::json::{mod, Json};
+
+use insults::{mod, Insults};
+
+impl Insults {
+    /// Load insults from a YAML file.
+    pub fn from_yaml_path(location: &str) -> Insults {
+        Insults::from_yaml_str(insults::read_file(location)[])
+    }
+
+    /// Load insults from a YAML string.
+    pub fn from_yaml_str(s: &str) -> Insults {
+        match parse(s) {
+            Ok(x) => Insults::from_json(x),
+            Err(e) => panic!("yaml error: {}", e),
+        }
+    }
+}
+
+/// A line with content, comments stripped.
+struct Line<'a> {
+    num: uint,
+    indent: uint,
+    text: &'a str,
+}
+
+/// Parse a YAML document to JSON.
+pub fn parse(s: &str) -> Result<Json, String> {
+    let mut lines = Vec::new();
+    for (i, line) in s.lines().enumerate() {
+        let text = strip_comment(line).trim_right();
+        if text.trim_left().is_empty() || text == "---" {
+            continue;
+        }
+        let content = text.trim_left();
+        lines.push(Line {
+            num: i + 1,
+            indent: text.len() - content.len(),
+            text: content,
+        });
+    }
+
+    if lines.is_empty() {
+        return Ok(json::Null);
+    }
+    let mut pos = 0;
+    let indent = lines[0].indent;
+    let res = try!(block(lines[], &mut pos, indent));
+    match lines[].get(pos) {
+        Some(line) => Err(format!("line {}: unexpected indentation", line.num)),
+        None => Ok(res),
+    }
+}
+
+/// Parse the mapping or sequence at `indent` starting at `lines[*pos]`.
+fn block(lines: &[Line], pos: &mut uint, indent: uint) -> Result<Json, String> {
+    if lines[*pos].text.starts_with("-") {
+        let mut res = Vec::new();
+        while *pos < lines.len() && lines[*pos].indent == indent && lines[*pos].text.starts_with("-") {
+            let line = &lines[*pos];
+            *pos += 1;
+            let item = line.text[1..].trim_left();
+            res.push(try!(value(lines, pos, indent, item, line.num)));
+        }
+        Ok(json::List(res))
+    } else {
+        let mut res = TreeMap::new();
+        while *pos < lines.len() && lines[*pos].indent == indent {
+            let line = &lines[*pos];
+            *pos += 1;
+            let (key, rest) = try!(key(line.text, line.num));
+            let v = match lines.get(*pos) {
+                // A sequence may sit at the indentation of its key
+                Some(next) if rest.is_empty() && next.indent == indent && next.text.starts_with("-") =>
+                    try!(block(lines, pos, indent)),
+                _ => try!(value(lines, pos, indent, rest, line.num)),
+            };
+            res.insert(key, v);
+        }
+        Ok(json::Object(res))
+    }
+}
+
+/// The value `text` after a key or `-`, or the nested block when empty.
+fn value(lines: &[Line], pos: &mut uint, indent: uint, text: &str, num: uint)
+        -> Result<Json, String> {
+    if !text.is_empty() {
+        return scalar(text, num);
+    }
+    match lines.get(*pos) {
+        Some(next) if next.indent > indent => block(lines, pos, next.indent),
+        _ => Ok(json::Null),
+    }
+}
+
+/// Split `key: value`, the key may be quoted.
+fn key<'a>(text: &'a str, num: uint) -> Result<(String, &'a str), String> {
+    let (key, rest) = if text.starts_with("\"") || text.starts_with("'") {
+        let (key, len) = try!(quoted(text, num));
+        (key, text[len..].trim_left())
+    } else {
+        match text.find_str(": ") {
+            Some(i) => (text[..i].trim_right().to_string(), text[i..]),
+            None if text.ends_with(":") => (text[..text.len() - 1].trim_right().to_string(), ":"),
+            None => return Err(format!("line {}: expected `key: value`", num)),
+        }
+    };
+
+    if rest.starts_with(":") {
+        Ok((key, rest[1..].trim_left()))
+    } else {
+        Err(format!("line {}: expected `:` after key", num))
+    }
+}
+
+fn scalar(text: &str, num: uint) -> Result<Json, String> {
+    if text.starts_with("\"") || text.starts_with("'") {
+        let (s, len) = try!(quoted(text, num));
+        if !text[len..].trim().is_empty() {
+            return Err(format!("line {}: trailing characters after string", num));
+        }
+        return Ok(json::String(s));
+    }
+
+    Ok(match text {
+        "{}" => json::Object(TreeMap::new()),
+        "[]" => json::List(Vec::new()),
+        "~" | "null" => json::Null,
+        "true" => json::Boolean(true),
+        "false" => json::Boolean(false),
+        "|" | ">" => return Err(format!("line {}: block scalars are not supported", num)),
+        _ => match from_str::<i64>(text) {
+            Some(i) => json::I64(i),
+            None => json::String(text.to_string()),
+        },
+    })
+}
+
+/// Parse the quoted string starting `text`, returning it and its length in `text`.
+fn quoted(text: &str, num: uint) -> Result<(String, uint), String> {
+    let quote = text.char_at(0);
+    let mut res = String::new();
+    let mut chars = text.char_indices().skip(1);
+    loop {
+        match chars.next() {
+            Some((i, c)) if c == quote => {
+                // '' is an escaped ' in single quoted strings
+                if quote == '\'' && text[i + 1..].starts_with("'") {
+                    chars.next();
+                    res.push('\'');
+                } else {
+                    return Ok((res, i + 1));
+                }
+            },
+            Some((_, '\\')) if quote == '"' => match chars.next() {
+                Some((_, 'n')) => res.push('\n'),
+                Some((_, 't')) => res.push('\t'),
+                Some((_, c)) => res.push(c),
+                None => break,
+            },
+            Some((_, c)) => res.push(c),
+            None => break,
+        }
+    }
+    Err(format!("line {}: unterminated string", num))
+}
+
+/// Remove a trailing comment, `#` starts a comment at the start or after whitespace outside quotes.
+fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    let mut prev = ' ';
+    for (i, c) in line.char_indices() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => {},
+            None if (c == '"' || c == '\'') && (prev.is_whitespace() || prev == ':' || prev == '-') =>
+                quote = Some(c),
+            None if c == '#' && prev.is_whitespace() => return line[..i],
+            None => {},
+        }
+        prev = c;
+    }
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use insults::Insults;
+
+    #[test]
+    fn test_from_yaml_str() {
+        let mi = Insults::from_yaml_str(r#"
+# Translated by the crew of the Sea Monkey
+failed_retorts:
+  - Oh yeah?
+  - 'I''m shaking, I''m shaking!'
+
+monkey_island1:
+  # The classic
+  "You fight like a dairy farmer.": "How appropriate. You fight like a cow."
+  I once owned a dog that was smarter then you.: He must have taught you everything you know.
+sword_master: {}
+monkey_island3: {}
+captain_rottingham: {}
+monkey_island4:
+  "Hey, look over there!": "Yeah, yeah I know: it's a three headed monkey."
+"#);
+
+        assert_eq!(mi.failed_retorts(), ["Oh yeah?".to_string(),
+                                         "I'm shaking, I'm shaking!".to_string()][]);
+        assert_eq!(mi.mi1_retort("You fight like a dairy farmer."),
+            Some("How appropriate. You fight like a cow."));
+        assert_eq!(mi.mi1_retort("I once owned a dog that was smarter then you."),
+            Some("He must have taught you everything you know."));
+        assert_eq!(mi.mi4_retort("Hey, look over there!"),
+            Some("Yeah, yeah I know: it's a three headed monkey."));
+    }
+
+    #[test]
+    fn test_unindented_sequence() {
+        let mi = Insults::from_yaml_str(r#"
+failed_retorts:
+- Oh yeah?
+- I'm shaking, I'm shaking!
+monkey_island1:
+  "You fight like a dairy farmer.": "How appropriate. You fight like a cow."
+sword_master: {}
+monkey_island3: {}
+captain_rottingham: {}
+monkey_island4: {}
+"#);
+
+        assert_eq!(mi.failed_retorts(), ["Oh yeah?".to_string(),
+                                         "I'm shaking, I'm shaking!".to_string()][]);
+        assert_eq!(mi.mi1_retort("You fight like a dairy farmer."),
+            Some("How appropriate. You fight like a cow."));
+    }
+}
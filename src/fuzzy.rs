@@ -0,0 +1,143 @@
+//! Matching misspelled insults, and suggestions when nothing matches.
+//!
+//! Insults are compared to the input by edit distance after normalizing
+//! both, scored from 0 for nothing in common to 1 for the same text.
+
+use std::cmp;
+
+use insults::Insults;
+use matching::{Index, MatchResult, Fuzzy};
+use normalize::normalize;
+
+/// Score a misspelling needs to count as a match.
+pub const MIN_SCORE: f64 = 0.8;
+
+/// A known insult close to the input.
+#[deriving(Clone, PartialEq, Show)]
+pub struct Suggestion<'a> {
+    pub insult: &'a str,
+    /// From 0 to 1, 1 when the normalized texts are the same.
+    pub score: f64,
+}
+
+/// Edits to turn `a` into `b`, counting insertions, deletions and substitutions.
+pub fn levenshtein(a: &str, b: &str) -> uint {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<uint> = range(0, b.len() + 1).collect();
+    for (i, x) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, y) in b.iter().enumerate() {
+            let above = row[j + 1];
+            row[j + 1] = if x == *y {
+                diagonal
+            } else {
+                1 + cmp::min(diagonal, cmp::min(above, row[j]))
+            };
+            diagonal = above;
+        }
+    }
+    row[b.len()]
+}
+
+/// How alike `a` and `b` are, from 0 to 1.
+fn similarity(a: &str, b: &str) -> f64 {
+    let len = cmp::max(a.chars().count(), b.chars().count());
+    if len == 0 {
+        return 1.0;
+    }
+    1.0 - levenshtein(a, b) as f64 / len as f64
+}
+
+/// Insults indexed for comparing by edit distance.
+pub struct FuzzyIndex<'a> {
+    insults: &'a Insults,
+    /// Normalized insults with the insult they came from, ordered by insult.
+    keys: Vec<(String, &'a str)>,
+}
+
+impl<'a> FuzzyIndex<'a> {
+    /// Index the insults of `insults` and its fallbacks.
+    pub fn new(insults: &'a Insults) -> FuzzyIndex<'a> {
+        let mut all: Vec<&'a str> = Vec::new();
+        let mut current = Some(insults);
+        loop {
+            match current {
+                Some(x) => {
+                    all.extend(x.insults().into_iter().map(|s| s[]));
+                    current = x.fallback();
+                },
+                None => break,
+            }
+        }
+        all.sort();
+        all.dedup();
+        FuzzyIndex { insults: insults, keys: all.into_iter().map(|s| (normalize(s), s)).collect() }
+    }
+
+    /// The `n` insults closest to `input`, best first.
+    pub fn suggest(&self, input: &str, n: uint) -> Vec<Suggestion<'a>> {
+        let input = normalize(input);
+        let mut res: Vec<Suggestion<'a>> = self.keys.iter()
+            .map(|&(ref key, insult)| Suggestion { insult: insult, score: similarity(input[], key[]) })
+            .collect();
+        // Stable, so equal scores stay ordered by insult
+        res.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap_or(Equal));
+        res.truncate(n);
+        res
+    }
+
+    /// The insult `input` is a misspelling of, if one scores at least `MIN_SCORE`.
+    pub fn insult(&self, input: &str) -> Option<Suggestion<'a>> {
+        self.suggest(input, 1).into_iter().find(|x| x.score >= MIN_SCORE)
+    }
+
+    /// The retort to the insult `input` is a misspelling of, as confident as it scored.
+    pub fn retort(&self, input: &str) -> Option<MatchResult<'a>> {
+        self.insult(input).and_then(|x| MatchResult::new(self.insults, x.insult, Fuzzy, x.score))
+    }
+}
+
+impl<'a> Index<'a> for FuzzyIndex<'a> {
+    fn lookup(&self, input: &str) -> Option<MatchResult<'a>> {
+        self.retort(input)
+    }
+}
+
+impl Insults {
+    /// An index matching misspelled insults.
+    pub fn fuzzy_index<'a>(&'a self) -> FuzzyIndex<'a> {
+        FuzzyIndex::new(self)
+    }
+
+    /// The `n` known insults closest to `insult`, best first, for a "Did you mean" prompt.
+    ///
+    /// Indexes the insults on every call, keep the one from `fuzzy_index`
+    /// to suggest many.
+    pub fn suggest<'a>(&'a self, insult: &str, n: uint) -> Vec<Suggestion<'a>> {
+        self.fuzzy_index().suggest(insult, n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use insults::Insults;
+    use super::*;
+
+    #[test]
+    fn test_suggest() {
+        assert_eq!(levenshtein("kitten", "sitting"), 3);
+        assert_eq!(levenshtein("", "arr"), 3);
+
+        let mi = Insults::new("insults.json");
+        let suggestions = mi.suggest("You fight like a dary farmer!", 3);
+        assert_eq!(suggestions.len(), 3);
+        assert_eq!(suggestions[0].insult, "You fight like a dairy farmer.");
+        assert!(suggestions[0].score > suggestions[1].score);
+
+        let index = mi.fuzzy_index();
+        assert_eq!(index.retort("you fihgt like a dairy farmer").map(|x| x.retort),
+                   Some("How appropriate. You fight like a cow."));
+        assert_eq!(index.insult("Arr"), None);
+    }
+}
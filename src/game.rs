@@ -0,0 +1,50 @@
+//! The games, and sets within them, insults come from.
+
+#[cfg(not(feature = "std"))]
+use core::prelude::*;
+
+/// A set of insults.
+#[deriving(Clone, PartialEq, Eq, PartialOrd, Ord, Show)]
+#[cfg_attr(feature = "std", deriving(Hash))]
+pub enum Game {
+    /// The Secret of Monkey Island.
+    MonkeyIsland1,
+    /// The Sword Master in The Secret of Monkey Island.
+    SwordMaster,
+    /// The Curse of Monkey Island.
+    MonkeyIsland3,
+    /// Captain Rottingham in The Curse of Monkey Island.
+    CaptainRottingham,
+    /// Escape from Monkey Island, with Monkey Kombat arm wrestling.
+    MonkeyIsland4,
+}
+
+static GAMES: [Game, ..5] = [MonkeyIsland1, SwordMaster, MonkeyIsland3,
+                             CaptainRottingham, MonkeyIsland4];
+
+/// The retort to an insult of the Sword Master in Monkey Island 1.
+pub static NOT_FAIR_SWORD_MASTER: &'static str = "That's not fair, you're using the Sword Master's insults!";
+/// The retort to an insult of Captain Rottingham in Monkey Island 3.
+pub static NOT_FAIR_ROTTINGHAM: &'static str = "That's not fair, you're using Captain Rottingham's insults!";
+
+impl Game {
+    /// All games, in the order of the games.
+    pub fn all() -> &'static [Game] {
+        GAMES[]
+    }
+
+    /// The section of the game in the data files.
+    pub fn key(&self) -> &'static str {
+        match *self {
+            MonkeyIsland1 => "monkey_island1",
+            SwordMaster => "sword_master",
+            MonkeyIsland3 => "monkey_island3",
+            CaptainRottingham => "captain_rottingham",
+            MonkeyIsland4 => "monkey_island4",
+        }
+    }
+
+    pub fn from_key(key: &str) -> Option<Game> {
+        Game::all().iter().find(|g| g.key() == key).map(|g| *g)
+    }
+}
@@ -0,0 +1,486 @@
+//! GraphQL queries over a dataset, for front-ends fetching exactly the fields they need.
+//!
+//! Queries select fields with arguments and aliases from the schema in
+//! `SDL`. Variables, fragments, mutations and introspection aren't
+//! supported, only `__typename`.
+//!
+//! ```
+//! use mi_insults::Insults;
+//! use mi_insults::graphql::Schema;
+//!
+//! let schema = Schema::new(Insults::new("insults.json").into_shared());
+//! let res = schema.execute(r#"{ insult(text: "You fight like a dairy farmer.") { retort game { key } } }"#);
+//! assert_eq!(res.to_string()[],
+//!            r#"{"data":{"insult":{"game":{"key":"monkey_island1"},"retort":"How appropriate. You fight like a cow."}}}"#);
+//! ```
+//!
+//! With the `serve` feature the server answers queries at `/graphql`.
+
+use std::collections::TreeMap;
+use std::sync::Arc;
+use serialize::json::{mod, Json, ToJson};
+
+use game::Game;
+use insults::{Insults, Entry};
+use search;
+use translations::Translations;
+
+/// The schema in the GraphQL schema language.
+pub static SDL: &'static str = r#"type Query {
+  games: [Game!]!
+  game(key: String!): Game
+  insults(game: String): [Insult!]!
+  insult(text: String!): Insult
+  retort(insult: String!): String
+  search(query: String!): [SearchHit!]!
+  failedRetorts: [String!]!
+  locale: String!
+}
+
+type Game {
+  key: String!
+  insults: [Insult!]!
+}
+
+type Insult {
+  text: String!
+  retort: String!
+  game: Game!
+  speaker: String
+  context: String
+  canonicalId: String
+  translation(lang: String!): Insult
+}
+
+type SearchHit {
+  field: String!
+  insult: Insult!
+}
+"#;
+
+/// A selected field of a query.
+#[deriving(Clone, PartialEq, Show)]
+pub struct Field {
+    pub alias: Option<String>,
+    pub name: String,
+    pub arguments: TreeMap<String, Json>,
+    /// Fields selected of the value, empty for scalars.
+    pub selections: Vec<Field>,
+}
+
+/// Deepest nesting of selections in a query.
+///
+/// Results grow with every level of lists like `{ games { insults { game { insults ... } } } }`.
+pub const MAX_DEPTH: uint = 8;
+
+/// Parse a query into the fields it selects on `Query`.
+pub fn parse(query: &str) -> Result<Vec<Field>, String> {
+    let mut parser = Parser { s: query, pos: 0, depth: 0 };
+    parser.document().map_err(|e| format!("{} at {}", e, parser.pos))
+}
+
+/// Answers queries on a shared dataset.
+pub struct Schema {
+    insults: Arc<Insults>,
+    translations: Option<Translations>,
+}
+
+#[deriving(Clone)]
+struct InsultRef<'a> {
+    insults: &'a Insults,
+    game: Game,
+    text: &'a str,
+    entry: &'a Entry,
+}
+
+enum Node<'a> {
+    QueryNode,
+    GameNode(Game),
+    InsultNode(InsultRef<'a>),
+    HitNode(&'static str, InsultRef<'a>),
+}
+
+impl<'a> Node<'a> {
+    fn type_name(&self) -> &'static str {
+        match *self {
+            QueryNode => "Query",
+            GameNode(_) => "Game",
+            InsultNode(_) => "Insult",
+            HitNode(..) => "SearchHit",
+        }
+    }
+}
+
+/// A field value before its fields are selected.
+enum Resolved<'a> {
+    Scalar(Json),
+    One(Option<Node<'a>>),
+    Many(Vec<Node<'a>>),
+}
+
+impl Schema {
+    pub fn new(insults: Arc<Insults>) -> Schema {
+        Schema { insults: insults, translations: None }
+    }
+
+    /// Resolve `Insult.translation` with `translations`, it's null otherwise.
+    pub fn with_translations(mut self, translations: Translations) -> Schema {
+        self.translations = Some(translations);
+        self
+    }
+
+    /// Run `query`, returning `{"data": ...}` or `{"errors": [{"message": ...}]}`.
+    pub fn execute(&self, query: &str) -> Json {
+        let mut res = TreeMap::new();
+        match parse(query).and_then(|fields| self.select(QueryNode, fields[])) {
+            Ok(data) => {
+                res.insert("data".to_string(), data);
+            },
+            Err(msg) => {
+                let mut error = TreeMap::new();
+                error.insert("message".to_string(), msg.to_json());
+                res.insert("errors".to_string(), json::List(vec![json::Object(error)]));
+            },
+        }
+        json::Object(res)
+    }
+
+    fn select<'a>(&'a self, node: Node<'a>, fields: &[Field]) -> Result<Json, String> {
+        let mut res = TreeMap::new();
+        for field in fields.iter() {
+            let value = if field.name[] == "__typename" {
+                Scalar(node.type_name().to_json())
+            } else {
+                try!(self.resolve(&node, field))
+            };
+            let value = match value {
+                Scalar(_) if !field.selections.is_empty() =>
+                    return Err(format!("`{}` has no fields to select", field.name)),
+                Scalar(x) => x,
+                _ if field.selections.is_empty() =>
+                    return Err(format!("`{}` needs fields to select", field.name)),
+                One(None) => json::Null,
+                One(Some(x)) => try!(self.select(x, field.selections[])),
+                Many(xs) => {
+                    let mut list = Vec::new();
+                    for x in xs.into_iter() {
+                        list.push(try!(self.select(x, field.selections[])));
+                    }
+                    json::List(list)
+                },
+            };
+            res.insert(field.alias.clone().unwrap_or(field.name.clone()), value);
+        }
+        Ok(json::Object(res))
+    }
+
+    fn resolve<'a>(&'a self, node: &Node<'a>, field: &Field) -> Result<Resolved<'a>, String> {
+        let insults = &*self.insults;
+        let res = match (node, field.name[]) {
+            (&QueryNode, "games") => Many(Game::all().iter().map(|g| GameNode(*g)).collect()),
+            (&QueryNode, "game") => One(Game::from_key(try!(string_arg(field, "key"))).map(GameNode)),
+            (&QueryNode, "insults") => {
+                let games = match field.arguments.get(&"game".to_string()) {
+                    Some(_) => {
+                        let key = try!(string_arg(field, "game"));
+                        match Game::from_key(key) {
+                            Some(game) => vec![game],
+                            None => return Err(format!("unknown game `{}`", key)),
+                        }
+                    },
+                    None => Game::all().to_vec(),
+                };
+                let mut res = Vec::new();
+                for game in games.into_iter() {
+                    res.extend(insults_of(insults, game).into_iter());
+                }
+                Many(res)
+            },
+            (&QueryNode, "insult") => One(find(insults, try!(string_arg(field, "text"))).map(InsultNode)),
+            (&QueryNode, "retort") =>
+                Scalar(insults.retort(try!(string_arg(field, "insult"))).map(|x| x.to_string()).to_json()),
+            (&QueryNode, "search") => {
+                let hits = insults.search(try!(string_arg(field, "query")));
+                Many(hits.into_iter().filter_map(|hit| {
+                    let name = match hit.field {
+                        search::Insult => "insult",
+                        search::Retort => "retort",
+                    };
+                    find_in(insults, hit.game, hit.insult).map(|x| HitNode(name, x))
+                }).collect())
+            },
+            (&QueryNode, "failedRetorts") => Scalar(insults.failed_retorts().to_vec().to_json()),
+            (&QueryNode, "locale") => Scalar(insults.locale().to_json()),
+            (&GameNode(game), "key") => Scalar(game.key().to_json()),
+            (&GameNode(game), "insults") => Many(insults_of(insults, game)),
+            (&InsultNode(ref x), "text") => Scalar(x.text.to_json()),
+            (&InsultNode(ref x), "retort") => Scalar(x.entry.retort.to_json()),
+            (&InsultNode(ref x), "game") => One(Some(GameNode(x.game))),
+            (&InsultNode(ref x), "speaker") => Scalar(x.entry.speaker.to_json()),
+            (&InsultNode(ref x), "context") => Scalar(x.entry.context.to_json()),
+            (&InsultNode(ref x), "canonicalId") =>
+                Scalar(x.insults.canonical_id(x.text).map(|x| x.to_string()).to_json()),
+            (&InsultNode(ref x), "translation") =>
+                One(self.translation(x, try!(string_arg(field, "lang"))).map(InsultNode)),
+            (&HitNode(name, _), "field") => Scalar(name.to_json()),
+            (&HitNode(_, ref x), "insult") => One(Some(InsultNode(x.clone()))),
+            (node, name) => return Err(format!("unknown field `{}` on {}", name, node.type_name())),
+        };
+        Ok(res)
+    }
+
+    fn translation<'a>(&'a self, insult: &InsultRef<'a>, lang: &str) -> Option<InsultRef<'a>> {
+        let translations = match self.translations {
+            Some(ref x) => x,
+            None => return None,
+        };
+        let pack = match translations.get(lang) {
+            Some(x) => x,
+            None => return None,
+        };
+        insult.insults.canonical_id(insult.text)
+            .and_then(|id| find(pack, translations.insult_in(id, lang)))
+    }
+}
+
+fn string_arg<'a>(field: &'a Field, name: &str) -> Result<&'a str, String> {
+    match field.arguments.get(&name.to_string()) {
+        Some(&json::String(ref x)) => Ok(x[]),
+        Some(_) => Err(format!("`{}` of `{}` must be a string", name, field.name)),
+        None => Err(format!("`{}` needs the argument `{}`", field.name, name)),
+    }
+}
+
+fn insults_of<'a>(insults: &'a Insults, game: Game) -> Vec<Node<'a>> {
+    insults.entries(game).into_iter()
+        .map(|(text, entry)| InsultNode(InsultRef { insults: insults, game: game, text: text[], entry: entry }))
+        .collect()
+}
+
+fn find_in<'a>(insults: &'a Insults, game: Game, text: &str) -> Option<InsultRef<'a>> {
+    insults.entries(game).into_iter()
+        .find(|&(x, _)| x[] == text)
+        .map(|(x, entry)| InsultRef { insults: insults, game: game, text: x[], entry: entry })
+}
+
+/// The insult `text` in the first game having it.
+fn find<'a>(insults: &'a Insults, text: &str) -> Option<InsultRef<'a>> {
+    Game::all().iter().filter_map(|game| find_in(insults, *game, text)).next()
+}
+
+struct Parser<'a> {
+    s: &'a str,
+    pos: uint,
+    /// Selection sets entered and not yet left.
+    depth: uint,
+}
+
+impl<'a> Parser<'a> {
+    fn document(&mut self) -> Result<Vec<Field>, String> {
+        self.skip();
+        if self.peek() != Some('{') {
+            // A named or anonymous query operation
+            if self.name() != "query" {
+                return Err("only queries are supported".to_string());
+            }
+            self.skip();
+            self.name();
+        }
+        let res = try!(self.selection_set());
+        self.skip();
+        if self.pos < self.s.len() {
+            return Err("trailing characters".to_string());
+        }
+        Ok(res)
+    }
+
+    fn rest(&self) -> &'a str {
+        self.s[self.pos..]
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    /// Skip whitespace, commas and comments.
+    fn skip(&mut self) {
+        loop {
+            let rest = self.rest();
+            let trimmed = rest.trim_left_chars(|c: char| c.is_whitespace() || c == ',');
+            self.pos += rest.len() - trimmed.len();
+
+            if trimmed.starts_with("#") {
+                self.pos += trimmed.find('\n').unwrap_or(trimmed.len());
+            } else {
+                return;
+            }
+        }
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), String> {
+        if self.eat(c) {
+            Ok(())
+        } else {
+            Err(format!("expected `{}`", c))
+        }
+    }
+
+    /// Consume `c` if it's next.
+    fn eat(&mut self, c: char) -> bool {
+        self.skip();
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// A name, empty if there's none.
+    fn name(&mut self) -> &'a str {
+        let rest = self.rest();
+        let len = rest.find(|c: char| !(c.is_alphanumeric() || c == '_')).unwrap_or(rest.len());
+        self.pos += len;
+        rest[..len]
+    }
+
+    fn selection_set(&mut self) -> Result<Vec<Field>, String> {
+        try!(self.expect('{'));
+        if self.depth == MAX_DEPTH {
+            return Err(format!("selections nested deeper than {}", MAX_DEPTH));
+        }
+        self.depth += 1;
+        let mut res = Vec::new();
+        while !self.eat('}') {
+            res.push(try!(self.field()));
+        }
+        self.depth -= 1;
+        Ok(res)
+    }
+
+    fn field(&mut self) -> Result<Field, String> {
+        let mut name = self.name();
+        let mut alias = None;
+        if name.is_empty() {
+            return Err("expected a field".to_string());
+        }
+        if self.eat(':') {
+            self.skip();
+            alias = Some(name.to_string());
+            name = self.name();
+            if name.is_empty() {
+                return Err("expected a field".to_string());
+            }
+        }
+
+        let mut arguments = TreeMap::new();
+        if self.eat('(') {
+            while !self.eat(')') {
+                self.skip();
+                let key = self.name();
+                if key.is_empty() {
+                    return Err("expected an argument".to_string());
+                }
+                try!(self.expect(':'));
+                arguments.insert(key.to_string(), try!(self.value()));
+            }
+        }
+
+        self.skip();
+        let selections = if self.peek() == Some('{') {
+            try!(self.selection_set())
+        } else {
+            Vec::new()
+        };
+        Ok(Field { alias: alias, name: name.to_string(), arguments: arguments, selections: selections })
+    }
+
+    fn value(&mut self) -> Result<Json, String> {
+        self.skip();
+        match self.peek() {
+            Some('"') => self.string().map(json::String),
+            Some(c) if c.is_digit() || c == '-' => {
+                let rest = self.rest();
+                let len = rest.find(|c: char| !(c.is_digit() || c == '-')).unwrap_or(rest.len());
+                self.pos += len;
+                match from_str::<i64>(rest[..len]) {
+                    Some(i) => Ok(json::I64(i)),
+                    None => Err(format!("invalid number `{}`", rest[..len])),
+                }
+            },
+            Some(c) if c.is_alphabetic() || c == '_' => match self.name() {
+                "true" => Ok(json::Boolean(true)),
+                "false" => Ok(json::Boolean(false)),
+                "null" => Ok(json::Null),
+                x => Err(format!("unexpected `{}`, variables and enums aren't supported", x)),
+            },
+            Some(c) => Err(format!("unexpected `{}`", c)),
+            None => Err("unexpected end of input".to_string()),
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        try!(self.expect('"'));
+        let mut res = String::new();
+        let mut chars = self.rest().char_indices();
+        loop {
+            match chars.next() {
+                Some((i, '"')) => {
+                    self.pos += i + 1;
+                    return Ok(res);
+                },
+                Some((_, '\\')) => match chars.next() {
+                    Some((_, 'n')) => res.push('\n'),
+                    Some((_, 'r')) => res.push('\r'),
+                    Some((_, 't')) => res.push('\t'),
+                    Some((_, c)) => res.push(c),
+                    None => break,
+                },
+                Some((_, c)) => res.push(c),
+                None => break,
+            }
+        }
+        Err("unterminated string".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serialize::json;
+
+    use insults::Insults;
+    use translations::Translations;
+    use super::*;
+
+    #[test]
+    fn test_execute() {
+        let schema = Schema::new(Insults::new("insults.json").into_shared())
+            .with_translations(Translations::load("insults.json", &["de"]));
+
+        let res = schema.execute(r#"
+            query Farmer {
+              farmer: insult(text: "You fight like a dairy farmer.") {
+                __typename
+                translation(lang: "de") { text retort }
+              }
+              retort(insult: "You're lazy!")
+            }"#);
+        assert_eq!(res.to_string()[], concat!(r#"{"data":{"farmer":{"__typename":"Insult","#,
+            r#""translation":{"retort":"Wie passend. Du kämpfst wie eine Kuh.","#,
+            r#""text":"Du kämpfst wie ein dummer Bauer."}},"retort":null}}"#));
+
+        let res = schema.execute(r#"{ games { key insults { text } } search(query: "cow") { field } }"#);
+        let games = res.find_path(&[&"data".to_string(), &"games".to_string()]).unwrap();
+        assert_eq!(games.as_list().unwrap().len(), 5);
+        assert!(res.to_string()[].contains(r#"{"field":"retort"}"#));
+
+        let error = json::from_str(r#"{"errors":[{"message":"unknown field `name` on Game"}]}"#).unwrap();
+        assert_eq!(schema.execute("{ games { name } }"), error);
+        assert!(parse("{ insult(text: $text) { text } }").is_err());
+
+        let deep = "{ games { insults { game { insults { game { insults { game { insults { text } } } } } } } } }";
+        assert!(parse(deep).is_err());
+        let res = schema.execute(deep);
+        assert!(res.find(&"errors".to_string()).is_some() && res.find(&"data".to_string()).is_none());
+    }
+}
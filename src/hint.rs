@@ -0,0 +1,83 @@
+//! Partially revealed retorts for practice modes.
+
+use insults::Insults;
+
+/// How much of a retort a hint reveals, from least to most.
+#[deriving(Clone, PartialEq, Eq, PartialOrd, Ord, Show)]
+pub enum HintLevel {
+    /// The first word with its punctuation, "Why, ...".
+    FirstWord,
+    /// The first letter of every word, "W__, d__ y__ w___ t_ b_____ o__?".
+    Initials,
+    /// Every other word, the rest by their first letter, "W__, did y__ want t_ borrow o__?".
+    Masked,
+}
+
+/// Reveal `retort` as much as `level` allows.
+pub fn reveal(retort: &str, level: HintLevel) -> String {
+    match level {
+        FirstWord => match retort.words().next() {
+            Some(word) => format!("{} ...", word),
+            None => String::new(),
+        },
+        Initials => mask_words(retort, |_| true),
+        Masked => mask_words(retort, |i| i % 2 == 0),
+    }
+}
+
+/// Mask the letters after the first of the words `i` where `f(i)` holds,
+/// keeping whitespace and punctuation.
+fn mask_words(s: &str, f: |uint| -> bool) -> String {
+    let mut res = String::new();
+    let mut word = 0u;
+    let mut in_word = false;
+    let mut first = false;
+    for c in s.chars() {
+        if c.is_whitespace() {
+            if in_word {
+                word += 1;
+            }
+            in_word = false;
+            res.push(c);
+            continue;
+        }
+        if !in_word {
+            in_word = true;
+            first = true;
+        }
+        if c.is_alphanumeric() && f(word) && !first {
+            res.push('_');
+        } else {
+            res.push(c);
+        }
+        if c.is_alphanumeric() {
+            first = false;
+        }
+    }
+    res
+}
+
+impl Insults {
+    /// A hint to the retort of `insult`, or None if it has no retort.
+    pub fn hint(&self, insult: &str, level: HintLevel) -> Option<String> {
+        self.retort(insult).map(|x| reveal(x, level))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use insults::Insults;
+    use super::*;
+
+    #[test]
+    fn test_hint() {
+        let retort = "Why, did you want to borrow one?";
+        assert_eq!(reveal(retort, FirstWord)[], "Why, ...");
+        assert_eq!(reveal(retort, Initials)[], "W__, d__ y__ w___ t_ b_____ o__?");
+        assert_eq!(reveal(retort, Masked)[], "W__, did y__ want t_ borrow o__?");
+
+        let mi = Insults::new("insults.json");
+        assert_eq!(mi.hint("You fight like a dairy farmer.", FirstWord), Some("How ...".to_string()));
+        assert_eq!(mi.hint("How appropriate. You fight like a cow.", Initials), None);
+    }
+}
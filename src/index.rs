@@ -0,0 +1,201 @@
+//! The exact index, precomputed answers to the common lookup.
+//!
+//! `Insults::retort` walks the games in priority order and then the
+//! fallbacks, composing the input to NFC for every table it looks in. An
+//! `ExactIndex` does that walk once, so a lookup is a single hash probe.
+//!
+//! The index and the tables of `Insults` hash with FNV-1a, like the
+//! built-in dataset of `static-data`. The SipHash of `HashMap` guards against keys picked to
+//! collide, which a dataset fixed once it's loaded doesn't need, and FNV
+//! is quicker on keys as short as insults. The benchmarks in
+//! `benches/lookup.rs` compare the two, run them with `cargo bench`.
+//!
+//! `ReverseIndex` answers the other way around, the insults of a retort.
+//! `FastMap` is the map behind them and the dataset, other indices may take it up.
+
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher, Writer};
+
+use game::Game;
+use insults::Insults;
+use matching::{Index, MatchResult, Exact};
+use normalize::nfc;
+
+/// Hashes with FNV-1a.
+#[deriving(Clone, Default)]
+pub struct FnvHasher;
+
+/// The state of FNV-1a while hashing.
+pub struct FnvState(u64);
+
+impl Hasher<FnvState> for FnvHasher {
+    fn hash<T: Hash<FnvState>>(&self, value: &T) -> u64 {
+        let mut state = FnvState(0xcbf29ce484222325);
+        value.hash(&mut state);
+        let FnvState(res) = state;
+        res
+    }
+}
+
+impl Writer for FnvState {
+    fn write(&mut self, bytes: &[u8]) {
+        let FnvState(mut h) = *self;
+        for b in bytes.iter() {
+            h ^= *b as u64;
+            h *= 0x100000001b3;
+        }
+        *self = FnvState(h);
+    }
+}
+
+/// A hash map hashing with FNV-1a.
+pub type FastMap<K, V> = HashMap<K, V, FnvHasher>;
+
+/// An empty `FastMap`.
+pub fn fast_map<K: Eq + Hash<FnvState>, V>() -> FastMap<K, V> {
+    HashMap::with_hasher(FnvHasher)
+}
+
+/// What a lookup of an insult answers.
+struct Answer<'a> {
+    insult: &'a str,
+    retort: &'a str,
+    game: Option<Game>,
+}
+
+/// Every insult of a dataset and its fallbacks with its answer, by the insult in NFC.
+pub struct ExactIndex<'a> {
+    answers: FastMap<String, Answer<'a>>,
+}
+
+impl<'a> ExactIndex<'a> {
+    pub fn new(insults: &'a Insults) -> ExactIndex<'a> {
+        let mut answers = fast_map();
+        let mut current = Some(insults);
+        loop {
+            let x = match current {
+                Some(x) => x,
+                None => break,
+            };
+            for insult in x.insults().into_iter() {
+                if answers.contains_key(insult) {
+                    continue;
+                }
+                // The answers of the dataset, which may prefer another game or a translation
+                if let Some(retort) = insults.retort(insult[]) {
+                    let answer = Answer { insult: insult[], retort: retort, game: insults.game_of(insult[]) };
+                    answers.insert(insult.clone(), answer);
+                }
+            }
+            current = x.fallback();
+        }
+        ExactIndex { answers: answers }
+    }
+
+    pub fn len(&self) -> uint {
+        self.answers.len()
+    }
+
+    /// The retort to `insult`, like `Insults::retort`.
+    pub fn retort(&self, insult: &str) -> Option<MatchResult<'a>> {
+        self.answers.get(&nfc(insult)).map(|x| MatchResult {
+            retort: x.retort,
+            insult_matched: x.insult,
+            game: x.game,
+            method: Exact,
+            confidence: 1.0,
+        })
+    }
+}
+
+impl<'a> Index<'a> for ExactIndex<'a> {
+    fn lookup(&self, input: &str) -> Option<MatchResult<'a>> {
+        self.retort(input)
+    }
+}
+
+/// The insults of every retort of a dataset and its fallbacks, by the retort in NFC.
+pub struct ReverseIndex<'a> {
+    insults: FastMap<String, Vec<&'a str>>,
+}
+
+impl<'a> ReverseIndex<'a> {
+    pub fn new(insults: &'a Insults) -> ReverseIndex<'a> {
+        let mut res: FastMap<String, Vec<&'a str>> = fast_map();
+        let mut current = Some(insults);
+        loop {
+            let x = match current {
+                Some(x) => x,
+                None => break,
+            };
+            for insult in x.insults().into_iter() {
+                if let Some(retort) = insults.retort(insult[]) {
+                    let key = retort.to_string();
+                    if !res.contains_key(&key) {
+                        res.insert(key.clone(), Vec::new());
+                    }
+                    let list = res.get_mut(&key).unwrap();
+                    if !list.contains(&insult[]) {
+                        list.push(insult[]);
+                    }
+                }
+            }
+            current = x.fallback();
+        }
+        for (_, list) in res.iter_mut() {
+            list.sort();
+        }
+        ReverseIndex { insults: res }
+    }
+
+    /// The insults `retort` answers, ordered.
+    pub fn insults<'b>(&'b self, retort: &str) -> &'b [&'a str] {
+        match self.insults.get(&nfc(retort)) {
+            Some(x) => x[],
+            None => NO_INSULTS,
+        }
+    }
+}
+
+static NO_INSULTS: &'static [&'static str] = &[];
+
+impl Insults {
+    /// An index answering exact lookups with a single hash probe.
+    pub fn exact_index<'a>(&'a self) -> ExactIndex<'a> {
+        ExactIndex::new(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use game::{Game, MonkeyIsland1};
+    use insults::Insults;
+    use matching::Index;
+    use super::*;
+
+    fn game_of<'a, I: Index<'a>>(index: &I, input: &str) -> Option<Game> {
+        index.lookup(input).and_then(|x| x.game)
+    }
+
+    #[test]
+    fn test_exact_index() {
+        let mut map = fast_map();
+        map.insert("Arr".to_string(), 1u);
+        assert_eq!(map.get(&"Arr".to_string()), Some(&1));
+
+        let de = Insults::with_locale("insults.json", "de");
+        let index = de.exact_index();
+        for insult in ["You fight like a dairy farmer.", "Hey, look over there!", "Oh yeah?"].iter() {
+            assert_eq!(index.retort(*insult).map(|x| x.retort), de.retort(*insult));
+        }
+        let mi = Insults::new("insults.json");
+        let index = mi.exact_index();
+        let insult = "You fight like a dairy farmer.";
+        assert_eq!(game_of(&index, insult), Some(MonkeyIsland1));
+        assert_eq!(game_of(&mi.normalized_index(), insult), game_of(&index, insult));
+
+        let reverse = ReverseIndex::new(&mi);
+        assert!(reverse.insults(mi.retort(insult).unwrap()).contains(&insult));
+        assert!(reverse.insults("Oh yeah?").is_empty());
+    }
+}
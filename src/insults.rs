@@ -1,85 +1,785 @@
-use std::collections::HashMap;
+#[cfg(not(feature = "std"))]
+use prelude::*;
+#[cfg(feature = "std")]
+use std::ascii::AsciiExt;
+#[cfg(feature = "std")]
+use std::collections::TreeMap;
+#[cfg(feature = "std")]
 use serialize::{json, Decodable};
-use std::io::{File, Open, Read};
+#[cfg(feature = "std")]
+use serialize::json::{Json, ToJson};
+#[cfg(feature = "std")]
+use std::io::{mod, Buffer, BufferedReader, File, IoError, IoResult, Open, Read};
+#[cfg(feature = "std")]
+use std::io::fs::PathExtensions;
+use std::iter::AdditiveIterator;
+#[cfg(feature = "std")]
+use std::mem;
+#[cfg(feature = "std")]
 use std::rand::{ mod, Rng };
+#[cfg(feature = "std")]
+use std::sync::{Arc, Future, Once, ONCE_INIT};
 
-#[deriving(Decodable)]
+use dialogue::{Dialogue, DialogueLine};
+use game::{Game, MonkeyIsland1, SwordMaster, MonkeyIsland3, CaptainRottingham, MonkeyIsland4};
+use game::{NOT_FAIR_SWORD_MASTER, NOT_FAIR_ROTTINGHAM};
+#[cfg(feature = "std")]
+use index::{FastMap, fast_map};
+use lookup::{mod, LookupPolicy};
+#[cfg(feature = "std")]
+use migrations;
+#[cfg(feature = "std")]
+use normalize::nfc;
+#[cfg(feature = "std")]
+use schema::{mod, LoadError, Checksum, Compression, Io, Syntax, Migration, Schema};
+#[cfg(feature = "std")]
+use sha256;
+
+#[cfg(feature = "flate")]
+use formats::gzip;
+#[cfg(feature = "std")]
+use formats::stream;
+
+/// The map of the tables, ordered without std.
+#[cfg(not(feature = "std"))]
+type FastMap<K, V> = TreeMap<K, V>;
+
+#[cfg(feature = "std")]
+/// Read all of the file at `location`.
+pub fn try_read_bytes(location: &str) -> IoResult<Vec<u8>> {
+    let p = Path::new(location);
+    let mut file = try!(File::open_mode(&p, Open, Read));
+    file.read_to_end()
+}
+
+#[cfg(feature = "std")]
+/// Read all of the file at `location`, panicking on errors.
+pub fn read_bytes(location: &str) -> Vec<u8> {
+    match try_read_bytes(location) {
+        Ok(f) => f,
+        Err(e) => panic!("file error: {}", e)
+    }
+}
+
+#[cfg(feature = "std")]
+pub fn not_utf8(location: &str) -> IoError {
+    IoError {
+        kind: io::InvalidInput,
+        desc: "not utf-8",
+        detail: Some(location.to_string()),
+    }
+}
+
+#[cfg(feature = "std")]
+/// Read all of the file at `location` as text.
+pub fn read_file(location: &str) -> String {
+    match String::from_utf8(read_bytes(location)) {
+        Ok(s) => s,
+        Err(_) => panic!("file error: {} is not utf-8", location)
+    }
+}
+
+#[cfg(feature = "std")]
+fn is_gzip(data: &[u8]) -> bool {
+    data.len() >= 2 && data[0] == 0x1f && data[1] == 0x8b
+}
+
+#[cfg(feature = "flate")]
+fn decompress(location: &str, data: &[u8]) -> Result<Vec<u8>, LoadError> {
+    match gzip::decompress(data) {
+        Ok(x) => Ok(x),
+        Err(e) => Err(Compression(format!("{}: {}", location, e)))
+    }
+}
+
+#[cfg(all(feature = "std", not(feature = "flate")))]
+fn decompress(location: &str, _: &[u8]) -> Result<Vec<u8>, LoadError> {
+    Err(Compression(format!("{} is gzip compressed, enable the flate feature to load it", location)))
+}
+
+/// A voice line in an audio file, for playing the original recordings.
+#[deriving(Clone, PartialEq, Show)]
+#[cfg_attr(feature = "std", deriving(Encodable, Decodable))]
+pub struct AudioClip {
+    /// The file, relative to the pack.
+    pub file: String,
+    /// Where the line starts in milliseconds, the start of the file if missing.
+    pub start_ms: Option<u64>,
+    /// Where the line ends in milliseconds, the end of the file if missing.
+    pub end_ms: Option<u64>,
+}
+
+impl AudioClip {
+    pub fn new(file: &str) -> AudioClip {
+        AudioClip { file: String::from_str(file), start_ms: None, end_ms: None }
+    }
+}
+
+#[cfg(feature = "std")]
+impl ToJson for AudioClip {
+    fn to_json(&self) -> Json {
+        let mut res = TreeMap::new();
+        res.insert("file".to_string(), self.file.to_json());
+        if let Some(x) = self.start_ms {
+            res.insert("start_ms".to_string(), x.to_json());
+        }
+        if let Some(x) = self.end_ms {
+            res.insert("end_ms".to_string(), x.to_json());
+        }
+        json::Object(res)
+    }
+}
+
+/// The retort to an insult, with metadata.
+#[deriving(Clone, PartialEq, Show, Default)]
+#[cfg_attr(feature = "std", deriving(Encodable, Decodable))]
+pub struct Entry {
+    pub retort: String,
+    /// Who throws the insult.
+    pub speaker: Option<String>,
+    /// Where in the game the insult is used.
+    pub context: Option<String>,
+    /// Content rating, `harsh` for entries too rough for kids and `everyone` otherwise.
+    pub rating: Option<String>,
+    /// The insult as said in the game.
+    pub audio: Option<AudioClip>,
+    /// The retort as said in the game.
+    pub retort_audio: Option<AudioClip>,
+}
+
+impl Entry {
+    pub fn new(retort: &str) -> Entry {
+        Entry {
+            retort: String::from_str(retort),
+            speaker: None,
+            context: None,
+            rating: None,
+            audio: None,
+            retort_audio: None,
+        }
+    }
+
+    /// Whether the entry is rated `harsh`, untagged entries aren't.
+    pub fn is_harsh(&self) -> bool {
+        self.rating.as_ref().map_or(false, |x| x[] == "harsh")
+    }
+}
+
+/// Lines said around duels rather than in them.
+#[deriving(Clone, PartialEq, Show, Default)]
+#[cfg_attr(feature = "std", deriving(Encodable, Decodable))]
+pub struct Banter {
+    /// Said to pick a fight.
+    pub taunts: Option<Vec<String>>,
+    /// Said after winning a duel.
+    pub victory: Option<Vec<String>>,
+    /// Said after losing a duel.
+    pub defeat: Option<Vec<String>>,
+}
+
+#[cfg(feature = "std")]
+impl ToJson for Banter {
+    fn to_json(&self) -> Json {
+        let mut res = TreeMap::new();
+        for &(key, ref lines) in [("taunts", &self.taunts), ("victory", &self.victory),
+                                  ("defeat", &self.defeat)].iter() {
+            if let Some(ref x) = **lines {
+                res.insert(key.to_string(), x.to_json());
+            }
+        }
+        json::Object(res)
+    }
+}
+
+static NO_LINES: &'static [String] = &[];
+
+/// What a retort to an insult is, from `retort_answers`.
+#[deriving(Clone, PartialEq, Eq, Show)]
+pub enum RetortJudgement {
+    /// The retort answers the insult.
+    Correct,
+    /// A retort of the dataset, to another insult.
+    KnownButWrong,
+    /// Not a retort of the dataset.
+    Unknown,
+}
+
+/// Which entries a dataset keeps.
+#[deriving(Clone, PartialEq, Show)]
+pub enum Filter {
+    Everything,
+    /// Only entries not rated `harsh`. No entry of the bundled
+    /// `insults.json` is, only packs rating their entries are affected.
+    FamilyFriendly,
+}
+
+impl Filter {
+    pub fn allows(&self, entry: &Entry) -> bool {
+        match *self {
+            Everything => true,
+            FamilyFriendly => !entry.is_harsh(),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl ToJson for Entry {
+    fn to_json(&self) -> Json {
+        let mut res = TreeMap::new();
+        res.insert("retort".to_string(), self.retort.to_json());
+        if let Some(ref x) = self.speaker {
+            res.insert("speaker".to_string(), x.to_json());
+        }
+        if let Some(ref x) = self.context {
+            res.insert("context".to_string(), x.to_json());
+        }
+        if let Some(ref x) = self.rating {
+            res.insert("rating".to_string(), x.to_json());
+        }
+        if let Some(ref x) = self.audio {
+            res.insert("audio".to_string(), x.to_json());
+        }
+        if let Some(ref x) = self.retort_audio {
+            res.insert("retort_audio".to_string(), x.to_json());
+        }
+        json::Object(res)
+    }
+}
+
+/// A loaded dataset.
+///
+/// Datasets are only changed while putting them together, like with
+/// `merge`, and are `Send + Sync`, share one between threads with
+/// `into_shared`. Cloning a shared one gives a copy to change for a
+/// session. The default dataset is empty.
+#[deriving(Clone, PartialEq, Show, Default)]
+#[cfg_attr(feature = "std", deriving(Encodable, Decodable))]
 pub struct Insults {
     failed_retorts: Vec<String>,
 
-    monkey_island1: HashMap<String, String>,
-    sword_master: HashMap<String, String>,
+    monkey_island1: FastMap<String, Entry>,
+    sword_master: FastMap<String, Entry>,
 
-    monkey_island3: HashMap<String, String>,
-    captain_rottingham: HashMap<String, String>,
+    monkey_island3: FastMap<String, Entry>,
+    captain_rottingham: FastMap<String, Entry>,
 
-    monkey_island4: HashMap<String, String>,
+    monkey_island4: FastMap<String, Entry>,
+
+    /// Language of the dataset, English if missing.
+    locale: Option<String>,
+    /// Dataset consulted when a lookup misses in this one.
+    fallback: Option<Box<Insults>>,
+    /// Translated insults mapped to their canonical id, the English insult.
+    canonical: Option<FastMap<String, String>>,
+    /// Games consulted by `retort`, the default policy if missing.
+    lookup_priority: Option<LookupPolicy>,
+    /// Dialogue trees by name.
+    dialogues: Option<FastMap<String, DialogueLine>>,
+    /// Taunts, victory and defeat lines.
+    banter: Option<Banter>,
+    /// Wrong answers of the opponent, the failed retorts if missing.
+    opponent_failed_retorts: Option<Vec<String>>,
+}
+
+/// The value of `key` composed to NFC, only allocating for non-ASCII keys.
+#[cfg(feature = "std")]
+fn get_nfc<'a, V>(map: &'a FastMap<String, V>, key: &str) -> Option<&'a V> {
+    if key.bytes().all(|b| b < 0x80) {
+        map.get(key)
+    } else {
+        map.get(nfc(key)[])
+    }
+}
+
+/// The value of `key`, as is without std.
+#[cfg(not(feature = "std"))]
+fn get_nfc<'a, V>(map: &'a FastMap<String, V>, key: &str) -> Option<&'a V> {
+    map.get(key)
+}
+
+/// `text` composed to NFC for the tables, as is without std.
+#[cfg(feature = "std")]
+fn normalized(text: &str) -> String {
+    nfc(text)
+}
+
+#[cfg(not(feature = "std"))]
+fn normalized(text: &str) -> String {
+    String::from_str(text)
+}
+
+#[cfg(feature = "std")]
+fn map_to_json<V: ToJson>(map: &FastMap<String, V>) -> Json {
+    json::Object(map.iter().map(|(k, v)| (k.clone(), v.to_json())).collect())
+}
+
+/// `insults.json` as it was when the crate was built.
+#[cfg(feature = "std")]
+pub static EMBEDDED: &'static str = include_str!("../insults.json");
+
+/// The embedded dataset, loaded on first use.
+///
+/// For scripts, `mi_insults::global().retort(x)` needs no instance to be
+/// passed around.
+#[cfg(feature = "std")]
+pub fn global() -> &'static Insults {
+    static mut GLOBAL: *const Insults = 0 as *const Insults;
+    static INIT: Once = ONCE_INIT;
+    unsafe {
+        INIT.doit(|| GLOBAL = mem::transmute(box Insults::from_str(EMBEDDED)));
+        &*GLOBAL
+    }
+}
+
+// Fails to compile if a field stops the dataset from being shared between threads
+#[allow(dead_code)]
+fn assert_send_sync() {
+    fn check<T: Send + Sync>() {}
+    check::<Insults>();
 }
 
 impl Insults {
+    #[cfg(feature = "std")]
     pub fn new(location: &str) -> Insults {
-        let p = Path::new(location);
-        let mut file = match File::open_mode(&p, Open, Read) {
-            Ok(f) => f,
-            Err(e) => panic!("file error: {}", e)
+        Insults::from_path(location)
+    }
+
+    /// The dataset behind a reference count, for the threads of a server.
+    #[cfg(feature = "std")]
+    pub fn into_shared(self) -> Arc<Insults> {
+        Arc::new(self)
+    }
+
+    /// Load insults from a JSON file.
+    ///
+    /// Gzip compressed files, like `insults.json.gz`, are decompressed
+    /// when the `flate` feature is enabled.
+    #[cfg(feature = "std")]
+    pub fn from_path(location: &str) -> Insults {
+        match Insults::try_from_path(location) {
+            Ok(x) => x,
+            Err(e) => panic!("{}: {}", location, e)
+        }
+    }
+
+    /// Load insults from a JSON string.
+    #[cfg(feature = "std")]
+    pub fn from_str(s: &str) -> Insults {
+        match Insults::try_from_str(s) {
+            Ok(x) => x,
+            Err(e) => panic!("{}", e)
+        }
+    }
+
+    /// Load insults from parsed JSON, in the layout of `insults.json`.
+    ///
+    /// Older layouts are migrated to the current one first.
+    #[cfg(feature = "std")]
+    pub fn from_json(json_object: Json) -> Insults {
+        match Insults::try_from_json(json_object) {
+            Ok(x) => x,
+            Err(e) => panic!("{}", e)
+        }
+    }
+
+    /// Load insults from a JSON file, like `from_path` but returning errors.
+    ///
+    /// Uncompressed files are parsed as they're read, without holding all of the file.
+    #[cfg(feature = "std")]
+    pub fn try_from_path(location: &str) -> Result<Insults, LoadError> {
+        trace_event!("loading {}", location);
+        let file = match File::open_mode(&Path::new(location), Open, Read) {
+            Ok(x) => x,
+            Err(e) => return Err(Io(e))
+        };
+        let mut reader = BufferedReader::new(file);
+        let compressed = match reader.fill_buf() {
+            Ok(buf) => is_gzip(buf),
+            Err(ref e) if e.kind == io::EndOfFile => false,
+            Err(e) => return Err(Io(e))
         };
+        if !compressed {
+            return stream::load(reader);
+        }
+        match reader.read_to_end() {
+            Ok(x) => Insults::try_from_bytes(location, x),
+            Err(e) => Err(Io(e))
+        }
+    }
 
-        let decoded: String = match file.read_to_string() {
-            Ok(f) => f,
-            Err(e) => panic!("file error: {}", e)
+    /// Load insults from a JSON file, checking that it has the SHA-256 digest
+    /// `expected_sha256` in hex so tampered or truncated files are rejected.
+    ///
+    /// The digest is of the file as distributed, before decompressing it.
+    #[cfg(feature = "std")]
+    pub fn from_path_verified(location: &str, expected_sha256: &str) -> Result<Insults, LoadError> {
+        let data = match try_read_bytes(location) {
+            Ok(x) => x,
+            Err(e) => return Err(Io(e))
         };
+        let actual = sha256::hex_digest(data[]);
+        if actual[] != expected_sha256.to_ascii_lower()[] {
+            return Err(Checksum(expected_sha256.to_string(), actual));
+        }
+        Insults::try_from_bytes(location, data)
+    }
+
+    /// Load insults from a JSON file on a separate task.
+    ///
+    /// The load runs on another task while the caller carries on, `get`
+    /// on the future blocks until it has finished.
+    #[cfg(feature = "std")]
+    pub fn from_path_async(location: &str) -> Future<Result<Insults, LoadError>> {
+        let location = location.to_string();
+        Future::spawn(proc() Insults::try_from_path(location[]))
+    }
+
+    /// Load insults from the JSON contents of `location`, maybe gzip compressed.
+    #[cfg(feature = "std")]
+    pub fn try_from_bytes(location: &str, data: Vec<u8>) -> Result<Insults, LoadError> {
+        let data = if is_gzip(data[]) { try!(decompress(location, data[])) } else { data };
+        match String::from_utf8(data) {
+            Ok(s) => Insults::try_from_str(s[]),
+            Err(_) => Err(Io(not_utf8(location)))
+        }
+    }
+
+    /// Load insults from a JSON string, like `from_str` but returning errors.
+    #[cfg(feature = "std")]
+    pub fn try_from_str(s: &str) -> Result<Insults, LoadError> {
+        match json::from_str(s) {
+            Ok(x) => Insults::try_from_json(x),
+            Err(e) => Err(Syntax(e))
+        }
+    }
 
-        let json_object = match json::from_str(decoded[]) {
+    /// Load insults from parsed JSON, like `from_json` but returning errors.
+    ///
+    /// The JSON is validated, errors point at the offending section and insult.
+    /// Insults and retorts are composed to Unicode NFC, so packs mixing
+    /// encodings match whatever form the lookups come in.
+    #[cfg(feature = "std")]
+    pub fn try_from_json(json_object: Json) -> Result<Insults, LoadError> {
+        let json_object = match migrations::migrate(json_object) {
             Ok(x) => x,
-            Err(e) => panic!("json error: {}", e)
+            Err(e) => return Err(Migration(e))
         };
-        let mut decoder = json::Decoder::new(json_object);
+        if let Err(e) = schema::validate(&json_object) {
+            trace_event!("dataset rejected, {}", e);
+            return Err(e);
+        }
 
+        let mut decoder = json::Decoder::new(json_object);
         match Decodable::decode(&mut decoder) {
-            Ok(v) => v,
-            Err(e) => panic!("Decoding error: {}", e)
+            Ok(v) => Ok(Insults::into_nfc(v)),
+            Err(e) => Err(Schema(Vec::new(), e.to_string()))
+        }
+    }
+
+    /// The dataset with every insult and retort in NFC.
+    #[cfg(feature = "std")]
+    fn into_nfc(mut self) -> Insults {
+        for game in Game::all().iter() {
+            let set = self.set_mut(*game);
+            let entries = mem::replace(set, fast_map());
+            *set = entries.into_iter().map(|(insult, mut entry)| {
+                entry.retort = nfc(entry.retort[]);
+                (nfc(insult[]), entry)
+            }).collect();
+        }
+        self.failed_retorts = self.failed_retorts.iter().map(|x| nfc(x[])).collect();
+        self.opponent_failed_retorts = self.opponent_failed_retorts.take().map(|x| {
+            x.iter().map(|x| nfc(x[])).collect()
+        });
+        self.canonical = self.canonical.take().map(|c| {
+            c.into_iter().map(|(insult, id)| (nfc(insult[]), nfc(id[]))).collect()
+        });
+        self
+    }
+
+    /// Load the dataset for `locale`, falling back to English for missing translations.
+    ///
+    /// `location` is the English dataset, translations are expected next to it,
+    /// `insults.json` is translated to German in `insults.de.json`.
+    /// Without a translation only the English dataset is used.
+    #[cfg(feature = "std")]
+    pub fn with_locale(location: &str, locale: &str) -> Insults {
+        let english = Insults::new(location);
+        if locale == "en" {
+            return english;
+        }
+
+        let p = Path::new(location);
+        let stem = p.filestem_str().unwrap_or("insults");
+        let translated = p.dir_path().join(format!("{}.{}.json", stem, locale));
+        if !translated.exists() {
+            return english;
+        }
+
+        let mut res = Insults::new(translated.as_str().unwrap());
+        res.locale = Some(locale.to_string());
+        res.with_fallback(english)
+    }
+
+    /// Use `fallback` for lookups missing in this dataset.
+    pub fn with_fallback(mut self, fallback: Insults) -> Insults {
+        self.fallback = Some(box fallback);
+        self
+    }
+
+    /// Drop the entries `filter` doesn't allow, here and in the fallback.
+    ///
+    /// Listing, random insults, lookups and duels then never see them.
+    #[cfg(feature = "std")]
+    pub fn with_filter(mut self, filter: Filter) -> Insults {
+        for game in Game::all().iter() {
+            let set = self.set_mut(*game);
+            let entries = mem::replace(set, fast_map());
+            *set = entries.into_iter().filter(|&(_, ref e)| filter.allows(e)).collect();
+        }
+        self.fallback = self.fallback.take().map(|f| box (*f).with_filter(filter.clone()));
+        self
+    }
+
+    /// Language of the dataset.
+    pub fn locale<'a>(&'a self) -> &'a str {
+        match self.locale {
+            Some(ref x) => x[],
+            None => "en",
+        }
+    }
+
+    /// The dataset used when a translation is missing.
+    pub fn fallback<'a>(&'a self) -> Option<&'a Insults> {
+        match self.fallback {
+            Some(ref x) => Some(&**x),
+            None => None,
+        }
+    }
+
+    /// The canonical id of `insult`, the English insult it translates.
+    ///
+    /// English insults are their own canonical id.
+    pub fn canonical_id<'a>(&'a self, insult: &'a str) -> Option<&'a str> {
+        let translated = self.canonical.as_ref().and_then(|c| get_nfc(c, insult));
+        match translated {
+            Some(id) => Some(id[]),
+            None if self.locale.is_none() && self.retort(insult).is_some() => Some(insult),
+            None => self.fallback().and_then(|f| f.canonical_id(insult)),
+        }
+    }
+
+    /// Translated insults paired with their canonical id.
+    pub fn translated_insults(&self) -> Vec<(&String, &String)> {
+        match self.canonical {
+            Some(ref c) => c.iter().collect(),
+            None => Vec::new(),
         }
     }
 
     pub fn failed_retorts<'a>(&'a self) -> &'a [String] {
-        self.failed_retorts[]
+        match self.fallback() {
+            Some(f) if self.failed_retorts.is_empty() => f.failed_retorts(),
+            _ => self.failed_retorts[],
+        }
     }
 
+    #[cfg(feature = "std")]
     pub fn rand_failed_retort<'a, R: Rng>(&'a self, rng: &mut R) -> &'a str {
         let retorts = self.failed_retorts();
         rand::sample(rng, retorts.iter(), 1)[0][]
     }
 
+    /// Wrong answers for the opponent, the failed retorts unless the dataset or
+    /// its fallback has some of its own.
+    pub fn opponent_failed_retorts<'a>(&'a self) -> &'a [String] {
+        match self.opponent_failed_retorts {
+            Some(ref x) if !x.is_empty() => x[],
+            _ => match self.fallback() {
+                Some(f) if !f.opponent_failed_retorts().is_empty() => f.opponent_failed_retorts(),
+                _ => self.failed_retorts(),
+            },
+        }
+    }
+
+    /// A random wrong answer for the opponent.
+    #[cfg(feature = "std")]
+    pub fn rand_opponent_failed_retort<'a, R: Rng>(&'a self, rng: &mut R) -> &'a str {
+        let retorts = self.opponent_failed_retorts();
+        rand::sample(rng, retorts.iter(), 1)[0][]
+    }
+
+    /// A random failed retort from the thread's random generator.
+    #[cfg(feature = "std")]
+    pub fn rand_failed_retort_default<'a>(&'a self) -> &'a str {
+        self.rand_failed_retort(&mut rand::task_rng())
+    }
+
+    /// The lines of the banter section `section` picks, the fallback's if this
+    /// dataset has none.
+    fn banter_lines<'a>(&'a self, section: |&'a Banter| -> &'a Option<Vec<String>>) -> &'a [String] {
+        let mut current = Some(self);
+        loop {
+            match current {
+                Some(x) => {
+                    if let Some(ref banter) = x.banter {
+                        match *section(banter) {
+                            Some(ref lines) if !lines.is_empty() => return lines[],
+                            _ => (),
+                        }
+                    }
+                    current = x.fallback();
+                },
+                None => return NO_LINES,
+            }
+        }
+    }
+
+    /// Lines to pick a fight with.
+    pub fn taunts<'a>(&'a self) -> &'a [String] {
+        self.banter_lines(|b| &b.taunts)
+    }
+
+    /// Lines for winning a duel.
+    pub fn victory_lines<'a>(&'a self) -> &'a [String] {
+        self.banter_lines(|b| &b.victory)
+    }
+
+    /// Lines for losing a duel.
+    pub fn defeat_lines<'a>(&'a self) -> &'a [String] {
+        self.banter_lines(|b| &b.defeat)
+    }
+
+    /// A random taunt, None if the dataset has none.
+    #[cfg(feature = "std")]
+    pub fn rand_taunt<'a, R: Rng>(&'a self, rng: &mut R) -> Option<&'a str> {
+        rng.choose(self.taunts()).map(|x| x[])
+    }
+
+    /// A random line for winning a duel, None if the dataset has none.
+    #[cfg(feature = "std")]
+    pub fn rand_victory_line<'a, R: Rng>(&'a self, rng: &mut R) -> Option<&'a str> {
+        rng.choose(self.victory_lines()).map(|x| x[])
+    }
+
+    /// A random line for losing a duel, None if the dataset has none.
+    #[cfg(feature = "std")]
+    pub fn rand_defeat_line<'a, R: Rng>(&'a self, rng: &mut R) -> Option<&'a str> {
+        rng.choose(self.defeat_lines()).map(|x| x[])
+    }
+
+    /// Consult `games` in this order in `retort`, leaving out the others.
+    pub fn set_game_priority(&mut self, games: &[Game]) {
+        self.lookup_priority = Some(LookupPolicy::new(games));
+    }
+
+    /// Like `set_game_priority`, for building a dataset.
+    pub fn with_lookup_policy(mut self, policy: LookupPolicy) -> Insults {
+        self.lookup_priority = Some(policy);
+        self
+    }
+
+    /// The games consulted by `retort`, in order.
+    pub fn game_priority<'a>(&'a self) -> &'a [Game] {
+        match self.lookup_priority {
+            Some(ref x) => x.games(),
+            None => lookup::DEFAULT_PRIORITY[],
+        }
+    }
+
+    /// The dialogue tree `name` from its first line, here or in the fallback.
+    pub fn dialogue<'a>(&'a self, name: &str) -> Option<Dialogue<'a>> {
+        match self.dialogues.as_ref().and_then(|x| x.get(name)) {
+            Some(x) => Some(Dialogue::new(x)),
+            None => self.fallback().and_then(|f| f.dialogue(name)),
+        }
+    }
+
+    /// Names of the dialogue trees of the dataset, sorted.
+    pub fn dialogue_names<'a>(&'a self) -> Vec<&'a str> {
+        let mut res: Vec<&str> = match self.dialogues {
+            Some(ref x) => x.keys().map(|s| s[]).collect(),
+            None => Vec::new(),
+        };
+        res.sort();
+        res
+    }
+
     /// Correctly retort to insult, if there is one.
+    ///
+    /// The games are consulted in the order of `game_priority`, by default
+    /// the Sword Master first.
     pub fn retort<'a>(&'a self, insult: &str) -> Option<&'a str> {
-        self.sword_master_retort(insult).or_else(||
-            self.mi1_retort(insult).or_else(||
-                self.mi3_retort(insult).or_else(||
-                    self.captain_rottingham_retort(insult).or_else(||
-                        self.mi4_retort(insult)))))
+        let mut found = None;
+        for game in self.game_priority().iter() {
+            found = self.game_retort(*game, insult).map(|x| (*game, x));
+            if found.is_some() {
+                break;
+            }
+        }
+        count!(record_lookup(found.map(|(game, _)| game)));
+        found.map(|(_, x)| x)
+    }
+
+    /// The retorts to `insult` in every game having it, in game order.
+    ///
+    /// Unlike `retort` this doesn't stop at the first game, and gives the
+    /// retorts of the data rather than the Sword Master's custom one.
+    pub fn retort_all<'a>(&'a self, insult: &str) -> Vec<(Game, &'a str)> {
+        Game::all().iter()
+            .filter_map(|game| self.game_entry(*game, insult).map(|x| (*game, x.retort[])))
+            .collect()
+    }
+
+    /// The entry of `insult` in `game`, here or in the fallback.
+    fn game_entry<'a>(&'a self, game: Game, insult: &str) -> Option<&'a Entry> {
+        self.entry(game, insult).or_else(|| self.fallback().and_then(|f| f.game_entry(game, insult)))
+    }
+
+    /// Retort to an insult from `game`, like the retort function of the game.
+    fn game_retort<'a>(&'a self, game: Game, insult: &str) -> Option<&'a str> {
+        match game {
+            MonkeyIsland1 => self.mi1_retort(insult),
+            SwordMaster => self.sword_master_retort(insult),
+            MonkeyIsland3 => self.mi3_retort(insult),
+            CaptainRottingham => self.captain_rottingham_retort(insult),
+            MonkeyIsland4 => self.mi4_retort(insult),
+        }
     }
 
     /// Correctly retort to an insult, with fallback to a random failed retort.
+    #[cfg(feature = "std")]
     pub fn retort_or_rand_fail<'a, R: Rng>(&'a self, insult: &str, rng: &mut R) -> &'a str {
         match self.retort(insult) {
             Some(x) => x,
-            None => self.rand_failed_retort(rng),
+            None => {
+                trace_event!("no retort to {}, answering with a failed retort", insult);
+                self.rand_failed_retort(rng)
+            },
         }
     }
 
+    /// Like `retort_or_rand_fail` with the thread's random generator.
+    #[cfg(feature = "std")]
+    pub fn retort_or_rand_fail_default<'a>(&'a self, insult: &str) -> &'a str {
+        self.retort_or_rand_fail(insult, &mut rand::task_rng())
+    }
+
     /// Retort to an insult from Monkey Island 1.
     ///
     /// Will return a custom retort if you're using an insult from the Sword Master.
     pub fn mi1_retort<'a>(&'a self, insult: &str) -> Option<&'a str> {
         match self.sword_master_retort(insult) {
-            Some(_) => Some("That's not fair, you're using the Sword Master's insults!"),
-            None => self.retort_from(insult, &self.monkey_island1)
+            Some(_) => Some(NOT_FAIR_SWORD_MASTER),
+            None => self.retort_from(insult, &self.monkey_island1).or_else(||
+                self.fallback().and_then(|f| f.mi1_retort(insult)))
         }
     }
 
     /// Retort to a sword master insult.
     pub fn sword_master_retort<'a>(&'a self, insult: &str) -> Option<&'a str> {
-        self.retort_from(insult, &self.sword_master)
+        self.retort_from(insult, &self.sword_master).or_else(||
+            self.fallback().and_then(|f| f.sword_master_retort(insult)))
     }
 
     /// Retort to an insult from Monkey Island 3.
@@ -87,28 +787,98 @@ impl Insults {
     /// Will return a custom retort if you're using an insult from Captain Rottingham.
     pub fn mi3_retort<'a>(&'a self, insult: &str) -> Option<&'a str> {
         match self.captain_rottingham_retort(insult) {
-            Some(_) => Some("That's not fair, you're using Captain Rottingham's insults!"),
-            None => self.retort_from(insult, &self.monkey_island3)
+            Some(_) => Some(NOT_FAIR_ROTTINGHAM),
+            None => self.retort_from(insult, &self.monkey_island3).or_else(||
+                self.fallback().and_then(|f| f.mi3_retort(insult)))
         }
     }
 
     /// Retort to a sword master insult.
     pub fn captain_rottingham_retort<'a>(&'a self, insult: &str) -> Option<&'a str> {
-        self.retort_from(insult, &self.captain_rottingham)
+        self.retort_from(insult, &self.captain_rottingham).or_else(||
+            self.fallback().and_then(|f| f.captain_rottingham_retort(insult)))
     }
 
     /// Retort to an insult from Monkey Island 4.
     pub fn mi4_retort<'a>(&'a self, insult: &str) -> Option<&'a str> {
-        self.retort_from(insult, &self.monkey_island4)
+        self.retort_from(insult, &self.monkey_island4).or_else(||
+            self.fallback().and_then(|f| f.mi4_retort(insult)))
     }
 
-    fn retort_from<'a>(&self, insult: &str, map: &'a HashMap<String, String>) -> Option<&'a str> {
-        match map.get(insult) {
-            Some(x) => Some(x[]),
+    fn retort_from<'a>(&self, insult: &str, map: &'a FastMap<String, Entry>) -> Option<&'a str> {
+        match get_nfc(map, insult) {
+            Some(x) => Some(x.retort[]),
             None => None
         }
     }
 
+    fn set<'a>(&'a self, game: Game) -> &'a FastMap<String, Entry> {
+        match game {
+            MonkeyIsland1 => &self.monkey_island1,
+            SwordMaster => &self.sword_master,
+            MonkeyIsland3 => &self.monkey_island3,
+            CaptainRottingham => &self.captain_rottingham,
+            MonkeyIsland4 => &self.monkey_island4,
+        }
+    }
+
+    fn set_mut<'a>(&'a mut self, game: Game) -> &'a mut FastMap<String, Entry> {
+        match game {
+            MonkeyIsland1 => &mut self.monkey_island1,
+            SwordMaster => &mut self.sword_master,
+            MonkeyIsland3 => &mut self.monkey_island3,
+            CaptainRottingham => &mut self.captain_rottingham,
+            MonkeyIsland4 => &mut self.monkey_island4,
+        }
+    }
+
+    /// The insults of `game` with their entries, ordered by insult.
+    pub fn entries<'a>(&'a self, game: Game) -> Vec<(&'a String, &'a Entry)> {
+        let mut res: Vec<(&String, &Entry)> = self.set(game).iter().collect();
+        res.sort_by(|&(a, _), &(b, _)| a.cmp(b));
+        res
+    }
+
+    /// The entry of `insult` in `game`.
+    pub fn entry<'a>(&'a self, game: Game, insult: &str) -> Option<&'a Entry> {
+        get_nfc(self.set(game), insult)
+    }
+
+    /// Add `entry` for `insult` to `game`, returning the entry it replaced.
+    pub fn insert(&mut self, game: Game, insult: &str, entry: Entry) -> Option<Entry> {
+        self.set_mut(game).insert(normalized(insult), entry)
+    }
+
+    /// The game of `insult`, here or in the fallback.
+    pub fn game_of(&self, insult: &str) -> Option<Game> {
+        for game in Game::all().iter() {
+            if self.entry(*game, insult).is_some() {
+                return Some(*game);
+            }
+        }
+        self.fallback().and_then(|f| f.game_of(insult))
+    }
+
+    /// The entry of `insult` in whichever game has it, like `retort`.
+    fn find_entry<'a>(&'a self, insult: &str) -> Option<&'a Entry> {
+        for game in Game::all().iter() {
+            if let Some(x) = self.entry(*game, insult) {
+                return Some(x);
+            }
+        }
+        self.fallback().and_then(|f| f.find_entry(insult))
+    }
+
+    /// The recording of `insult`, if the pack has one.
+    pub fn audio_for<'a>(&'a self, insult: &str) -> Option<&'a AudioClip> {
+        self.find_entry(insult).and_then(|x| x.audio.as_ref())
+    }
+
+    /// The recording of the retort to `insult`, if the pack has one.
+    pub fn retort_audio_for<'a>(&'a self, insult: &str) -> Option<&'a AudioClip> {
+        self.find_entry(insult).and_then(|x| x.retort_audio.as_ref())
+    }
+
     pub fn is_retort(&self, insult: &str, retort: &str) -> bool {
         match self.retort(insult) {
             Some(x) => x == retort,
@@ -116,6 +886,33 @@ impl Insults {
         }
     }
 
+    /// Whether `text` is an insult of some game, here or in the fallback.
+    pub fn is_insult(&self, text: &str) -> bool {
+        self.find_entry(text).is_some()
+    }
+
+    /// Whether `text` is the retort to some insult, here or in the fallback.
+    ///
+    /// Failed retorts don't count.
+    pub fn is_known_retort(&self, text: &str) -> bool {
+        let text = normalized(text);
+        Game::all().iter().any(|game| self.set(*game).values().any(|x| x.retort == text))
+            || self.fallback().map_or(false, |f| f.is_known_retort(text[]))
+    }
+
+    /// Whether `retort` answers `insult`, in the game of `retort` or of `retort_all`.
+    pub fn retort_answers(&self, insult: &str, retort: &str) -> RetortJudgement {
+        let retort = normalized(retort);
+        if self.retort(insult) == Some(retort[])
+                || self.retort_all(insult).iter().any(|&(_, x)| x == retort[]) {
+            Correct
+        } else if self.is_known_retort(retort[]) {
+            KnownButWrong
+        } else {
+            Unknown
+        }
+    }
+
     /// Get all insults.
     pub fn insults(&self) -> Vec<&String> {
         let mut res = self.mi1_insults();
@@ -126,11 +923,54 @@ impl Insults {
         res
     }
 
+    /// The insults `retort` answers, those of the games in `game_priority`.
+    pub fn answered_insults(&self) -> Vec<&String> {
+        let mut res = Vec::new();
+        for game in Game::all().iter().filter(|g| self.game_priority().contains(*g)) {
+            res.extend(self.set(*game).keys());
+        }
+        res
+    }
+
+    /// How many insults the dataset has, without the fallback, like `insults`.
+    pub fn len(&self) -> uint {
+        Game::all().iter().map(|game| self.count_in(*game)).sum()
+    }
+
+    /// How many insults `game` has.
+    pub fn count_in(&self, game: Game) -> uint {
+        self.set(game).len()
+    }
+
+    /// How many failed retorts there are, the fallback's if this dataset has none.
+    pub fn failed_retort_count(&self) -> uint {
+        self.failed_retorts().len()
+    }
+
+    /// Whether no game has an insult.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// A random insult, the same for the same seed of `rng`.
+    ///
+    /// Only insults `retort` answers are drawn.
+    #[cfg(feature = "std")]
     pub fn rand_insult<R: Rng>(&self, rng: &mut R) -> &String {
-        let all = self.insults();
+        let mut all = self.answered_insults();
+        // Map order differs between runs
+        all.sort();
         rand::sample(rng, all.into_iter(), 1)[0]
     }
 
+    /// A random insult from the thread's random generator.
+    ///
+    /// Pass a seeded generator to `rand_insult` for reproducible insults.
+    #[cfg(feature = "std")]
+    pub fn rand_insult_default(&self) -> &String {
+        self.rand_insult(&mut rand::task_rng())
+    }
+
     pub fn mi1_insults(&self) -> Vec<&String> {
         self.monkey_island1.keys().collect()
     }
@@ -150,10 +990,84 @@ impl Insults {
     pub fn monkey_island4_insults(&self) -> Vec<&String> {
         self.monkey_island4.keys().collect()
     }
+
+    /// The dataset as indented JSON, like `to_json`.
+    ///
+    /// Keys are sorted, so saving the same dataset gives the same file.
+    #[cfg(feature = "std")]
+    pub fn to_json_pretty(&self) -> String {
+        let mut res = self.to_json().to_pretty_str();
+        res.push('\n');
+        res
+    }
+
+    /// Save the dataset as indented JSON to `location`.
+    #[cfg(feature = "std")]
+    pub fn save(&self, location: &str) -> IoResult<()> {
+        File::create(&Path::new(location)).write_str(self.to_json_pretty()[])
+    }
+}
+
+#[cfg(feature = "std")]
+impl ToJson for Insults {
+    /// The dataset in the current layout, without the fallback.
+    fn to_json(&self) -> Json {
+        let mut res = TreeMap::new();
+        res.insert("version".to_string(), json::U64(migrations::CURRENT_VERSION));
+        res.insert("failed_retorts".to_string(), self.failed_retorts.to_json());
+        for game in Game::all().iter() {
+            res.insert(game.key().to_string(), map_to_json(self.set(*game)));
+        }
+        if let Some(ref x) = self.locale {
+            res.insert("locale".to_string(), x.to_json());
+        }
+        if let Some(ref x) = self.canonical {
+            res.insert("canonical".to_string(), map_to_json(x));
+        }
+        if let Some(ref x) = self.lookup_priority {
+            res.insert("lookup_priority".to_string(), x.to_json());
+        }
+        if let Some(ref x) = self.dialogues {
+            res.insert("dialogues".to_string(), map_to_json(x));
+        }
+        if let Some(ref x) = self.banter {
+            res.insert("banter".to_string(), x.to_json());
+        }
+        if let Some(ref x) = self.opponent_failed_retorts {
+            res.insert("opponent_failed_retorts".to_string(), x.to_json());
+        }
+        json::Object(res)
+    }
+}
+
+/// The dataset `partial` with the sections it doesn't have added empty, at
+/// the current version unless it has one.
+#[cfg(test)]
+pub fn fixture(partial: &str) -> String {
+    let mut data = match json::from_str(partial) {
+        Ok(json::Object(x)) => x,
+        _ => panic!("fixture is not a JSON object"),
+    };
+    let mut sections = vec![("version".to_string(), json::U64(migrations::CURRENT_VERSION)),
+                            ("failed_retorts".to_string(), json::List(Vec::new()))];
+    for game in Game::all().iter() {
+        sections.push((game.key().to_string(), json::Object(TreeMap::new())));
+    }
+    for (key, value) in sections.into_iter() {
+        if !data.contains_key(&key) {
+            data.insert(key, value);
+        }
+    }
+    json::Object(data).to_string()
 }
 
 #[cfg(test)]
 mod tests {
+    use serialize::json::ToJson;
+    use std::io::TempDir;
+
+    use game::{MonkeyIsland1, SwordMaster, MonkeyIsland4, NOT_FAIR_SWORD_MASTER};
+    use quiz::Quiz;
     use super::*;
 
     #[test]
@@ -217,5 +1131,204 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn test_rand_default() {
+        let mi = Insults::new("insults.json");
+        assert!(mi.retort(mi.rand_insult_default()[]).is_some());
+        assert_eq!(mi.retort_or_rand_fail_default("Have you stopped wearing diapers yet?"),
+            "Why, did you want to borrow one?");
+        let failed = mi.retort_or_rand_fail_default("You're lazy!");
+        assert!(mi.failed_retorts().iter().any(|x| x[] == failed));
+    }
+
+    #[test]
+    fn test_retort_all() {
+        let mi = Insults::new("insults.json");
+        let retort = "He must have taught you everything you know.";
+        assert_eq!(mi.retort_all("Only once have I met such a coward!"),
+                   vec![(SwordMaster, retort), (MonkeyIsland4, retort)]);
+        assert_eq!(mi.retort_all("Have you stopped wearing diapers yet?"),
+                   vec![(MonkeyIsland1, "Why, did you want to borrow one?")]);
+        assert!(mi.retort_all("You're lazy!").is_empty());
+    }
+
+    #[test]
+    fn test_retort_answers() {
+        let mi = Insults::new("insults.json");
+        let cow = "How appropriate. You fight like a cow.";
+        assert!(mi.is_insult("You fight like a dairy farmer.") && !mi.is_insult(cow));
+        assert!(mi.is_known_retort(cow) && !mi.is_known_retort("You fight like a dairy farmer."));
+        assert!(!mi.is_known_retort(mi.failed_retorts()[0][]));
+
+        assert_eq!(mi.retort_answers("You fight like a dairy farmer.", cow), Correct);
+        assert_eq!(mi.retort_answers("Have you stopped wearing diapers yet?", cow), KnownButWrong);
+        assert_eq!(mi.retort_answers("You fight like a dairy farmer.", "Arr"), Unknown);
+    }
+
+    #[test]
+    fn test_clone() {
+        let mi = Insults::new("insults.json");
+        let mut session = mi.clone();
+        assert!(session == mi);
+        session.insert(MonkeyIsland1, "Arr!", Entry::new("Arr yourself."));
+        assert!(session != mi && session.is_insult("Arr!") && !mi.is_insult("Arr!"));
+
+        let empty: Insults = Default::default();
+        assert!(empty.is_empty() && empty.locale() == "en");
+        assert_eq!(Entry::new(""), Default::default());
+    }
+
+    #[test]
+    fn test_banter() {
+        let mut rng = rand::task_rng();
+        let mi = Insults::new("insults.json");
+        let taunt = mi.rand_taunt(&mut rng).unwrap();
+        assert!(mi.taunts().iter().any(|x| x[] == taunt));
+        assert!(mi.rand_victory_line(&mut rng).is_some() && mi.rand_defeat_line(&mut rng).is_some());
+        assert!(!mi.failed_retorts().iter().any(|x| x[] == taunt));
+
+        let de = Insults::with_locale("insults.json", "de");
+        assert_eq!(de.taunts(), mi.taunts());
+        assert_eq!(Insults::default().rand_taunt(&mut rng), None);
+    }
+
+    #[test]
+    fn test_opponent_failed_retorts() {
+        let mut rng = rand::task_rng();
+        let mi = Insults::new("insults.json");
+        let wrong = mi.rand_opponent_failed_retort(&mut rng);
+        assert!(mi.opponent_failed_retorts().iter().any(|x| x[] == wrong));
+        assert!(!mi.failed_retorts().iter().any(|x| x[] == wrong));
+        assert_eq!(Insults::with_locale("insults.json", "de").opponent_failed_retorts(),
+                   mi.opponent_failed_retorts());
+
+        let plain = Insults::from_str(fixture(r#"{"failed_retorts": ["Oh yeah?"]}"#)[]);
+        assert_eq!(plain.rand_opponent_failed_retort(&mut rng), "Oh yeah?");
+    }
+
+    #[test]
+    fn test_len() {
+        let mi = Insults::new("insults.json");
+        assert_eq!(mi.len(), mi.insults().len());
+        assert_eq!(mi.count_in(MonkeyIsland1), mi.mi1_insults().len());
+        assert_eq!(mi.failed_retort_count(), mi.failed_retorts().len());
+        assert!(!mi.is_empty());
+
+        let empty = Insults::from_str(fixture("{}")[]);
+        assert!(empty.is_empty() && empty.failed_retort_count() == 0);
+    }
+
+    #[test]
+    fn test_insert() {
+        // What a dataset put together without std can do
+        let mut mi = Insults::default();
+        mi.insert(SwordMaster, "I've got a long, sharp lesson for you to learn today.",
+                  Entry::new("And I've got a little TIP for you, get the POINT?"));
+        mi.set_game_priority([MonkeyIsland1, SwordMaster]);
+        assert_eq!(mi.retort("I've got a long, sharp lesson for you to learn today."),
+                   Some(NOT_FAIR_SWORD_MASTER));
+        assert_eq!(mi.count_in(SwordMaster), 1);
+        assert!(Insults::default().retort("You fight like a dairy farmer.").is_none());
+    }
+
+    #[test]
+    fn test_answered_insults() {
+        let mut data = match Insults::new("insults.json").to_json() {
+            json::Object(x) => x,
+            _ => panic!("not an object"),
+        };
+        data.insert("lookup_priority".to_string(), vec!["monkey_island1".to_string()].to_json());
+        let mi = Insults::from_json(json::Object(data));
+        assert_eq!(mi.answered_insults(), mi.mi1_insults());
+
+        let mut rng = rand::task_rng();
+        let quiz = Quiz::new(&mi, 3);
+        for _ in range(0u, 20) {
+            assert!(mi.retort(mi.rand_insult(&mut rng)[]).is_some());
+            let q = quiz.rand_question(&mut rng);
+            assert!(mi.is_retort(q.insult, q.retort()));
+        }
+    }
+
+    #[test]
+    fn test_global() {
+        assert_eq!(global().retort("You fight like a dairy farmer."),
+            Some("How appropriate. You fight like a cow."));
+        assert!(global() as *const Insults == global() as *const Insults);
+    }
+
+    #[test]
+    fn test_locale() {
+        let de = Insults::with_locale("insults.json", "de");
+        assert_eq!(de.locale(), "de");
+        assert_eq!(de.retort("Du kämpfst wie ein dummer Bauer."),
+            Some("Wie passend. Du kämpfst wie eine Kuh."));
+
+        // Missing translations fall back to English
+        assert_eq!(de.retort("Hey, look over there!"),
+            Some("Yeah, yeah I know: it's a three headed monkey."));
+        assert!(de.failed_retorts().len() > 0);
+
+        let xx = Insults::with_locale("insults.json", "xx");
+        assert_eq!(xx.locale(), "en");
+        assert!(xx.fallback().is_none());
+    }
+
+    #[test]
+    fn test_filter() {
+        let mi = Insults::from_str(fixture(r#"{"failed_retorts": ["Oh yeah?"],
+            "monkey_island1": {
+                "You fight like a dairy farmer.": {"retort": "How appropriate. You fight like a cow."},
+                "Soon you'll be wearing my sword like a shish kebab!": {
+                    "retort": "First you'd better stop waving it like a feather-duster.",
+                    "rating": "harsh"
+                }
+            }}"#)[]);
+        assert!(mi.retort("Soon you'll be wearing my sword like a shish kebab!").is_some());
+
+        let family = mi.with_filter(FamilyFriendly);
+        assert_eq!(family.insults(), vec![&"You fight like a dairy farmer.".to_string()]);
+        assert_eq!(family.retort("Soon you'll be wearing my sword like a shish kebab!"), None);
+    }
+
+    #[test]
+    fn test_audio_for() {
+        let mi = Insults::from_str(fixture(r#"{"monkey_island1": {
+                "You fight like a dairy farmer.": {
+                    "retort": "How appropriate. You fight like a cow.",
+                    "audio": {"file": "mi1_duels.ogg", "start_ms": 1200, "end_ms": 2900},
+                    "retort_audio": {"file": "mi1_duels.ogg", "start_ms": 3100}
+                }
+            }}"#)[]);
+        let clip = mi.audio_for("You fight like a dairy farmer.").unwrap();
+        assert_eq!((clip.file[], clip.start_ms, clip.end_ms), ("mi1_duels.ogg", Some(1200), Some(2900)));
+        assert_eq!(mi.retort_audio_for("You fight like a dairy farmer.").unwrap().end_ms, None);
+        assert_eq!(Insults::new("insults.json").audio_for("You fight like a dairy farmer."), None);
+    }
+
+    #[test]
+    fn test_save() {
+        let tmp = TempDir::new("mi_insults").unwrap();
+        let path = tmp.path().join("merged.json");
+        let mut mi = Insults::new("insults.json");
+        mi.insert(MonkeyIsland1, "Arr!", Entry::new("Arr yourself."));
+        mi.save(path.as_str().unwrap()).unwrap();
+
+        let saved = Insults::new(path.as_str().unwrap());
+        assert!(saved == mi);
+        assert_eq!(saved.to_json_pretty(), mi.to_json_pretty());
+        assert!(mi.to_json_pretty()[].starts_with("{\n"));
+    }
+
+    #[test]
+    fn test_from_path_async() {
+        let mut mi = Insults::from_path_async("insults.json");
+        assert_eq!(mi.get_ref().as_ref().ok().map(|x| x.to_json()),
+            Some(Insults::new("insults.json").to_json()));
+
+        let mut missing = Insults::from_path_async("missing.json");
+        assert!(missing.get_ref().is_err());
+    }
 }
 
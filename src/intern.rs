@@ -0,0 +1,250 @@
+//! Datasets sharing their strings, for serving many locales at once.
+//!
+//! Translations repeat many insults and retorts of the English pack, with
+//! interning every unique string is stored once and the packs only hold
+//! symbols:
+//!
+//! ```
+//! use mi_insults::Insults;
+//! use mi_insults::intern::InternedInsults;
+//!
+//! let mut packs = InternedInsults::new();
+//! packs.add(&Insults::with_locale("insults.json", "de"));
+//! assert_eq!(packs.retort("de", "You fight like a dairy farmer."),
+//!            Some("How appropriate. You fight like a cow."));
+//! ```
+
+use std::collections::HashMap;
+use std::hash;
+
+use game::{Game, MonkeyIsland1, SwordMaster, MonkeyIsland3, CaptainRottingham};
+use game::{NOT_FAIR_SWORD_MASTER, NOT_FAIR_ROTTINGHAM};
+use insults::Insults;
+
+/// An interned string.
+#[deriving(Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Show)]
+pub struct Symbol(u32);
+
+/// Stores every unique string once.
+pub struct Interner {
+    strings: Vec<String>,
+    /// Symbols by the hash of their string.
+    ids: HashMap<u64, Vec<Symbol>>,
+}
+
+impl Interner {
+    pub fn new() -> Interner {
+        Interner { strings: Vec::new(), ids: HashMap::new() }
+    }
+
+    /// The symbol of `s`, adding it if it's new.
+    pub fn intern(&mut self, s: &str) -> Symbol {
+        match self.get(s) {
+            Some(x) => x,
+            None => {
+                let sym = Symbol(self.strings.len() as u32);
+                self.strings.push(s.to_string());
+                let key = hash::hash(&s);
+                if !self.ids.contains_key(&key) {
+                    self.ids.insert(key, Vec::new());
+                }
+                self.ids.get_mut(&key).unwrap().push(sym);
+                sym
+            }
+        }
+    }
+
+    /// The symbol of `s` if it has been interned.
+    pub fn get(&self, s: &str) -> Option<Symbol> {
+        match self.ids.get(&hash::hash(&s)) {
+            Some(syms) => syms.iter().find(|x| self.resolve(**x) == s).map(|x| *x),
+            None => None,
+        }
+    }
+
+    /// The string of `sym`.
+    pub fn resolve<'a>(&'a self, sym: Symbol) -> &'a str {
+        let Symbol(i) = sym;
+        self.strings[i as uint][]
+    }
+
+    /// Number of unique strings.
+    pub fn len(&self) -> uint {
+        self.strings.len()
+    }
+}
+
+struct Pack {
+    /// Insults and retorts of every game in `Game::all` order, sorted by insult.
+    games: Vec<Vec<(Symbol, Symbol)>>,
+    failed_retorts: Vec<Symbol>,
+    /// The games `retort` consults, in order.
+    priority: Vec<Game>,
+    fallback: Option<String>,
+}
+
+/// Datasets of several locales sharing one interner.
+pub struct InternedInsults {
+    interner: Interner,
+    packs: HashMap<String, Pack>,
+}
+
+impl InternedInsults {
+    pub fn new() -> InternedInsults {
+        InternedInsults { interner: Interner::new(), packs: HashMap::new() }
+    }
+
+    /// Add `insults` under its locale, together with its fallbacks.
+    pub fn add(&mut self, insults: &Insults) {
+        let fallback = insults.fallback().map(|x| x.locale().to_string());
+        let mut games = Vec::new();
+        for game in Game::all().iter() {
+            let mut pairs: Vec<(Symbol, Symbol)> = insults.entries(*game).into_iter()
+                .map(|(insult, entry)| (self.interner.intern(insult[]),
+                                        self.interner.intern(entry.retort[])))
+                .collect();
+            pairs.sort();
+            games.push(pairs);
+        }
+        let failed_retorts = insults.failed_retorts().iter()
+            .map(|x| self.interner.intern(x[]))
+            .collect();
+
+        self.packs.insert(insults.locale().to_string(), Pack {
+            games: games,
+            failed_retorts: failed_retorts,
+            priority: insults.game_priority().to_vec(),
+            fallback: fallback,
+        });
+        if let Some(f) = insults.fallback() {
+            self.add(f);
+        }
+    }
+
+    /// The strings shared by all packs.
+    pub fn interner<'a>(&'a self) -> &'a Interner {
+        &self.interner
+    }
+
+    /// Locales of the added packs.
+    pub fn locales<'a>(&'a self) -> Vec<&'a str> {
+        let mut res: Vec<&str> = self.packs.keys().map(|x| x[]).collect();
+        res.sort();
+        res
+    }
+
+    /// The retort to `insult` from `game` for `locale`, with fallback.
+    ///
+    /// Like `Insults::mi1_retort` and `Insults::mi3_retort` Monkey Island 1
+    /// and 3 answer insults of the Sword Master and Captain Rottingham with
+    /// a custom retort.
+    pub fn game_retort<'a>(&'a self, locale: &str, game: Game, insult: &str) -> Option<&'a str> {
+        match game {
+            MonkeyIsland1 if self.table_retort(locale, SwordMaster, insult).is_some() =>
+                Some(NOT_FAIR_SWORD_MASTER),
+            MonkeyIsland3 if self.table_retort(locale, CaptainRottingham, insult).is_some() =>
+                Some(NOT_FAIR_ROTTINGHAM),
+            _ => self.table_retort(locale, game, insult),
+        }
+    }
+
+    /// The retort to `insult` in the table of `game` for `locale`, with fallback.
+    fn table_retort<'a>(&'a self, locale: &str, game: Game, insult: &str) -> Option<&'a str> {
+        let i = Game::all().iter().position(|x| *x == game).unwrap();
+        self.find(locale, insult, |pack, sym| {
+            let pairs = &pack.games[i];
+            let (mut lo, mut hi) = (0, pairs.len());
+            while lo < hi {
+                let mid = (lo + hi) / 2;
+                let (x, retort) = pairs[mid];
+                match x.cmp(&sym) {
+                    Less => lo = mid + 1,
+                    Greater => hi = mid,
+                    Equal => return Some(retort),
+                }
+            }
+            None
+        })
+    }
+
+    /// The retort to `insult` from any game for `locale`, searched like `Insults::retort`.
+    pub fn retort<'a>(&'a self, locale: &str, insult: &str) -> Option<&'a str> {
+        let pack = match self.packs.get(&locale.to_string()) {
+            Some(x) => x,
+            None => return None,
+        };
+        for game in pack.priority.iter() {
+            let res = self.game_retort(locale, *game, insult);
+            if res.is_some() {
+                return res;
+            }
+        }
+        None
+    }
+
+    /// Failed retorts for `locale`, with fallback when it has none.
+    pub fn failed_retorts<'a>(&'a self, locale: &str) -> Vec<&'a str> {
+        let mut current = self.packs.get(&locale.to_string());
+        loop {
+            match current {
+                Some(pack) if pack.failed_retorts.is_empty() =>
+                    current = pack.fallback.as_ref().and_then(|f| self.packs.get(f)),
+                Some(pack) =>
+                    return pack.failed_retorts.iter().map(|x| self.interner.resolve(*x)).collect(),
+                None => return Vec::new(),
+            }
+        }
+    }
+
+    /// Look up `insult` in the pack of `locale` and its fallbacks with `f`.
+    fn find<'a>(&'a self, locale: &str, insult: &str,
+                f: |&Pack, Symbol| -> Option<Symbol>) -> Option<&'a str> {
+        let sym = match self.interner.get(insult) {
+            Some(x) => x,
+            None => return None,
+        };
+        let mut current = self.packs.get(&locale.to_string());
+        loop {
+            match current {
+                Some(pack) => match f(pack, sym) {
+                    Some(x) => return Some(self.interner.resolve(x)),
+                    None => current = pack.fallback.as_ref().and_then(|x| self.packs.get(x)),
+                },
+                None => return None,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use game::{MonkeyIsland1, SwordMaster};
+    use insults::Insults;
+    use super::*;
+
+    #[test]
+    fn test_interned() {
+        let mut packs = InternedInsults::new();
+        packs.add(&Insults::with_locale("insults.json", "de"));
+        assert_eq!(packs.locales(), vec!["de", "en"]);
+
+        assert_eq!(packs.retort("de", "Du kämpfst wie ein dummer Bauer."),
+            Some("Wie passend. Du kämpfst wie eine Kuh."));
+        assert_eq!(packs.game_retort("de", MonkeyIsland1, "You fight like a dairy farmer."),
+            Some("How appropriate. You fight like a cow."));
+        assert_eq!(packs.retort("en", "Du kämpfst wie ein dummer Bauer."), None);
+        assert_eq!(packs.failed_retorts("de"), packs.failed_retorts("en"));
+
+        let mi = Insults::new("insults.json");
+        for insult in mi.insults().into_iter() {
+            assert_eq!(packs.retort("en", insult[]), mi.retort(insult[]));
+        }
+
+        // A retort of several games is stored once, both resolve the same symbol
+        let farmer = packs.game_retort("en", MonkeyIsland1, "You fight like a dairy farmer.").unwrap();
+        let milk = packs.game_retort("en", SwordMaster, "I will milk every drop of blood from your body!").unwrap();
+        assert_eq!(farmer, milk);
+        assert!(farmer.as_ptr() == milk.as_ptr());
+        assert_eq!(packs.interner().get(farmer), packs.interner().get(milk));
+    }
+}
@@ -0,0 +1,230 @@
+//! Wins, losses and streaks of players, kept in a pluggable store.
+//!
+//! A `Leaderboard` records finished duels in a `LeaderboardStore`: the
+//! `MemoryStore`, a `JsonFileStore` or, with the `sqlite` feature,
+//! `sqlite::SqliteLeaderboard`. Bots answer `!top` and `!rank` from it and
+//! the server serves it at `/leaderboard`.
+
+use std::collections::TreeMap;
+use std::io::File;
+use serialize::{Decodable, json};
+use serialize::json::{Json, ToJson};
+
+use schema::{LoadError, Io, Syntax, Schema};
+
+/// The record of a player.
+#[deriving(Clone, PartialEq, Show, Encodable, Decodable)]
+pub struct Standing {
+    pub player: String,
+    pub wins: uint,
+    pub losses: uint,
+    /// Duels won in a row.
+    pub streak: uint,
+    pub best_streak: uint,
+}
+
+impl Standing {
+    pub fn new(player: &str) -> Standing {
+        Standing { player: player.to_string(), wins: 0, losses: 0, streak: 0, best_streak: 0 }
+    }
+
+    /// Count a duel won or lost.
+    pub fn record(&mut self, won: bool) {
+        if won {
+            self.wins += 1;
+            self.streak += 1;
+            self.best_streak = self.best_streak.max(self.streak);
+        } else {
+            self.losses += 1;
+            self.streak = 0;
+        }
+    }
+
+    /// Order by rank: more wins first, then fewer losses, then by name.
+    fn cmp_rank(&self, other: &Standing) -> Ordering {
+        match other.wins.cmp(&self.wins) {
+            Equal => match self.losses.cmp(&other.losses) {
+                Equal => self.player.cmp(&other.player),
+                x => x,
+            },
+            x => x,
+        }
+    }
+}
+
+impl ToJson for Standing {
+    fn to_json(&self) -> Json {
+        let mut res = TreeMap::new();
+        res.insert("player".to_string(), self.player.to_json());
+        res.insert("wins".to_string(), self.wins.to_json());
+        res.insert("losses".to_string(), self.losses.to_json());
+        res.insert("streak".to_string(), self.streak.to_json());
+        res.insert("best_streak".to_string(), self.best_streak.to_json());
+        json::Object(res)
+    }
+}
+
+/// Where a leaderboard keeps the standings.
+pub trait LeaderboardStore {
+    fn get(&self, player: &str) -> Result<Option<Standing>, LoadError>;
+
+    /// Store `standing`, replacing the player's previous one.
+    fn put(&mut self, standing: &Standing) -> Result<(), LoadError>;
+
+    /// Every standing, in any order.
+    fn all(&self) -> Result<Vec<Standing>, LoadError>;
+}
+
+/// Standings kept in memory only.
+pub struct MemoryStore {
+    standings: TreeMap<String, Standing>,
+}
+
+impl MemoryStore {
+    pub fn new() -> MemoryStore {
+        MemoryStore { standings: TreeMap::new() }
+    }
+}
+
+impl LeaderboardStore for MemoryStore {
+    fn get(&self, player: &str) -> Result<Option<Standing>, LoadError> {
+        Ok(self.standings.get(&player.to_string()).map(|x| x.clone()))
+    }
+
+    fn put(&mut self, standing: &Standing) -> Result<(), LoadError> {
+        self.standings.insert(standing.player.clone(), standing.clone());
+        Ok(())
+    }
+
+    fn all(&self) -> Result<Vec<Standing>, LoadError> {
+        Ok(self.standings.values().map(|x| x.clone()).collect())
+    }
+}
+
+/// Standings in a JSON file, rewritten on every update.
+pub struct JsonFileStore {
+    path: Path,
+    memory: MemoryStore,
+}
+
+impl JsonFileStore {
+    /// Open the standings at `path`, empty if the file doesn't exist yet.
+    pub fn open(path: &Path) -> Result<JsonFileStore, LoadError> {
+        let mut res = JsonFileStore { path: path.clone(), memory: MemoryStore::new() };
+        if !path.exists() {
+            return Ok(res);
+        }
+        let data = match File::open(path).read_to_string() {
+            Ok(x) => try!(json::from_str(x[]).map_err(Syntax)),
+            Err(e) => return Err(Io(e)),
+        };
+        let mut decoder = json::Decoder::new(data);
+        let standings: Vec<Standing> = match Decodable::decode(&mut decoder) {
+            Ok(x) => x,
+            Err(e) => return Err(Schema(Vec::new(), e.to_string())),
+        };
+        for standing in standings.iter() {
+            try!(res.memory.put(standing));
+        }
+        Ok(res)
+    }
+}
+
+impl LeaderboardStore for JsonFileStore {
+    fn get(&self, player: &str) -> Result<Option<Standing>, LoadError> {
+        self.memory.get(player)
+    }
+
+    fn put(&mut self, standing: &Standing) -> Result<(), LoadError> {
+        try!(self.memory.put(standing));
+        let all = try!(self.memory.all());
+        File::create(&self.path).write_str(json::encode(&all)[]).map_err(Io)
+    }
+
+    fn all(&self) -> Result<Vec<Standing>, LoadError> {
+        self.memory.all()
+    }
+}
+
+/// Records duels and ranks the players.
+pub struct Leaderboard {
+    store: Box<LeaderboardStore + Send>,
+}
+
+impl Leaderboard {
+    pub fn new(store: Box<LeaderboardStore + Send>) -> Leaderboard {
+        Leaderboard { store: store }
+    }
+
+    /// A leaderboard in a `MemoryStore`.
+    pub fn in_memory() -> Leaderboard {
+        Leaderboard::new(box MemoryStore::new())
+    }
+
+    /// Count a duel of `player`, like one against the computer.
+    pub fn record(&mut self, player: &str, won: bool) -> Result<Standing, LoadError> {
+        let mut standing = try!(self.standing(player)).unwrap_or(Standing::new(player));
+        standing.record(won);
+        try!(self.store.put(&standing));
+        Ok(standing)
+    }
+
+    /// Count a duel between two players.
+    pub fn record_duel(&mut self, winner: &str, loser: &str) -> Result<(), LoadError> {
+        try!(self.record(winner, true));
+        try!(self.record(loser, false));
+        Ok(())
+    }
+
+    pub fn standing(&self, player: &str) -> Result<Option<Standing>, LoadError> {
+        self.store.get(player)
+    }
+
+    /// Every standing, best first.
+    pub fn ranked(&self) -> Result<Vec<Standing>, LoadError> {
+        let mut res = try!(self.store.all());
+        res.sort_by(|a, b| a.cmp_rank(b));
+        Ok(res)
+    }
+
+    /// The best `n` standings.
+    pub fn top(&self, n: uint) -> Result<Vec<Standing>, LoadError> {
+        let mut res = try!(self.ranked());
+        res.truncate(n);
+        Ok(res)
+    }
+
+    /// The rank of `player` starting at 1, None if they haven't dueled.
+    pub fn rank_of(&self, player: &str) -> Result<Option<uint>, LoadError> {
+        let ranked = try!(self.ranked());
+        Ok(ranked.iter().position(|x| x.player[] == player).map(|i| i + 1))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::TempDir;
+    use super::*;
+
+    #[test]
+    fn test_leaderboard() {
+        let mut board = Leaderboard::in_memory();
+        board.record_duel("guybrush", "carla").unwrap();
+        board.record_duel("guybrush", "otis").unwrap();
+        board.record_duel("carla", "otis").unwrap();
+        let guybrush = board.record("guybrush", false).unwrap();
+        assert_eq!((guybrush.wins, guybrush.losses, guybrush.streak, guybrush.best_streak), (2, 1, 0, 2));
+
+        let top: Vec<String> = board.top(2).unwrap().into_iter().map(|x| x.player).collect();
+        assert_eq!(top, vec!["guybrush".to_string(), "carla".to_string()]);
+        assert_eq!(board.rank_of("otis").unwrap(), Some(3));
+        assert_eq!(board.rank_of("lechuck").unwrap(), None);
+
+        let tmp = TempDir::new("mi_insults").unwrap();
+        let path = tmp.path().join("leaderboard.json");
+        let mut file = Leaderboard::new(box JsonFileStore::open(&path).unwrap());
+        file.record_duel("guybrush", "carla").unwrap();
+        let file = Leaderboard::new(box JsonFileStore::open(&path).unwrap());
+        assert_eq!(file.standing("carla").unwrap().unwrap().losses, 1);
+    }
+}
@@ -1,10 +1,226 @@
+//! Insults and retorts from the Monkey Island games.
+//!
+//! Everything needs the `std` feature, enabled by default, except the games,
+//! the built-in dataset of `static-data` and the lookup types of `alloc`.
+//! Built with `--no-default-features --features static-data` the crate only
+//! depends on `core`, for retorting on embedded devices with `static_data::retort`.
+//!
+//! With `--no-default-features --features alloc` the crate depends on the
+//! `alloc` and `collections` crates as well, for `Insults`, `Entry` and
+//! `LookupPolicy` on devices with an allocator. The tables are `TreeMap`s
+//! instead of hash maps, datasets are put together with `Insults::insert`
+//! and looked up like with `std`, without Unicode normalization. Loading
+//! packs, JSON and random choices need `std`.
+
 #![feature(slicing_syntax)]
 #![feature(if_let)]
 #![feature(globs)]
+#![feature(phase)]
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg(not(feature = "std"))]
+#[phase(plugin, link)]
+extern crate core;
+#[cfg(all(feature = "alloc", not(feature = "std")))]
+extern crate alloc;
+#[cfg(all(feature = "alloc", not(feature = "std")))]
+#[phase(plugin, link)]
+extern crate collections;
 
+#[cfg(feature = "std")]
 extern crate serialize;
+#[cfg(feature = "std")]
+extern crate time;
+
+#[cfg(feature = "tracing")]
+#[phase(plugin, link)]
+extern crate log;
+
+#[cfg(any(feature = "ffi", feature = "mmap", feature = "plugins", feature = "sqlite", feature = "wasm"))]
+extern crate libc;
+#[cfg(feature = "flate")]
+extern crate flate;
+#[cfg(feature = "toml")]
+extern crate toml;
+
+// Debug events through `log` with the tracing feature, compiled out otherwise.
+// Enable them with RUST_LOG=mi_insults=debug.
+#[cfg(feature = "tracing")]
+macro_rules! trace_event(
+    ($($arg:tt)*) => (debug!($($arg)*))
+)
+#[cfg(not(feature = "tracing"))]
+macro_rules! trace_event(
+    ($($arg:tt)*) => (())
+)
+
+// Usage counters of the metrics feature, compiled out otherwise
+#[cfg(feature = "metrics")]
+macro_rules! count(
+    ($f:ident($($arg:expr),*)) => (::metrics::$f($($arg),*))
+)
+#[cfg(not(feature = "metrics"))]
+macro_rules! count(
+    ($f:ident($($arg:expr),*)) => (())
+)
+
+// What deriving and `use std::iter` expand to without std
+#[cfg(not(feature = "std"))]
+mod std {
+    pub use core::{clone, cmp, default, fmt, iter, option};
+}
+
+// The part of the std prelude the modules of `alloc` use
+#[cfg(all(feature = "alloc", not(feature = "std")))]
+mod prelude {
+    pub use core::prelude::*;
+    pub use alloc::boxed::Box;
+    pub use collections::{String, TreeMap, Vec};
+    pub use collections::slice::{CloneSliceAllocPrelude, OrdSliceAllocPrelude, SliceAllocPrelude};
+}
 
-pub use insults::Insults;
+#[cfg(feature = "alloc")]
+pub use insults::{Insults, AudioClip, Banter, Entry, Filter, Everything, FamilyFriendly};
+#[cfg(feature = "std")]
+pub use insults::global;
+#[cfg(feature = "alloc")]
+pub use insults::{RetortJudgement, Correct, KnownButWrong, Unknown};
+#[cfg(feature = "alloc")]
+pub use lookup::LookupPolicy;
+#[cfg(feature = "std")]
+pub use schema::LoadError;
 
+#[cfg(feature = "std")]
+mod formats;
+#[cfg(feature = "alloc")]
 mod insults;
+#[cfg(feature = "websocket")]
+mod sha1;
+#[cfg(feature = "std")]
+mod sha256;
+#[cfg(feature = "static-data")]
+mod static_hash;
 
+#[cfg(feature = "std")]
+pub mod achievements;
+#[cfg(feature = "std")]
+pub mod bots;
+#[cfg(feature = "std")]
+pub mod cache;
+#[cfg(feature = "std")]
+pub mod campaign;
+#[cfg(feature = "std")]
+pub mod collection;
+#[cfg(feature = "std")]
+pub mod compact;
+#[cfg(feature = "std")]
+pub mod complete;
+#[cfg(feature = "std")]
+pub mod crew;
+#[cfg(feature = "alloc")]
+pub mod dialogue;
+#[cfg(feature = "std")]
+pub mod diff;
+#[cfg(feature = "std")]
+pub mod duel;
+pub mod game;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "std")]
+pub mod fuzzy;
+#[cfg(feature = "graphql")]
+pub mod graphql;
+#[cfg(feature = "std")]
+pub mod hint;
+#[cfg(feature = "std")]
+pub mod index;
+#[cfg(feature = "std")]
+pub mod intern;
+#[cfg(feature = "std")]
+pub mod leaderboard;
+#[cfg(feature = "std")]
+pub mod lint;
+#[cfg(feature = "std")]
+pub mod lobby;
+#[cfg(feature = "alloc")]
+pub mod lookup;
+#[cfg(feature = "mmap")]
+pub mod mapped;
+#[cfg(feature = "std")]
+pub mod markov;
+#[cfg(feature = "std")]
+pub mod matching;
+#[cfg(feature = "std")]
+pub mod merge;
+#[cfg(feature = "metrics")]
+pub mod metrics;
+#[cfg(feature = "std")]
+pub mod migrations;
+#[cfg(feature = "std")]
+pub mod narrator;
+#[cfg(feature = "std")]
+pub mod normalize;
+#[cfg(feature = "serve")]
+pub mod openapi;
+#[cfg(feature = "std")]
+pub mod overlay;
+#[cfg(feature = "std")]
+pub mod pack;
+#[cfg(feature = "std")]
+pub mod partial;
+#[cfg(feature = "std")]
+pub mod phonetic;
+#[cfg(feature = "std")]
+pub mod pirate;
+#[cfg(feature = "plugins")]
+pub mod plugin;
+#[cfg(feature = "std")]
+pub mod profile;
+#[cfg(feature = "std")]
+pub mod progress;
+#[cfg(feature = "std")]
+pub mod query;
+#[cfg(feature = "std")]
+pub mod quiz;
+#[cfg(feature = "std")]
+pub mod ranking;
+#[cfg(feature = "serve")]
+pub mod ratelimit;
+#[cfg(feature = "std")]
+pub mod registry;
+#[cfg(feature = "std")]
+pub mod schema;
+#[cfg(feature = "scripting")]
+pub mod script;
+#[cfg(feature = "serve")]
+pub mod serve;
+#[cfg(feature = "std")]
+pub mod search;
+#[cfg(feature = "std")]
+pub mod seeded;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+#[cfg(feature = "static-data")]
+pub mod static_data;
+#[cfg(feature = "std")]
+pub mod stats;
+#[cfg(feature = "std")]
+pub mod stem;
+#[cfg(feature = "std")]
+pub mod subtitles;
+#[cfg(feature = "std")]
+pub mod tournament;
+#[cfg(feature = "std")]
+pub mod trainer;
+#[cfg(feature = "std")]
+pub mod transcript;
+#[cfg(feature = "std")]
+pub mod translations;
+#[cfg(feature = "tts")]
+pub mod tts;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+#[cfg(feature = "watch")]
+pub mod watch;
+#[cfg(feature = "websocket")]
+pub mod websocket;
@@ -0,0 +1,408 @@
+//! Consistency checks for insult packs, with automatic fixes.
+//!
+//! The linter works on the raw JSON of a pack so fixes can be written back
+//! without touching anything else in the file.
+
+use std::ascii::AsciiExt;
+use std::collections::TreeMap;
+use std::fmt;
+use serialize::json::{mod, Json};
+
+use game::Game;
+
+/// Preferred quote style.
+#[deriving(Clone, PartialEq, Show)]
+pub enum Quotes {
+    /// `'` and `"`
+    Straight,
+    /// `‘’` and `“”`
+    Curly,
+}
+
+/// Which rules to check.
+#[deriving(Clone, PartialEq, Show)]
+pub struct Config {
+    /// Lines should end with `.`, `!` or `?`.
+    pub terminal_punctuation: bool,
+    /// Quote style to enforce, if any.
+    pub quotes: Option<Quotes>,
+    /// No double, leading or trailing spaces.
+    pub whitespace: bool,
+    /// Proper nouns which should always be capitalized like this.
+    pub proper_nouns: Vec<String>,
+    /// Known misspellings and their corrections.
+    pub spelling: Vec<(String, String)>,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        let nouns = ["Sword Master", "Captain Rottingham", "Caribbean"];
+        let spelling = [("smarter then", "smarter than"),
+                        ("sharper then", "sharper than"),
+                        ("polite then", "polite than"),
+                        ("been dip", "bean dip")];
+        Config {
+            terminal_punctuation: true,
+            quotes: Some(Straight),
+            whitespace: true,
+            proper_nouns: nouns.iter().map(|x| x.to_string()).collect(),
+            spelling: spelling.iter().map(|&(a, b)| (a.to_string(), b.to_string())).collect(),
+        }
+    }
+}
+
+/// Where in a pack a line is.
+#[deriving(Clone, PartialEq, Show)]
+pub enum Part {
+    Insult,
+    Retort,
+    /// A failed retort, by index.
+    FailedRetort(uint),
+    /// A taunt, victory or defeat line, by index.
+    BanterLine(uint),
+}
+
+/// A problem found by the linter.
+#[deriving(Clone, PartialEq)]
+pub struct Lint {
+    /// Section of the pack, like `monkey_island1`.
+    pub section: String,
+    /// The insult the line belongs to, for insults and retorts.
+    pub insult: Option<String>,
+    pub part: Part,
+    pub message: String,
+    /// The line after fixing.
+    pub fixed: String,
+}
+
+impl fmt::Show for Lint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.insult {
+            Some(ref insult) => write!(f, "{} → `{}` ({}): {}",
+                                       self.section, insult, self.part, self.message),
+            None => write!(f, "{} ({}): {}", self.section, self.part, self.message),
+        }
+    }
+}
+
+/// Find all problems in `pack`.
+pub fn lint(pack: &Json, config: &Config) -> Vec<Lint> {
+    let mut res = Vec::new();
+    walk(pack, config, &mut res);
+    res
+}
+
+/// A copy of `pack` with all problems fixed.
+pub fn fix(pack: &Json, config: &Config) -> Json {
+    let mut lints = Vec::new();
+    walk(pack, config, &mut lints)
+}
+
+/// Lint every line of `pack`, returning a fixed copy.
+///
+/// Only the games, the failed retorts and the banter have lines, other
+/// sections are copied as they are.
+fn walk(pack: &Json, config: &Config, lints: &mut Vec<Lint>) -> Json {
+    let sections = match *pack {
+        json::Object(ref x) => x,
+        _ => return pack.clone(),
+    };
+
+    let mut res = TreeMap::new();
+    let mut renames = TreeMap::new();
+    for (section, value) in sections.iter() {
+        let fixed = match (section[], value) {
+            (_, &json::Object(ref pairs)) if Game::from_key(section[]).is_some() =>
+                walk_game(pairs, section, config, lints, &mut renames),
+            ("failed_retorts", &json::List(ref lines)) | ("opponent_failed_retorts", &json::List(ref lines)) =>
+                walk_lines(lines[], section, FailedRetort, config, lints),
+            ("banter", &json::Object(ref parts)) => {
+                let mut fixed = TreeMap::new();
+                for (name, lines) in parts.iter() {
+                    let value = match *lines {
+                        json::List(ref lines) =>
+                            walk_lines(lines[], &format!("banter.{}", name), BanterLine, config, lints),
+                        ref x => x.clone(),
+                    };
+                    fixed.insert(name.clone(), value);
+                }
+                json::Object(fixed)
+            },
+            (_, x) => x.clone(),
+        };
+        res.insert(section.clone(), fixed);
+    }
+
+    // Translations are mapped to their canonical id by the translated insult
+    let key = "canonical".to_string();
+    if let Some(json::Object(canonical)) = res.pop(&key) {
+        let canonical: TreeMap<String, Json> = canonical.into_iter()
+            .map(|(k, v)| (renames.get(&k).map(|x| x.clone()).unwrap_or(k), v))
+            .collect();
+        res.insert(key, json::Object(canonical));
+    }
+    json::Object(res)
+}
+
+fn walk_lines(lines: &[Json], section: &String, part: fn(uint) -> Part,
+              config: &Config, lints: &mut Vec<Lint>) -> Json {
+    let mut fixed = Vec::new();
+    for (i, line) in lines.iter().enumerate() {
+        fixed.push(match *line {
+            json::String(ref s) => json::String(check(s[], section, None, part(i), config, lints)),
+            ref x => x.clone(),
+        });
+    }
+    json::List(fixed)
+}
+
+/// Lint the entries of a game, adding the insults fixed to `renames`.
+///
+/// Insults which would be fixed to the same text as another insult are kept
+/// as they are, with a lint for the collision.
+fn walk_game(pairs: &TreeMap<String, Json>, section: &String, config: &Config, lints: &mut Vec<Lint>,
+             renames: &mut TreeMap<String, String>) -> Json {
+    let mut keys = Vec::new();
+    for insult in pairs.keys() {
+        let mut found = Vec::new();
+        let key = check(insult[], section, Some(insult), Insult, config, &mut found);
+        keys.push((key, found));
+    }
+    let mut targets: TreeMap<&String, Vec<&String>> = TreeMap::new();
+    for (insult, &(ref key, _)) in pairs.keys().zip(keys.iter()) {
+        if !targets.contains_key(&key) {
+            targets.insert(key, Vec::new());
+        }
+        targets.get_mut(&key).unwrap().push(insult);
+    }
+
+    let mut fixed = TreeMap::new();
+    for ((insult, retort), &(ref key, ref found)) in pairs.iter().zip(keys.iter()) {
+        let other = targets[key].iter().find(|x| **x != insult).map(|x| *x);
+        let key = match other {
+            Some(other) if key != insult => {
+                lints.push(Lint {
+                    section: section.clone(),
+                    insult: Some(insult.clone()),
+                    part: Insult,
+                    message: format!("fixing it would collide with `{}`", other),
+                    fixed: insult.clone(),
+                });
+                insult.clone()
+            },
+            // Left alone, the other insult is reported
+            Some(_) => insult.clone(),
+            None => {
+                lints.push_all(found[]);
+                if key != insult {
+                    renames.insert(insult.clone(), key.clone());
+                }
+                key.clone()
+            },
+        };
+        let value = match *retort {
+            json::String(ref s) =>
+                json::String(check(s[], section, Some(insult), Retort, config, lints)),
+            json::Object(ref entry) => {
+                let mut entry = entry.clone();
+                let key = "retort".to_string();
+                let fixed = match entry.get(&key) {
+                    Some(&json::String(ref s)) =>
+                        Some(check(s[], section, Some(insult), Retort, config, lints)),
+                    _ => None,
+                };
+                if let Some(s) = fixed {
+                    entry.insert(key, json::String(s));
+                }
+                json::Object(entry)
+            },
+            ref x => x.clone(),
+        };
+        fixed.insert(key, value);
+    }
+    json::Object(fixed)
+}
+
+fn check(text: &str, section: &String, insult: Option<&String>, part: Part,
+         config: &Config, lints: &mut Vec<Lint>) -> String {
+    let mut text = text.to_string();
+    for (fixed, message) in apply(text[], config).into_iter() {
+        lints.push(Lint {
+            section: section.clone(),
+            insult: insult.map(|x| x.clone()),
+            part: part.clone(),
+            message: message,
+            fixed: fixed.clone(),
+        });
+        text = fixed;
+    }
+    text
+}
+
+/// Apply all enabled rules in order, returning each fix with its message.
+fn apply(text: &str, config: &Config) -> Vec<(String, String)> {
+    let mut res = Vec::new();
+    let mut text = text.to_string();
+
+    if config.whitespace {
+        let fixed = text.words().collect::<Vec<&str>>().connect(" ");
+        if fixed != text {
+            res.push((fixed.clone(), "double, leading or trailing space".to_string()));
+            text = fixed;
+        }
+    }
+
+    for &(ref wrong, ref right) in config.spelling.iter() {
+        let fixed = replace_ignore_case(text[], wrong[], right[]);
+        if fixed != text {
+            res.push((fixed.clone(), format!("`{}` should be `{}`", wrong, right)));
+            text = fixed;
+        }
+    }
+
+    for noun in config.proper_nouns.iter() {
+        let fixed = replace_ignore_case(text[], noun[], noun[]);
+        if fixed != text {
+            res.push((fixed.clone(), format!("`{}` should be capitalized", noun)));
+            text = fixed;
+        }
+    }
+
+    match config.quotes {
+        Some(ref style) => {
+            let fixed = quotes(text[], style);
+            if fixed != text {
+                res.push((fixed.clone(), format!("use {} quotes", match *style {
+                    Straight => "straight",
+                    Curly => "curly",
+                })));
+                text = fixed;
+            }
+        },
+        None => {},
+    }
+
+    if config.terminal_punctuation {
+        let end = text[].trim_right_chars(|c: char| c == '\'' || c == '"' || c == '’' || c == '”');
+        if !text.is_empty() && !end.ends_with(".") && !end.ends_with("!") && !end.ends_with("?") {
+            let fixed = format!("{}.", text);
+            res.push((fixed, "missing terminal punctuation".to_string()));
+        }
+    }
+    res
+}
+
+/// Replace every case-insensitive occurrence of `pattern` with `with`.
+fn replace_ignore_case(text: &str, pattern: &str, with: &str) -> String {
+    let lower = text.to_ascii_lower();
+    let pattern = pattern.to_ascii_lower();
+
+    let mut res = String::new();
+    let mut last = 0;
+    for (start, end) in lower[].match_indices(pattern[]) {
+        res.push_str(text[last..start]);
+        res.push_str(with);
+        last = end;
+    }
+    res.push_str(text[last..]);
+    res
+}
+
+fn quotes(text: &str, style: &Quotes) -> String {
+    let mut res = String::new();
+    let mut prev = ' ';
+    for c in text.chars() {
+        let opening = prev.is_whitespace();
+        res.push(match (*style, c) {
+            (Straight, '‘') | (Straight, '’') => '\'',
+            (Straight, '“') | (Straight, '”') => '"',
+            (Curly, '\'') => if opening { '‘' } else { '’' },
+            (Curly, '"') => if opening { '“' } else { '”' },
+            (_, c) => c,
+        });
+        prev = c;
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::File;
+    use serialize::json;
+
+    use insults::Insults;
+    use super::*;
+
+    #[test]
+    fn test_lint() {
+        let pack = json::from_str(r#"{
+            "failed_retorts": ["Oh yeah?", "I'm  shaking, I’m shaking!"],
+            "lookup_priority": ["monkey_island1", "sword_master"],
+            "monkey_island1": {
+                "I once owned a dog that was smarter then you.":
+                "He must have taught you everything you know",
+                "Are you the sword master?": "Yes."
+            }
+        }"#).unwrap();
+        let config = Config::default();
+
+        let lints = lint(&pack, &config);
+        let messages: Vec<&str> = lints.iter().map(|x| x.message[]).collect();
+        assert_eq!(messages, vec!["double, leading or trailing space",
+                                  "use straight quotes",
+                                  "`Sword Master` should be capitalized",
+                                  "`smarter then` should be `smarter than`",
+                                  "missing terminal punctuation"]);
+
+        let fixed = fix(&pack, &config);
+        assert_eq!(lint(&fixed, &config).len(), 0);
+        let insult = "I once owned a dog that was smarter than you.".to_string();
+        assert_eq!(fixed.find(&"monkey_island1".to_string())
+                        .and_then(|x| x.find(&insult))
+                        .and_then(|x| x.as_string()),
+                   Some("He must have taught you everything you know."));
+        assert_eq!(fixed.find(&"lookup_priority".to_string()), pack.find(&"lookup_priority".to_string()));
+    }
+
+    #[test]
+    fn test_fix_collision() {
+        let pack = json::from_str(r#"{
+            "canonical": {"Du bist  dumm!": "You're dumb!", "Du stinkst!": "You stink!"},
+            "monkey_island1": {
+                "Du bist  dumm!": "Bist du nicht.",
+                "Du stinkst": "Tust du.",
+                "Du stinkst.": "Du auch."
+            }
+        }"#).unwrap();
+        let config = Config::default();
+
+        let lints = lint(&pack, &config);
+        assert!(lints.iter().any(|x| x.insult == Some("Du stinkst".to_string())
+                                     && x.message[] == "fixing it would collide with `Du stinkst.`"));
+
+        let fixed = fix(&pack, &config);
+        let game = fixed.find(&"monkey_island1".to_string()).unwrap();
+        assert!(game.find(&"Du stinkst".to_string()).is_some() && game.find(&"Du stinkst.".to_string()).is_some());
+        assert!(game.find(&"Du bist dumm!".to_string()).is_some());
+        let canonical = fixed.find(&"canonical".to_string()).unwrap();
+        assert_eq!(canonical.find(&"Du bist dumm!".to_string()).and_then(|x| x.as_string()), Some("You're dumb!"));
+        assert_eq!(lint(&fixed, &config).len(), 1);
+    }
+
+    #[test]
+    fn test_lint_shipped() {
+        let data = File::open(&Path::new("insults.json")).read_to_string().unwrap();
+        let pack = json::from_str(data[]).unwrap();
+        let config = Config::default();
+        assert!(lint(&pack, &config).iter().all(|x| x.insult.is_some()));
+
+        let fixed = fix(&pack, &config);
+        assert_eq!(lint(&fixed, &config).len(), 0);
+        let (mi, fixed) = (Insults::from_str(data[]), Insults::from_json(fixed));
+        assert_eq!(fixed.len(), mi.len());
+        assert_eq!(fixed.failed_retorts(), mi.failed_retorts());
+        assert_eq!(fixed.opponent_failed_retorts(), mi.opponent_failed_retorts());
+        assert_eq!((fixed.taunts(), fixed.victory_lines(), fixed.defeat_lines()),
+                   (mi.taunts(), mi.victory_lines(), mi.defeat_lines()));
+    }
+}
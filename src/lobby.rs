@@ -0,0 +1,294 @@
+//! Matchmaking and refereeing for duels between two people.
+//!
+//! Every connection gets a `SessionId` from `Lobby::connect`. Sessions
+//! looking for a match are paired in the order they asked, the first one
+//! plays `Player` and serves. Moves go through `Lobby::play`, which checks
+//! it's the session's turn, that insults are insults, and scores the
+//! exchange. Every call returns the events to send, addressed by session.
+//!
+//! Whoever is to move when the turn timer runs out loses the exchange when
+//! defending, and the duel when serving since nothing can happen until
+//! they do. Call `Lobby::tick` regularly to enforce it. Disconnecting hands
+//! the duel to the opponent.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use duel::{Duel, Exchange, Rules, Score, Side, Player, Opponent, Retry};
+use insults::Insults;
+
+pub type SessionId = u64;
+
+/// Something to tell a session.
+#[deriving(Clone, PartialEq, Show)]
+pub enum LobbyEvent {
+    /// Queued until someone else looks for a match.
+    Waiting,
+    /// Matched, playing this side.
+    Paired(Side),
+    /// A side threw an insult.
+    Insulted(Side, String),
+    /// The outcome of an exchange and the score after it.
+    Exchanged(Exchange, Score),
+    /// A side didn't move in time.
+    TimedOut(Side),
+    OpponentLeft,
+    MatchOver(Side),
+    /// The move was invalid, nothing happened.
+    Rejected(String),
+}
+
+struct Match {
+    duel: Duel<'static>,
+    /// The sessions playing `Player` and `Opponent`.
+    sessions: (SessionId, SessionId),
+    /// The insult waiting for a retort.
+    pending: Option<String>,
+    /// When the side to move runs out of time, in seconds.
+    deadline: i64,
+}
+
+impl Match {
+    fn session(&self, side: Side) -> SessionId {
+        match side {
+            Player => self.sessions.val0(),
+            Opponent => self.sessions.val1(),
+        }
+    }
+
+    fn side(&self, session: SessionId) -> Side {
+        if self.sessions.val0() == session { Player } else { Opponent }
+    }
+
+    /// The side whose move it is.
+    fn to_move(&self) -> Side {
+        match self.pending {
+            Some(_) => self.duel.attacker().other(),
+            None => self.duel.attacker(),
+        }
+    }
+
+    fn both(&self, event: LobbyEvent) -> Vec<(SessionId, LobbyEvent)> {
+        vec![(self.sessions.val0(), event.clone()), (self.sessions.val1(), event)]
+    }
+}
+
+/// Pairs sessions and referees their duels.
+pub struct Lobby {
+    insults: Arc<Insults>,
+    rules: Rules,
+    turn_time: Duration,
+    next_id: u64,
+    /// Sessions waiting for a match, in order.
+    waiting: Vec<SessionId>,
+    matches: HashMap<u64, Match>,
+    /// Connected sessions and their match.
+    sessions: HashMap<SessionId, Option<u64>>,
+}
+
+impl Lobby {
+    /// A lobby for duels by `rules`, with 30 seconds to move.
+    pub fn new(insults: Arc<Insults>, rules: Rules) -> Lobby {
+        Lobby {
+            insults: insults,
+            rules: rules,
+            turn_time: Duration::seconds(30),
+            next_id: 1,
+            waiting: Vec::new(),
+            matches: HashMap::new(),
+            sessions: HashMap::new(),
+        }
+    }
+
+    pub fn with_turn_time(mut self, turn_time: Duration) -> Lobby {
+        self.turn_time = turn_time;
+        self
+    }
+
+    fn next_id(&mut self) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        id
+    }
+
+    /// A new session.
+    pub fn connect(&mut self) -> SessionId {
+        let id = self.next_id();
+        self.sessions.insert(id, None);
+        id
+    }
+
+    /// Whether `session` is connected.
+    pub fn is_connected(&self, session: SessionId) -> bool {
+        self.sessions.contains_key(&session)
+    }
+
+    /// Pair `session` with the longest waiting session, or queue it.
+    pub fn find_match(&mut self, session: SessionId, now: i64) -> Vec<(SessionId, LobbyEvent)> {
+        match self.sessions.get(&session) {
+            Some(&None) if !self.waiting.contains(&session) => (),
+            Some(_) => return vec![(session, Rejected("already looking or playing".to_string()))],
+            None => return vec![(session, Rejected("unknown session".to_string()))],
+        }
+        if self.waiting.is_empty() {
+            self.waiting.push(session);
+            return vec![(session, Waiting)];
+        }
+
+        let first = self.waiting.remove(0).unwrap();
+        let id = self.next_id();
+        let m = Match {
+            duel: Duel::shared(self.insults.clone(), self.rules.clone()),
+            sessions: (first, session),
+            pending: None,
+            deadline: now + self.turn_time.num_seconds(),
+        };
+        self.matches.insert(id, m);
+        self.sessions.insert(first, Some(id));
+        self.sessions.insert(session, Some(id));
+        vec![(first, Paired(Player)), (session, Paired(Opponent))]
+    }
+
+    /// Play `text` for `session`, an insult on its serve and a retort otherwise.
+    pub fn play(&mut self, session: SessionId, text: &str, now: i64) -> Vec<(SessionId, LobbyEvent)> {
+        let id = match self.sessions.get(&session) {
+            Some(&Some(id)) => id,
+            _ => return vec![(session, Rejected("not in a match".to_string()))],
+        };
+        let turn_time = self.turn_time.num_seconds();
+        let res = {
+            let m = self.matches.get_mut(&id).unwrap();
+            let side = m.side(session);
+            if m.to_move() != side {
+                return vec![(session, Rejected("not your turn".to_string()))];
+            }
+            m.deadline = now + turn_time;
+
+            match m.pending.clone() {
+                None => {
+                    if m.duel.insults().retort(text).is_none() {
+                        return vec![(session, Rejected("not an insult".to_string()))];
+                    }
+                    m.pending = Some(text.to_string());
+                    m.both(Insulted(side, text.to_string()))
+                },
+                Some(insult) => {
+                    let exchange = if m.duel.retrying().is_some() {
+                        m.duel.retry(text)
+                    } else {
+                        m.duel.exchange(insult[], text)
+                    };
+                    if let Retry(_) = exchange {} else {
+                        m.pending = None;
+                    }
+                    m.both(Exchanged(exchange, m.duel.score().clone()))
+                },
+            }
+        };
+        self.finish(id, res)
+    }
+
+    /// Apply the turn timers at `now`.
+    pub fn tick(&mut self, now: i64) -> Vec<(SessionId, LobbyEvent)> {
+        let late: Vec<u64> = self.matches.iter()
+            .filter(|&(_, m)| m.deadline <= now)
+            .map(|(id, _)| *id)
+            .collect();
+        let mut res = Vec::new();
+        for id in late.into_iter() {
+            let (mut events, forfeit) = {
+                let m = self.matches.get_mut(&id).unwrap();
+                let side = m.to_move();
+                let mut events = m.both(TimedOut(side));
+                m.deadline = now + self.turn_time.num_seconds();
+                if m.pending.take().is_some() {
+                    let exchange = m.duel.give_up();
+                    events.push_all(m.both(Exchanged(exchange, m.duel.score().clone()))[]);
+                    (events, None)
+                } else {
+                    (events, Some(side.other()))
+                }
+            };
+            match forfeit {
+                Some(winner) => events.push_all(self.end(id, winner)[]),
+                None => events = self.finish(id, events),
+            }
+            res.push_all(events[]);
+        }
+        res
+    }
+
+    /// Leave the lobby, handing any duel to the opponent.
+    pub fn disconnect(&mut self, session: SessionId) -> Vec<(SessionId, LobbyEvent)> {
+        self.waiting.retain(|x| *x != session);
+        let id = match self.sessions.remove(&session) {
+            Some(Some(id)) => id,
+            _ => return Vec::new(),
+        };
+        let m = self.matches.remove(&id).unwrap();
+        let other = m.side(session).other();
+        self.sessions.insert(m.session(other), None);
+        vec![(m.session(other), OpponentLeft), (m.session(other), MatchOver(other))]
+    }
+
+    /// End match `id` if it's been won.
+    fn finish(&mut self, id: u64, mut events: Vec<(SessionId, LobbyEvent)>)
+            -> Vec<(SessionId, LobbyEvent)> {
+        if let Some(winner) = self.matches.get(&id).and_then(|m| m.duel.winner()) {
+            events.push_all(self.end(id, winner)[]);
+        }
+        events
+    }
+
+    /// End match `id`, its sessions may look for a new match.
+    fn end(&mut self, id: u64, winner: Side) -> Vec<(SessionId, LobbyEvent)> {
+        let m = self.matches.remove(&id).unwrap();
+        self.sessions.insert(m.sessions.val0(), None);
+        self.sessions.insert(m.sessions.val1(), None);
+        m.both(MatchOver(winner))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use duel::{Rules, Score, Point, Player, Opponent};
+    use insults::Insults;
+    use super::*;
+
+    #[test]
+    fn test_lobby() {
+        let mut lobby = Lobby::new(Insults::new("insults.json").into_shared(), Rules::first_to(1))
+            .with_turn_time(Duration::seconds(10));
+        let (a, b, c) = (lobby.connect(), lobby.connect(), lobby.connect());
+        let insult = "You fight like a dairy farmer.";
+
+        assert_eq!(lobby.find_match(a, 0), vec![(a, Waiting)]);
+        assert_eq!(lobby.find_match(b, 0), vec![(a, Paired(Player)), (b, Paired(Opponent))]);
+        assert_eq!(lobby.play(b, insult, 1), vec![(b, Rejected("not your turn".to_string()))]);
+        assert_eq!(lobby.play(a, "Arr!", 1), vec![(a, Rejected("not an insult".to_string()))]);
+        assert_eq!(lobby.play(a, insult, 1).len(), 2);
+        let score = Score { player: 0, opponent: 1 };
+        assert_eq!(lobby.play(b, "How appropriate. You fight like a cow.", 2), vec![
+            (a, Exchanged(Point(Opponent), score.clone())), (b, Exchanged(Point(Opponent), score.clone())),
+            (a, MatchOver(Opponent)), (b, MatchOver(Opponent)),
+        ]);
+
+        // Serving too slowly forfeits
+        lobby.find_match(c, 3);
+        lobby.find_match(a, 3);
+        assert!(lobby.tick(12).is_empty());
+        assert_eq!(lobby.tick(13), vec![
+            (c, TimedOut(Player)), (a, TimedOut(Player)),
+            (c, MatchOver(Opponent)), (a, MatchOver(Opponent)),
+        ]);
+
+        lobby.find_match(c, 20);
+        lobby.find_match(b, 20);
+        assert_eq!(lobby.disconnect(c), vec![(b, OpponentLeft), (b, MatchOver(Opponent))]);
+        assert!(!lobby.is_connected(c));
+        assert_eq!(lobby.play(b, insult, 21), vec![(b, Rejected("not in a match".to_string()))]);
+    }
+}
@@ -0,0 +1,143 @@
+//! Lookups shared by every source of insults.
+//!
+//! `Insults` keeps everything in memory, other sources like a database
+//! query what they need when they need it. Without `std` only the policy
+//! is built, `Lookup` hands out owned strings of any source.
+
+#[cfg(not(feature = "std"))]
+use prelude::*;
+#[cfg(feature = "std")]
+use serialize::{Decodable, Decoder, Encodable, Encoder};
+#[cfg(feature = "std")]
+use serialize::json::{Json, ToJson};
+
+use game::{Game, MonkeyIsland1, SwordMaster, MonkeyIsland3, CaptainRottingham, MonkeyIsland4};
+#[cfg(feature = "std")]
+use insults::Insults;
+
+/// Games consulted by `Insults::retort`, Sword Master first.
+pub static DEFAULT_PRIORITY: [Game, ..5] = [SwordMaster, MonkeyIsland1, MonkeyIsland3,
+                                        CaptainRottingham, MonkeyIsland4];
+
+/// Which games `Insults::retort` consults, in order.
+///
+/// Games left out are never consulted. In a data file it's the list of
+/// game keys under `lookup_priority`.
+#[deriving(Clone, PartialEq, Show)]
+pub struct LookupPolicy {
+    games: Vec<Game>,
+}
+
+impl LookupPolicy {
+    pub fn new(games: &[Game]) -> LookupPolicy {
+        LookupPolicy { games: games.to_vec() }
+    }
+
+    pub fn games<'a>(&'a self) -> &'a [Game] {
+        self.games[]
+    }
+}
+
+impl Default for LookupPolicy {
+    /// The Sword Master first, so Monkey Island 1 answers with the custom retort.
+    fn default() -> LookupPolicy {
+        LookupPolicy::new(DEFAULT_PRIORITY)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<E, D: Decoder<E>> Decodable<D, E> for LookupPolicy {
+    fn decode(d: &mut D) -> Result<LookupPolicy, E> {
+        let keys: Vec<String> = try!(Decodable::decode(d));
+        let mut games = Vec::new();
+        for key in keys.iter() {
+            match Game::from_key(key[]) {
+                Some(game) => games.push(game),
+                None => return Err(d.error(format!("unknown game {}", key)[])),
+            }
+        }
+        Ok(LookupPolicy { games: games })
+    }
+}
+
+#[cfg(feature = "std")]
+impl<E, S: Encoder<E>> Encodable<S, E> for LookupPolicy {
+    fn encode(&self, s: &mut S) -> Result<(), E> {
+        let keys: Vec<String> = self.games.iter().map(|x| x.key().to_string()).collect();
+        keys.encode(s)
+    }
+}
+
+#[cfg(feature = "std")]
+impl ToJson for LookupPolicy {
+    fn to_json(&self) -> Json {
+        self.games.iter().map(|x| x.key().to_string()).collect::<Vec<String>>().to_json()
+    }
+}
+
+/// A source of insults and their retorts.
+#[cfg(feature = "std")]
+pub trait Lookup {
+    /// The retort to `insult` from any game.
+    fn lookup(&self, insult: &str) -> Option<String>;
+
+    /// The retort to `insult` in `game`.
+    fn lookup_in(&self, game: Game, insult: &str) -> Option<String>;
+
+    /// The insults of `game`, ordered.
+    fn insults_of(&self, game: Game) -> Vec<String>;
+
+    /// Retorts for when the right one isn't known.
+    fn failed(&self) -> Vec<String>;
+}
+
+#[cfg(feature = "std")]
+impl Lookup for Insults {
+    fn lookup(&self, insult: &str) -> Option<String> {
+        self.retort(insult).map(|x| x.to_string())
+    }
+
+    fn lookup_in(&self, game: Game, insult: &str) -> Option<String> {
+        let retort = match game {
+            MonkeyIsland1 => self.mi1_retort(insult),
+            SwordMaster => self.sword_master_retort(insult),
+            MonkeyIsland3 => self.mi3_retort(insult),
+            CaptainRottingham => self.captain_rottingham_retort(insult),
+            MonkeyIsland4 => self.mi4_retort(insult),
+        };
+        retort.map(|x| x.to_string())
+    }
+
+    fn insults_of(&self, game: Game) -> Vec<String> {
+        self.entries(game).into_iter().map(|(insult, _)| insult.clone()).collect()
+    }
+
+    fn failed(&self) -> Vec<String> {
+        self.failed_retorts().to_vec()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serialize::json::ToJson;
+
+    use game::{MonkeyIsland3, CaptainRottingham};
+    use insults::{Insults, fixture};
+
+    #[test]
+    fn test_lookup_policy() {
+        let mut mi = Insults::new("insults.json");
+        let insult = mi.captain_rottingham_insults()[0].clone();
+        let retort = mi.entry(CaptainRottingham, insult[]).unwrap().retort.clone();
+        assert_eq!(mi.retort(insult[]), Some("That's not fair, you're using Captain Rottingham's insults!"));
+
+        mi.set_game_priority([CaptainRottingham, MonkeyIsland3]);
+        assert_eq!(mi.retort(insult[]), Some(retort[]));
+        assert_eq!(mi.retort("Have you stopped wearing diapers yet?"), None);
+        assert_eq!(mi.game_priority(), [CaptainRottingham, MonkeyIsland3][]);
+
+        let json = mi.to_json().to_string();
+        assert_eq!(Insults::from_str(json[]).game_priority(), mi.game_priority());
+        assert!(Insults::try_from_str(fixture(r#"{"lookup_priority": ["monkey_island5"]}"#)[]).is_err());
+    }
+}
@@ -0,0 +1,503 @@
+#![feature(slicing_syntax)]
+
+extern crate getopts;
+extern crate serialize;
+extern crate mi_insults;
+
+use std::io::{File, stdin, stdio};
+use std::os;
+use std::rand::{mod, Rng};
+use getopts::{optflag, optmulti, optopt, getopts, usage, OptGroup};
+use serialize::json;
+
+use mi_insults::{lint, pack, Insults, Filter, Everything, FamilyFriendly};
+use mi_insults::quiz::Quiz;
+#[cfg(feature = "serve")]
+use mi_insults::{openapi, serve};
+#[cfg(feature = "serve")]
+use mi_insults::leaderboard::{JsonFileStore, Leaderboard};
+#[cfg(feature = "serve")]
+use mi_insults::ratelimit::{Limits, Quota};
+use mi_insults::duel::{Ai, Duel, Rules, Side, Player, Opponent, Point, Serve, Retry};
+#[cfg(feature = "mmap")]
+use mi_insults::mapped::MappedSnapshot;
+
+static USAGE: &'static str = "Usage:
+    {program} duel [options]
+    {program} lint [options] PACK
+    {program} pack new [options] NAME
+    {program} retort [options] INSULT
+    {program} serve [options]";
+
+fn print_usage(brief: &str, opts: &[OptGroup]) {
+    println!("{}", usage(brief, opts));
+}
+
+/// The filter asked for with `--family-friendly`.
+fn filter_opt(matches: &getopts::Matches) -> Filter {
+    if matches.opt_present("family-friendly") { FamilyFriendly } else { Everything }
+}
+
+/// Argument `i`, empty if missing.
+fn arg<'a>(args: &'a [String], i: uint) -> &'a str {
+    args.get(i).map(|x| x[]).unwrap_or("")
+}
+
+fn main() {
+    let args = os::args();
+    let program = args[0].clone();
+
+    let status = match (arg(args[], 1), arg(args[], 2)) {
+        ("duel", _) => cmd_duel(program[], args[2..]),
+        ("lint", _) => cmd_lint(program[], args[2..]),
+        ("pack", "new") => cmd_pack_new(program[], args[3..]),
+        ("retort", _) => cmd_retort(program[], args[2..]),
+        ("serve", _) => cmd_serve(program[], args[2..]),
+        _ => {
+            println!("{}", USAGE.replace("{program}", program[]));
+            1
+        },
+    };
+    os::set_exit_status(status);
+}
+
+fn cmd_lint(program: &str, args: &[String]) -> int {
+    let opts = [
+        optflag("", "fix", "write the fixed pack back to PACK"),
+        optopt("", "quotes", "quote style to enforce, or none", "straight|curly|none"),
+        optflag("h", "help", "print this help"),
+    ];
+    let matches = match getopts(args, opts) {
+        Ok(m) => m,
+        Err(e) => {
+            println!("{}", e);
+            return 1;
+        },
+    };
+    if matches.opt_present("help") || matches.free.len() != 1 {
+        print_usage(format!("Usage: {} lint [options] PACK", program)[], opts);
+        return 1;
+    }
+
+    let mut config = lint::Config::default();
+    match matches.opt_str("quotes").as_ref().map(|x| x[]) {
+        Some("straight") | None => config.quotes = Some(lint::Straight),
+        Some("curly") => config.quotes = Some(lint::Curly),
+        Some("none") => config.quotes = None,
+        Some(x) => {
+            println!("unknown quote style: {}", x);
+            return 1;
+        },
+    }
+
+    let path = Path::new(matches.free[0][]);
+    let pack = match File::open(&path).read_to_string() {
+        Ok(s) => match json::from_str(s[]) {
+            Ok(x) => x,
+            Err(e) => {
+                println!("json error: {}", e);
+                return 1;
+            },
+        },
+        Err(e) => {
+            println!("file error: {}", e);
+            return 1;
+        },
+    };
+
+    let lints = lint::lint(&pack, &config);
+    for l in lints.iter() {
+        println!("{}", l);
+    }
+
+    if matches.opt_present("fix") && !lints.is_empty() {
+        let fixed = lint::fix(&pack, &config);
+        // Like insults fixed to the same text, some problems need a human
+        let left = lint::lint(&fixed, &config);
+        match File::create(&path).write_str(fixed.to_pretty_str()[]) {
+            Ok(_) => println!("fixed {} problems in {}", lints.len() - left.len(), path.display()),
+            Err(e) => {
+                println!("file error: {}", e);
+                return 1;
+            },
+        }
+        if left.is_empty() {
+            0
+        } else {
+            println!("{} problems can't be fixed automatically", left.len());
+            1
+        }
+    } else if lints.is_empty() {
+        0
+    } else {
+        1
+    }
+}
+
+fn cmd_pack_new(program: &str, args: &[String]) -> int {
+    let opts = [
+        optopt("l", "locale", "language of the pack, defaults to en", "LOCALE"),
+        optopt("d", "dir", "where to create the pack, defaults to the current directory", "DIR"),
+        optflag("h", "help", "print this help"),
+    ];
+    let matches = match getopts(args, opts) {
+        Ok(m) => m,
+        Err(e) => {
+            println!("{}", e);
+            return 1;
+        },
+    };
+    if matches.opt_present("help") || matches.free.len() != 1 {
+        print_usage(format!("Usage: {} pack new [options] NAME", program)[], opts);
+        return 1;
+    }
+
+    let locale = matches.opt_str("locale").unwrap_or("en".to_string());
+    let dir = Path::new(matches.opt_str("dir").unwrap_or(".".to_string()));
+    match pack::scaffold(&dir, matches.free[0][], locale[]) {
+        Ok(root) => {
+            println!("created {}, run `make check` in it to lint the pack", root.display());
+            0
+        },
+        Err(e) => {
+            println!("file error: {}", e);
+            1
+        },
+    }
+}
+
+fn cmd_retort(program: &str, args: &[String]) -> int {
+    let opts = [
+        optopt("p", "pack", "pack to look up in, defaults to insults.json", "PACK"),
+        optopt("", "snapshot", "mapped snapshot to look up in instead of a pack", "FILE"),
+        optopt("", "save-snapshot", "save the pack as a mapped snapshot instead of looking up", "FILE"),
+        optflag("h", "help", "print this help"),
+    ];
+    let matches = match getopts(args, opts) {
+        Ok(m) => m,
+        Err(e) => {
+            println!("{}", e);
+            return 1;
+        },
+    };
+    let save = matches.opt_str("save-snapshot");
+    let looking_up = save.is_none() || matches.opt_present("snapshot");
+    if matches.opt_present("help") || (looking_up && matches.free.len() != 1) {
+        print_usage(format!("Usage: {} retort [options] INSULT", program)[], opts);
+        return 1;
+    }
+
+    if let Some(location) = matches.opt_str("snapshot") {
+        return retort_mapped(location[], matches.free[0][]);
+    }
+    let location = matches.opt_str("pack").unwrap_or("insults.json".to_string());
+    let insults = match Insults::try_from_path(location[]) {
+        Ok(x) => x,
+        Err(e) => {
+            println!("{}: {}", location, e);
+            return 1;
+        },
+    };
+    if let Some(location) = save {
+        return save_mapped(&insults, location[]);
+    }
+    match insults.retort(matches.free[0][]) {
+        Some(x) => {
+            println!("{}", x);
+            0
+        },
+        None => {
+            println!("unknown insult");
+            1
+        },
+    }
+}
+
+#[cfg(feature = "mmap")]
+fn retort_mapped(location: &str, insult: &str) -> int {
+    let snapshot = match MappedSnapshot::open(&Path::new(location)) {
+        Ok(x) => x,
+        Err(e) => {
+            println!("{}: {}", location, e);
+            return 1;
+        },
+    };
+    match snapshot.retort(insult) {
+        Some(x) => {
+            println!("{}", x);
+            0
+        },
+        None => {
+            println!("unknown insult");
+            1
+        },
+    }
+}
+
+#[cfg(not(feature = "mmap"))]
+fn retort_mapped(_: &str, _: &str) -> int {
+    println!("built without the mmap feature");
+    1
+}
+
+#[cfg(feature = "mmap")]
+fn save_mapped(insults: &Insults, location: &str) -> int {
+    match insults.save_mapped(location) {
+        Ok(_) => 0,
+        Err(e) => {
+            println!("{}: {}", location, e);
+            1
+        },
+    }
+}
+
+#[cfg(not(feature = "mmap"))]
+fn save_mapped(_: &Insults, _: &str) -> int {
+    println!("built without the mmap feature");
+    1
+}
+
+#[cfg(feature = "serve")]
+fn cmd_serve(program: &str, args: &[String]) -> int {
+    let opts = [
+        optopt("p", "pack", "pack to serve, defaults to insults.json", "PACK"),
+        optflag("", "family-friendly", "leave out insults rated harsh"),
+        optopt("", "host", "address to listen on, defaults to 127.0.0.1", "HOST"),
+        optopt("", "port", "port to listen on, defaults to 8080", "PORT"),
+        optopt("", "lookup-limit", "lookups a minute per client, unlimited by default", "N"),
+        optopt("", "duel-limit", "duels a minute per client, unlimited by default", "N"),
+        optmulti("", "api-key", "limit clients sending this X-Api-Key apart from their IP", "KEY"),
+        optopt("", "leaderboard", "JSON file to keep the leaderboard in, in memory by default", "FILE"),
+        optflag("", "openapi", "print the OpenAPI document of the API and exit"),
+        optflag("h", "help", "print this help"),
+    ];
+    let matches = match getopts(args, opts) {
+        Ok(m) => m,
+        Err(e) => {
+            println!("{}", e);
+            return 1;
+        },
+    };
+    if matches.opt_present("help") || !matches.free.is_empty() {
+        print_usage(format!("Usage: {} serve [options]", program)[], opts);
+        return 1;
+    }
+    if matches.opt_present("openapi") {
+        println!("{}", openapi::openapi().to_pretty_str());
+        return 0;
+    }
+
+    let location = matches.opt_str("pack").unwrap_or("insults.json".to_string());
+    let insults = match Insults::try_from_path(location[]) {
+        Ok(x) => x.with_filter(filter_opt(&matches)),
+        Err(e) => {
+            println!("{}: {}", location, e);
+            return 1;
+        },
+    };
+    let host = matches.opt_str("host").unwrap_or("127.0.0.1".to_string());
+    let port = match matches.opt_str("port").map(|x| from_str::<u16>(x[])) {
+        None => 8080,
+        Some(Some(x)) => x,
+        Some(None) => {
+            println!("--port must be a port number");
+            return 1;
+        },
+    };
+
+    let limits = match (quota_opt(&matches, "lookup-limit"), quota_opt(&matches, "duel-limit")) {
+        (Ok(lookups), Ok(duels)) => Limits {
+            lookups: lookups,
+            duels: duels,
+            api_keys: matches.opt_strs("api-key"),
+        },
+        _ => {
+            println!("--lookup-limit and --duel-limit must be positive numbers");
+            return 1;
+        },
+    };
+
+    let mut server = serve::Server::new(insults.into_shared()).with_limits(limits);
+    if let Some(path) = matches.opt_str("leaderboard") {
+        match JsonFileStore::open(&Path::new(path[])) {
+            Ok(store) => server = server.with_leaderboard(Leaderboard::new(box store)),
+            Err(e) => {
+                println!("{}: {}", path, e);
+                return 1;
+            },
+        }
+    }
+
+    println!("serving {} on http://{}:{}", location, host, port);
+    match server.listen(host[], port) {
+        Ok(_) => 0,
+        Err(e) => {
+            println!("server error: {}", e);
+            1
+        },
+    }
+}
+
+/// The quota of requests a minute given as `name`, None if it's not given.
+#[cfg(feature = "serve")]
+fn quota_opt(matches: &getopts::Matches, name: &str) -> Result<Option<Quota>, ()> {
+    match matches.opt_str(name).map(|x| from_str::<uint>(x[])) {
+        None => Ok(None),
+        Some(Some(n)) if n > 0 => Ok(Some(Quota::per_minute(n))),
+        Some(_) => Err(()),
+    }
+}
+
+#[cfg(not(feature = "serve"))]
+fn cmd_serve(_: &str, _: &[String]) -> int {
+    println!("built without the serve feature");
+    1
+}
+
+/// Number of retorts to choose from when defending.
+const CHOICES: uint = 4;
+
+fn cmd_duel(program: &str, args: &[String]) -> int {
+    let opts = [
+        optopt("p", "pack", "pack to duel with, defaults to insults.json", "PACK"),
+        optopt("n", "first-to", "points needed to win, defaults to 3", "N"),
+        optopt("s", "skill", "chance the opponent knows a retort, defaults to 0.5", "SKILL"),
+        optflag("", "family-friendly", "leave out insults rated harsh"),
+        optflag("h", "help", "print this help"),
+    ];
+    let matches = match getopts(args, opts) {
+        Ok(m) => m,
+        Err(e) => {
+            println!("{}", e);
+            return 1;
+        },
+    };
+    if matches.opt_present("help") || !matches.free.is_empty() {
+        print_usage(format!("Usage: {} duel [options]", program)[], opts);
+        return 1;
+    }
+
+    let location = matches.opt_str("pack").unwrap_or("insults.json".to_string());
+    let insults = match Insults::try_from_path(location[]) {
+        Ok(x) => x.with_filter(filter_opt(&matches)),
+        Err(e) => {
+            println!("{}: {}", location, e);
+            return 1;
+        },
+    };
+    let first_to = match matches.opt_str("first-to").map(|x| from_str::<uint>(x[])) {
+        None => 3,
+        Some(Some(n)) if n > 0 => n,
+        Some(_) => {
+            println!("--first-to must be a positive number");
+            return 1;
+        },
+    };
+    let ai = match matches.opt_str("skill").map(|x| from_str::<f64>(x[])) {
+        None => Ai::default(),
+        Some(Some(x)) if x >= 0.0 && x <= 1.0 => Ai::new(x),
+        Some(_) => {
+            println!("--skill must be between 0 and 1");
+            return 1;
+        },
+    };
+
+    let mut rng = rand::task_rng();
+    let mut input = stdin();
+    let quiz = Quiz::new(&insults, CHOICES - 2);
+    let mut duel = Duel::new(&insults, Rules::first_to(first_to));
+    println!("En garde! First to {} wins.", first_to);
+
+    while !duel.is_over() {
+        println!("");
+        let exchange = match duel.attacker() {
+            Player => {
+                let mut all = insults.answered_insults();
+                all.sort();
+                let mut offered: Vec<&str> = rand::sample(&mut rng, all.into_iter(), CHOICES)
+                    .into_iter().map(|x| x[]).collect();
+                rng.shuffle(offered[mut]);
+                let insult = match prompt_insult(&mut input, &insults, offered[]) {
+                    Some(x) => x,
+                    None => return 0,
+                };
+                let retort = ai.retort(&insults, insult[], &mut rng);
+                println!("Opponent: {}", retort);
+                duel.exchange(insult[], retort)
+            },
+            Opponent => {
+                let insult = ai.insult(&insults, &mut rng);
+                println!("Opponent: {}", insult);
+                let mut choices = match quiz.question(insult, &mut rng) {
+                    Some(q) => q.choices,
+                    None => Vec::new(),
+                };
+                if insults.failed_retort_count() > 0 {
+                    choices.push(insults.rand_failed_retort(&mut rng));
+                }
+                rng.shuffle(choices[mut]);
+                match prompt(&mut input, "Your retort:", choices[]) {
+                    Some(retort) => duel.exchange(insult, retort[]),
+                    None => return 0,
+                }
+            },
+        };
+        match exchange {
+            Point(side) => println!("{} ({} - {})", point_line(side),
+                                    duel.score().player, duel.score().opponent),
+            Serve(_) | Retry(_) => (),
+        }
+    }
+
+    println!("");
+    match duel.winner() {
+        Some(Player) => println!("You've won! I give up, you're too good for me."),
+        _ => println!("You've lost. I'll get you next time!"),
+    }
+    0
+}
+
+fn point_line(side: Side) -> &'static str {
+    match side {
+        Player => "Touché!",
+        Opponent => "Ha! Got you.",
+    }
+}
+
+/// Like `prompt`, asking again until the answer is an insult with a retort.
+fn prompt_insult<R: Buffer>(input: &mut R, insults: &Insults, choices: &[&str]) -> Option<String> {
+    loop {
+        match prompt(input, "Your insult:", choices) {
+            Some(ref x) if insults.retort(x[]).is_none() => println!("That's not an insult."),
+            x => return x,
+        }
+    }
+}
+
+/// Ask for one of `choices` by number, or free text. None at end of input.
+fn prompt<R: Buffer>(input: &mut R, question: &str, choices: &[&str]) -> Option<String> {
+    println!("{}", question);
+    for (i, x) in choices.iter().enumerate() {
+        println!("  {}. {}", i + 1, x);
+    }
+    loop {
+        print!("> ");
+        stdio::flush();
+        let line = match input.read_line() {
+            Ok(x) => x,
+            Err(_) => return None,
+        };
+        let line = line[].trim();
+        if line.is_empty() {
+            continue;
+        }
+        return match from_str::<uint>(line) {
+            Some(n) if n >= 1 && n <= choices.len() => Some(choices[n - 1].to_string()),
+            Some(_) => {
+                println!("Pick 1 to {} or type your own.", choices.len());
+                continue;
+            },
+            None => Some(line.to_string()),
+        };
+    }
+}
@@ -0,0 +1,203 @@
+//! Retorts looked up straight from a memory mapped file, with the `mmap` feature.
+//!
+//! A mapped snapshot is a table of insults and their retorts, sorted by
+//! insult, laid out so lookups read it where it lies:
+//!
+//! * `MIIM`, a version byte and three bytes of padding
+//! * the number of insults, a little endian `u32`
+//! * for every insult the offset and length of the insult and of its
+//!   retort, four little endian `u32`s
+//! * the strings, UTF-8
+//!
+//! Opening one maps the file without reading it, a lookup is a binary
+//! search touching a handful of pages. The fallbacks of the dataset are
+//! written into the table, so answering a single `retort` costs next to
+//! nothing.
+
+use libc;
+use std::c_str::ToCStr;
+use std::collections::TreeMap;
+use std::io::{mod, BufferedWriter, File, IoError, IoResult};
+use std::io::fs;
+use std::mem;
+use std::os::{MemoryMap, MapReadable, MapFd};
+use std::raw;
+use std::str;
+
+use insults::Insults;
+use normalize::nfc;
+use schema::{LoadError, Io};
+
+static MAGIC: &'static [u8] = b"MIIM";
+static VERSION: u8 = 1;
+const HEADER_LEN: uint = 12;
+const ROW_LEN: uint = 16;
+
+impl Insults {
+    /// Save the retorts of the dataset and its fallbacks as a mapped snapshot.
+    pub fn save_mapped(&self, location: &str) -> IoResult<()> {
+        let mut rows = TreeMap::new();
+        let mut dataset = Some(self);
+        loop {
+            let x = match dataset {
+                Some(x) => x,
+                None => break,
+            };
+            for insult in x.insults().into_iter() {
+                if !rows.contains_key(insult) {
+                    if let Some(retort) = self.retort(insult[]) {
+                        rows.insert(insult.clone(), retort.to_string());
+                    }
+                }
+            }
+            dataset = x.fallback();
+        }
+
+        let mut w = BufferedWriter::new(try!(File::create(&Path::new(location))));
+        try!(w.write(MAGIC));
+        try!(w.write(&[VERSION, 0, 0, 0]));
+        try!(w.write_le_u32(rows.len() as u32));
+        let mut offset = HEADER_LEN + ROW_LEN * rows.len();
+        for (insult, retort) in rows.iter() {
+            for s in [insult, retort].iter() {
+                try!(w.write_le_u32(offset as u32));
+                try!(w.write_le_u32(s.len() as u32));
+                offset += s.len();
+            }
+        }
+        for (insult, retort) in rows.iter() {
+            try!(w.write_str(insult[]));
+            try!(w.write_str(retort[]));
+        }
+        w.flush()
+    }
+}
+
+fn invalid(location: &Path, desc: &'static str) -> LoadError {
+    Io(IoError { kind: io::InvalidInput, desc: desc, detail: Some(location.display().to_string()) })
+}
+
+fn read_u32(data: &[u8], at: uint) -> uint {
+    data[at] as uint | (data[at + 1] as uint << 8) | (data[at + 2] as uint << 16) | (data[at + 3] as uint << 24)
+}
+
+/// A mapped snapshot, see the module documentation.
+pub struct MappedSnapshot {
+    map: MemoryMap,
+    len: uint,
+}
+
+impl MappedSnapshot {
+    /// Map the snapshot at `location`, saved with `Insults::save_mapped`.
+    ///
+    /// Only the header is checked, strings are checked when they're looked up.
+    pub fn open(location: &Path) -> Result<MappedSnapshot, LoadError> {
+        let size = match fs::stat(location) {
+            Ok(x) => x.size as uint,
+            Err(e) => return Err(Io(e)),
+        };
+        if size < HEADER_LEN {
+            return Err(invalid(location, "not a mapped snapshot"));
+        }
+        let fd = location.with_c_str(|path| unsafe { libc::open(path, libc::O_RDONLY, 0) });
+        if fd < 0 {
+            return Err(Io(IoError::last_error()));
+        }
+        let map = MemoryMap::new(size, [MapReadable, MapFd(fd)][]);
+        // The mapping stays valid without the descriptor
+        unsafe { libc::close(fd); }
+        let map = match map {
+            Ok(x) => x,
+            Err(e) => return Err(Io(IoError {
+                kind: io::OtherIoError,
+                desc: "couldn't map the file",
+                detail: Some(e.to_string()),
+            })),
+        };
+
+        let mut res = MappedSnapshot { map: map, len: 0 };
+        let (magic, len) = {
+            let data = res.data();
+            (data[..MAGIC.len()] == MAGIC && data[MAGIC.len()] == VERSION, read_u32(data, 8))
+        };
+        if !magic {
+            return Err(invalid(location, "not a mapped snapshot of this version"));
+        }
+        if HEADER_LEN + ROW_LEN * len > size {
+            return Err(invalid(location, "the mapped snapshot is truncated"));
+        }
+        res.len = len;
+        Ok(res)
+    }
+
+    fn data<'a>(&'a self) -> &'a [u8] {
+        unsafe { mem::transmute(raw::Slice { data: self.map.data() as *const u8, len: self.map.len() }) }
+    }
+
+    /// String `i` of row `row`, the insult or the retort.
+    fn column<'a>(&'a self, row: uint, i: uint) -> Option<&'a [u8]> {
+        let data = self.data();
+        let at = HEADER_LEN + ROW_LEN * row + 8 * i;
+        let (start, len) = (read_u32(data, at), read_u32(data, at + 4));
+        if start + len > data.len() {
+            None
+        } else {
+            Some(data[start..start + len])
+        }
+    }
+
+    /// How many insults have a retort.
+    pub fn len(&self) -> uint {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The retort to `insult`, like `Insults::retort`.
+    pub fn retort<'a>(&'a self, insult: &str) -> Option<&'a str> {
+        let key = nfc(insult);
+        let (mut lo, mut hi) = (0u, self.len);
+        while lo < hi {
+            let mid = (lo + hi) / 2;
+            let found = match self.column(mid, 0) {
+                Some(x) => x,
+                None => return None,
+            };
+            match found.cmp(&key.as_bytes()) {
+                Less => lo = mid + 1,
+                Greater => hi = mid,
+                Equal => return self.column(mid, 1).and_then(str::from_utf8),
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{File, TempDir};
+
+    use insults::Insults;
+    use super::*;
+
+    #[test]
+    fn test_mapped() {
+        let tmp = TempDir::new("mi_insults").unwrap();
+        let path = tmp.path().join("insults.map");
+        let de = Insults::with_locale("insults.json", "de");
+        de.save_mapped(path.as_str().unwrap()).unwrap();
+
+        let mapped = MappedSnapshot::open(&path).unwrap();
+        assert!(mapped.len() > de.insults().len());
+        let insult = "You fight like a dairy farmer.";
+        assert_eq!(mapped.retort(insult), de.retort(insult));
+        assert_eq!(mapped.retort("Hey, look over there!"), de.retort("Hey, look over there!"));
+        assert_eq!(mapped.retort("Oh yeah?"), None);
+
+        let bad = tmp.path().join("bad.map");
+        File::create(&bad).write_str("not a snapshot").unwrap();
+        assert!(MappedSnapshot::open(&bad).is_err());
+    }
+}
@@ -0,0 +1,116 @@
+//! Flavor text made up from the insults, for menus and loading screens.
+//!
+//! A `Markov` chain of words is trained on all insults and retorts, then
+//! strings together lines that sound like them. Generation only draws from
+//! the `Rng` it's given, so the same seed makes the same lines.
+
+use std::collections::{HashMap, HashSet};
+use std::rand::Rng;
+
+use insults::Insults;
+
+/// Most words in a generated line.
+const MAX_WORDS: uint = 30;
+/// Tries at a line that isn't in the training lines.
+const TRIES: uint = 10;
+
+/// Words following each pair of words, in the order they were seen.
+pub struct Markov {
+    /// The empty word marks the start and end of a line.
+    chain: HashMap<(String, String), Vec<String>>,
+    lines: HashSet<String>,
+}
+
+impl Markov {
+    /// Train on `lines`, each a sentence or two.
+    pub fn train(lines: &[&str]) -> Markov {
+        let mut res = Markov { chain: HashMap::new(), lines: HashSet::new() };
+        for line in lines.iter() {
+            let mut words: Vec<String> = line.words().map(|x| x.to_string()).collect();
+            if words.is_empty() {
+                continue;
+            }
+            res.lines.insert(words.connect(" "));
+            words.push(String::new());
+
+            let (mut a, mut b) = (String::new(), String::new());
+            for word in words.into_iter() {
+                let key = (a, b.clone());
+                if !res.chain.contains_key(&key) {
+                    res.chain.insert(key.clone(), Vec::new());
+                }
+                res.chain.get_mut(&key).unwrap().push(word.clone());
+                a = b;
+                b = word;
+            }
+        }
+        res
+    }
+
+    /// A made up line, avoiding the training lines when it can.
+    pub fn generate<R: Rng>(&self, rng: &mut R) -> String {
+        let mut line = String::new();
+        for _ in range(0, TRIES) {
+            line = self.walk(rng);
+            if !self.lines.contains(&line) {
+                break;
+            }
+        }
+        line
+    }
+
+    fn walk<R: Rng>(&self, rng: &mut R) -> String {
+        let mut words = Vec::new();
+        let (mut a, mut b) = (String::new(), String::new());
+        while words.len() < MAX_WORDS {
+            let next = match self.chain.get(&(a, b.clone())).and_then(|x| rng.choose(x[])) {
+                Some(x) if !x.is_empty() => x.clone(),
+                _ => break,
+            };
+            words.push(next.clone());
+            a = b;
+            b = next;
+        }
+        words.connect(" ")
+    }
+}
+
+impl Insults {
+    /// A chain trained on every insult and retort, ordered so training is reproducible.
+    pub fn markov(&self) -> Markov {
+        let mut lines: Vec<&str> = Vec::new();
+        for insult in self.insults().into_iter() {
+            lines.push(insult[]);
+            lines.push(self.retort(insult[]).unwrap());
+        }
+        lines.sort();
+        Markov::train(lines[])
+    }
+
+    /// A made up line that sounds like an insult.
+    ///
+    /// Trains a chain on every call, keep the one from `markov` to generate many.
+    pub fn generate_insult_like<R: Rng>(&self, rng: &mut R) -> String {
+        self.markov().generate(rng)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rand::{Isaac64Rng, SeedableRng};
+
+    use insults::Insults;
+    use super::*;
+
+    #[test]
+    fn test_generate() {
+        let mi = Insults::new("insults.json");
+        let seeded = |seed: u64| -> Isaac64Rng { SeedableRng::from_seed([seed][]) };
+        let line = mi.generate_insult_like(&mut seeded(42));
+        assert!(!line.is_empty());
+        assert_eq!(mi.generate_insult_like(&mut seeded(42)), line);
+
+        let markov = Markov::train(["You fight like a cow."]);
+        assert_eq!(markov.generate(&mut seeded(1)), "You fight like a cow.".to_string());
+    }
+}
@@ -0,0 +1,82 @@
+//! What a loose match found and how much to trust it.
+//!
+//! The normalized, fuzzy and phonetic indices answer with a `MatchResult`
+//! rather than a bare retort, so callers can take a misspelling at its
+//! word or ask the user to confirm it first. They all implement `Index`,
+//! so callers can swap one for another, or for a future one.
+
+use game::Game;
+use insults::Insults;
+
+/// How the input was matched to an insult.
+#[deriving(Clone, PartialEq, Eq, Show)]
+pub enum Method {
+    /// The input was the insult.
+    Exact,
+    /// The input only differed in case, spacing or accents.
+    Normalized,
+    /// The words of the input have the same stems.
+    Stemmed,
+    /// The input is a misspelling of the insult.
+    Fuzzy,
+    /// The input sounds like the insult.
+    Phonetic,
+}
+
+/// An insult matched by an index, with its retort.
+#[deriving(Clone, PartialEq, Show)]
+pub struct MatchResult<'a> {
+    pub retort: &'a str,
+    /// The insult the input was taken for.
+    pub insult_matched: &'a str,
+    /// The game of the insult, None if it only has a retort in a fallback.
+    pub game: Option<Game>,
+    pub method: Method,
+    /// From 0 to 1, 1 for exact and normalized matches.
+    pub confidence: f64,
+}
+
+/// An index answering lookups, for code that shouldn't care which one it asks.
+pub trait Index<'a> {
+    /// The retort to the insult `input` was taken for.
+    fn lookup(&self, input: &str) -> Option<MatchResult<'a>>;
+}
+
+impl<'a> MatchResult<'a> {
+    /// The match of `insult`, None if `insults` has no retort to it.
+    pub fn new(insults: &'a Insults, insult: &'a str, method: Method, confidence: f64)
+            -> Option<MatchResult<'a>> {
+        insults.retort(insult).map(|retort| MatchResult {
+            retort: retort,
+            insult_matched: insult,
+            game: insults.game_of(insult),
+            method: method,
+            confidence: confidence,
+        })
+    }
+
+    /// Whether the input was the insult, give or take case, spacing and accents.
+    pub fn is_certain(&self) -> bool {
+        self.method == Exact || self.method == Normalized
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use game::MonkeyIsland1;
+    use insults::Insults;
+    use super::*;
+
+    #[test]
+    fn test_match_result() {
+        let mi = Insults::new("insults.json");
+        let exact = mi.normalized_index().retort("You fight like a dairy farmer.").unwrap();
+        assert_eq!((exact.method.clone(), exact.game, exact.confidence), (Exact, Some(MonkeyIsland1), 1.0));
+
+        let fuzzy = mi.fuzzy_index().retort("you fihgt like a dairy farmer").unwrap();
+        assert_eq!(fuzzy.insult_matched, "You fight like a dairy farmer.");
+        assert_eq!(fuzzy.retort, exact.retort);
+        assert!(fuzzy.method == Fuzzy && !fuzzy.is_certain() && fuzzy.confidence < 1.0);
+        assert_eq!(MatchResult::new(&mi, "Arr", Exact, 1.0), None);
+    }
+}
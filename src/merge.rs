@@ -0,0 +1,133 @@
+//! Merging packs into a dataset.
+//!
+//! An insult in both datasets with different entries is a conflict, the
+//! `MergePolicy` decides which entry stays. The `MergeReport` lists every
+//! conflict with how it was resolved.
+
+use game::Game;
+use insults::{Insults, Entry};
+
+/// What to do when both datasets have an insult.
+pub enum MergePolicy<'a> {
+    /// The incoming entry replaces the existing one.
+    Overwrite,
+    /// The existing entry stays.
+    KeepExisting,
+    /// Nothing is merged if there's a conflict.
+    Error,
+    /// The closure gets the existing and incoming entries and returns the one to keep.
+    Custom(|&Entry, &Entry|: 'a -> Entry),
+}
+
+/// How a conflict was resolved.
+#[deriving(Clone, PartialEq, Eq, Show)]
+pub enum Resolution {
+    Overwritten,
+    Kept,
+    /// The custom policy made the entry.
+    Resolved,
+    /// The merge was abandoned.
+    Rejected,
+}
+
+/// An insult both datasets have with different entries.
+#[deriving(Clone, PartialEq, Show)]
+pub struct Conflict {
+    pub game: Game,
+    pub insult: String,
+    pub existing: Entry,
+    pub incoming: Entry,
+    pub resolution: Resolution,
+}
+
+/// What a merge did.
+#[deriving(Clone, PartialEq, Show)]
+pub struct MergeReport {
+    /// Insults only the incoming dataset had.
+    pub added: uint,
+    pub conflicts: Vec<Conflict>,
+}
+
+impl Insults {
+    /// Merge the insults of `other` into this dataset, failed retorts and
+    /// other metadata stay as they are.
+    ///
+    /// With the `Error` policy nothing is merged if there's a conflict and
+    /// the report of the conflicts is the error.
+    pub fn merge(&mut self, other: &Insults, mut policy: MergePolicy) -> Result<MergeReport, MergeReport> {
+        let mut report = MergeReport { added: 0, conflicts: Vec::new() };
+        let mut merged = Vec::new();
+        for game in Game::all().iter() {
+            for &(insult, incoming) in other.entries(*game).iter() {
+                let existing = match self.entry(*game, insult[]) {
+                    Some(x) if x == incoming => continue,
+                    Some(x) => x.clone(),
+                    None => {
+                        report.added += 1;
+                        merged.push((*game, insult.clone(), incoming.clone()));
+                        continue;
+                    },
+                };
+                let (entry, resolution) = match policy {
+                    Overwrite => (incoming.clone(), Overwritten),
+                    KeepExisting => (existing.clone(), Kept),
+                    Error => (existing.clone(), Rejected),
+                    Custom(ref mut resolve) => ((*resolve)(&existing, incoming), Resolved),
+                };
+                merged.push((*game, insult.clone(), entry));
+                report.conflicts.push(Conflict {
+                    game: *game,
+                    insult: insult.clone(),
+                    existing: existing,
+                    incoming: incoming.clone(),
+                    resolution: resolution,
+                });
+            }
+        }
+
+        if report.conflicts.iter().any(|x| x.resolution == Rejected) {
+            return Err(report);
+        }
+        for (game, insult, entry) in merged.into_iter() {
+            self.insert(game, insult[], entry);
+        }
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use game::MonkeyIsland1;
+    use insults::{Insults, Entry, fixture};
+    use super::*;
+
+    fn pack() -> Insults {
+        Insults::from_str(fixture(r#"{"monkey_island1": {
+                "You fight like a dairy farmer.": {"retort": "Moo."},
+                "Your mother was a hamster!": {"retort": "And your father smelt of elderberries!"}
+            }}"#)[])
+    }
+
+    #[test]
+    fn test_merge() {
+        let cow = "How appropriate. You fight like a cow.";
+        let mut mi = Insults::new("insults.json");
+        let report = mi.merge(&pack(), Error).unwrap_err();
+        assert_eq!((report.added, report.conflicts[0].resolution.clone()), (1, Rejected));
+        assert_eq!(mi.retort("Your mother was a hamster!"), None);
+
+        let report = mi.merge(&pack(), KeepExisting).unwrap();
+        assert_eq!(report.conflicts.len(), 1);
+        assert_eq!(mi.retort("You fight like a dairy farmer."), Some(cow));
+        assert!(mi.retort("Your mother was a hamster!").is_some());
+
+        let report = mi.merge(&pack(), Custom(|existing, incoming| {
+            Entry::new(format!("{} {}", existing.retort, incoming.retort)[])
+        })).unwrap();
+        assert_eq!((report.added, report.conflicts[0].game), (0, MonkeyIsland1));
+        assert_eq!(mi.retort("You fight like a dairy farmer."), Some(format!("{} Moo.", cow)[]));
+
+        mi.merge(&pack(), Overwrite).unwrap();
+        assert_eq!(mi.retort("You fight like a dairy farmer."), Some("Moo."));
+    }
+}
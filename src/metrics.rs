@@ -0,0 +1,108 @@
+//! Usage counters for service deployments.
+//!
+//! With the `metrics` feature every retort lookup is counted, take a
+//! `snapshot` to scrape the counts. They're shared by all datasets of the
+//! process and only grow until `reset`.
+
+use std::collections::TreeMap;
+use std::num::Saturating;
+use std::sync::atomic::{AtomicUint, INIT_ATOMIC_UINT, Relaxed};
+
+use game::Game;
+
+static LOOKUPS: AtomicUint = INIT_ATOMIC_UINT;
+static HITS: AtomicUint = INIT_ATOMIC_UINT;
+static FUZZY_LOOKUPS: AtomicUint = INIT_ATOMIC_UINT;
+static FUZZY_HITS: AtomicUint = INIT_ATOMIC_UINT;
+/// Hits by game, in `Game::all` order.
+static GAMES: [AtomicUint, ..5] = [INIT_ATOMIC_UINT, INIT_ATOMIC_UINT, INIT_ATOMIC_UINT,
+                                   INIT_ATOMIC_UINT, INIT_ATOMIC_UINT];
+
+/// The counters at one point in time.
+#[deriving(Clone, PartialEq, Show, Encodable)]
+pub struct Snapshot {
+    /// Retort lookups, exact or fuzzy.
+    pub lookups: uint,
+    pub hits: uint,
+    pub misses: uint,
+    /// Lookups through a `NormalizedIndex`, tolerating case and spacing.
+    pub fuzzy_lookups: uint,
+    pub fuzzy_misses: uint,
+    /// Hits by game key.
+    pub games: TreeMap<String, uint>,
+}
+
+/// The current counts.
+pub fn snapshot() -> Snapshot {
+    // The counters are read one by one while lookups go on, a lookup may be
+    // missing while its hit is counted, so misses are clamped at zero
+    let hits = HITS.load(Relaxed);
+    let fuzzy_hits = FUZZY_HITS.load(Relaxed);
+    let lookups = LOOKUPS.load(Relaxed);
+    let fuzzy_lookups = FUZZY_LOOKUPS.load(Relaxed);
+    let mut games = TreeMap::new();
+    for (i, game) in Game::all().iter().enumerate() {
+        games.insert(game.key().to_string(), GAMES[i].load(Relaxed));
+    }
+    Snapshot {
+        lookups: lookups,
+        hits: hits,
+        misses: lookups.saturating_sub(hits),
+        fuzzy_lookups: fuzzy_lookups,
+        fuzzy_misses: fuzzy_lookups.saturating_sub(fuzzy_hits),
+        games: games,
+    }
+}
+
+/// Start counting from zero.
+pub fn reset() {
+    for x in [&LOOKUPS, &HITS, &FUZZY_LOOKUPS, &FUZZY_HITS].iter() {
+        x.store(0, Relaxed);
+    }
+    for x in GAMES.iter() {
+        x.store(0, Relaxed);
+    }
+}
+
+/// Count a lookup, with the game of the insult if it was found.
+#[doc(hidden)]
+pub fn record_lookup(found: Option<Game>) {
+    LOOKUPS.fetch_add(1, Relaxed);
+    if let Some(game) = found {
+        HITS.fetch_add(1, Relaxed);
+        let i = Game::all().iter().position(|x| *x == game).unwrap();
+        GAMES[i].fetch_add(1, Relaxed);
+    }
+}
+
+/// Count a fuzzy lookup of an insult.
+#[doc(hidden)]
+pub fn record_fuzzy(found: bool) {
+    FUZZY_LOOKUPS.fetch_add(1, Relaxed);
+    if found {
+        FUZZY_HITS.fetch_add(1, Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use insults::Insults;
+    use super::*;
+
+    #[test]
+    fn test_metrics() {
+        let mi = Insults::new("insults.json");
+        let before = snapshot();
+        mi.retort("You fight like a dairy farmer.");
+        mi.retort("You're lazy!");
+        mi.normalized_index().insult("you fight like a DAIRY farmer.");
+
+        // Other tests look up insults at the same time
+        let after = snapshot();
+        assert!(after.lookups >= before.lookups + 2);
+        assert!(after.misses >= before.misses + 1);
+        assert!(after.fuzzy_lookups >= before.fuzzy_lookups + 1);
+        let mi1 = "monkey_island1".to_string();
+        assert!(after.games.get(&mi1).unwrap() > before.games.get(&mi1).unwrap());
+    }
+}
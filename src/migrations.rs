@@ -0,0 +1,129 @@
+//! Upgrade data files from older layouts to the current one.
+//!
+//! Version 1 is the flat layout, every insult maps directly to its retort:
+//!
+//! ```json
+//! { "monkey_island1": { "You fight like a dairy farmer.": "How appropriate. You fight like a cow." } }
+//! ```
+//!
+//! Version 2 makes every entry an object with room for metadata:
+//!
+//! ```json
+//! {
+//!     "version": 2,
+//!     "locale": "en",
+//!     "monkey_island1": {
+//!         "You fight like a dairy farmer.": {
+//!             "retort": "How appropriate. You fight like a cow.",
+//!             "speaker": "Pirate",
+//!             "context": "Sword fighting on Mêlée Island",
+//!             "rating": "everyone",
+//!             "audio": { "file": "mi1_duels.ogg", "start_ms": 1200, "end_ms": 2900 }
+//!         }
+//!     },
+//!     "dialogues": {
+//!         "barkeep": { "line": "Ahoy!", "replies": [{ "line": "Ahoy yourself." }] }
+//!     }
+//! }
+//! ```
+//!
+//! Files without a `version` are version 1.
+
+use std::collections::TreeMap;
+use serialize::json::{mod, Json};
+
+use game::Game;
+
+/// The layout written by this version of the crate.
+pub static CURRENT_VERSION: u64 = 2;
+
+/// The layout version of `data`.
+pub fn version(data: &Json) -> Option<u64> {
+    match data.find(&"version".to_string()) {
+        Some(v) => v.as_u64(),
+        None => Some(1),
+    }
+}
+
+/// Upgrade `data` to the current layout.
+pub fn migrate(mut data: Json) -> Result<Json, String> {
+    loop {
+        data = match version(&data) {
+            Some(1) => v1_to_v2(data),
+            Some(v) if v == CURRENT_VERSION => return Ok(data),
+            Some(v) if v > CURRENT_VERSION =>
+                return Err(format!("version {} is newer than the supported version {}",
+                                   v, CURRENT_VERSION)),
+            _ => return Err("invalid version".to_string()),
+        };
+    }
+}
+
+/// Retorts become entries.
+fn v1_to_v2(data: Json) -> Json {
+    let mut data = match data {
+        json::Object(x) => x,
+        x => return x,
+    };
+
+    for game in Game::all().iter() {
+        let key = game.key().to_string();
+        let pairs = match data.pop(&key) {
+            Some(json::Object(pairs)) => pairs,
+            Some(x) => {
+                data.insert(key, x);
+                continue;
+            },
+            None => continue,
+        };
+
+        let mut entries = TreeMap::new();
+        for (insult, retort) in pairs.into_iter() {
+            let entry = match retort {
+                json::String(retort) => {
+                    let mut entry = TreeMap::new();
+                    entry.insert("retort".to_string(), json::String(retort));
+                    json::Object(entry)
+                },
+                x => x,
+            };
+            entries.insert(insult, entry);
+        }
+        data.insert(key, json::Object(entries));
+    }
+
+    data.insert("version".to_string(), json::U64(2));
+    json::Object(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use serialize::json;
+
+    use super::*;
+
+    #[test]
+    fn test_migrate() {
+        let v1 = json::from_str(r#"{
+            "failed_retorts": ["Oh yeah?"],
+            "monkey_island1": {
+                "You fight like a dairy farmer.": "How appropriate. You fight like a cow."
+            }
+        }"#).unwrap();
+        let v2 = json::from_str(r#"{
+            "version": 2,
+            "failed_retorts": ["Oh yeah?"],
+            "monkey_island1": {
+                "You fight like a dairy farmer.": {
+                    "retort": "How appropriate. You fight like a cow."
+                }
+            }
+        }"#).unwrap();
+
+        assert_eq!(version(&v1), Some(1));
+        assert_eq!(migrate(v1), Ok(v2.clone()));
+        assert_eq!(migrate(v2.clone()), Ok(v2));
+
+        assert!(migrate(json::from_str(r#"{"version": 3}"#).unwrap()).is_err());
+    }
+}
@@ -0,0 +1,127 @@
+//! Flavor lines between the exchanges of a duel.
+//!
+//! A `Narrator` given to `Duel::with_narrator` follows the events of the
+//! duel and now and then picks a line from its pool for the moment, which
+//! observers get as a `Narrated` event right after the event it follows.
+
+use std::rand::{Isaac64Rng, Rng, SeedableRng};
+
+use duel::{DuelEvent, Player, Opponent};
+use duel::{RetortSucceeded, RetortFailed, GaveUp, RoundWon, DuelWon};
+
+/// When a line can be narrated.
+#[deriving(Clone, PartialEq, Eq, Show)]
+pub enum Moment {
+    /// After a correct retort.
+    Retorted,
+    /// After a wrong retort or giving up.
+    Missed,
+    PlayerPoint,
+    OpponentPoint,
+    PlayerWins,
+    OpponentWins,
+}
+
+/// The moment `event` is, if it can be narrated.
+fn moment(event: &DuelEvent) -> Option<Moment> {
+    match *event {
+        RetortSucceeded(..) => Some(Retorted),
+        RetortFailed(..) | GaveUp(_) => Some(Missed),
+        RoundWon(Player) => Some(PlayerPoint),
+        RoundWon(Opponent) => Some(OpponentPoint),
+        DuelWon(Player) => Some(PlayerWins),
+        DuelWon(Opponent) => Some(OpponentWins),
+        _ => None,
+    }
+}
+
+/// Picks flavor lines for the moments of a duel.
+pub struct Narrator {
+    lines: Vec<(Moment, String)>,
+    /// Chance of narrating a moment with lines, between 0 and 1.
+    chance: f64,
+    rng: Isaac64Rng,
+}
+
+impl Narrator {
+    /// A narrator with the default lines, the same for the same seed.
+    pub fn new(seed: u64) -> Narrator {
+        let mut res = Narrator::empty(seed);
+        for &(moment, line) in DEFAULT_LINES.iter() {
+            res = res.line(moment, line);
+        }
+        res
+    }
+
+    /// A narrator without lines.
+    pub fn empty(seed: u64) -> Narrator {
+        Narrator { lines: Vec::new(), chance: 0.5, rng: SeedableRng::from_seed([seed][]) }
+    }
+
+    /// Add `line` to the pool of `moment`.
+    pub fn line(mut self, moment: Moment, line: &str) -> Narrator {
+        self.lines.push((moment, line.to_string()));
+        self
+    }
+
+    /// Narrate a moment with the chance `chance`, one in two by default.
+    pub fn chance(mut self, chance: f64) -> Narrator {
+        self.chance = chance;
+        self
+    }
+
+    /// The lines of `moment`.
+    pub fn lines<'a>(&'a self, moment: Moment) -> Vec<&'a str> {
+        self.lines.iter().filter(|&&(m, _)| m == moment).map(|&(_, ref x)| x[]).collect()
+    }
+
+    /// A line following `event`, if the narrator says something.
+    pub fn narrate(&mut self, event: &DuelEvent) -> Option<String> {
+        let lines = match moment(event) {
+            Some(m) => self.lines(m).into_iter().map(|x| x.to_string()).collect::<Vec<String>>(),
+            None => return None,
+        };
+        if lines.is_empty() || self.rng.gen::<f64>() >= self.chance {
+            return None;
+        }
+        self.rng.choose(lines[]).map(|x| x.clone())
+    }
+}
+
+static DEFAULT_LINES: [(Moment, &'static str), ..8] = [
+    (Retorted, "Steel rings against steel."),
+    (Retorted, "The blow is parried with a flourish."),
+    (Missed, "An awkward silence falls over the deck."),
+    (Missed, "Someone in the crowd snickers."),
+    (PlayerPoint, "The pirate stumbles backwards, clutching his pride."),
+    (OpponentPoint, "Guybrush loses his footing and some of his dignity."),
+    (PlayerWins, "The pirate drops his sword and flees."),
+    (OpponentWins, "Guybrush limps away to practice some more."),
+];
+
+#[cfg(test)]
+mod tests {
+    use duel::{Duel, Rules, Player, RoundWon, DuelWon, Narrated};
+    use insults::Insults;
+    use super::*;
+
+    #[test]
+    fn test_narrator() {
+        let mut narrator = Narrator::empty(7).line(PlayerPoint, "Touché!").chance(1.0);
+        assert_eq!(narrator.narrate(&RoundWon(Player)), Some("Touché!".to_string()));
+        assert_eq!(narrator.narrate(&DuelWon(Player)), None);
+        assert_eq!(Narrator::new(7).lines(Missed).len(), 2);
+
+        let mi = Insults::new("insults.json");
+        let (tx, rx) = channel();
+        let mut duel = Duel::new(&mi, Rules::first_to(1)).with_narrator(Narrator::new(7).chance(1.0));
+        duel.observe(box tx);
+        duel.exchange("You fight like a dairy farmer.", "Oh yeah?");
+        drop(duel);
+        let events: Vec<_> = rx.iter().collect();
+        assert!(events.iter().zip(events.iter().skip(1)).any(|(a, b)| *a == RoundWon(Player) && match *b {
+            Narrated(ref x) => x[] == "The pirate stumbles backwards, clutching his pride.",
+            _ => false,
+        }));
+    }
+}
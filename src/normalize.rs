@@ -0,0 +1,293 @@
+//! Matching typed insults regardless of case, spacing and Unicode encoding.
+//!
+//! The insults are normalized once when the index is built. Input is
+//! normalized into a buffer on the stack, so lookups of ASCII input don't
+//! allocate for anything shorter than `INLINE` bytes. Other input has
+//! accents typed as combining characters composed first, like `nfc` does,
+//! and the composition allocates, like `get_nfc` of `Insults::retort`.
+//!
+//! For the locales in `ACCENT_FOLDING` accents are dropped as well, so
+//! `como` typed without them matches `cómo`. An index `with_stemming` also
+//! matches insults whose words have the same stems, see `stem`.
+
+use std::ascii::AsciiExt;
+use std::str;
+
+use insults::Insults;
+use matching::{mod, Index, MatchResult, Method};
+use stem::{StemmedIndex, Stemmer, stemmer_for};
+
+/// Normalized input up to this many bytes stays on the stack.
+pub const INLINE: uint = 256;
+
+/// Confidence in insults matched by their stems.
+const STEMMED_CONFIDENCE: f64 = 0.9;
+
+/// Locales whose players often type without accents, folded by default.
+pub static ACCENT_FOLDING: &'static [&'static str] = &["es", "fr", "pt", "it", "ca"];
+
+/// Whether `locale`, like `es` or `pt-BR`, folds accents by default.
+pub fn folds_accents(locale: &str) -> bool {
+    let language = locale.split(|c: char| c == '-' || c == '_').next().unwrap_or("");
+    ACCENT_FOLDING.iter().any(|x| x.eq_ignore_ascii_case(language))
+}
+
+/// Whether `c` is an accent combining with the letter before it.
+fn is_combining(c: char) -> bool {
+    c >= '\u0300' && c <= '\u036f'
+}
+
+/// Normalized text, on the stack when it fits.
+pub struct Normalized {
+    inline: [u8, ..INLINE],
+    len: uint,
+    heap: Option<String>,
+}
+
+impl Normalized {
+    /// Lower case `s` in NFC, with runs of whitespace collapsed to one space and trimmed.
+    pub fn new(s: &str) -> Normalized {
+        Normalized::build(s, false)
+    }
+
+    /// Like `new` but without accents, `Cómo` becomes `como`.
+    pub fn folded(s: &str) -> Normalized {
+        Normalized::build(s, true)
+    }
+
+    fn build(s: &str, fold: bool) -> Normalized {
+        let mut res = Normalized { inline: [0, ..INLINE], len: 0, heap: None };
+        let mut space = false;
+        let s = s.trim();
+        if s.bytes().all(|b| b < 0x80) {
+            // Already composed, without accents to fold
+            for c in s.chars() {
+                res.push_normalized(c, &mut space);
+            }
+        } else if fold {
+            for c in s.nfd_chars().filter(|&c| !is_combining(c)) {
+                res.push_normalized(c, &mut space);
+            }
+        } else {
+            for c in s.nfc_chars() {
+                res.push_normalized(c, &mut space);
+            }
+        }
+        res
+    }
+
+    /// Push `c` lower cased, with `space` tracking a pending run of whitespace.
+    fn push_normalized(&mut self, c: char, space: &mut bool) {
+        if c.is_whitespace() {
+            *space = true;
+            return;
+        }
+        if *space {
+            self.push(' ');
+            *space = false;
+        }
+        self.push(c.to_lowercase());
+    }
+
+    fn push(&mut self, c: char) {
+        if let Some(ref mut heap) = self.heap {
+            heap.push(c);
+            return;
+        }
+        let mut buf = [0u8, ..4];
+        let n = c.encode_utf8(buf).unwrap_or(0);
+        if self.len + n <= INLINE {
+            for i in range(0, n) {
+                self.inline[self.len + i] = buf[i];
+            }
+            self.len += n;
+        } else {
+            let mut heap = self.as_str().to_string();
+            heap.push(c);
+            self.heap = Some(heap);
+        }
+    }
+
+    pub fn as_str<'a>(&'a self) -> &'a str {
+        match self.heap {
+            Some(ref x) => x[],
+            // Only whole characters are ever written
+            None => unsafe { str::raw::from_utf8(self.inline[..self.len]) },
+        }
+    }
+}
+
+/// `s` in Unicode normalization form C, with accents composed onto their letters.
+pub fn nfc(s: &str) -> String {
+    s.nfc_chars().collect()
+}
+
+/// `s` normalized, see `Normalized::new`.
+pub fn normalize(s: &str) -> String {
+    Normalized::new(s).as_str().to_string()
+}
+
+/// `s` normalized without accents, see `Normalized::folded`.
+pub fn fold(s: &str) -> String {
+    Normalized::folded(s).as_str().to_string()
+}
+
+/// Insults indexed by their normalized text.
+pub struct NormalizedIndex<'a> {
+    insults: &'a Insults,
+    /// Whether accents are folded, in the keys and lookups alike.
+    fold: bool,
+    /// Normalized insults with the insult they came from, sorted.
+    keys: Vec<(String, &'a str)>,
+    /// Tried when the normalized insult matches nothing.
+    stems: Option<StemmedIndex<'a>>,
+}
+
+impl<'a> NormalizedIndex<'a> {
+    /// Index the insults of `insults` and its fallbacks.
+    ///
+    /// Accents are folded if the locale of the dataset is in `ACCENT_FOLDING`.
+    pub fn new(insults: &'a Insults) -> NormalizedIndex<'a> {
+        NormalizedIndex::with_folding(insults, folds_accents(insults.locale()))
+    }
+
+    /// Index the insults, folding accents or not whatever the locale.
+    pub fn with_folding(insults: &'a Insults, fold: bool) -> NormalizedIndex<'a> {
+        let key = if fold { fold_key } else { normalize_key };
+        let mut keys = Vec::new();
+        let mut current = Some(insults);
+        loop {
+            match current {
+                Some(x) => {
+                    keys.extend(x.insults().into_iter().map(|s| (key(s[]), s[])));
+                    current = x.fallback();
+                },
+                None => break,
+            }
+        }
+        keys.sort();
+
+        // Insults only differing in case and spacing are the same insult
+        let mut unique: Vec<(String, &'a str)> = Vec::new();
+        for (key, insult) in keys.into_iter() {
+            if unique.last().map_or(true, |&(ref last, _)| *last != key) {
+                unique.push((key, insult));
+            }
+        }
+        NormalizedIndex { insults: insults, fold: fold, keys: unique, stems: None }
+    }
+
+    /// Also match by stems, with the stemmer of the locale if it has one.
+    pub fn with_stemming(self) -> NormalizedIndex<'a> {
+        match stemmer_for(self.insults.locale()) {
+            Some(x) => self.with_stemmer(x),
+            None => self,
+        }
+    }
+
+    /// Also match by the stems of `stemmer`.
+    pub fn with_stemmer(mut self, stemmer: Box<Stemmer + Send>) -> NormalizedIndex<'a> {
+        self.stems = Some(StemmedIndex::new(self.insults, stemmer));
+        self
+    }
+
+    pub fn folds_accents(&self) -> bool {
+        self.fold
+    }
+
+    /// The insult `input` was typed as.
+    pub fn insult(&self, input: &str) -> Option<&'a str> {
+        self.find(input).map(|(insult, _)| insult)
+    }
+
+    /// The insult `input` was typed as and how it matched.
+    fn find(&self, input: &str) -> Option<(&'a str, Method)> {
+        let typed = input;
+        let input = if self.fold { Normalized::folded(input) } else { Normalized::new(input) };
+        let input = input.as_str();
+        let (mut lo, mut hi) = (0, self.keys.len());
+        while lo < hi {
+            let mid = (lo + hi) / 2;
+            let (ref key, insult) = self.keys[mid];
+            match key[].cmp(input) {
+                Less => lo = mid + 1,
+                Greater => hi = mid,
+                Equal => {
+                    count!(record_fuzzy(true));
+                    let method = if insult == typed { matching::Exact } else { matching::Normalized };
+                    return Some((insult, method));
+                },
+            }
+        }
+        let stemmed = self.stems.as_ref().and_then(|x| x.insult(input));
+        count!(record_fuzzy(stemmed.is_some()));
+        if stemmed.is_none() {
+            trace_event!("normalized {} matches no insult", input);
+        }
+        stemmed.map(|x| (x, matching::Stemmed))
+    }
+
+    /// The retort to the insult `input` was typed as.
+    pub fn retort(&self, input: &str) -> Option<MatchResult<'a>> {
+        self.find(input).and_then(|(insult, method)| {
+            let confidence = if method == matching::Stemmed { STEMMED_CONFIDENCE } else { 1.0 };
+            MatchResult::new(self.insults, insult, method, confidence)
+        })
+    }
+}
+
+impl<'a> Index<'a> for NormalizedIndex<'a> {
+    fn lookup(&self, input: &str) -> Option<MatchResult<'a>> {
+        self.retort(input)
+    }
+}
+
+fn normalize_key(s: &str) -> String {
+    normalize(s)
+}
+
+fn fold_key(s: &str) -> String {
+    fold(s)
+}
+
+impl Insults {
+    /// An index matching insults regardless of case and spacing.
+    pub fn normalized_index<'a>(&'a self) -> NormalizedIndex<'a> {
+        NormalizedIndex::new(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use insults::Insults;
+    use super::*;
+
+    #[test]
+    fn test_normalized_index() {
+        assert_eq!(Normalized::new("  You  FIGHT\tlike ").as_str(), "you fight like");
+        assert_eq!(Normalized::folded("  You  FIGHT\tlike ").as_str(), "you fight like");
+        let long = String::from_char(INLINE + 10, 'A');
+        assert_eq!(normalize(long[]), String::from_char(INLINE + 10, 'a'));
+
+        let mi = Insults::new("insults.json");
+        let index = mi.normalized_index();
+        assert_eq!(index.insult("you fight like a DAIRY  farmer."), Some("You fight like a dairy farmer."));
+        assert_eq!(index.retort(" You fight like a dairy farmer. ").map(|x| x.retort),
+            Some("How appropriate. You fight like a cow."));
+        assert!(index.retort("You fight like a cow.").is_none());
+
+        assert_eq!(nfc("Cafe\u0301"), "Caf\u00e9".to_string());
+        let decomposed = Insults::from_str(fixture(r#"{
+            "monkey_island1": {"Qu\u0065\u0301 dices?": {"retort": "Nada."}}}"#)[]);
+        assert_eq!(decomposed.retort("Qu\u00e9 dices?"), Some("Nada."));
+        assert_eq!(decomposed.retort("Que\u0301 dices?"), Some("Nada."));
+        assert_eq!(decomposed.normalized_index().insult("QUE\u0301 DICES?"), Some("Qu\u00e9 dices?"));
+
+        assert_eq!(fold("  C\u00f3mo   te atreves? "), "como te atreves?".to_string());
+        assert!(folds_accents("es") && folds_accents("pt-BR") && !folds_accents("en"));
+        assert_eq!(decomposed.normalized_index().insult("Que dices?"), None);
+        let folding = NormalizedIndex::with_folding(&decomposed, true);
+        assert_eq!(folding.insult("que dices?"), Some("Qu\u00e9 dices?"));
+        assert_eq!(folding.insult("Qu\u00e9 dices?"), Some("Qu\u00e9 dices?"));
+    }
+}
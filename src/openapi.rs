@@ -0,0 +1,279 @@
+//! OpenAPI description of the `serve` API, for generating client SDKs.
+//!
+//! The document is built from `endpoints`, which lists every route
+//! `Server::handle` answers and the endpoints of enabled features. It's
+//! served at `GET /openapi.json` and printed by `mi-insults serve --openapi`.
+
+use std::collections::TreeMap;
+use serialize::json::{mod, Json, ToJson};
+
+/// A parameter of an endpoint.
+#[deriving(Clone, PartialEq, Show)]
+pub struct Param {
+    pub name: &'static str,
+    /// `query` or `path`.
+    pub location: &'static str,
+    pub description: &'static str,
+}
+
+/// A route of the API.
+#[deriving(Clone, PartialEq, Show)]
+pub struct Endpoint {
+    pub method: &'static str,
+    /// The path with `{param}` placeholders.
+    pub path: &'static str,
+    pub operation_id: &'static str,
+    pub summary: &'static str,
+    pub params: Vec<Param>,
+    /// Status codes with a description and the schema of the body, if any.
+    pub responses: Vec<(u16, &'static str, Option<&'static str>)>,
+}
+
+fn query(name: &'static str, description: &'static str) -> Param {
+    Param { name: name, location: "query", description: description }
+}
+
+/// The endpoints of the API, with those of enabled features.
+pub fn endpoints() -> Vec<Endpoint> {
+    let mut res = vec![
+        Endpoint {
+            method: "get",
+            path: "/retort",
+            operation_id: "retort",
+            summary: "The retort to an insult",
+            params: vec![query("insult", "The insult, exactly as in the dataset")],
+            responses: vec![(200, "The insult and its retort", Some("Pair")),
+                            (400, "No insult given", Some("Error")),
+                            (404, "No retort to the insult", Some("Error"))],
+        },
+        Endpoint {
+            method: "get",
+            path: "/random",
+            operation_id: "random",
+            summary: "A random insult and its retort",
+            params: Vec::new(),
+            responses: vec![(200, "The insult and its retort", Some("Pair"))],
+        },
+        Endpoint {
+            method: "get",
+            path: "/games/{game}/insults",
+            operation_id: "gameInsults",
+            summary: "The insults of a game",
+            params: vec![Param { name: "game", location: "path", description: "Game key like monkey_island1" },
+                         query("offset", "Insults to skip, 0 by default"),
+                         query("limit", "Most insults to return, all by default")],
+            responses: vec![(200, "The insults, ordered, with how many there are", Some("InsultList")),
+                            (400, "offset or limit isn't a number", Some("Error")),
+                            (404, "Unknown game", Some("Error"))],
+        },
+        Endpoint {
+            method: "get",
+            path: "/leaderboard",
+            operation_id: "leaderboard",
+            summary: "The best players",
+            params: vec![query("top", "How many players, 10 by default")],
+            responses: vec![(200, "The standings, best first", Some("Standings")),
+                            (400, "top isn't a number", Some("Error"))],
+        },
+        Endpoint {
+            method: "get",
+            path: "/leaderboard/{player}",
+            operation_id: "rank",
+            summary: "The rank and standing of a player",
+            params: vec![Param { name: "player", location: "path", description: "The player" }],
+            responses: vec![(200, "The standing with the rank", Some("Standing")),
+                            (404, "The player hasn't dueled", Some("Error"))],
+        },
+        Endpoint {
+            method: "get",
+            path: "/profiles/{id}/knowledge",
+            operation_id: "knowledge",
+            summary: "The insults a player has learned",
+            params: vec![Param { name: "id", location: "path", description: "The profile" }],
+            responses: vec![(200, "The learned insults", Some("Knowledge")),
+                            (404, "Unknown profile", Some("Error"))],
+        },
+        Endpoint {
+            method: "put",
+            path: "/profiles/{id}/knowledge",
+            operation_id: "setKnowledge",
+            summary: "Replace the insults a player has learned with a Knowledge body",
+            params: vec![Param { name: "id", location: "path", description: "The profile" }],
+            responses: vec![(200, "The learned insults", Some("Knowledge")),
+                            (400, "The body isn't a Knowledge", Some("Error")),
+                            (422, "An insult isn't in the dataset", Some("Error"))],
+        },
+        Endpoint {
+            method: "get",
+            path: "/openapi.json",
+            operation_id: "openapi",
+            summary: "This document",
+            params: Vec::new(),
+            responses: vec![(200, "The OpenAPI document", None)],
+        },
+    ];
+    if cfg!(feature = "graphql") {
+        res.push(Endpoint {
+            method: "get",
+            path: "/graphql",
+            operation_id: "graphql",
+            summary: "Run a GraphQL query, see graphql::SDL for the schema",
+            params: vec![query("query", "The GraphQL query")],
+            responses: vec![(200, "The data or the errors of the query", Some("GraphQLResponse")),
+                            (400, "No query given", Some("Error"))],
+        });
+    }
+    if cfg!(feature = "websocket") {
+        res.push(Endpoint {
+            method: "get",
+            path: "/duel",
+            operation_id: "duel",
+            summary: "Upgrade to a WebSocket playing a duel, see the websocket module for the messages",
+            params: Vec::new(),
+            responses: vec![(101, "Switching to the WebSocket protocol", None),
+                            (400, "Missing Sec-WebSocket-Key", Some("Error"))],
+        });
+    }
+    res
+}
+
+fn object(fields: Vec<(&str, Json)>) -> Json {
+    let mut res = TreeMap::new();
+    for (k, v) in fields.into_iter() {
+        res.insert(k.to_string(), v);
+    }
+    json::Object(res)
+}
+
+fn schema_ref(name: &str) -> Json {
+    object(vec![("$ref", format!("#/components/schemas/{}", name).to_json())])
+}
+
+fn string_schema() -> Json {
+    object(vec![("type", "string".to_json())])
+}
+
+fn integer_schema() -> Json {
+    object(vec![("type", "integer".to_json())])
+}
+
+/// A leaderboard standing, with the rank or without.
+fn standing_schema(rank: bool) -> Json {
+    let mut fields = vec![("player", string_schema()), ("wins", integer_schema()),
+                          ("losses", integer_schema()), ("streak", integer_schema()),
+                          ("best_streak", integer_schema())];
+    if rank {
+        fields.push(("rank", integer_schema()));
+    }
+    object_schema(fields)
+}
+
+fn object_schema(properties: Vec<(&str, Json)>) -> Json {
+    let required: Vec<Json> = properties.iter().map(|&(k, _)| k.to_json()).collect();
+    object(vec![
+        ("type", "object".to_json()),
+        ("required", json::List(required)),
+        ("properties", object(properties)),
+    ])
+}
+
+fn schemas() -> Json {
+    let list = object(vec![("type", "array".to_json()), ("items", string_schema())]);
+    let free_object = object(vec![("type", "object".to_json())]);
+    let errors = object(vec![
+        ("type", "array".to_json()),
+        ("items", object_schema(vec![("message", string_schema())])),
+    ]);
+    object(vec![
+        ("Pair", object_schema(vec![("insult", string_schema()), ("retort", string_schema())])),
+        ("InsultList", object_schema(vec![("insults", list.clone()), ("total", integer_schema())])),
+        ("Knowledge", object_schema(vec![("learned", list)])),
+        ("Error", object_schema(vec![("error", string_schema())])),
+        ("Standings", object_schema(vec![("standings", object(vec![
+            ("type", "array".to_json()),
+            ("items", standing_schema(false)),
+        ]))])),
+        ("Standing", standing_schema(true)),
+        ("GraphQLResponse", object(vec![
+            ("type", "object".to_json()),
+            ("properties", object(vec![("data", free_object), ("errors", errors)])),
+        ])),
+    ])
+}
+
+fn operation(endpoint: &Endpoint) -> Json {
+    let params: Vec<Json> = endpoint.params.iter().map(|p| object(vec![
+        ("name", p.name.to_json()),
+        ("in", p.location.to_json()),
+        ("description", p.description.to_json()),
+        ("required", true.to_json()),
+        ("schema", string_schema()),
+    ])).collect();
+
+    let mut responses = TreeMap::new();
+    for &(status, description, schema) in endpoint.responses.iter() {
+        let mut response = vec![("description", description.to_json())];
+        if let Some(schema) = schema {
+            response.push(("content", object(vec![
+                ("application/json", object(vec![("schema", schema_ref(schema))])),
+            ])));
+        }
+        responses.insert(status.to_string(), object(response));
+    }
+
+    object(vec![
+        ("operationId", endpoint.operation_id.to_json()),
+        ("summary", endpoint.summary.to_json()),
+        ("parameters", json::List(params)),
+        ("responses", json::Object(responses)),
+    ])
+}
+
+/// The OpenAPI 3.0 document of the API.
+pub fn openapi() -> Json {
+    let mut paths: TreeMap<String, TreeMap<String, Json>> = TreeMap::new();
+    for endpoint in endpoints().iter() {
+        if !paths.contains_key(&endpoint.path.to_string()) {
+            paths.insert(endpoint.path.to_string(), TreeMap::new());
+        }
+        paths.get_mut(&endpoint.path.to_string()).unwrap()
+            .insert(endpoint.method.to_string(), operation(endpoint));
+    }
+    let paths = paths.into_iter().map(|(k, v)| (k, json::Object(v))).collect();
+
+    object(vec![
+        ("openapi", "3.0.3".to_json()),
+        ("info", object(vec![
+            ("title", "mi_insults".to_json()),
+            ("version", env!("CARGO_PKG_VERSION").to_json()),
+            ("description", "Insult sword fighting from the Monkey Island games".to_json()),
+        ])),
+        ("paths", json::Object(paths)),
+        ("components", object(vec![("schemas", schemas())])),
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use insults::Insults;
+    use profile::Knowledge;
+    use serve::Server;
+    use super::*;
+
+    #[test]
+    fn test_openapi() {
+        let doc = openapi();
+        let path = |p: &str| doc.find_path(&[&"paths".to_string(), &p.to_string()]).is_some();
+        assert!(path("/retort") && path("/random") && path("/games/{game}/insults"));
+
+        // Every documented path is answered
+        let server = Server::new(Insults::new("insults.json").into_shared());
+        server.leaderboard().lock().record("guybrush", true).unwrap();
+        server.profiles().lock().set("guybrush", Knowledge::new());
+        for endpoint in endpoints().iter() {
+            let path = endpoint.path.replace("{game}", "monkey_island1").replace("{player}", "guybrush")
+                .replace("{id}", "guybrush");
+            assert!(server.handle("GET", path[], "").status != 404 || endpoint.path == "/duel");
+        }
+    }
+}
@@ -0,0 +1,151 @@
+//! Small patch files adding or overriding entries of a dataset.
+//!
+//! An overlay has the layout of `insults.json` with only the games and
+//! insults it changes:
+//!
+//! ```json
+//! {
+//!     "monkey_island1": {
+//!         "You fight like a dairy farmer.": {"retort": "Moo."}
+//!     }
+//! }
+//! ```
+//!
+//! Overlays are applied in order, an entry of a later one overrides the
+//! same insult of an earlier one. Failed retorts and other metadata of an
+//! overlay are ignored.
+
+use std::collections::TreeMap;
+use serialize::json;
+
+use game::Game;
+use insults::{Insults, try_read_bytes, not_utf8};
+use merge::{MergeReport, Overwrite};
+use migrations;
+use schema::{LoadError, Io, Syntax, Schema};
+
+/// Entries to add to or override in a dataset.
+#[deriving(Clone, PartialEq, Show)]
+pub struct Overlay {
+    entries: Insults,
+}
+
+impl Overlay {
+    /// Load an overlay from JSON, panicking on errors.
+    pub fn from_str(s: &str) -> Overlay {
+        match Overlay::try_from_str(s) {
+            Ok(x) => x,
+            Err(e) => panic!("{}", e)
+        }
+    }
+
+    /// Load an overlay from a JSON file, panicking on errors.
+    pub fn from_path(location: &str) -> Overlay {
+        match Overlay::try_from_path(location) {
+            Ok(x) => x,
+            Err(e) => panic!("{}: {}", location, e)
+        }
+    }
+
+    /// Load an overlay from a JSON file, like `from_path` but returning errors.
+    pub fn try_from_path(location: &str) -> Result<Overlay, LoadError> {
+        let data = match try_read_bytes(location) {
+            Ok(x) => x,
+            Err(e) => return Err(Io(e))
+        };
+        match String::from_utf8(data) {
+            Ok(s) => Overlay::try_from_str(s[]),
+            Err(_) => Err(Io(not_utf8(location)))
+        }
+    }
+
+    /// Load an overlay from JSON, like `from_str` but returning errors.
+    ///
+    /// The entries are validated like those of a dataset.
+    pub fn try_from_str(s: &str) -> Result<Overlay, LoadError> {
+        let mut sections = match json::from_str(s) {
+            Ok(json::Object(x)) => x,
+            Ok(_) => return Err(Schema(Vec::new(), "the overlay is not an object".to_string())),
+            Err(e) => return Err(Syntax(e))
+        };
+        // The sections a dataset needs and the overlay leaves out
+        if !sections.contains_key(&"version".to_string()) {
+            sections.insert("version".to_string(), json::U64(migrations::CURRENT_VERSION));
+        }
+        sections.insert("failed_retorts".to_string(), json::List(Vec::new()));
+        for game in Game::all().iter() {
+            if !sections.contains_key(&game.key().to_string()) {
+                sections.insert(game.key().to_string(), json::Object(TreeMap::new()));
+            }
+        }
+        let entries = try!(Insults::try_from_json(json::Object(sections)));
+        Ok(Overlay { entries: entries })
+    }
+
+    /// The entries of the overlay, as a dataset without failed retorts.
+    pub fn entries<'a>(&'a self) -> &'a Insults {
+        &self.entries
+    }
+}
+
+impl Insults {
+    /// Add the entries of `overlay`, replacing those of the same insults.
+    ///
+    /// The report lists the entries it replaced.
+    pub fn apply(&mut self, overlay: &Overlay) -> MergeReport {
+        match self.merge(&overlay.entries, Overwrite) {
+            Ok(x) => x,
+            Err(x) => x,
+        }
+    }
+
+    /// Load the dataset at `location` with the overlays at `overlays` applied
+    /// in order, like `try_with_overlays` but panicking on errors.
+    pub fn with_overlays(location: &str, overlays: &[&str]) -> Insults {
+        match Insults::try_with_overlays(location, overlays) {
+            Ok(x) => x,
+            Err(e) => panic!("{}", e)
+        }
+    }
+
+    /// Load the dataset at `location` with the overlays at `overlays` applied in order.
+    pub fn try_with_overlays(location: &str, overlays: &[&str]) -> Result<Insults, LoadError> {
+        let mut res = try!(Insults::try_from_path(location));
+        for path in overlays.iter() {
+            let overlay = try!(Overlay::try_from_path(*path));
+            res.apply(&overlay);
+        }
+        Ok(res)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{File, TempDir};
+
+    use insults::Insults;
+    use super::*;
+
+    #[test]
+    fn test_overlay() {
+        let overlay = Overlay::from_str(r#"{"monkey_island1": {
+            "You fight like a dairy farmer.": {"retort": "Moo."},
+            "Your mother was a hamster!": {"retort": "And your father smelt of elderberries!"}
+        }}"#);
+        let mut mi = Insults::new("insults.json");
+        let report = mi.apply(&overlay);
+        assert_eq!((report.added, report.conflicts.len()), (1, 1));
+        assert_eq!(mi.retort("You fight like a dairy farmer."), Some("Moo."));
+        assert!(mi.is_insult("Your mother was a hamster!"));
+        assert!(Overlay::try_from_str(r#"{"monkey_island1": {"Arr!": {}}}"#).is_err());
+
+        let tmp = TempDir::new("mi_insults").unwrap();
+        let first = tmp.path().join("first.json");
+        let second = tmp.path().join("second.json");
+        File::create(&first).write_str(r#"{"sword_master": {"Arr!": {"retort": "Arr."}}}"#).unwrap();
+        File::create(&second).write_str(r#"{"sword_master": {"Arr!": {"retort": "Arr!!"}}}"#).unwrap();
+        let patched = Insults::with_overlays("insults.json",
+                                             [first.as_str().unwrap(), second.as_str().unwrap()][]);
+        assert_eq!(patched.sword_master_retort("Arr!"), Some("Arr!!"));
+    }
+}
@@ -0,0 +1,188 @@
+//! Community insult packs.
+//!
+//! A pack is a directory with a `pack.json` manifest next to its insults:
+//!
+//! ```text
+//! my-theme/
+//!     pack.json
+//!     insults.sv.json
+//!     Makefile
+//! ```
+
+use std::io::{File, IoResult};
+use std::io::fs;
+use std::io;
+use serialize::json;
+
+use insults::Insults;
+use migrations::CURRENT_VERSION;
+use schema::{LoadError, Io, Schema};
+use sha256;
+
+/// Describes a pack and where its data is.
+#[deriving(Clone, PartialEq, Show, Encodable, Decodable)]
+pub struct PackManifest {
+    pub name: String,
+    pub version: String,
+    pub locale: String,
+    /// The insults, relative to the manifest.
+    pub data: String,
+    /// SHA-256 of the insults in hex, checked when the pack is loaded.
+    pub sha256: Option<String>,
+}
+
+impl PackManifest {
+    pub fn new(name: &str, locale: &str) -> PackManifest {
+        PackManifest {
+            name: name.to_string(),
+            version: "0.1.0".to_string(),
+            locale: locale.to_string(),
+            data: data_file(locale),
+            sha256: None,
+        }
+    }
+
+    /// Read the manifest `pack.json` in the pack `root`.
+    pub fn open(root: &Path) -> Result<PackManifest, LoadError> {
+        let data = match File::open(&root.join("pack.json")).read_to_string() {
+            Ok(x) => x,
+            Err(e) => return Err(Io(e)),
+        };
+        match json::decode(data[]) {
+            Ok(x) => Ok(x),
+            Err(e) => Err(Schema(vec!["pack.json".to_string()], e.to_string())),
+        }
+    }
+
+    /// Set `sha256` to the digest of the insults in the pack `root`.
+    pub fn update_checksum(&mut self, root: &Path) -> IoResult<()> {
+        let data = try!(File::open(&root.join(self.data[])).read_to_end());
+        self.sha256 = Some(sha256::hex_digest(data[]));
+        Ok(())
+    }
+
+    /// Load the insults of the pack `root`, verified against `sha256` if set.
+    pub fn load(&self, root: &Path) -> Result<Insults, LoadError> {
+        let path = root.join(self.data[]);
+        let location = path.display().to_string();
+        match self.sha256 {
+            Some(ref x) => Insults::from_path_verified(location[], x[]),
+            None => Insults::try_from_path(location[]),
+        }
+    }
+}
+
+/// Load the pack `root`, verifying its insults if the manifest has a checksum.
+pub fn load(root: &Path) -> Result<Insults, LoadError> {
+    try!(PackManifest::open(root)).load(root)
+}
+
+/// File name of the insults for `locale`, following `Insults::with_locale`.
+fn data_file(locale: &str) -> String {
+    if locale == "en" {
+        "insults.json".to_string()
+    } else {
+        format!("insults.{}.json", locale)
+    }
+}
+
+/// Create a skeleton pack `name` in `dir`, returning the path to the new pack.
+///
+/// The pack comes with one example insult to replace and a Makefile
+/// with a `check` target linting the pack.
+pub fn scaffold(dir: &Path, name: &str, locale: &str) -> IoResult<Path> {
+    let root = dir.join(name);
+    try!(fs::mkdir_recursive(&root, io::USER_RWX));
+
+    let manifest = PackManifest::new(name, locale);
+    try!(File::create(&root.join("pack.json")).write_str(json::encode(&manifest)[]));
+    try!(File::create(&root.join(manifest.data[])).write_str(template(locale)[]));
+    try!(File::create(&root.join("Makefile")).write_str(
+        format!("check:\n\tmi-insults lint {}\n", manifest.data)[]));
+
+    Ok(root)
+}
+
+fn template(locale: &str) -> String {
+    let locale = if locale == "en" {
+        String::new()
+    } else {
+        format!(",\n\n    \"locale\": {}", json::encode(&locale.to_string()))
+    };
+
+    format!(r#"{{
+    "version": {},
+
+    "failed_retorts": [
+        "Oh yeah?"
+    ],
+
+    "monkey_island1": {{
+        "Replace me with an insult.": {{
+            "retort": "Replace me with its retort."
+        }}
+    }},
+
+    "sword_master": {{}},
+
+    "monkey_island3": {{}},
+
+    "captain_rottingham": {{}},
+
+    "monkey_island4": {{}}{}
+}}
+"#, CURRENT_VERSION, locale)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{File, TempDir};
+    use serialize::json;
+
+    use insults::Insults;
+    use lint;
+    use migrations::{mod, CURRENT_VERSION};
+    use schema::Checksum;
+    use super::*;
+
+    #[test]
+    fn test_scaffold() {
+        let tmp = TempDir::new("mi_insults").unwrap();
+        let root = scaffold(tmp.path(), "my-theme", "sv").unwrap();
+
+        let manifest: PackManifest = json::decode(
+            File::open(&root.join("pack.json")).read_to_string().unwrap()[]).unwrap();
+        assert_eq!(manifest, PackManifest::new("my-theme", "sv"));
+
+        let data = File::open(&root.join(manifest.data[])).read_to_string().unwrap();
+        let parsed = json::from_str(data[]).unwrap();
+        assert_eq!(lint::lint(&parsed, &lint::Config::default()).len(), 0);
+        assert_eq!(migrations::version(&parsed), Some(CURRENT_VERSION));
+        assert!(data[].contains(r#""retort": "Replace me with its retort.""#));
+
+        let mi = Insults::from_str(data[]);
+        assert_eq!(mi.locale(), "sv");
+        assert_eq!(mi.retort("Replace me with an insult."), Some("Replace me with its retort."));
+    }
+
+    #[test]
+    fn test_verify() {
+        let tmp = TempDir::new("mi_insults").unwrap();
+        let root = scaffold(tmp.path(), "my-theme", "en").unwrap();
+        let mut manifest = PackManifest::open(&root).unwrap();
+        assert!(load(&root).is_ok());
+
+        manifest.update_checksum(&root).unwrap();
+        File::create(&root.join("pack.json")).write_str(json::encode(&manifest)[]).unwrap();
+        assert!(load(&root).is_ok());
+
+        // Truncate the insults
+        let data = root.join(manifest.data[]);
+        let contents = File::open(&data).read_to_string().unwrap();
+        File::create(&data).write_str(contents[..10]).unwrap();
+        match load(&root) {
+            Err(Checksum(ref expected, _)) => assert_eq!(Some(expected), manifest.sha256.as_ref()),
+            _ => panic!("expected a checksum error"),
+        }
+    }
+}
@@ -0,0 +1,158 @@
+//! Matching insults from a fragment, like "dairy farmer".
+//!
+//! The words of every insult go in an inverted index. Candidates share
+//! words with the query, rare words counting more than "you" and "a", and
+//! rank higher when the query's words follow each other in the insult.
+
+use std::cmp;
+use std::collections::HashMap;
+use std::iter::AdditiveIterator;
+
+use insults::Insults;
+use normalize::{normalize, fold, folds_accents};
+
+/// Share of the score for the query's words being in order.
+const ORDER_WEIGHT: f64 = 0.25;
+
+/// An insult sharing words with a query.
+#[deriving(Clone, PartialEq, Show)]
+pub struct PartialMatch<'a> {
+    pub insult: &'a str,
+    /// From 0 to 1, 1 when the insult has all the words of the query in order.
+    pub score: f64,
+}
+
+/// The normalized words of `text`, without punctuation.
+fn tokens(text: &str, fold_accents: bool) -> Vec<String> {
+    let normalized = if fold_accents { fold(text) } else { normalize(text) };
+    normalized[].words()
+        .map(|w| w.chars().filter(|c| c.is_alphanumeric() || *c == '\'').collect::<String>())
+        .filter(|w| !w.is_empty())
+        .collect()
+}
+
+/// Insults indexed by their words.
+pub struct PartialIndex<'a> {
+    fold: bool,
+    /// Insults with their words, ordered by insult.
+    insults: Vec<(&'a str, Vec<String>)>,
+    /// Indices of the insults having each word.
+    postings: HashMap<String, Vec<uint>>,
+}
+
+impl<'a> PartialIndex<'a> {
+    /// Index the insults of `insults` and its fallbacks.
+    pub fn new(insults: &'a Insults) -> PartialIndex<'a> {
+        let fold = folds_accents(insults.locale());
+        let mut all: Vec<&'a str> = Vec::new();
+        let mut current = Some(insults);
+        loop {
+            match current {
+                Some(x) => {
+                    all.extend(x.insults().into_iter().map(|s| s[]));
+                    current = x.fallback();
+                },
+                None => break,
+            }
+        }
+        all.sort();
+        all.dedup();
+
+        let mut postings: HashMap<String, Vec<uint>> = HashMap::new();
+        let insults: Vec<(&'a str, Vec<String>)> = all.into_iter().map(|s| (s, tokens(s, fold))).collect();
+        for (i, &(_, ref words)) in insults.iter().enumerate() {
+            for word in words.iter() {
+                if !postings.contains_key(word) {
+                    postings.insert(word.clone(), Vec::new());
+                }
+                let ids = postings.get_mut(word).unwrap();
+                if ids.last() != Some(&i) {
+                    ids.push(i);
+                }
+            }
+        }
+        PartialIndex { fold: fold, insults: insults, postings: postings }
+    }
+
+    /// How rare `word` is, 1 for words of every insult and more the rarer it is.
+    fn weight(&self, word: &str) -> f64 {
+        match self.postings.get(word) {
+            Some(ids) => (self.insults.len() as f64 / ids.len() as f64).ln() + 1.0,
+            None => 1.0,
+        }
+    }
+
+    /// Insults with words of `query`, best first.
+    pub fn matches(&self, query: &str) -> Vec<PartialMatch<'a>> {
+        let query = tokens(query, self.fold);
+        let total: f64 = query.iter().map(|w| self.weight(w[])).sum();
+
+        let mut candidates: Vec<uint> = Vec::new();
+        for word in query.iter() {
+            if let Some(ids) = self.postings.get(word) {
+                candidates.push_all(ids[]);
+            }
+        }
+        candidates.sort();
+        candidates.dedup();
+
+        let mut res: Vec<PartialMatch<'a>> = candidates.into_iter().map(|i| {
+            let (insult, ref words) = self.insults[i];
+            let shared: f64 = query.iter().filter(|w| words.contains(*w)).map(|w| self.weight(w[])).sum();
+            let order = longest_run(query[], words[]) as f64 / query.len() as f64;
+            PartialMatch { insult: insult, score: (1.0 - ORDER_WEIGHT) * shared / total + ORDER_WEIGHT * order }
+        }).collect();
+        // Stable, so equal scores stay ordered by insult
+        res.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap_or(Equal));
+        res
+    }
+}
+
+/// Most words of `query` following each other in `words` in the same order.
+fn longest_run(query: &[String], words: &[String]) -> uint {
+    let mut best = 0;
+    for i in range(0, query.len()) {
+        for j in range(0, words.len()) {
+            let mut n = 0;
+            while i + n < query.len() && j + n < words.len() && query[i + n] == words[j + n] {
+                n += 1;
+            }
+            best = cmp::max(best, n);
+        }
+    }
+    best
+}
+
+impl Insults {
+    /// An index matching insults from fragments.
+    pub fn partial_index<'a>(&'a self) -> PartialIndex<'a> {
+        PartialIndex::new(self)
+    }
+
+    /// Insults sharing words with `query`, best first.
+    ///
+    /// Indexes the insults on every call, keep the one from `partial_index`
+    /// to match many.
+    pub fn match_partial<'a>(&'a self, query: &str) -> Vec<PartialMatch<'a>> {
+        self.partial_index().matches(query)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use insults::Insults;
+
+    #[test]
+    fn test_match_partial() {
+        let mi = Insults::new("insults.json");
+        let matches = mi.match_partial("dairy farmer");
+        assert_eq!(matches[0].insult, "You fight like a dairy farmer.");
+        assert!((matches[0].score - 1.0).abs() < 1e-9);
+        assert!(matches.iter().skip(1).all(|x| x.score < matches[0].score));
+
+        let swapped = mi.match_partial("farmer dairy");
+        assert_eq!(swapped[0].insult, "You fight like a dairy farmer.");
+        assert!(swapped[0].score < matches[0].score);
+        assert!(mi.match_partial("xyzzy").is_empty());
+    }
+}
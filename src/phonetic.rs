@@ -0,0 +1,382 @@
+//! Matching insults by how they sound, for voice input.
+//!
+//! Speech to text gets words wrong in ways that sound alike, "dary farmer"
+//! for "dairy farmer". Every word is encoded with Double Metaphone and a
+//! transcript matches the insult whose codes it shares the most, in order.
+
+use std::cmp;
+
+use insults::Insults;
+use matching::{Index, MatchResult, Phonetic};
+use normalize::fold;
+
+/// Longest code of a word.
+const MAX_CODE: uint = 4;
+/// Share of the words that have to sound alike for a match.
+pub const MIN_SCORE: f64 = 0.75;
+
+/// Builds the primary and alternate code of a word.
+struct Coder {
+    word: Vec<char>,
+    primary: String,
+    alternate: String,
+}
+
+impl Coder {
+    fn at(&self, pos: uint) -> char {
+        if pos < self.word.len() { self.word[pos] } else { '\0' }
+    }
+
+    /// Whether the letters from `pos` are one of `options`.
+    fn is_at(&self, pos: uint, options: &[&str]) -> bool {
+        options.iter().any(|x| x.chars().enumerate().all(|(i, c)| self.at(pos + i) == c))
+    }
+
+    fn is_vowel(&self, pos: uint) -> bool {
+        "AEIOUY".contains_char(self.at(pos))
+    }
+
+    fn add(&mut self, primary: &str, alternate: &str) {
+        self.primary.push_str(primary);
+        self.alternate.push_str(alternate);
+    }
+
+    fn both(&mut self, code: &str) {
+        self.add(code, code);
+    }
+
+    /// Encode the letter at `pos`, returning the position of the next one.
+    fn letter(&mut self, pos: uint) -> uint {
+        let next = self.at(pos + 1);
+        match self.at(pos) {
+            'A' | 'E' | 'I' | 'O' | 'U' | 'Y' => {
+                if pos == 0 {
+                    self.both("A");
+                }
+                pos + 1
+            },
+            'B' => {
+                self.both("P");
+                if next == 'B' { pos + 2 } else { pos + 1 }
+            },
+            'C' => self.letter_c(pos),
+            'D' => {
+                if self.is_at(pos, ["DGE", "DGI", "DGY"]) {
+                    self.both("J");
+                    pos + 3
+                } else if self.is_at(pos, ["DT", "DD", "DG"]) {
+                    self.both("T");
+                    pos + 2
+                } else {
+                    self.both("T");
+                    pos + 1
+                }
+            },
+            'G' => self.letter_g(pos),
+            'H' => {
+                if (pos == 0 || self.is_vowel(pos - 1)) && self.is_vowel(pos + 1) {
+                    self.both("H");
+                }
+                pos + 1
+            },
+            'J' => {
+                if self.is_at(pos, ["JOSE"]) {
+                    self.both("H");
+                } else if pos == 0 {
+                    self.add("J", "A");
+                } else {
+                    self.add("J", "H");
+                }
+                if next == 'J' { pos + 2 } else { pos + 1 }
+            },
+            'M' => {
+                self.both("M");
+                if next == 'M' || (self.is_at(pos, ["MB"]) && pos + 2 == self.word.len()) { pos + 2 } else { pos + 1 }
+            },
+            'P' => {
+                if next == 'H' {
+                    self.both("F");
+                    pos + 2
+                } else {
+                    self.both("P");
+                    if next == 'P' || next == 'B' { pos + 2 } else { pos + 1 }
+                }
+            },
+            'Q' => {
+                self.both("K");
+                if next == 'Q' { pos + 2 } else { pos + 1 }
+            },
+            'S' => self.letter_s(pos),
+            'T' => {
+                if self.is_at(pos, ["TION", "TIA", "TCH"]) {
+                    self.both("X");
+                    pos + 3
+                } else if next == 'H' {
+                    self.add("0", "T");
+                    pos + 2
+                } else {
+                    self.both("T");
+                    if next == 'T' || next == 'D' { pos + 2 } else { pos + 1 }
+                }
+            },
+            'V' => {
+                self.both("F");
+                if next == 'V' { pos + 2 } else { pos + 1 }
+            },
+            'W' => {
+                if next == 'R' {
+                    self.both("R");
+                    pos + 2
+                } else {
+                    if pos == 0 && (self.is_vowel(1) || next == 'H') {
+                        self.add("A", "F");
+                    }
+                    pos + 1
+                }
+            },
+            'X' => {
+                self.both("KS");
+                if next == 'C' || next == 'X' { pos + 2 } else { pos + 1 }
+            },
+            'Z' => {
+                if next == 'H' {
+                    self.both("J");
+                    pos + 2
+                } else {
+                    self.add("S", "TS");
+                    if next == 'Z' { pos + 2 } else { pos + 1 }
+                }
+            },
+            c => {
+                // F, K, L, N and R sound like themselves
+                self.both(c.to_string()[]);
+                if next == c { pos + 2 } else { pos + 1 }
+            },
+        }
+    }
+
+    fn letter_c(&mut self, pos: uint) -> uint {
+        if self.is_at(pos, ["CHR", "CHL"]) || (pos == 0 && self.is_at(pos, ["CHORD", "CHORU", "CHARAC"])) {
+            self.both("K");
+            pos + 2
+        } else if self.is_at(pos, ["CH"]) {
+            if pos == 0 { self.add("X", "K") } else { self.both("X") }
+            pos + 2
+        } else if self.is_at(pos, ["CIA"]) {
+            self.both("X");
+            pos + 3
+        } else if self.is_at(pos, ["CCE", "CCI"]) {
+            self.both("KS");
+            pos + 3
+        } else if self.is_at(pos, ["CE", "CI", "CY"]) {
+            self.both("S");
+            pos + 2
+        } else if self.is_at(pos, ["CZ"]) {
+            self.add("S", "X");
+            pos + 2
+        } else {
+            self.both("K");
+            if self.is_at(pos, ["CC", "CK", "CG", "CQ"]) { pos + 2 } else { pos + 1 }
+        }
+    }
+
+    fn letter_g(&mut self, pos: uint) -> uint {
+        let next = self.at(pos + 1);
+        if next == 'H' {
+            if pos == 0 || !self.is_vowel(pos - 1) {
+                self.both("K");
+            } else if pos >= 3 && self.at(pos - 1) == 'U' && "CGLRT".contains_char(self.at(pos - 3)) {
+                // Laugh, cough and tough
+                self.both("F");
+            }
+            pos + 2
+        } else if next == 'N' {
+            if pos + 2 == self.word.len() || self.is_at(pos, ["GNE"]) {
+                self.add("N", "KN");
+            } else {
+                self.both("KN");
+            }
+            pos + 2
+        } else if next == 'E' || next == 'I' || next == 'Y' {
+            self.add("J", "K");
+            pos + 2
+        } else {
+            self.both("K");
+            if next == 'G' { pos + 2 } else { pos + 1 }
+        }
+    }
+
+    fn letter_s(&mut self, pos: uint) -> uint {
+        if self.is_at(pos, ["SH"]) {
+            self.both("X");
+            pos + 2
+        } else if self.is_at(pos, ["SIO", "SIA"]) {
+            self.add("X", "S");
+            pos + 3
+        } else if self.is_at(pos, ["SCH"]) {
+            self.add("SK", "X");
+            pos + 3
+        } else if self.is_at(pos, ["SCE", "SCI", "SCY"]) {
+            self.both("S");
+            pos + 3
+        } else if self.is_at(pos, ["SC"]) {
+            self.both("SK");
+            pos + 2
+        } else {
+            self.both("S");
+            let next = self.at(pos + 1);
+            if next == 'S' || next == 'Z' { pos + 2 } else { pos + 1 }
+        }
+    }
+}
+
+/// The primary and alternate Double Metaphone codes of `word`.
+///
+/// Letters outside A to Z are dropped, after folding accents.
+pub fn double_metaphone(word: &str) -> (String, String) {
+    let folded = fold(word);
+    let word: Vec<char> = folded[].chars()
+        .filter(|&c| c.is_alphabetic() && (c as u32) < 0x80)
+        .map(|c| c.to_uppercase())
+        .collect();
+    let mut coder = Coder { word: word, primary: String::new(), alternate: String::new() };
+
+    let mut pos = 0;
+    if coder.is_at(0, ["GN", "KN", "PN", "WR", "PS"]) {
+        pos = 1;
+    } else if coder.at(0) == 'X' {
+        coder.both("S");
+        pos = 1;
+    }
+    while pos < coder.word.len() && (coder.primary.len() < MAX_CODE || coder.alternate.len() < MAX_CODE) {
+        pos = coder.letter(pos);
+    }
+    coder.primary.truncate(cmp::min(MAX_CODE, coder.primary.len()));
+    coder.alternate.truncate(cmp::min(MAX_CODE, coder.alternate.len()));
+    (coder.primary, coder.alternate)
+}
+
+/// The codes of the words of `text` that make a sound.
+fn codes(text: &str) -> Vec<(String, String)> {
+    text.words().map(double_metaphone).filter(|&(ref p, _)| !p.is_empty()).collect()
+}
+
+fn sounds_alike(a: &(String, String), b: &(String, String)) -> bool {
+    let (&(ref a1, ref a2), &(ref b1, ref b2)) = (a, b);
+    a1 == b1 || a1 == b2 || a2 == b1 || a2 == b2
+}
+
+/// How many words of `a` and `b` sound alike in order, as a share of the longer.
+fn score(a: &[(String, String)], b: &[(String, String)]) -> f64 {
+    if a.is_empty() || b.is_empty() {
+        return 0.0;
+    }
+    // Longest common subsequence, a row at a time
+    let mut row = Vec::from_elem(b.len() + 1, 0u);
+    for x in a.iter() {
+        let mut diagonal = 0;
+        for (j, y) in b.iter().enumerate() {
+            let above = row[j + 1];
+            row[j + 1] = if sounds_alike(x, y) { diagonal + 1 } else { cmp::max(above, row[j]) };
+            diagonal = above;
+        }
+    }
+    row[b.len()] as f64 / cmp::max(a.len(), b.len()) as f64
+}
+
+/// Insults indexed by the codes of their words.
+pub struct PhoneticIndex<'a> {
+    insults: &'a Insults,
+    /// Codes of the insults with the insult they came from, ordered by insult.
+    keys: Vec<(Vec<(String, String)>, &'a str)>,
+}
+
+impl<'a> PhoneticIndex<'a> {
+    /// Index the insults of `insults` and its fallbacks.
+    pub fn new(insults: &'a Insults) -> PhoneticIndex<'a> {
+        let mut all: Vec<&'a str> = Vec::new();
+        let mut current = Some(insults);
+        loop {
+            match current {
+                Some(x) => {
+                    all.extend(x.insults().into_iter().map(|s| s[]));
+                    current = x.fallback();
+                },
+                None => break,
+            }
+        }
+        all.sort();
+        all.dedup();
+        PhoneticIndex { insults: insults, keys: all.into_iter().map(|s| (codes(s), s)).collect() }
+    }
+
+    /// The insult sounding the most like `transcript`, if it's close enough.
+    pub fn insult(&self, transcript: &str) -> Option<&'a str> {
+        self.find(transcript).map(|(insult, _)| insult)
+    }
+
+    /// The insult sounding the most like `transcript` with its score.
+    fn find(&self, transcript: &str) -> Option<(&'a str, f64)> {
+        let heard = codes(transcript);
+        let mut best = None;
+        let mut best_score = MIN_SCORE;
+        for &(ref key, insult) in self.keys.iter() {
+            let score = score(heard[], key[]);
+            if score >= best_score && (best.is_none() || score > best_score) {
+                best = Some(insult);
+                best_score = score;
+            }
+        }
+        best.map(|x| (x, best_score))
+    }
+
+    /// The retort to the insult sounding the most like `transcript`, as confident as it scored.
+    pub fn retort(&self, transcript: &str) -> Option<MatchResult<'a>> {
+        self.find(transcript).and_then(|(insult, score)| MatchResult::new(self.insults, insult, Phonetic, score))
+    }
+}
+
+impl<'a> Index<'a> for PhoneticIndex<'a> {
+    fn lookup(&self, input: &str) -> Option<MatchResult<'a>> {
+        self.retort(input)
+    }
+}
+
+impl Insults {
+    /// An index matching insults by how they sound.
+    pub fn phonetic_index<'a>(&'a self) -> PhoneticIndex<'a> {
+        PhoneticIndex::new(self)
+    }
+
+    /// The retort to the insult a voice transcript sounds like.
+    ///
+    /// Indexes the insults on every call, keep the one from `phonetic_index`
+    /// to look up many.
+    pub fn retort_phonetic<'a>(&'a self, transcript: &str) -> Option<MatchResult<'a>> {
+        self.phonetic_index().retort(transcript)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use insults::Insults;
+    use super::*;
+
+    fn code(word: &str) -> (String, String) {
+        double_metaphone(word)
+    }
+
+    #[test]
+    fn test_retort_phonetic() {
+        assert_eq!(code("fight"), code("fite"));
+        assert_eq!(code("dairy"), code("dary"));
+        assert_eq!(code("Thomas").val0(), "0MS".to_string());
+        assert_eq!(code("knight").val0(), "NT".to_string());
+        assert_eq!(code("laugh").val0(), "LF".to_string());
+
+        let mi = Insults::new("insults.json");
+        assert_eq!(mi.retort_phonetic("you fite like a dary farmer").map(|x| x.retort),
+                   Some("How appropriate. You fight like a cow."));
+        assert!(mi.retort_phonetic("what a lovely day for a picnic").is_none());
+    }
+}
@@ -0,0 +1,113 @@
+//! Pirate speak, for bot messages in the voice of the insults.
+//!
+//! `piratify` swaps words for their pirate counterparts, more of them the
+//! higher the `Intensity`. It's deterministic, the same text always comes
+//! out the same.
+
+/// How much pirate to speak.
+#[deriving(Clone, PartialEq, Eq, PartialOrd, Ord, Show)]
+pub enum Intensity {
+    /// Only the pronouns and greetings.
+    Light,
+    /// Also common words and dropped g's.
+    Medium,
+    /// Also an "Arr!" up front.
+    Heavy,
+}
+
+/// Words swapped at every intensity.
+static LIGHT: &'static [(&'static str, &'static str)] = &[
+    ("you", "ye"),
+    ("your", "yer"),
+    ("my", "me"),
+    ("yes", "aye"),
+    ("hello", "ahoy"),
+    ("hi", "ahoy"),
+    ("friend", "matey"),
+    ("friends", "mateys"),
+];
+
+/// Words swapped from `Medium` up.
+static MEDIUM: &'static [(&'static str, &'static str)] = &[
+    ("is", "be"),
+    ("are", "be"),
+    ("stop", "avast"),
+    ("money", "doubloons"),
+    ("boy", "lad"),
+    ("girl", "lass"),
+    ("wow", "blimey"),
+    ("the", "th'"),
+    ("of", "o'"),
+];
+
+/// `text` in pirate speak at `Medium` intensity.
+pub fn piratify(text: &str) -> String {
+    piratify_with(text, Medium)
+}
+
+/// `text` in pirate speak at `intensity`.
+pub fn piratify_with(text: &str, intensity: Intensity) -> String {
+    let mut res = String::new();
+    if intensity == Heavy && !text.is_empty() {
+        res.push_str("Arr! ");
+    }
+    let mut word = String::new();
+    for c in text.chars() {
+        if c.is_alphabetic() || (c == '\'' && !word.is_empty()) {
+            word.push(c);
+        } else {
+            res.push_str(pirate_word(word[], intensity)[]);
+            word.clear();
+            res.push(c);
+        }
+    }
+    res.push_str(pirate_word(word[], intensity)[]);
+    res
+}
+
+fn pirate_word(word: &str, intensity: Intensity) -> String {
+    let lower = word.chars().map(|c| c.to_lowercase()).collect::<String>();
+    let swap = |swaps: &[(&'static str, &'static str)]| {
+        swaps.iter().find(|&&(k, _)| k == lower[]).map(|&(_, v)| v)
+    };
+    let found = match swap(LIGHT) {
+        None if intensity >= Medium => swap(MEDIUM),
+        x => x,
+    };
+    let pirate = match found {
+        Some(v) => v.to_string(),
+        None if intensity >= Medium && lower.len() > 4 && lower[].ends_with("ing") =>
+            format!("{}'", word[..word.len() - 1]),
+        None => return word.to_string(),
+    };
+    keep_case(word, pirate[])
+}
+
+/// `pirate` capitalized like `word`.
+fn keep_case(word: &str, pirate: &str) -> String {
+    let upper = |x: &str| x.chars().map(|c| c.to_uppercase()).collect::<String>();
+    if word.len() > 1 && word.chars().all(|c| !c.is_lowercase()) {
+        upper(pirate)
+    } else if word.chars().next().map_or(false, |c| c.is_uppercase()) {
+        let mut chars = pirate.chars();
+        let first = chars.next().map(|c| c.to_uppercase().to_string()).unwrap_or(String::new());
+        format!("{}{}", first, chars.collect::<String>())
+    } else {
+        pirate.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_piratify() {
+        assert_eq!(piratify_with("Hello, my friend! You are fighting.", Light),
+                   "Ahoy, me matey! Ye are fighting.".to_string());
+        assert_eq!(piratify("Hello, my friend! You are fighting."),
+                   "Ahoy, me matey! Ye be fightin'.".to_string());
+        assert_eq!(piratify_with("STOP the duel", Heavy), "Arr! AVAST th' duel".to_string());
+        assert_eq!(piratify("I'm rubber, you're glue."), "I'm rubber, you're glue.".to_string());
+    }
+}
@@ -0,0 +1,181 @@
+//! Insult providers loaded from shared libraries, with the `plugins` feature.
+//!
+//! Rust traits have no stable ABI, so a plugin exports a C function
+//! returning its `PluginV1` description:
+//!
+//! ```ignore
+//! static PLUGIN: PluginV1 = PluginV1 {
+//!     abi_version: ABI_VERSION,
+//!     name: b"mi-remaster\0" as *const u8 as *const c_char,
+//!     version: b"1.0.0\0" as *const u8 as *const c_char,
+//!     dataset: dataset,
+//!     free: free,
+//! };
+//!
+//! #[no_mangle]
+//! pub extern "C" fn mi_insults_plugin() -> *const PluginV1 { &PLUGIN }
+//! ```
+//!
+//! `dataset` returns the insults as JSON in the layout of `insults.json`,
+//! the host hands the string back to `free` once it's parsed. A `Plugin`
+//! is an `InsultSource`, `load_dir` registers every plugin of a directory.
+
+use libc::c_char;
+use std::c_str::CString;
+use std::dynamic_lib::DynamicLibrary;
+use std::io::fs::{mod, PathExtensions};
+use std::mem;
+use std::os;
+
+use insults::Insults;
+use registry::{InsultSource, PackRegistry, SharedLibrary};
+use schema::{LoadError, Io, Library};
+
+/// The layout of `PluginV1` this version of the crate loads.
+pub const ABI_VERSION: u32 = 1;
+
+/// The symbol every plugin exports.
+pub const ENTRY_POINT: &'static str = "mi_insults_plugin";
+
+/// What a plugin exports, shared with it as a C struct.
+#[repr(C)]
+pub struct PluginV1 {
+    /// `ABI_VERSION` of the crate the plugin was built against.
+    pub abi_version: u32,
+    /// Name of the pack, a C string.
+    pub name: *const c_char,
+    /// Version of the pack, a C string.
+    pub version: *const c_char,
+    /// The insults as JSON, null if they couldn't be made.
+    pub dataset: extern "C" fn() -> *mut c_char,
+    /// Frees a string returned by `dataset`.
+    pub free: extern "C" fn(*mut c_char),
+}
+
+/// A loaded plugin.
+pub struct Plugin {
+    name: String,
+    version: String,
+    path: Path,
+    description: *const PluginV1,
+    // Unloaded with the plugin, after `description` is last used
+    #[allow(dead_code)]
+    lib: DynamicLibrary,
+}
+
+/// The C string `s` copied, None if it's null or not UTF-8.
+unsafe fn copy_str(s: *const c_char) -> Option<String> {
+    if s.is_null() {
+        return None;
+    }
+    CString::new(s, false).as_str().map(|x| x.to_string())
+}
+
+impl Plugin {
+    /// Load the plugin at `path`, checking that it has the ABI of this crate.
+    pub fn open(path: &Path) -> Result<Plugin, LoadError> {
+        let lib = match DynamicLibrary::open(Some(path)) {
+            Ok(x) => x,
+            Err(e) => return Err(Library(format!("{}: {}", path.display(), e))),
+        };
+        let description = unsafe {
+            match lib.symbol::<u8>(ENTRY_POINT) {
+                Ok(f) => {
+                    let entry: extern "C" fn() -> *const PluginV1 = mem::transmute(f);
+                    entry()
+                },
+                Err(e) => return Err(Library(format!("{}: {}", path.display(), e))),
+            }
+        };
+        if description.is_null() {
+            return Err(Library(format!("{}: {} returned null", path.display(), ENTRY_POINT)));
+        }
+        let (abi_version, name, version) = unsafe {
+            let d = &*description;
+            (d.abi_version, copy_str(d.name), copy_str(d.version))
+        };
+        if abi_version != ABI_VERSION {
+            return Err(Library(format!("{}: ABI version {}, expected {}",
+                                       path.display(), abi_version, ABI_VERSION)));
+        }
+        match (name, version) {
+            (Some(name), Some(version)) => Ok(Plugin {
+                name: name,
+                version: version,
+                path: path.clone(),
+                description: description,
+                lib: lib,
+            }),
+            _ => Err(Library(format!("{}: the name or version is not a string", path.display()))),
+        }
+    }
+
+    pub fn path<'a>(&'a self) -> &'a Path {
+        &self.path
+    }
+}
+
+impl InsultSource for Plugin {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn version(&self) -> String {
+        self.version.clone()
+    }
+
+    fn insults(&self) -> Result<Insults, LoadError> {
+        let description = unsafe { &*self.description };
+        let data = (description.dataset)();
+        let json = unsafe { copy_str(data as *const c_char) };
+        if !data.is_null() {
+            (description.free)(data);
+        }
+        match json {
+            Some(x) => Insults::try_from_str(x[]),
+            None => Err(Library(format!("{}: no dataset", self.path.display()))),
+        }
+    }
+}
+
+/// Register every plugin in `dir` with `registry`, in file name order.
+///
+/// Returns the names of the packs registered.
+pub fn load_dir(dir: &Path, registry: &mut PackRegistry) -> Result<Vec<String>, LoadError> {
+    let mut paths = match fs::readdir(dir) {
+        Ok(x) => x,
+        Err(e) => return Err(Io(e)),
+    };
+    paths.retain(|p| p.is_file() && p.extension_str() == Some(os::consts::DLL_EXTENSION));
+    paths.sort_by(|a, b| a.as_vec().cmp(b.as_vec()));
+
+    let mut res = Vec::new();
+    for path in paths.iter() {
+        let plugin = try!(Plugin::open(path));
+        try!(registry.register_source(&plugin, SharedLibrary(path.clone())));
+        res.push(plugin.name());
+    }
+    Ok(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{File, TempDir};
+    use std::os;
+
+    use registry::PackRegistry;
+    use super::*;
+
+    #[test]
+    fn test_load_dir() {
+        let tmp = TempDir::new("mi_insults").unwrap();
+        let mut registry = PackRegistry::new();
+        assert_eq!(load_dir(tmp.path(), &mut registry), Ok(Vec::new()));
+
+        let fake = tmp.path().join(format!("fake.{}", os::consts::DLL_EXTENSION));
+        File::create(&fake).write_str("not a library").unwrap();
+        assert!(Plugin::open(&fake).is_err());
+        assert!(load_dir(tmp.path(), &mut registry).is_err());
+        assert!(registry.packs().is_empty());
+    }
+}
@@ -0,0 +1,153 @@
+//! Player profiles tracking which insults have been learned in a real playthrough.
+//!
+//! A companion app marks insults as learned through a small JSON API:
+//!
+//! * `GET /profiles/:id/knowledge` returns `{"learned": [...]}`
+//! * `PUT /profiles/:id/knowledge` replaces the learned insults with the body
+//!
+//! The handler is transport agnostic, hook `Profiles::handle` up to any HTTP
+//! server. `Server::handle` in the `serve` module mounts it under `/profiles/`.
+
+use std::collections::{HashMap, TreeSet};
+use std::rand::{mod, Rng};
+use serialize::json;
+
+use insults::Insults;
+
+/// Insults a player has learned.
+#[deriving(Clone, PartialEq, Show, Encodable, Decodable)]
+pub struct Knowledge {
+    pub learned: TreeSet<String>,
+}
+
+impl Knowledge {
+    pub fn new() -> Knowledge {
+        Knowledge { learned: TreeSet::new() }
+    }
+
+    pub fn learn(&mut self, insult: &str) {
+        self.learned.insert(insult.to_string());
+    }
+
+    pub fn knows(&self, insult: &str) -> bool {
+        self.learned.contains(&insult.to_string())
+    }
+
+    /// Insults worth drilling, the learned ones which exist in `insults`.
+    pub fn drill_insults<'a>(&self, insults: &'a Insults) -> Vec<&'a String> {
+        let mut res: Vec<&String> = insults.insults().into_iter()
+            .filter(|x| self.learned.contains(*x))
+            .collect();
+        res.sort();
+        res
+    }
+
+    /// A random learned insult to drill, if any has been learned.
+    pub fn rand_drill_insult<'a, R: Rng>(&self, insults: &'a Insults, rng: &mut R) -> Option<&'a String> {
+        let drill = self.drill_insults(insults);
+        if drill.is_empty() {
+            None
+        } else {
+            Some(rand::sample(rng, drill.into_iter(), 1)[0])
+        }
+    }
+}
+
+/// A response from the profile API.
+#[deriving(Clone, PartialEq, Show)]
+pub struct Response {
+    pub status: u16,
+    pub body: String,
+}
+
+impl Response {
+    fn json(status: u16, body: String) -> Response {
+        Response { status: status, body: body }
+    }
+
+    fn error(status: u16, msg: &str) -> Response {
+        Response::json(status, format!("{{\"error\":{}}}", json::encode(&msg.to_string())))
+    }
+}
+
+/// Knowledge for every profile, keyed by profile id.
+pub struct Profiles {
+    profiles: HashMap<String, Knowledge>,
+}
+
+impl Profiles {
+    pub fn new() -> Profiles {
+        Profiles { profiles: HashMap::new() }
+    }
+
+    pub fn get<'a>(&'a self, id: &str) -> Option<&'a Knowledge> {
+        self.profiles.get(&id.to_string())
+    }
+
+    pub fn set(&mut self, id: &str, knowledge: Knowledge) {
+        self.profiles.insert(id.to_string(), knowledge);
+    }
+
+    /// Handle an API request, `insults` is used to reject unknown insults.
+    pub fn handle(&mut self, insults: &Insults, method: &str, path: &str, body: &str) -> Response {
+        let path = match path.find('?') {
+            Some(i) => path[..i],
+            None => path,
+        };
+        let parts: Vec<&str> = path.split('/').filter(|x| !x.is_empty()).collect();
+        if parts.len() != 3 || parts[0] != "profiles" || parts[2] != "knowledge" {
+            return Response::error(404, "not found");
+        }
+        let id = parts[1];
+
+        match method {
+            "GET" => match self.get(id) {
+                Some(k) => Response::json(200, json::encode(k)),
+                None => Response::error(404, "unknown profile"),
+            },
+            "PUT" => {
+                let knowledge: Knowledge = match json::decode(body) {
+                    Ok(k) => k,
+                    Err(e) => return Response::error(400, format!("invalid body: {}", e)[]),
+                };
+                for insult in knowledge.learned.iter() {
+                    if insults.retort(insult[]).is_none() {
+                        return Response::error(422, format!("unknown insult: {}", insult)[]);
+                    }
+                }
+                let res = json::encode(&knowledge);
+                self.set(id, knowledge);
+                Response::json(200, res)
+            },
+            _ => Response::error(405, "method not allowed"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use insults::Insults;
+    use super::*;
+
+    #[test]
+    fn test_knowledge_api() {
+        let mi = Insults::new("insults.json");
+        let mut profiles = Profiles::new();
+
+        assert_eq!(profiles.handle(&mi, "GET", "/profiles/guybrush/knowledge", "").status, 404);
+
+        let body = r#"{"learned":["You fight like a dairy farmer."]}"#;
+        let res = profiles.handle(&mi, "PUT", "/profiles/guybrush/knowledge", body);
+        assert_eq!(res.status, 200);
+        assert_eq!(res.body[], body);
+
+        let res = profiles.handle(&mi, "GET", "/profiles/guybrush/knowledge", "");
+        assert_eq!(res.body[], body);
+
+        let bad = r#"{"learned":["You're lazy!"]}"#;
+        assert_eq!(profiles.handle(&mi, "PUT", "/profiles/guybrush/knowledge", bad).status, 422);
+
+        let k = profiles.get("guybrush").unwrap();
+        assert_eq!(k.drill_insults(&mi), vec![&"You fight like a dairy farmer.".to_string()]);
+    }
+}
@@ -0,0 +1,137 @@
+//! Player progress saved between sessions.
+//!
+//! Holds the learned insults, the trainer schedule, answer statistics and
+//! the duels fought. The file carries a `version` so older saves can be
+//! read after the format grows.
+
+use std::io::{File, IoResult};
+use serialize::{Decodable, json};
+use serialize::json::Json;
+
+use duel::{Duel, Player};
+use profile::Knowledge;
+use schema::{LoadError, Io, Syntax, Migration, Schema};
+use stats::Stats;
+use trainer::Schedule;
+
+/// Version of the progress files written by this crate.
+pub const PROGRESS_VERSION: u64 = 1;
+
+/// The outcome of a finished duel.
+#[deriving(Clone, PartialEq, Show, Encodable, Decodable)]
+pub struct DuelRecord {
+    pub won: bool,
+    pub player: uint,
+    pub opponent: uint,
+}
+
+impl DuelRecord {
+    /// The record of `duel`, None if it isn't over.
+    pub fn of(duel: &Duel) -> Option<DuelRecord> {
+        duel.winner().map(|winner| DuelRecord {
+            won: winner == Player,
+            player: duel.score().player,
+            opponent: duel.score().opponent,
+        })
+    }
+}
+
+/// Everything needed to resume where the player left off.
+#[deriving(Clone, PartialEq, Show, Encodable, Decodable)]
+pub struct Progress {
+    pub version: u64,
+    pub knowledge: Knowledge,
+    pub schedule: Schedule,
+    pub stats: Stats,
+    pub duels: Vec<DuelRecord>,
+}
+
+impl Progress {
+    pub fn new() -> Progress {
+        Progress {
+            version: PROGRESS_VERSION,
+            knowledge: Knowledge::new(),
+            schedule: Schedule::new(),
+            stats: Stats::new(),
+            duels: Vec::new(),
+        }
+    }
+
+    /// Duels won and lost.
+    pub fn record(&self) -> (uint, uint) {
+        let won = self.duels.iter().filter(|x| x.won).count();
+        (won, self.duels.len() - won)
+    }
+
+    pub fn save(&self, path: &Path) -> IoResult<()> {
+        File::create(path).write_str(json::encode(self)[])
+    }
+
+    pub fn load(path: &Path) -> Result<Progress, LoadError> {
+        match File::open(path).read_to_string() {
+            Ok(x) => Progress::from_str(x[]),
+            Err(e) => Err(Io(e)),
+        }
+    }
+
+    /// Read progress saved by this or an earlier version.
+    pub fn from_str(s: &str) -> Result<Progress, LoadError> {
+        let data = match json::from_str(s) {
+            Ok(x) => x,
+            Err(e) => return Err(Syntax(e)),
+        };
+        let version = match data.find(&"version".to_string()) {
+            Some(&json::U64(x)) => x,
+            Some(&json::I64(x)) if x >= 0 => x as u64,
+            _ => return Err(Schema(vec!["version".to_string()], "is missing".to_string())),
+        };
+        if version > PROGRESS_VERSION {
+            return Err(Migration(format!("progress version {} is newer than {}",
+                                         version, PROGRESS_VERSION)));
+        }
+        decode(data)
+    }
+}
+
+fn decode(data: Json) -> Result<Progress, LoadError> {
+    let mut decoder = json::Decoder::new(data);
+    match Decodable::decode(&mut decoder) {
+        Ok(x) => Ok(x),
+        Err(e) => Err(Schema(Vec::new(), e.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::TempDir;
+    use std::time::Duration;
+
+    use duel::{Duel, Rules};
+    use insults::Insults;
+    use schema::Migration;
+    use super::*;
+
+    #[test]
+    fn test_progress() {
+        let mi = Insults::new("insults.json");
+        let insult = "You fight like a dairy farmer.";
+        let mut duel = Duel::new(&mi, Rules::first_to(1));
+        duel.exchange(insult, "Oh yeah?");
+
+        let mut progress = Progress::new();
+        progress.knowledge.learn(insult);
+        progress.stats.record(insult, false, Duration::seconds(2));
+        progress.duels.push(DuelRecord::of(&duel).unwrap());
+        assert_eq!(progress.record(), (1, 0));
+
+        let dir = TempDir::new("progress").unwrap();
+        let path = dir.path().join("progress.json");
+        progress.save(&path).unwrap();
+        assert_eq!(Progress::load(&path), Ok(progress));
+
+        match Progress::from_str(r#"{"version": 2}"#) {
+            Err(Migration(_)) => (),
+            x => panic!("{}", x),
+        }
+    }
+}
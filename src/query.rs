@@ -0,0 +1,304 @@
+//! Filtering the dataset without writing the loops.
+//!
+//! ```ignore
+//! let hits = insults.query().game(MonkeyIsland3).speaker("Guybrush").min_len(20).collect();
+//! ```
+//!
+//! Every filter narrows the query, calling `game` more than once takes the
+//! insults of any of the games. The fallback datasets are queried too,
+//! `locale` picks one of them. `page` returns a slice of the hits with the
+//! total, for listings a page at a time. The hits come in the `SortOrder`
+//! of the query, the same on every call.
+
+use std::rand::{Isaac64Rng, Rng, SeedableRng};
+
+use game::Game;
+use insults::{Insults, Entry};
+
+/// Orders of the hits of a query.
+#[deriving(Clone, PartialEq, Eq, Show)]
+pub enum SortOrder {
+    /// By dataset, then game, then insult.
+    ByGame,
+    /// By insult, then game.
+    Alphabetical,
+    /// Shortest first, then alphabetical.
+    ByLength,
+    /// By the English insult a translation has, then alphabetical.
+    ByCanonicalId,
+    /// Shuffled by a generator seeded with the seed.
+    Shuffled(u64),
+}
+
+/// An insult a query found.
+#[deriving(Clone, PartialEq, Show)]
+pub struct QueryHit<'a> {
+    pub game: Game,
+    pub insult: &'a str,
+    pub entry: &'a Entry,
+    /// The locale of the dataset with the insult.
+    pub locale: &'a str,
+}
+
+/// A slice of a listing, with the size of the whole listing.
+#[deriving(Clone, PartialEq, Show)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub offset: uint,
+    pub limit: uint,
+    /// How many there are on all the pages.
+    pub total: uint,
+}
+
+impl<T> Page<T> {
+    /// Whether there are more after this page.
+    pub fn has_next(&self) -> bool {
+        self.offset + self.items.len() < self.total
+    }
+}
+
+/// Filters over the insults of a dataset and its fallbacks.
+pub struct Query<'a> {
+    insults: &'a Insults,
+    games: Vec<Game>,
+    locale: Option<String>,
+    speaker: Option<String>,
+    context: Option<String>,
+    rating: Option<String>,
+    min_len: Option<uint>,
+    max_len: Option<uint>,
+    audio: bool,
+    matching: Option<fn(&str, &Entry) -> bool>,
+    order: SortOrder,
+}
+
+fn lower(s: &str) -> String {
+    s.chars().map(|c| c.to_lowercase()).collect()
+}
+
+impl<'a> Query<'a> {
+    /// A query for every insult of `insults`.
+    pub fn new(insults: &'a Insults) -> Query<'a> {
+        Query {
+            insults: insults,
+            games: Vec::new(),
+            locale: None,
+            speaker: None,
+            context: None,
+            rating: None,
+            min_len: None,
+            max_len: None,
+            audio: false,
+            matching: None,
+            order: ByGame,
+        }
+    }
+
+    /// Insults of `game`, or of the other games asked for.
+    pub fn game(mut self, game: Game) -> Query<'a> {
+        if !self.games.contains(&game) {
+            self.games.push(game);
+        }
+        self
+    }
+
+    /// Insults of the dataset in `locale`, like `de`.
+    pub fn locale(mut self, locale: &str) -> Query<'a> {
+        self.locale = Some(locale.to_string());
+        self
+    }
+
+    /// Insults said by `speaker`, ignoring case.
+    pub fn speaker(mut self, speaker: &str) -> Query<'a> {
+        self.speaker = Some(lower(speaker));
+        self
+    }
+
+    /// Insults whose context contains `context`, ignoring case.
+    pub fn context(mut self, context: &str) -> Query<'a> {
+        self.context = Some(lower(context));
+        self
+    }
+
+    /// Insults rated `rating`, like `harsh`. Unrated insults are rated `everyone`.
+    pub fn rating(mut self, rating: &str) -> Query<'a> {
+        self.rating = Some(rating.to_string());
+        self
+    }
+
+    /// Insults of at least `len` characters.
+    pub fn min_len(mut self, len: uint) -> Query<'a> {
+        self.min_len = Some(len);
+        self
+    }
+
+    /// Insults of at most `len` characters.
+    pub fn max_len(mut self, len: uint) -> Query<'a> {
+        self.max_len = Some(len);
+        self
+    }
+
+    /// Insults with a recording.
+    pub fn with_audio(mut self) -> Query<'a> {
+        self.audio = true;
+        self
+    }
+
+    /// Insults `f` accepts, for metadata the other filters don't cover.
+    pub fn matching(mut self, f: fn(&str, &Entry) -> bool) -> Query<'a> {
+        self.matching = Some(f);
+        self
+    }
+
+    /// List the hits in `order` instead of `ByGame`.
+    pub fn sort(mut self, order: SortOrder) -> Query<'a> {
+        self.order = order;
+        self
+    }
+
+    fn accepts(&self, insult: &str, entry: &Entry) -> bool {
+        let len = insult.chars().count();
+        self.min_len.map_or(true, |n| len >= n)
+            && self.max_len.map_or(true, |n| len <= n)
+            && self.speaker.as_ref().map_or(true, |x| {
+                entry.speaker.as_ref().map_or(false, |s| lower(s[]) == *x)
+            })
+            && self.context.as_ref().map_or(true, |x| {
+                entry.context.as_ref().map_or(false, |c| lower(c[])[].contains(x[]))
+            })
+            && self.rating.as_ref().map_or(true, |x| {
+                entry.rating.as_ref().map_or("everyone", |r| r[]) == x[]
+            })
+            && (!self.audio || entry.audio.is_some())
+            && self.matching.map_or(true, |f| f(insult, entry))
+    }
+
+    /// The insults passing every filter, in the order of the query.
+    pub fn collect(&self) -> Vec<QueryHit<'a>> {
+        let mut res = Vec::new();
+        self.each(|hit| res.push(hit));
+        match self.order {
+            ByGame => (),
+            Alphabetical => res.sort_by(|a, b| (a.insult, a.game).cmp(&(b.insult, b.game))),
+            ByLength => {
+                let len = |hit: &QueryHit<'a>| hit.insult.chars().count();
+                res.sort_by(|a, b| (len(a), a.insult).cmp(&(len(b), b.insult)));
+            },
+            ByCanonicalId => {
+                let id = |hit: &QueryHit<'a>| self.insults.canonical_id(hit.insult).unwrap_or(hit.insult);
+                res.sort_by(|a, b| (id(a), a.insult).cmp(&(id(b), b.insult)));
+            },
+            Shuffled(seed) => {
+                let mut rng: Isaac64Rng = SeedableRng::from_seed([seed][]);
+                rng.shuffle(res[mut]);
+            },
+        }
+        res
+    }
+
+    /// The `limit` hits from `offset` in the order of `collect`.
+    ///
+    /// In the `ByGame` order only the hits of the page are kept.
+    pub fn page(&self, offset: uint, limit: uint) -> Page<QueryHit<'a>> {
+        let mut res = Page { items: Vec::new(), offset: offset, limit: limit, total: 0 };
+        if self.order != ByGame {
+            let all = self.collect();
+            res.total = all.len();
+            res.items = all.into_iter().skip(offset).take(limit).collect();
+            return res;
+        }
+        self.each(|hit| {
+            if res.total >= offset && res.items.len() < limit {
+                res.items.push(hit);
+            }
+            res.total += 1;
+        });
+        res
+    }
+
+    /// Call `f` with every hit, in order.
+    fn each(&self, f: |QueryHit<'a>|) {
+        let mut current = Some(self.insults);
+        loop {
+            let insults = match current {
+                Some(x) => x,
+                None => break,
+            };
+            current = insults.fallback();
+            if self.locale.as_ref().map_or(false, |x| x[] != insults.locale()) {
+                continue;
+            }
+            for game in Game::all().iter() {
+                if !self.games.is_empty() && !self.games.contains(game) {
+                    continue;
+                }
+                for (insult, entry) in insults.entries(*game).into_iter() {
+                    if self.accepts(insult[], entry) {
+                        f(QueryHit { game: *game, insult: insult[], entry: entry, locale: insults.locale() });
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Insults {
+    /// A query over every insult, narrowed by its filters.
+    pub fn query<'a>(&'a self) -> Query<'a> {
+        Query::new(self)
+    }
+
+    /// The `limit` insults from `offset`, by dataset, then game, then insult.
+    pub fn page<'a>(&'a self, offset: uint, limit: uint) -> Page<QueryHit<'a>> {
+        self.query().page(offset, limit)
+    }
+
+    /// Every insult, in `order`.
+    pub fn sorted_insults<'a>(&'a self, order: SortOrder) -> Vec<&'a str> {
+        self.query().sort(order).collect().into_iter().map(|x| x.insult).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use game::{MonkeyIsland1, MonkeyIsland3, CaptainRottingham};
+    use insults::{Insults, Entry};
+    use super::*;
+
+    fn asks(insult: &str, _: &Entry) -> bool {
+        insult.ends_with("?")
+    }
+
+    #[test]
+    fn test_query() {
+        let mi = Insults::new("insults.json");
+        let all = mi.query().collect();
+        assert_eq!(all.len(), mi.insults().len());
+
+        let hits = mi.query().game(MonkeyIsland3).game(CaptainRottingham).min_len(40).collect();
+        assert!(!hits.is_empty());
+        assert!(hits.iter().all(|x| x.game != MonkeyIsland1 && x.insult.chars().count() >= 40));
+        assert!(hits.windows(2).all(|w| w[0].game <= w[1].game));
+
+        let questions = mi.query().game(MonkeyIsland1).matching(asks).collect();
+        assert!(questions.iter().any(|x| x.insult == "Have you stopped wearing diapers yet?"));
+        assert!(mi.query().speaker("Nobody in particular").collect().is_empty());
+        assert!(mi.query().locale("sv").collect().is_empty());
+
+        let page = mi.page(10, 5);
+        assert_eq!((page.total, page.items.len()), (all.len(), 5));
+        assert_eq!(page.items[], all[10..15]);
+        assert!(page.has_next() && !mi.page(all.len() - 2, 5).has_next());
+        let last = mi.query().game(MonkeyIsland1).page(0, 1000);
+        assert_eq!(last.items.len(), last.total);
+
+        let shortest = mi.sorted_insults(ByLength);
+        assert!(shortest.windows(2).all(|w| w[0].chars().count() <= w[1].chars().count()));
+        let alphabetical = mi.sorted_insults(Alphabetical);
+        assert!(alphabetical.windows(2).all(|w| w[0] <= w[1]));
+        assert_eq!(mi.sorted_insults(Shuffled(7)), mi.sorted_insults(Shuffled(7)));
+        assert!(mi.sorted_insults(Shuffled(7)) != mi.sorted_insults(ByGame));
+        assert_eq!(mi.query().sort(ByLength).page(0, 3).items.iter().map(|x| x.insult).collect::<Vec<&str>>(),
+                   shortest[..3].to_vec());
+    }
+}
@@ -0,0 +1,149 @@
+//! Multiple choice questions for flashcard and trivia apps.
+//!
+//! A question shows an insult with its retort among wrong retorts, drawn
+//! from the same game first so they sound plausible:
+//!
+//! ```
+//! use mi_insults::Insults;
+//! use mi_insults::quiz::Quiz;
+//!
+//! let mi = Insults::new("insults.json");
+//! let mut rng = std::rand::task_rng();
+//! let q = Quiz::new(&mi, 3).question("You fight like a dairy farmer.", &mut rng).unwrap();
+//! assert!(q.check_answer("How appropriate. You fight like a cow."));
+//! ```
+
+use std::rand::{mod, Rng};
+
+use game::Game;
+use insults::Insults;
+
+/// An insult with retorts to choose from.
+#[deriving(Clone, PartialEq, Show)]
+pub struct Question<'a> {
+    pub insult: &'a str,
+    /// The correct retort among the distractors, shuffled.
+    pub choices: Vec<&'a str>,
+    answer: uint,
+}
+
+impl<'a> Question<'a> {
+    /// Index of the correct retort in `choices`.
+    pub fn answer(&self) -> uint {
+        self.answer
+    }
+
+    /// The correct retort.
+    pub fn retort(&self) -> &'a str {
+        self.choices[self.answer]
+    }
+
+    pub fn check_answer(&self, retort: &str) -> bool {
+        self.retort() == retort
+    }
+
+    /// Check the answer given as an index into `choices`.
+    pub fn check_choice(&self, choice: uint) -> bool {
+        choice == self.answer
+    }
+}
+
+/// Generates questions from a dataset.
+pub struct Quiz<'a> {
+    insults: &'a Insults,
+    distractors: uint,
+}
+
+impl<'a> Quiz<'a> {
+    /// Questions with `distractors` wrong retorts each, fewer if the dataset is small.
+    pub fn new(insults: &'a Insults, distractors: uint) -> Quiz<'a> {
+        Quiz { insults: insults, distractors: distractors }
+    }
+
+    /// A question about `insult`, None if it has no retort.
+    pub fn question<R: Rng>(&self, insult: &str, rng: &mut R) -> Option<Question<'a>> {
+        let (insult, correct) = match self.find(insult) {
+            Some(x) => x,
+            None => return None,
+        };
+
+        let mut choices = Vec::new();
+        let game = insult_game(self.insults, insult);
+        let same_game = self.retorts(|g| Some(g) == game, correct);
+        choices.extend(rand::sample(rng, same_game.into_iter(), self.distractors).into_iter());
+        if choices.len() < self.distractors {
+            let rest: Vec<&str> = self.retorts(|_| true, correct).into_iter()
+                .filter(|x| !choices.contains(x))
+                .collect();
+            let n = self.distractors - choices.len();
+            choices.extend(rand::sample(rng, rest.into_iter(), n).into_iter());
+        }
+
+        choices.push(correct);
+        rng.shuffle(choices[mut]);
+        let answer = choices.iter().position(|x| *x == correct).unwrap();
+        Some(Question { insult: insult, choices: choices, answer: answer })
+    }
+
+    /// A question about a random insult, drawn from those `retort` answers.
+    pub fn rand_question<R: Rng>(&self, rng: &mut R) -> Question<'a> {
+        let insult = self.insults.rand_insult(rng);
+        self.question(insult[], rng).unwrap()
+    }
+
+    /// The insult and its retort, borrowed from the dataset.
+    fn find(&self, insult: &str) -> Option<(&'a str, &'a str)> {
+        let insults = self.insults;
+        let insult = match insults.insults().into_iter().find(|x| x[] == insult) {
+            Some(x) => x[],
+            None => return None,
+        };
+        insults.retort(insult).map(|r| (insult, r))
+    }
+
+    /// Unique retorts of the games matching `f`, except `correct`.
+    fn retorts(&self, f: |Game| -> bool, correct: &str) -> Vec<&'a str> {
+        let insults = self.insults;
+        let mut res: Vec<&str> = Vec::new();
+        for game in Game::all().iter().filter(|g| f(**g)) {
+            for (_, entry) in insults.entries(*game).into_iter() {
+                res.push(entry.retort[]);
+            }
+        }
+        res.sort();
+        res.dedup();
+        res.into_iter().filter(|x| *x != correct).collect()
+    }
+}
+
+/// The game `insult` is from.
+fn insult_game(insults: &Insults, insult: &str) -> Option<Game> {
+    Game::all().iter().find(|g| insults.entry(**g, insult).is_some()).map(|g| *g)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rand;
+
+    use insults::Insults;
+    use super::*;
+
+    #[test]
+    fn test_question() {
+        let mi = Insults::new("insults.json");
+        let quiz = Quiz::new(&mi, 3);
+        let mut rng = rand::task_rng();
+
+        let q = quiz.question("You fight like a dairy farmer.", &mut rng).unwrap();
+        assert_eq!(q.choices.len(), 4);
+        assert_eq!(q.retort(), "How appropriate. You fight like a cow.");
+        assert!(q.check_choice(q.answer()));
+        assert!(q.check_answer("How appropriate. You fight like a cow."));
+        assert!(!q.check_answer(q.choices[(q.answer() + 1) % 4]));
+        assert_eq!(q.choices.iter().filter(|x| mi.is_retort(q.insult, **x)).count(), 1);
+
+        assert!(quiz.question("How appropriate. You fight like a cow.", &mut rng).is_none());
+        let q = quiz.rand_question(&mut rng);
+        assert!(mi.is_retort(q.insult, q.retort()));
+    }
+}
@@ -0,0 +1,175 @@
+//! Elo ratings for a ladder of duelists.
+//!
+//! Record the winner and loser of every duel on a `Ladder`. Ratings can be
+//! saved to and loaded from a JSON file, or kept elsewhere by restoring
+//! them with `Ladder::insert` and observing every update.
+
+use std::collections::TreeMap;
+use std::io::{File, IoResult};
+use serialize::{Decodable, json};
+
+use schema::{LoadError, Io, Syntax, Schema};
+
+/// The rating of a duelist.
+#[deriving(Clone, PartialEq, Show, Encodable, Decodable)]
+pub struct Rating {
+    pub rating: f64,
+    pub duels: uint,
+    pub wins: uint,
+}
+
+/// How ratings move.
+#[deriving(Clone, PartialEq, Show)]
+pub struct Elo {
+    /// The most a rating moves in one duel.
+    pub k: f64,
+    /// The rating of new duelists.
+    pub initial: f64,
+}
+
+impl Elo {
+    /// K of 32 and 1500 to start with.
+    pub fn default() -> Elo {
+        Elo { k: 32.0, initial: 1500.0 }
+    }
+}
+
+/// The chance of a duelist rated `rating` to beat one rated `other`.
+pub fn expected(rating: f64, other: f64) -> f64 {
+    1.0 / (1.0 + 10.0f64.powf((other - rating) / 400.0))
+}
+
+/// Reacts to updated ratings, to store them as they change.
+pub trait RatingObserver {
+    fn on_rated(&mut self, player: &str, rating: &Rating);
+}
+
+/// The ratings of everyone who has dueled.
+pub struct Ladder {
+    elo: Elo,
+    ratings: TreeMap<String, Rating>,
+    observers: Vec<Box<RatingObserver + Send>>,
+}
+
+impl Ladder {
+    pub fn new() -> Ladder {
+        Ladder { elo: Elo::default(), ratings: TreeMap::new(), observers: Vec::new() }
+    }
+
+    pub fn with_elo(mut self, elo: Elo) -> Ladder {
+        self.elo = elo;
+        self
+    }
+
+    /// Notify `observer` of every updated rating.
+    pub fn observe(&mut self, observer: Box<RatingObserver + Send>) {
+        self.observers.push(observer);
+    }
+
+    /// Set the rating of `player`, to restore stored ratings.
+    pub fn insert(&mut self, player: &str, rating: Rating) {
+        self.ratings.insert(player.to_string(), rating);
+    }
+
+    /// The rating of `player`, the initial rating if they haven't dueled.
+    pub fn rating(&self, player: &str) -> Rating {
+        match self.ratings.get(&player.to_string()) {
+            Some(x) => x.clone(),
+            None => Rating { rating: self.elo.initial, duels: 0, wins: 0 },
+        }
+    }
+
+    /// Rate a duel, returning the points moved from the loser to the winner.
+    pub fn record(&mut self, winner: &str, loser: &str) -> f64 {
+        let (mut w, mut l) = (self.rating(winner), self.rating(loser));
+        let change = self.elo.k * (1.0 - expected(w.rating, l.rating));
+        w.rating += change;
+        w.duels += 1;
+        w.wins += 1;
+        l.rating -= change;
+        l.duels += 1;
+
+        for observer in self.observers.iter_mut() {
+            observer.on_rated(winner, &w);
+            observer.on_rated(loser, &l);
+        }
+        self.insert(winner, w);
+        self.insert(loser, l);
+        change
+    }
+
+    /// Everyone rated, best first.
+    pub fn standings<'a>(&'a self) -> Vec<(&'a str, &'a Rating)> {
+        let mut res: Vec<(&str, &Rating)> = self.ratings.iter().map(|(k, v)| (k[], v)).collect();
+        res.sort_by(|a, b| match b.val1().rating.partial_cmp(&a.val1().rating) {
+            Some(Equal) | None => a.val0().cmp(&b.val0()),
+            Some(x) => x,
+        });
+        res
+    }
+
+    pub fn save(&self, path: &Path) -> IoResult<()> {
+        File::create(path).write_str(json::encode(&self.ratings)[])
+    }
+
+    pub fn load(path: &Path) -> Result<Ladder, LoadError> {
+        match File::open(path).read_to_string() {
+            Ok(x) => Ladder::from_str(x[]),
+            Err(e) => Err(Io(e)),
+        }
+    }
+
+    /// Read ratings saved by `save`.
+    pub fn from_str(s: &str) -> Result<Ladder, LoadError> {
+        let data = match json::from_str(s) {
+            Ok(x) => x,
+            Err(e) => return Err(Syntax(e)),
+        };
+        let mut decoder = json::Decoder::new(data);
+        let ratings: TreeMap<String, Rating> = match Decodable::decode(&mut decoder) {
+            Ok(x) => x,
+            Err(e) => return Err(Schema(Vec::new(), e.to_string())),
+        };
+        let mut res = Ladder::new();
+        res.ratings = ratings;
+        Ok(res)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use serialize::json;
+    use super::*;
+
+    struct Log(Arc<Mutex<Vec<String>>>);
+
+    impl RatingObserver for Log {
+        fn on_rated(&mut self, player: &str, _: &Rating) {
+            let Log(ref log) = *self;
+            log.lock().push(player.to_string());
+        }
+    }
+
+    #[test]
+    fn test_ladder() {
+        assert_eq!(expected(1500.0, 1500.0), 0.5);
+
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let mut ladder = Ladder::new();
+        ladder.observe(box Log(log.clone()));
+        assert_eq!(ladder.record("guybrush", "carla"), 16.0);
+        assert!(ladder.record("guybrush", "carla") < 16.0);
+        assert!(ladder.record("carla", "guybrush") > 16.0);
+        assert_eq!(log.lock().len(), 6);
+
+        let guybrush = ladder.rating("guybrush");
+        assert_eq!((guybrush.duels, guybrush.wins), (3, 2));
+        assert!((ladder.rating("carla").rating + guybrush.rating - 3000.0).abs() < 1e-9);
+        let names: Vec<&str> = ladder.standings().iter().map(|x| x.val0()).collect();
+        assert_eq!(names, vec!["guybrush", "carla"]);
+
+        let restored = Ladder::from_str(json::encode(&ladder.ratings)[]).unwrap();
+        assert_eq!(restored.rating("guybrush"), guybrush);
+    }
+}
@@ -0,0 +1,185 @@
+//! Token bucket rate limiting for the `serve` API.
+//!
+//! Every client has a bucket per class of endpoint holding up to `burst`
+//! requests, refilled at `per_minute`. Clients are told apart by their
+//! `X-Api-Key` header if it's one of `Limits::api_keys`, or by their IP
+//! address otherwise.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use time;
+
+/// Clients tracked before buckets are dropped, those full again first, then the longest idle.
+const MAX_CLIENTS: uint = 10000;
+
+/// The requests a client may make.
+#[deriving(Clone, PartialEq, Show)]
+pub struct Quota {
+    /// Requests allowed at once.
+    pub burst: uint,
+    pub per_minute: uint,
+}
+
+impl Quota {
+    /// `n` requests a minute, all of them at once if needed.
+    pub fn per_minute(n: uint) -> Quota {
+        Quota { burst: n, per_minute: n }
+    }
+
+    pub fn with_burst(mut self, burst: uint) -> Quota {
+        self.burst = burst;
+        self
+    }
+}
+
+/// Endpoints sharing a quota.
+#[deriving(Clone, PartialEq, Eq, Hash, Show)]
+pub enum Class {
+    /// Lookups, random insults, GraphQL and the OpenAPI document.
+    Lookups,
+    /// Starting WebSocket duels.
+    Duels,
+}
+
+/// Quotas by class, None for no limit.
+#[deriving(Clone, PartialEq, Show)]
+pub struct Limits {
+    pub lookups: Option<Quota>,
+    pub duels: Option<Quota>,
+    /// API keys given a bucket of their own, any other key is ignored.
+    pub api_keys: Vec<String>,
+}
+
+impl Limits {
+    pub fn unlimited() -> Limits {
+        Limits { lookups: None, duels: None, api_keys: Vec::new() }
+    }
+
+    /// Whether clients sending `key` are told apart by it.
+    pub fn is_api_key(&self, key: &str) -> bool {
+        self.api_keys.iter().any(|x| x[] == key)
+    }
+
+    pub fn quota(&self, class: Class) -> Option<&Quota> {
+        match class {
+            Lookups => self.lookups.as_ref(),
+            Duels => self.duels.as_ref(),
+        }
+    }
+}
+
+struct Bucket {
+    tokens: f64,
+    /// When the tokens were counted, in seconds.
+    updated: f64,
+}
+
+/// Buckets of every client, shared by the connection tasks.
+pub struct RateLimiter {
+    limits: Limits,
+    buckets: Mutex<HashMap<(String, Class), Bucket>>,
+}
+
+impl RateLimiter {
+    pub fn new(limits: Limits) -> RateLimiter {
+        RateLimiter { limits: limits, buckets: Mutex::new(HashMap::new()) }
+    }
+
+    pub fn limits(&self) -> &Limits {
+        &self.limits
+    }
+
+    /// Take a request of `client` to `class` from its bucket, false if it's empty.
+    pub fn allow(&self, client: &str, class: Class) -> bool {
+        self.allow_at(client, class, time::precise_time_ns() as f64 / 1e9)
+    }
+
+    /// Like `allow` at `now` seconds on a monotonic clock.
+    pub fn allow_at(&self, client: &str, class: Class, now: f64) -> bool {
+        let quota = match self.limits.quota(class) {
+            Some(x) => x,
+            None => return true,
+        };
+        let rate = quota.per_minute as f64 / 60.0;
+        let burst = quota.burst as f64;
+
+        let mut buckets = self.buckets.lock();
+        if buckets.len() >= MAX_CLIENTS {
+            // Dropping a full bucket loses nothing, it would start full
+            let full: Vec<(String, Class)> = buckets.iter()
+                .filter(|&(_, b)| b.tokens + (now - b.updated) * rate >= burst)
+                .map(|(k, _)| k.clone())
+                .collect();
+            for key in full.iter() {
+                buckets.remove(key);
+            }
+        }
+        if buckets.len() >= MAX_CLIENTS {
+            let oldest = buckets.iter()
+                .fold(None, |acc: Option<(&(String, Class), f64)>, (k, b)| match acc {
+                    Some((_, updated)) if updated <= b.updated => acc,
+                    _ => Some((k, b.updated)),
+                })
+                .map(|(k, _)| k.clone());
+            if let Some(key) = oldest {
+                buckets.remove(&key);
+            }
+        }
+
+        let key = (client.to_string(), class);
+        if !buckets.contains_key(&key) {
+            buckets.insert(key.clone(), Bucket { tokens: burst, updated: now });
+        }
+        let bucket = buckets.get_mut(&key).unwrap();
+        bucket.tokens = (bucket.tokens + (now - bucket.updated) * rate).min(burst);
+        bucket.updated = now;
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_allow() {
+        let limiter = RateLimiter::new(Limits {
+            lookups: Some(Quota::per_minute(60).with_burst(2)),
+            duels: None,
+            api_keys: Vec::new(),
+        });
+
+        assert!(limiter.allow_at("10.0.0.1", Lookups, 0.0));
+        assert!(limiter.allow_at("10.0.0.1", Lookups, 0.0));
+        assert!(!limiter.allow_at("10.0.0.1", Lookups, 0.5));
+        assert!(limiter.allow_at("10.0.0.2", Lookups, 0.5));
+        assert!(limiter.allow_at("10.0.0.1", Lookups, 1.0));
+        assert!(!limiter.allow_at("10.0.0.1", Lookups, 1.0));
+        for _ in range(0u, 100) {
+            assert!(limiter.allow_at("10.0.0.1", Duels, 1.0));
+        }
+    }
+
+    #[test]
+    fn test_evict_oldest() {
+        let limiter = RateLimiter::new(Limits {
+            lookups: Some(Quota::per_minute(60).with_burst(2)),
+            duels: None,
+            api_keys: Vec::new(),
+        });
+
+        assert!(limiter.allow_at("first", Lookups, 0.0));
+        assert!(limiter.allow_at("first", Lookups, 0.0));
+        // None of the buckets fill up again, the first is dropped as the oldest
+        for i in range(0u, MAX_CLIENTS) {
+            assert!(limiter.allow_at(format!("client {}", i)[], Lookups, 0.1));
+        }
+        assert!(limiter.allow_at("first", Lookups, 0.2));
+        assert!(limiter.buckets.lock().len() <= MAX_CLIENTS);
+    }
+}
@@ -0,0 +1,296 @@
+//! Named packs merged into one dataset, switched on and off at runtime.
+//!
+//! Packs are merged in the order they were registered, an entry of a later
+//! pack overrides the same insult of an earlier one. The registry keeps the
+//! merged dataset after every pack, so enabling or disabling a pack only
+//! merges the packs from it on.
+//!
+//! Indices borrow the merged dataset, rebuild them when `revision` changes.
+//! `load_all` parses pack directories in parallel and merges them once,
+//! for servers loading a dozen locale packs at startup.
+
+use std::cmp;
+use std::mem;
+use std::sync::Future;
+
+use pack::PackManifest;
+use insults::{Insults, global};
+use merge::Overwrite;
+use schema::LoadError;
+
+/// Read the manifest of the pack directory `root` and load its insults.
+fn load_pack(root: &Path) -> Result<(PackManifest, Insults), LoadError> {
+    let manifest = try!(PackManifest::open(root));
+    let insults = try!(manifest.load(root));
+    Ok((manifest, insults))
+}
+
+/// Where a pack came from.
+#[deriving(Clone, PartialEq, Show)]
+pub enum Origin {
+    /// The dataset embedded in the crate.
+    Builtin,
+    /// A pack directory with a manifest.
+    Directory(Path),
+    /// A plugin, see `plugin`.
+    SharedLibrary(Path),
+    /// Put together by the application.
+    Custom,
+}
+
+/// Something providing a pack, like a plugin.
+pub trait InsultSource {
+    fn name(&self) -> String;
+    fn version(&self) -> String;
+    /// The insults of the pack, loaded when the source is registered.
+    fn insults(&self) -> Result<Insults, LoadError>;
+}
+
+/// A pack of the registry.
+#[deriving(Clone, PartialEq, Show)]
+pub struct RegisteredPack {
+    pub name: String,
+    pub version: String,
+    pub origin: Origin,
+    pub enabled: bool,
+    pub insults: Insults,
+}
+
+/// Packs by name, with their merged dataset.
+pub struct PackRegistry {
+    packs: Vec<RegisteredPack>,
+    /// The enabled packs merged up to and including each pack.
+    merged: Vec<Insults>,
+    empty: Insults,
+    revision: uint,
+}
+
+impl PackRegistry {
+    /// A registry without packs.
+    pub fn new() -> PackRegistry {
+        PackRegistry { packs: Vec::new(), merged: Vec::new(), empty: Insults::default(), revision: 0 }
+    }
+
+    /// A registry with the embedded dataset as the enabled pack `builtin`.
+    pub fn with_builtin() -> PackRegistry {
+        let mut res = PackRegistry::new();
+        res.register("builtin", env!("CARGO_PKG_VERSION"), Builtin, global().clone());
+        res
+    }
+
+    /// Add the enabled pack `name`, replacing the pack of that name if there is one.
+    ///
+    /// A replaced pack keeps its place in the order, and whether it's enabled.
+    pub fn register(&mut self, name: &str, version: &str, origin: Origin, insults: Insults)
+            -> Option<RegisteredPack> {
+        let (i, old) = self.insert(name, version, origin, insults);
+        self.rebuild_from(i);
+        old
+    }
+
+    /// Put the pack in place without merging, returning where it went.
+    fn insert(&mut self, name: &str, version: &str, origin: Origin, insults: Insults)
+            -> (uint, Option<RegisteredPack>) {
+        let mut pack = RegisteredPack {
+            name: name.to_string(),
+            version: version.to_string(),
+            origin: origin,
+            enabled: true,
+            insults: insults,
+        };
+        match self.position(name) {
+            Some(i) => {
+                pack.enabled = self.packs[i].enabled;
+                (i, Some(mem::replace(&mut self.packs[i], pack)))
+            },
+            None => {
+                self.packs.push(pack);
+                (self.packs.len() - 1, None)
+            },
+        }
+    }
+
+    /// Register the pack directory `root` under the name in its manifest.
+    pub fn load(&mut self, root: &Path) -> Result<(), LoadError> {
+        let (manifest, insults) = try!(load_pack(root));
+        self.register(manifest.name[], manifest.version[], Directory(root.clone()), insults);
+        Ok(())
+    }
+
+    /// Register the pack directories `roots` in order, returning their names.
+    ///
+    /// Every pack is read and parsed on a task of its own, and the packs are
+    /// merged once they're all in. If a pack fails to load none of them are
+    /// registered, and the error of the first failing one is returned.
+    pub fn load_all(&mut self, roots: &[Path]) -> Result<Vec<String>, LoadError> {
+        let tasks: Vec<Future<Result<(PackManifest, Insults), LoadError>>> = roots.iter()
+            .map(|root| {
+                let root = root.clone();
+                Future::spawn(proc() load_pack(&root))
+            })
+            .collect();
+        // Wait on every task before bailing out, so none is left sending to nobody
+        let results: Vec<Result<(PackManifest, Insults), LoadError>> =
+            tasks.into_iter().map(|x| x.unwrap()).collect();
+        let mut loaded = Vec::with_capacity(results.len());
+        for result in results.into_iter() {
+            loaded.push(try!(result));
+        }
+
+        let mut start = self.packs.len();
+        let mut names = Vec::with_capacity(loaded.len());
+        for (root, (manifest, insults)) in roots.iter().zip(loaded.into_iter()) {
+            let (i, _) = self.insert(manifest.name[], manifest.version[], Directory(root.clone()), insults);
+            start = cmp::min(start, i);
+            names.push(manifest.name);
+        }
+        if !names.is_empty() {
+            self.rebuild_from(start);
+        }
+        Ok(names)
+    }
+
+    /// Register the pack of `source`, like `register`.
+    pub fn register_source(&mut self, source: &InsultSource, origin: Origin) -> Result<(), LoadError> {
+        let insults = try!(source.insults());
+        self.register(source.name()[], source.version()[], origin, insults);
+        Ok(())
+    }
+
+    /// Remove the pack `name`.
+    pub fn unregister(&mut self, name: &str) -> Option<RegisteredPack> {
+        self.position(name).map(|i| {
+            let pack = self.packs.remove(i).unwrap();
+            self.rebuild_from(i);
+            pack
+        })
+    }
+
+    /// Merge the pack `name` again, false if there's no such pack.
+    pub fn enable(&mut self, name: &str) -> bool {
+        self.set_enabled(name, true)
+    }
+
+    /// Leave the pack `name` out of the merged dataset, false if there's no such pack.
+    pub fn disable(&mut self, name: &str) -> bool {
+        self.set_enabled(name, false)
+    }
+
+    fn set_enabled(&mut self, name: &str, enabled: bool) -> bool {
+        match self.position(name) {
+            Some(i) => {
+                if self.packs[i].enabled != enabled {
+                    self.packs[i].enabled = enabled;
+                    self.rebuild_from(i);
+                }
+                true
+            },
+            None => false,
+        }
+    }
+
+    pub fn is_enabled(&self, name: &str) -> bool {
+        self.pack(name).map_or(false, |x| x.enabled)
+    }
+
+    /// The packs in merge order.
+    pub fn packs<'a>(&'a self) -> &'a [RegisteredPack] {
+        self.packs[]
+    }
+
+    pub fn pack<'a>(&'a self, name: &str) -> Option<&'a RegisteredPack> {
+        self.position(name).map(|i| &self.packs[i])
+    }
+
+    /// The enabled packs merged, empty without any.
+    pub fn merged<'a>(&'a self) -> &'a Insults {
+        self.merged.last().unwrap_or(&self.empty)
+    }
+
+    /// Counts the changes to the merged dataset.
+    pub fn revision(&self) -> uint {
+        self.revision
+    }
+
+    fn position(&self, name: &str) -> Option<uint> {
+        self.packs.iter().position(|x| x.name[] == name)
+    }
+
+    /// Merge the packs again from the pack at `start` on.
+    fn rebuild_from(&mut self, start: uint) {
+        self.merged.truncate(start);
+        for i in range(start, self.packs.len()) {
+            let mut merged = match self.merged.last() {
+                Some(x) => x.clone(),
+                None => Insults::default(),
+            };
+            let pack = &self.packs[i];
+            if pack.enabled {
+                // The first enabled pack brings the failed retorts and locale
+                if merged.is_empty() && merged.failed_retort_count() == 0 {
+                    merged = pack.insults.clone();
+                } else {
+                    // Overwriting never fails
+                    merged.merge(&pack.insults, Overwrite).ok();
+                }
+            }
+            self.merged.push(merged);
+        }
+        self.revision += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::TempDir;
+
+    use game::MonkeyIsland1;
+    use insults::{Insults, Entry};
+    use pack::scaffold;
+    use super::*;
+
+    #[test]
+    fn test_registry() {
+        let mut registry = PackRegistry::with_builtin();
+        let cow = "How appropriate. You fight like a cow.";
+        assert_eq!(registry.merged().retort("You fight like a dairy farmer."), Some(cow));
+
+        let mut rules = Insults::default();
+        rules.insert(MonkeyIsland1, "You fight like a dairy farmer.", Entry::new("Moo."));
+        assert!(registry.register("my-server-house-rules", "1.0.0", Custom, rules).is_none());
+        assert_eq!(registry.merged().retort("You fight like a dairy farmer."), Some("Moo."));
+        assert!(!registry.merged().failed_retorts().is_empty());
+
+        let revision = registry.revision();
+        assert!(registry.disable("my-server-house-rules") && !registry.disable("mi-remaster"));
+        assert!(registry.revision() > revision && !registry.is_enabled("my-server-house-rules"));
+        assert_eq!(registry.merged().retort("You fight like a dairy farmer."), Some(cow));
+
+        registry.enable("my-server-house-rules");
+        registry.disable("builtin");
+        assert_eq!(registry.merged().len(), 1);
+        assert_eq!(registry.packs()[0].origin, Builtin);
+        assert!(registry.unregister("builtin").is_some());
+        assert_eq!(registry.packs().len(), 1);
+        assert!(PackRegistry::new().merged().is_empty());
+    }
+
+    #[test]
+    fn test_load_all() {
+        let tmp = TempDir::new("mi_insults").unwrap();
+        let roots = vec![scaffold(tmp.path(), "pack-sv", "sv").unwrap(),
+                         scaffold(tmp.path(), "pack-de", "de").unwrap()];
+        let mut registry = PackRegistry::new();
+        let revision = registry.revision();
+        assert_eq!(registry.load_all(roots[]).unwrap(), vec!["pack-sv".to_string(), "pack-de".to_string()]);
+        assert_eq!(registry.revision(), revision + 1);
+        assert_eq!(registry.packs()[1].origin, Directory(roots[1].clone()));
+        assert_eq!(registry.merged().locale(), "sv");
+        assert_eq!(registry.merged().retort("Replace me with an insult."), Some("Replace me with its retort."));
+
+        let missing = vec![roots[0].clone(), tmp.path().join("missing")];
+        let mut registry = PackRegistry::new();
+        assert!(registry.load_all(missing[]).is_err());
+        assert!(registry.packs().is_empty());
+    }
+}
@@ -0,0 +1,280 @@
+//! Validation of data files, with errors pointing at the offending entry.
+
+use std::fmt;
+use std::io::IoError;
+use serialize::json::{mod, Json};
+
+use game::Game;
+
+/// Why a dataset couldn't be loaded.
+#[deriving(Clone, PartialEq)]
+pub enum LoadError {
+    /// The file couldn't be read.
+    Io(IoError),
+    /// The file doesn't have the expected SHA-256, the expected and actual digests.
+    Checksum(String, String),
+    /// The file is gzip compressed but couldn't be decompressed.
+    Compression(String),
+    /// The database failed.
+    Database(String),
+    /// The server didn't send the dataset.
+    Http(String),
+    /// A plugin library couldn't be loaded or didn't provide a dataset.
+    Library(String),
+    /// The file isn't valid JSON.
+    Syntax(json::ParserError),
+    /// The file uses a layout newer than this version of the crate.
+    Migration(String),
+    /// The JSON doesn't have the layout of `insults.json`.
+    ///
+    /// Holds the path to the offending value, like the game section and
+    /// insult, and what is wrong with it.
+    Schema(Vec<String>, String),
+}
+
+impl fmt::Show for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Io(ref e) => write!(f, "file error: {}", e),
+            Checksum(ref expected, ref actual) =>
+                write!(f, "checksum error: expected sha256 {}, got {}", expected, actual),
+            Compression(ref e) => write!(f, "gzip error: {}", e),
+            Database(ref e) => write!(f, "database error: {}", e),
+            Http(ref e) => write!(f, "http error: {}", e),
+            Library(ref e) => write!(f, "plugin error: {}", e),
+            Syntax(ref e) => write!(f, "json error: {}", e),
+            Migration(ref e) => write!(f, "migration error: {}", e),
+            Schema(ref path, ref msg) if path.is_empty() => write!(f, "schema error: {}", msg),
+            Schema(ref path, ref msg) =>
+                write!(f, "schema error: {} {}", path.connect(" → "), msg),
+        }
+    }
+}
+
+/// Check that `data` has the current layout of `insults.json`.
+pub fn validate(data: &Json) -> Result<(), LoadError> {
+    let sections = match *data {
+        json::Object(ref x) => x,
+        _ => return Err(Schema(Vec::new(), "the dataset is not an object".to_string())),
+    };
+
+    match sections.get(&"failed_retorts".to_string()) {
+        Some(&json::List(ref retorts)) => {
+            for (i, retort) in retorts.iter().enumerate() {
+                try!(expect_string(retort, vec!["failed_retorts".to_string(), format!("index {}", i)]));
+            }
+        },
+        Some(_) => return Err(error(vec!["failed_retorts".to_string()], "is not a list")),
+        None => return Err(error(vec!["failed_retorts".to_string()], "is missing")),
+    }
+    match sections.get(&"opponent_failed_retorts".to_string()) {
+        Some(&json::List(ref retorts)) => {
+            for (i, retort) in retorts.iter().enumerate() {
+                let path = vec!["opponent_failed_retorts".to_string(), format!("index {}", i)];
+                try!(expect_string(retort, path));
+            }
+        },
+        Some(_) => return Err(error(vec!["opponent_failed_retorts".to_string()], "is not a list")),
+        None => {},
+    }
+
+    for game in Game::all().iter() {
+        let section = game.key().to_string();
+        let entries = match sections.get(&section) {
+            Some(&json::Object(ref x)) => x,
+            Some(_) => return Err(error(vec![section.clone()], "is not an object")),
+            None => return Err(error(vec![section.clone()], "is missing")),
+        };
+        for (insult, entry) in entries.iter() {
+            try!(validate_entry(*game, insult[], entry));
+        }
+    }
+
+    if let Some(x) = sections.get(&"locale".to_string()) {
+        try!(expect_string(x, vec!["locale".to_string()]));
+    }
+    match sections.get(&"canonical".to_string()) {
+        Some(&json::Object(ref ids)) => {
+            for (insult, id) in ids.iter() {
+                let path = vec!["canonical".to_string(), format!("key '{}'", abbreviate(insult[]))];
+                try!(expect_string(id, path));
+            }
+        },
+        Some(_) => return Err(error(vec!["canonical".to_string()], "is not an object")),
+        None => {},
+    }
+    match sections.get(&"lookup_priority".to_string()) {
+        Some(&json::List(ref keys)) => {
+            for (i, key) in keys.iter().enumerate() {
+                let path = vec!["lookup_priority".to_string(), format!("index {}", i)];
+                if key.as_string().and_then(Game::from_key).is_none() {
+                    return Err(error(path, "is not a game key"));
+                }
+            }
+        },
+        Some(_) => return Err(error(vec!["lookup_priority".to_string()], "is not a list")),
+        None => {},
+    }
+    match sections.get(&"banter".to_string()) {
+        Some(&json::Object(ref banter)) => {
+            for name in ["taunts", "victory", "defeat"].iter() {
+                match banter.get(&name.to_string()) {
+                    Some(&json::List(ref lines)) => {
+                        for (i, line) in lines.iter().enumerate() {
+                            try!(expect_string(line, vec!["banter".to_string(), name.to_string(),
+                                                          format!("index {}", i)]));
+                        }
+                    },
+                    Some(_) => return Err(error(vec!["banter".to_string(), name.to_string()], "is not a list")),
+                    None => {},
+                }
+            }
+        },
+        Some(_) => return Err(error(vec!["banter".to_string()], "is not an object")),
+        None => {},
+    }
+    match sections.get(&"dialogues".to_string()) {
+        Some(&json::Object(ref trees)) => {
+            for (name, tree) in trees.iter() {
+                try!(expect_dialogue(tree, vec!["dialogues".to_string(), format!("key '{}'", name)]));
+            }
+        },
+        Some(_) => return Err(error(vec!["dialogues".to_string()], "is not an object")),
+        None => {},
+    }
+    Ok(())
+}
+
+/// Check the entry of `insult` in the section of `game`.
+pub fn validate_entry(game: Game, insult: &str, entry: &Json) -> Result<(), LoadError> {
+    let path = vec![game.key().to_string(), format!("key '{}'", abbreviate(insult))];
+    let fields = match *entry {
+        json::Object(ref x) => x,
+        _ => return Err(error(path, "has non-string value")),
+    };
+    match fields.get(&"retort".to_string()) {
+        Some(retort) => try!(expect_string(retort, field(&path, "retort"))),
+        None => return Err(error(field(&path, "retort"), "is missing")),
+    }
+    for name in ["speaker", "context", "rating"].iter() {
+        if let Some(x) = fields.get(&name.to_string()) {
+            try!(expect_string(x, field(&path, *name)));
+        }
+    }
+    match fields.get(&"rating".to_string()).and_then(|x| x.as_string()) {
+        Some("everyone") | Some("harsh") | None => (),
+        Some(_) => return Err(error(field(&path, "rating"), "is not everyone or harsh")),
+    }
+    for name in ["audio", "retort_audio"].iter() {
+        if let Some(x) = fields.get(&name.to_string()) {
+            try!(expect_clip(x, field(&path, *name)));
+        }
+    }
+    Ok(())
+}
+
+/// Check a dialogue line, a `line` with an optional `speaker` and `replies`.
+fn expect_dialogue(value: &Json, path: Vec<String>) -> Result<(), LoadError> {
+    let fields = match *value {
+        json::Object(ref x) => x,
+        _ => return Err(error(path, "is not an object")),
+    };
+    match fields.get(&"line".to_string()) {
+        Some(line) => try!(expect_string(line, field(&path, "line"))),
+        None => return Err(error(field(&path, "line"), "is missing")),
+    }
+    if let Some(x) = fields.get(&"speaker".to_string()) {
+        try!(expect_string(x, field(&path, "speaker")));
+    }
+    match fields.get(&"replies".to_string()) {
+        Some(&json::List(ref replies)) => {
+            for (i, reply) in replies.iter().enumerate() {
+                try!(expect_dialogue(reply, field(&path, format!("reply {}", i)[])));
+            }
+        },
+        Some(_) => return Err(error(field(&path, "replies"), "is not a list")),
+        None => {},
+    }
+    Ok(())
+}
+
+fn error(path: Vec<String>, msg: &str) -> LoadError {
+    Schema(path, msg.to_string())
+}
+
+fn field(path: &Vec<String>, name: &str) -> Vec<String> {
+    let mut res = path.clone();
+    res.push(name.to_string());
+    res
+}
+
+fn expect_string(value: &Json, path: Vec<String>) -> Result<(), LoadError> {
+    match *value {
+        json::String(_) => Ok(()),
+        _ => Err(error(path, "has non-string value")),
+    }
+}
+
+/// Check an audio clip, a `file` with optional millisecond offsets.
+fn expect_clip(value: &Json, path: Vec<String>) -> Result<(), LoadError> {
+    let fields = match *value {
+        json::Object(ref x) => x,
+        _ => return Err(error(path, "is not an object")),
+    };
+    match fields.get(&"file".to_string()) {
+        Some(file) => try!(expect_string(file, field(&path, "file"))),
+        None => return Err(error(field(&path, "file"), "is missing")),
+    }
+    for name in ["start_ms", "end_ms"].iter() {
+        match fields.get(&name.to_string()) {
+            Some(x) if x.as_u64().is_none() => return Err(error(field(&path, *name), "is not a positive number")),
+            _ => (),
+        }
+    }
+    Ok(())
+}
+
+/// Shorten long insults so errors stay on one line.
+fn abbreviate(s: &str) -> String {
+    let max = 20;
+    if s.char_len() <= max {
+        s.to_string()
+    } else {
+        let mut res: String = s.chars().take(max - 1).collect();
+        res.push('…');
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serialize::json;
+
+    use insults::Insults;
+    use super::*;
+
+    #[test]
+    fn test_validate() {
+        let mi = Insults::try_from_str(r#"{
+            "failed_retorts": [],
+            "monkey_island1": {},
+            "sword_master": {},
+            "monkey_island3": { "Would you like to be a pirate?": 42 },
+            "captain_rottingham": {},
+            "monkey_island4": {}
+        }"#);
+        let e = mi.err().unwrap();
+        assert_eq!(e, Schema(vec!["monkey_island3".to_string(),
+                                  "key 'Would you like to b…'".to_string()],
+                             "has non-string value".to_string()));
+        assert_eq!(e.to_string()[],
+            "schema error: monkey_island3 → key 'Would you like to b…' has non-string value");
+
+        let missing = json::from_str(r#"{ "version": 2, "failed_retorts": [] }"#).unwrap();
+        assert_eq!(validate(&missing), Err(Schema(vec!["monkey_island1".to_string()],
+                                                  "is missing".to_string())));
+
+        assert!(Insults::try_from_str("{").is_err());
+        assert!(Insults::try_from_path("insults.json").is_ok());
+    }
+}
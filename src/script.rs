@@ -0,0 +1,384 @@
+//! Rules intercepting lookups and duel events, with the `scripting` feature.
+//!
+//! Operators change the answers without recompiling. No embeddable
+//! scripting engine builds with this compiler, so scripts are rules in a
+//! small language of their own, one rule per line:
+//!
+//! ```text
+//! # Meta-retorts
+//! insult is "You fight like a dairy farmer." retort "Moo."
+//! unknown retort "I don't know {insult}, but I know you."
+//!
+//! # Answers from the lookup API
+//! insult from monkey_island3 retort "{retort} Arr!"
+//!
+//! # Themed substitutions, applied to every retort
+//! replace "sword" with "cutlass"
+//!
+//! # Easter eggs for a date or a range of dates
+//! on 10-31 insult contains "ghost" retort "Boo!"
+//! on 12-24..12-26 event duel_won player say "Ho ho ho!"
+//! ```
+//!
+//! In a retort `{insult}` is the insult it answers, `{retort}` the retort
+//! of the dataset and `{game}` the key of the game of the insult, both
+//! empty if the dataset doesn't know it. `from` takes a game key,
+//! `contains` ignores case. Substitutions don't touch `{insult}`.
+//! The first matching rule wins, the dataset answers when none matches.
+//! Events are named like the `DuelEvent`s, `insult_thrown` to `narrated`,
+//! and match either side unless `player` or `opponent` follows.
+
+use time;
+
+use duel::{DuelEvent, DuelObserver, Side, Player, Opponent};
+use duel::{InsultThrown, RetortSucceeded, RetortFailed, GaveUp, HintGiven};
+use game::Game;
+use duel::{RoundWon, ServeTaken, SuddenDeathStarted, DuelWon, Narrated};
+use insults::{mod, Insults};
+use normalize::nfc;
+
+/// Days a rule applies, month and day inclusive.
+#[deriving(Clone, PartialEq, Show)]
+struct Dates {
+    from: (uint, uint),
+    to: (uint, uint),
+}
+
+impl Dates {
+    fn contains(&self, date: (uint, uint)) -> bool {
+        if self.from <= self.to {
+            self.from <= date && date <= self.to
+        } else {
+            // Over new year
+            date >= self.from || date <= self.to
+        }
+    }
+}
+
+#[deriving(Clone, PartialEq, Show)]
+enum Rule {
+    Is(String, String),
+    Contains(String, String),
+    From(Game, String),
+    Unknown(String),
+    Replace(String, String),
+    Event(String, Option<Side>, String),
+}
+
+/// Rules parsed from a script.
+#[deriving(Clone, PartialEq, Show)]
+pub struct Script {
+    rules: Vec<(Option<Dates>, Rule)>,
+}
+
+#[deriving(Clone, PartialEq, Show)]
+enum Token {
+    Word(String),
+    Quoted(String),
+}
+
+/// The words and quoted strings of `line`, up to a comment.
+fn tokenize(line: &str) -> Result<Vec<Token>, String> {
+    let mut res = Vec::new();
+    let mut chars = line.chars().peekable();
+    loop {
+        match chars.peek().map(|c| *c) {
+            None | Some('#') => break,
+            Some(c) if c.is_whitespace() => { chars.next(); },
+            Some('"') => {
+                chars.next();
+                let mut s = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c) => s.push(c),
+                            None => return Err("unterminated string".to_string()),
+                        },
+                        Some(c) => s.push(c),
+                        None => return Err("unterminated string".to_string()),
+                    }
+                }
+                res.push(Quoted(s));
+            },
+            Some(_) => {
+                let mut s = String::new();
+                loop {
+                    match chars.peek().map(|c| *c) {
+                        Some(c) if !c.is_whitespace() && c != '"' => { s.push(c); chars.next(); },
+                        _ => break,
+                    }
+                }
+                res.push(Word(s));
+            },
+        }
+    }
+    Ok(res)
+}
+
+/// A month and day like `10-31`.
+fn parse_date(s: &str) -> Option<(uint, uint)> {
+    let parts: Vec<&str> = s.split('-').collect();
+    if parts.len() != 2 {
+        return None;
+    }
+    match (from_str::<uint>(parts[0]), from_str::<uint>(parts[1])) {
+        (Some(m), Some(d)) if m >= 1 && m <= 12 && d >= 1 && d <= 31 => Some((m, d)),
+        _ => None,
+    }
+}
+
+fn parse_dates(s: &str) -> Option<Dates> {
+    let parts: Vec<&str> = s.split_str("..").collect();
+    match parts[] {
+        [day] => parse_date(day).map(|x| Dates { from: x, to: x }),
+        [from, to] => match (parse_date(from), parse_date(to)) {
+            (Some(from), Some(to)) => Some(Dates { from: from, to: to }),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn word(s: &str) -> Token {
+    Word(s.to_string())
+}
+
+fn parse_rule(tokens: &[Token]) -> Result<Rule, String> {
+    match tokens {
+        [ref a, ref b, Quoted(ref insult), ref c, Quoted(ref retort)]
+                if *a == word("insult") && *b == word("is") && *c == word("retort") =>
+            Ok(Is(nfc(insult[]), retort.clone())),
+        [ref a, ref b, Quoted(ref text), ref c, Quoted(ref retort)]
+                if *a == word("insult") && *b == word("contains") && *c == word("retort") =>
+            Ok(Contains(lower(text[]), retort.clone())),
+        [ref a, ref b, Word(ref key), ref c, Quoted(ref retort)]
+                if *a == word("insult") && *b == word("from") && *c == word("retort") =>
+            match Game::from_key(key[]) {
+                Some(game) => Ok(From(game, retort.clone())),
+                None => Err(format!("unknown game {}", key)),
+            },
+        [ref a, ref b, Quoted(ref retort)] if *a == word("unknown") && *b == word("retort") =>
+            Ok(Unknown(retort.clone())),
+        [ref a, Quoted(ref from), ref b, Quoted(ref to)] if *a == word("replace") && *b == word("with") =>
+            Ok(Replace(from.clone(), to.clone())),
+        [ref a, Word(ref kind), ref rest.., ref b, Quoted(ref line)]
+                if *a == word("event") && *b == word("say") => {
+            if !EVENTS.contains(&kind[]) {
+                return Err(format!("unknown event {}", kind));
+            }
+            let side = match rest {
+                [] => None,
+                [ref x] if *x == word("player") => Some(Player),
+                [ref x] if *x == word("opponent") => Some(Opponent),
+                _ => return Err("expected player or opponent".to_string()),
+            };
+            Ok(Event(kind.clone(), side, line.clone()))
+        },
+        _ => Err("expected insult, unknown, replace or event".to_string()),
+    }
+}
+
+/// Names of the events, in the order of `DuelEvent`.
+static EVENTS: [&'static str, ..10] = ["insult_thrown", "retort_succeeded", "retort_failed", "gave_up",
+    "hint_given", "round_won", "serve_taken", "sudden_death_started", "duel_won", "narrated"];
+
+/// The name of `event` and the side it happened to.
+fn event_name(event: &DuelEvent) -> (&'static str, Option<Side>) {
+    match *event {
+        InsultThrown(side, _) => (EVENTS[0], Some(side)),
+        RetortSucceeded(side, _) => (EVENTS[1], Some(side)),
+        RetortFailed(side, _) => (EVENTS[2], Some(side)),
+        GaveUp(side) => (EVENTS[3], Some(side)),
+        HintGiven(_) => (EVENTS[4], None),
+        RoundWon(side) => (EVENTS[5], Some(side)),
+        ServeTaken(side) => (EVENTS[6], Some(side)),
+        SuddenDeathStarted => (EVENTS[7], None),
+        DuelWon(side) => (EVENTS[8], Some(side)),
+        Narrated(_) => (EVENTS[9], None),
+    }
+}
+
+fn lower(s: &str) -> String {
+    s.chars().map(|c| c.to_lowercase()).collect()
+}
+
+/// Today's month and day.
+fn today() -> (uint, uint) {
+    let now = time::now();
+    (now.tm_mon as uint + 1, now.tm_mday as uint)
+}
+
+impl Script {
+    /// Parse a script, errors point at the line.
+    pub fn parse(s: &str) -> Result<Script, String> {
+        let mut rules = Vec::new();
+        for (i, line) in s.lines().enumerate() {
+            let tokens = match tokenize(line) {
+                Ok(x) => x,
+                Err(e) => return Err(format!("line {}: {}", i + 1, e)),
+            };
+            if tokens.is_empty() {
+                continue;
+            }
+            let (dates, rest) = match tokens[] {
+                [ref on, Word(ref dates), rest..] if *on == word("on") => match parse_dates(dates[]) {
+                    Some(x) => (Some(x), rest),
+                    None => return Err(format!("line {}: bad dates {}", i + 1, dates)),
+                },
+                rest => (None, rest),
+            };
+            match parse_rule(rest) {
+                Ok(rule) => rules.push((dates, rule)),
+                Err(e) => return Err(format!("line {}: {}", i + 1, e)),
+            }
+        }
+        Ok(Script { rules: rules })
+    }
+
+    /// Load a script from a file.
+    pub fn from_path(location: &str) -> Script {
+        match Script::parse(insults::read_file(location)[]) {
+            Ok(x) => x,
+            Err(e) => panic!("script error: {}: {}", location, e),
+        }
+    }
+
+    /// The rules applying on `date`.
+    fn rules<'a>(&'a self, date: (uint, uint)) -> Vec<&'a Rule> {
+        self.rules.iter()
+            .filter(|&&(ref dates, _)| dates.as_ref().map_or(true, |x| x.contains(date)))
+            .map(|&(_, ref rule)| rule)
+            .collect()
+    }
+}
+
+/// A dataset with a script answering first.
+pub struct ScriptedInsults<'a> {
+    insults: &'a Insults,
+    script: &'a Script,
+    date: (uint, uint),
+    said: Vec<String>,
+}
+
+impl<'a> ScriptedInsults<'a> {
+    /// The rules of `script` for today over `insults`.
+    pub fn new(insults: &'a Insults, script: &'a Script) -> ScriptedInsults<'a> {
+        ScriptedInsults { insults: insults, script: script, date: today(), said: Vec::new() }
+    }
+
+    /// Apply the rules of `month` and `day` instead of today's.
+    pub fn on(mut self, month: uint, day: uint) -> ScriptedInsults<'a> {
+        self.date = (month, day);
+        self
+    }
+
+    /// The retort of the first matching rule or the dataset, with the substitutions.
+    pub fn retort(&self, insult: &str) -> Option<String> {
+        let rules = self.rules();
+        let normalized = nfc(insult);
+        let lowered = lower(insult);
+        let game = self.insults.game_of(insult);
+        let known = self.insults.retort(insult);
+        let scripted = rules.iter().filter_map(|rule| match **rule {
+            Is(ref x, ref retort) if *x == normalized => Some(retort[]),
+            Contains(ref x, ref retort) if lowered[].contains(x[]) => Some(retort[]),
+            From(x, ref retort) if Some(x) == game => Some(retort[]),
+            _ => None,
+        }).next();
+        let retort = match scripted.or(known) {
+            Some(x) => x.to_string(),
+            None => match rules.iter().filter_map(|rule| match **rule {
+                Unknown(ref retort) => Some(retort[]),
+                _ => None,
+            }).next() {
+                Some(x) => x.to_string(),
+                None => return None,
+            },
+        };
+        let mut res = retort
+            .replace("{retort}", known.unwrap_or(""))
+            .replace("{game}", game.map_or("", |x| x.key()));
+        for rule in rules.iter() {
+            if let Replace(ref from, ref to) = **rule {
+                res = res.replace(from[], to[]);
+            }
+        }
+        Some(res.replace("{insult}", insult))
+    }
+
+    /// What the script says on `event`, if a rule matches it.
+    pub fn event(&self, event: &DuelEvent) -> Option<String> {
+        let (name, side) = event_name(event);
+        self.rules().into_iter().filter_map(|rule| match *rule {
+            Event(ref kind, ref only, ref line) if kind[] == name && (only.is_none() || *only == side) =>
+                Some(line.clone()),
+            _ => None,
+        }).next()
+    }
+
+    /// The lines said on the events observed so far, see `DuelObserver`.
+    pub fn said<'b>(&'b self) -> &'b [String] {
+        self.said[]
+    }
+
+    fn rules(&self) -> Vec<&'a Rule> {
+        self.script.rules(self.date)
+    }
+}
+
+/// Collects what the script says on every event of a duel.
+impl<'a> DuelObserver for ScriptedInsults<'a> {
+    fn on_event(&mut self, event: &DuelEvent) {
+        if let Some(line) = self.event(event) {
+            self.said.push(line);
+        }
+    }
+}
+
+impl Insults {
+    /// The dataset answering through the rules of `script`.
+    pub fn scripted<'a>(&'a self, script: &'a Script) -> ScriptedInsults<'a> {
+        ScriptedInsults::new(self, script)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use duel::{DuelObserver, Player, Opponent, DuelWon};
+    use insults::Insults;
+    use super::*;
+
+    #[test]
+    fn test_script() {
+        let script = Script::parse(r#"
+            # House rules
+            insult is "You fight like a dairy farmer." retort "Moo."
+            unknown retort "I don't know \"{insult}\", {game}."
+            insult from monkey_island3 retort "{retort} Arr!"
+            replace "sword" with "cutlass"
+            on 10-31 insult contains "WEARING" retort "Boo!"
+            on 12-24..12-26 event duel_won player say "Ho ho ho!"
+        "#).unwrap();
+        let mi = Insults::new("insults.json");
+        let scripted = mi.scripted(&script).on(6, 1);
+        assert_eq!(scripted.retort("You fight like a dairy farmer."), Some("Moo.".to_string()));
+        assert_eq!(scripted.retort("Arr"), Some("I don't know \"Arr\", .".to_string()));
+        assert_eq!(scripted.retort("Your sword is no match"), Some("I don't know \"Your sword is no match\", .".to_string()));
+        let insult = "Would you like to be buried, or cremated?";
+        assert_eq!(scripted.retort(insult), Some(format!("{} Arr!", mi.retort(insult).unwrap())));
+        assert_eq!(scripted.retort("Have you stopped wearing diapers yet?"),
+                   Some("Why, did you want to borrow one?".to_string()));
+        assert_eq!(mi.scripted(&script).on(10, 31).retort("Have you stopped wearing diapers yet?"),
+                   Some("Boo!".to_string()));
+
+        let mut christmas = mi.scripted(&script).on(12, 25);
+        christmas.on_event(&DuelWon(Opponent));
+        christmas.on_event(&DuelWon(Player));
+        assert_eq!(christmas.said(), ["Ho ho ho!".to_string()][]);
+
+        assert!(Script::parse("insult is \"Arr\"").is_err());
+        assert!(Script::parse("insult from mi3 retort \"Arr\"").is_err());
+        assert_eq!(Script::parse("on 13-01 unknown retort \"?\"").unwrap_err()[], "line 1: bad dates 13-01");
+    }
+}
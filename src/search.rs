@@ -0,0 +1,77 @@
+//! Case-insensitive search through insults and retorts.
+
+use game::Game;
+use insults::Insults;
+
+/// Where a search matched.
+#[deriving(Clone, PartialEq, Eq, Show)]
+pub enum Field {
+    Insult,
+    Retort,
+}
+
+/// An insult matching a search.
+#[deriving(Clone, PartialEq, Eq, Show)]
+pub struct SearchHit<'a> {
+    pub game: Game,
+    pub insult: &'a str,
+    pub retort: &'a str,
+    /// Whether the insult or the retort matched.
+    pub field: Field,
+}
+
+fn lower(s: &str) -> String {
+    s.chars().map(|c| c.to_lowercase()).collect()
+}
+
+impl Insults {
+    /// Insults and retorts containing `query`, ignoring case, in game order.
+    ///
+    /// An insult whose retort also matches gives two hits. Only this dataset
+    /// is searched, not its fallback.
+    pub fn search<'a>(&'a self, query: &str) -> Vec<SearchHit<'a>> {
+        let query = lower(query);
+        let mut res = Vec::new();
+        for game in Game::all().iter() {
+            for (insult, entry) in self.entries(*game).into_iter() {
+                let hit = |field| SearchHit {
+                    game: *game,
+                    insult: insult[],
+                    retort: entry.retort[],
+                    field: field,
+                };
+                if lower(insult[])[].contains(query[]) {
+                    res.push(hit(Insult));
+                }
+                if lower(entry.retort[])[].contains(query[]) {
+                    res.push(hit(Retort));
+                }
+            }
+        }
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use game::MonkeyIsland1;
+    use insults::Insults;
+    use super::*;
+
+    #[test]
+    fn test_search() {
+        let mi = Insults::new("insults.json");
+
+        let hits = mi.search("DAIRY farmer");
+        assert_eq!(hits, vec![SearchHit {
+            game: MonkeyIsland1,
+            insult: "You fight like a dairy farmer.",
+            retort: "How appropriate. You fight like a cow.",
+            field: Insult,
+        }]);
+
+        let hits = mi.search("like a cow");
+        assert!(hits.iter().any(|x| x.field == Retort && x.insult == "You fight like a dairy farmer."));
+        assert!(mi.search("no such insult").is_empty());
+    }
+}
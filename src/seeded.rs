@@ -0,0 +1,111 @@
+//! Reproducible randomness for tests and replays.
+//!
+//! Every random API takes its `Rng`, so passing a seeded one makes the
+//! insults thrown, the opponent's answers and the quiz choices repeat from
+//! run to run. `Insults::with_seed` bundles a dataset with its own seeded
+//! generator:
+//!
+//! ```
+//! use mi_insults::Insults;
+//!
+//! let mut a = Insults::new("insults.json").with_seed(42);
+//! let mut b = Insults::new("insults.json").with_seed(42);
+//! assert_eq!(a.rand_insult(), b.rand_insult());
+//! ```
+
+use std::rand::{Isaac64Rng, SeedableRng};
+
+use insults::Insults;
+
+/// A dataset owning a seeded random generator.
+///
+/// Derefs to the dataset for lookups.
+pub struct SeededInsults {
+    insults: Insults,
+    rng: Isaac64Rng,
+}
+
+impl SeededInsults {
+    pub fn new(insults: Insults, seed: u64) -> SeededInsults {
+        SeededInsults { insults: insults, rng: SeedableRng::from_seed([seed][]) }
+    }
+
+    /// Start the sequence over from `seed`.
+    pub fn reseed(&mut self, seed: u64) {
+        self.rng.reseed([seed][]);
+    }
+
+    /// The generator, to pass on to the duel opponent or a quiz.
+    pub fn rng(&mut self) -> &mut Isaac64Rng {
+        &mut self.rng
+    }
+
+    pub fn insults(&self) -> &Insults {
+        &self.insults
+    }
+
+    /// The dataset and the generator, to use both at once.
+    pub fn split<'a>(&'a mut self) -> (&'a Insults, &'a mut Isaac64Rng) {
+        (&self.insults, &mut self.rng)
+    }
+
+    pub fn rand_insult<'a>(&'a mut self) -> &'a str {
+        self.insults.rand_insult(&mut self.rng)[]
+    }
+
+    pub fn rand_failed_retort<'a>(&'a mut self) -> &'a str {
+        self.insults.rand_failed_retort(&mut self.rng)
+    }
+
+    pub fn retort_or_rand_fail<'a>(&'a mut self, insult: &str) -> &'a str {
+        self.insults.retort_or_rand_fail(insult, &mut self.rng)
+    }
+
+    pub fn generate_insult_like(&mut self) -> String {
+        self.insults.generate_insult_like(&mut self.rng)
+    }
+}
+
+impl Deref<Insults> for SeededInsults {
+    fn deref(&self) -> &Insults {
+        &self.insults
+    }
+}
+
+impl Insults {
+    /// This dataset with a random generator seeded by `seed`.
+    pub fn with_seed(self, seed: u64) -> SeededInsults {
+        SeededInsults::new(self, seed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use duel::Ai;
+    use insults::Insults;
+    use quiz::Quiz;
+
+    #[test]
+    fn test_seeded() {
+        let mut a = Insults::new("insults.json").with_seed(7);
+        let mut b = Insults::new("insults.json").with_seed(7);
+        for _ in range(0u, 10) {
+            assert_eq!(a.rand_insult().to_string(), b.rand_insult().to_string());
+            assert_eq!(a.rand_failed_retort().to_string(), b.rand_failed_retort().to_string());
+        }
+
+        let insult = "You fight like a dairy farmer.";
+        {
+            let ai = Ai::default();
+            let ((mi_a, rng_a), (mi_b, rng_b)) = (a.split(), b.split());
+            assert_eq!(ai.retort(mi_a, insult, rng_a), ai.retort(mi_b, insult, rng_b));
+        }
+
+        let mi = Insults::new("insults.json");
+        let quiz = Quiz::new(&mi, 3);
+        a.reseed(1);
+        b.reseed(1);
+        assert_eq!(quiz.question(insult, a.rng()), quiz.question(insult, b.rng()));
+        assert_eq!(a.retort(insult), Some("How appropriate. You fight like a cow."));
+    }
+}
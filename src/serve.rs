@@ -0,0 +1,469 @@
+//! HTTP API over a dataset, for clients written in other languages.
+//!
+//! * `GET /retort?insult=...` returns `{"insult": ..., "retort": ...}`
+//! * `GET /random` returns a random insult with its retort
+//! * `GET /games/:game/insults?offset=N&limit=N` returns `{"insults": [...],
+//!   "total": N}` for a game key like `monkey_island1`, every insult without
+//!   `limit`
+//! * `GET /leaderboard?top=N` returns `{"standings": [...]}`, the best 10
+//!   players by default
+//! * `GET /leaderboard/:player` returns the rank and standing of a player
+//! * `GET /profiles/:id/knowledge` and `PUT /profiles/:id/knowledge` read and
+//!   replace the insults a player has learned, see the `profile` module
+//! * `GET /openapi.json` returns the OpenAPI document of the API
+//! * `GET /graphql?query=...` or `POST /graphql` with `{"query": ...}` runs a
+//!   GraphQL query with the `graphql` feature
+//! * `/duel` plays live duels over WebSocket with the `websocket` feature
+//!
+//! `Server::handle` is transport agnostic like `Profiles::handle`,
+//! `Server::listen` serves it over HTTP/1.0 with a task per connection.
+//! Give the server `Limits` to answer clients over their quota with 429, see
+//! the `ratelimit` module.
+
+use std::ascii::AsciiExt;
+use std::collections::TreeMap;
+use std::io::{IoResult, TcpListener, TcpStream, Acceptor, Listener};
+use std::num::from_str_radix;
+use std::str;
+use std::sync::{Arc, Mutex};
+use std::uint;
+use serialize::json::{mod, Json, ToJson};
+
+use game::Game;
+#[cfg(feature = "graphql")]
+use graphql::Schema;
+use insults::Insults;
+use leaderboard::{Leaderboard, Standing};
+use openapi;
+use profile::{Profiles, Response};
+use ratelimit::{Class, Limits, RateLimiter, Lookups, Duels};
+#[cfg(feature = "websocket")]
+use websocket;
+
+/// Largest request accepted, headers and body.
+const MAX_REQUEST: uint = 64 * 1024;
+
+/// Standings returned by the leaderboard without `top`.
+const DEFAULT_TOP: uint = 10;
+
+/// Serves a shared dataset.
+#[deriving(Clone)]
+pub struct Server {
+    insults: Arc<Insults>,
+    limiter: Option<Arc<RateLimiter>>,
+    leaderboard: Arc<Mutex<Leaderboard>>,
+    profiles: Arc<Mutex<Profiles>>,
+}
+
+impl Server {
+    pub fn new(insults: Arc<Insults>) -> Server {
+        Server {
+            insults: insults,
+            limiter: None,
+            leaderboard: Arc::new(Mutex::new(Leaderboard::in_memory())),
+            profiles: Arc::new(Mutex::new(Profiles::new())),
+        }
+    }
+
+    /// Limit the requests of every client to `limits`.
+    pub fn with_limits(mut self, limits: Limits) -> Server {
+        self.limiter = Some(Arc::new(RateLimiter::new(limits)));
+        self
+    }
+
+    /// Serve `leaderboard` instead of an empty one in memory.
+    pub fn with_leaderboard(mut self, leaderboard: Leaderboard) -> Server {
+        self.leaderboard = Arc::new(Mutex::new(leaderboard));
+        self
+    }
+
+    /// The leaderboard, shared by the connection tasks.
+    pub fn leaderboard(&self) -> Arc<Mutex<Leaderboard>> {
+        self.leaderboard.clone()
+    }
+
+    /// The player profiles, shared by the connection tasks.
+    pub fn profiles(&self) -> Arc<Mutex<Profiles>> {
+        self.profiles.clone()
+    }
+
+    pub fn insults(&self) -> &Insults {
+        &*self.insults
+    }
+
+    /// Handle an API request, `path` includes the query string.
+    pub fn handle(&self, method: &str, path: &str, body: &str) -> Response {
+        let (path, query) = match path.find('?') {
+            Some(i) => (path[..i], path[i + 1..]),
+            None => (path, ""),
+        };
+        let parts: Vec<&str> = path.split('/').filter(|x| !x.is_empty()).collect();
+        if parts == vec!["graphql"] {
+            return self.graphql(method, query, body);
+        }
+        if parts.get(0) == Some(&"profiles") {
+            return self.profiles.lock().handle(self.insults(), method, path, body);
+        }
+        if method != "GET" {
+            return error(405, "method not allowed");
+        }
+
+        if parts == vec!["retort"] {
+            let insult = match query_param(query, "insult") {
+                Some(x) => x,
+                None => return error(400, "missing insult"),
+            };
+            match self.insults().retort(insult[]) {
+                Some(retort) => ok(pair(insult[], retort)),
+                None => error(404, "no retort to that insult"),
+            }
+        } else if parts == vec!["random"] {
+            if self.insults().answered_insults().is_empty() {
+                return error(404, "no insults");
+            }
+            let insult = self.insults().rand_insult_default();
+            match self.insults().retort(insult[]) {
+                Some(retort) => ok(pair(insult[], retort)),
+                None => error(404, "no retort to that insult"),
+            }
+        } else if parts == vec!["leaderboard"] {
+            let n = match query_param(query, "top").map(|x| from_str::<uint>(x[])) {
+                None => DEFAULT_TOP,
+                Some(Some(n)) => n,
+                Some(None) => return error(400, "top must be a number"),
+            };
+            match self.leaderboard.lock().top(n) {
+                Ok(top) => {
+                    let mut res = TreeMap::new();
+                    res.insert("standings".to_string(), top.to_json());
+                    ok(json::Object(res))
+                },
+                Err(e) => error(500, e.to_string()[]),
+            }
+        } else if parts.len() == 2 && parts[0] == "leaderboard" {
+            let player = percent_decode(parts[1]);
+            let board = self.leaderboard.lock();
+            match (board.rank_of(player[]), board.standing(player[])) {
+                (Ok(Some(rank)), Ok(Some(standing))) => ok(ranked(rank, &standing)),
+                (Ok(_), Ok(_)) => error(404, "unknown player"),
+                (Err(e), _) | (_, Err(e)) => error(500, e.to_string()[]),
+            }
+        } else if parts == vec!["openapi.json"] {
+            ok(openapi::openapi())
+        } else if parts.len() == 3 && parts[0] == "games" && parts[2] == "insults" {
+            let game = match Game::from_key(parts[1]) {
+                Some(x) => x,
+                None => return error(404, "unknown game"),
+            };
+            let (offset, limit) = match (number_param(query, "offset"), number_param(query, "limit")) {
+                (Ok(offset), Ok(limit)) => (offset.unwrap_or(0), limit.unwrap_or(uint::MAX)),
+                _ => return error(400, "offset and limit must be numbers"),
+            };
+            let page = self.insults().query().locale(self.insults().locale()).game(game).page(offset, limit);
+            let insults: Vec<Json> = page.items.iter().map(|x| x.insult.to_json()).collect();
+            let mut res = TreeMap::new();
+            res.insert("insults".to_string(), json::List(insults));
+            res.insert("total".to_string(), page.total.to_json());
+            ok(json::Object(res))
+        } else {
+            error(404, "not found")
+        }
+    }
+
+    #[cfg(feature = "graphql")]
+    fn graphql(&self, method: &str, query: &str, body: &str) -> Response {
+        let query = match method {
+            "GET" => query_param(query, "query"),
+            "POST" => json::from_str(body).ok()
+                .and_then(|x| x.find(&"query".to_string()).and_then(|q| q.as_string()).map(|q| q.to_string())),
+            _ => return error(405, "method not allowed"),
+        };
+        match query {
+            Some(query) => ok(Schema::new(self.insults.clone()).execute(query[])),
+            None => error(400, "missing query"),
+        }
+    }
+
+    #[cfg(not(feature = "graphql"))]
+    fn graphql(&self, _: &str, _: &str, _: &str) -> Response {
+        error(404, "built without the graphql feature")
+    }
+
+    /// Serve the API on `host` and `port` until the listener fails.
+    pub fn listen(&self, host: &str, port: u16) -> IoResult<()> {
+        let mut acceptor = try!(TcpListener::bind(host, port).listen());
+        for stream in acceptor.incoming() {
+            let server = self.clone();
+            match stream {
+                Ok(stream) => spawn(proc() {
+                    let _ = server.respond(stream);
+                }),
+                // Only the client failing to connect is affected
+                Err(_) => (),
+            }
+        }
+        Ok(())
+    }
+
+    /// Read one request from `stream` and write the response.
+    fn respond(&self, mut stream: TcpStream) -> IoResult<()> {
+        let req = match try!(read_request(&mut stream)) {
+            Some(x) => x,
+            None => return write_response(&mut stream, &error(400, "bad request")),
+        };
+        let class = if is_duel(&req) { Duels } else { Lookups };
+        if !self.allow(&mut stream, &req, class) {
+            return write_response(&mut stream, &error(429, "too many requests"));
+        }
+
+        if class == Duels {
+            return self.duel(stream, &req);
+        }
+        let res = self.handle(req.method[], req.path[], req.body[]);
+        write_response(&mut stream, &res)
+    }
+
+    /// Whether the client's quota allows `req`, clients are identified by a known API key or IP.
+    fn allow(&self, stream: &mut TcpStream, req: &Request, class: Class) -> bool {
+        let limiter = match self.limiter {
+            Some(ref x) => x,
+            None => return true,
+        };
+        let client = match req.header("X-Api-Key") {
+            // Unknown keys would get a fresh bucket with every request
+            Some(key) if limiter.limits().is_api_key(key) => format!("key {}", key),
+            _ => match stream.peer_name() {
+                Ok(addr) => format!("ip {}", addr.ip),
+                // Can't tell clients apart, but don't let them through unlimited
+                Err(_) => "unknown".to_string(),
+            },
+        };
+        limiter.allow(client[], class)
+    }
+
+    #[cfg(feature = "websocket")]
+    fn duel(&self, stream: TcpStream, req: &Request) -> IoResult<()> {
+        websocket::serve_duel(stream, req, self.insults.clone())
+    }
+
+    #[cfg(not(feature = "websocket"))]
+    fn duel(&self, mut stream: TcpStream, _: &Request) -> IoResult<()> {
+        write_response(&mut stream, &error(404, "built without the websocket feature"))
+    }
+}
+
+/// An HTTP request as read from a connection.
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    /// Header names and values, in the order sent.
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl Request {
+    /// The value of the header `name`, ignoring case.
+    pub fn header<'a>(&'a self, name: &str) -> Option<&'a str> {
+        self.headers.iter()
+            .find(|&&(ref k, _)| k[].eq_ignore_ascii_case(name))
+            .map(|&(_, ref v)| v[])
+    }
+}
+
+/// A WebSocket upgrade to the duel endpoint, see the `websocket` module.
+fn is_duel(req: &Request) -> bool {
+    req.path[] == "/duel" && req.header("Upgrade").map_or(false, |x| x.eq_ignore_ascii_case("websocket"))
+}
+
+/// Read a request, None if it isn't valid HTTP.
+pub fn read_request(stream: &mut TcpStream) -> IoResult<Option<Request>> {
+    let mut data = Vec::new();
+    let mut buf = [0u8, ..4096];
+    let mut head_len = 0;
+    while head_len == 0 {
+        match data[].windows(4).position(|x| x == b"\r\n\r\n") {
+            Some(i) => head_len = i + 4,
+            None if data.len() > MAX_REQUEST => return Ok(None),
+            None => {
+                let n = try!(stream.read(buf));
+                data.push_all(buf[..n]);
+            },
+        }
+    }
+
+    let head = match str::from_utf8(data[..head_len]) {
+        Some(x) => x.to_string(),
+        None => return Ok(None),
+    };
+    let mut lines = head[].lines();
+    let (method, path) = {
+        let words: Vec<&str> = lines.next().unwrap_or("").words().collect();
+        if words.len() < 2 {
+            return Ok(None);
+        }
+        (words[0].to_string(), words[1].to_string())
+    };
+    let mut headers = Vec::new();
+    for line in lines {
+        if let Some(i) = line.find(':') {
+            headers.push((line[..i].trim().to_string(), line[i + 1..].trim().to_string()));
+        }
+    }
+    let content_length = headers.iter()
+        .find(|&&(ref k, _)| k[].eq_ignore_ascii_case("content-length"))
+        .and_then(|&(_, ref v)| from_str::<uint>(v[]))
+        .unwrap_or(0);
+    if content_length > MAX_REQUEST {
+        return Ok(None);
+    }
+
+    while data.len() < head_len + content_length {
+        let n = try!(stream.read(buf));
+        data.push_all(buf[..n]);
+    }
+    let body = match str::from_utf8(data[head_len..head_len + content_length]) {
+        Some(x) => x.to_string(),
+        None => return Ok(None),
+    };
+    Ok(Some(Request { method: method, path: path, headers: headers, body: body }))
+}
+
+/// Write `res` as an HTTP/1.0 response with a JSON body.
+pub fn write_response(stream: &mut TcpStream, res: &Response) -> IoResult<()> {
+    try!(write!(stream, "HTTP/1.0 {} {}\r\n", res.status, reason(res.status)));
+    try!(write!(stream, "Content-Type: application/json\r\nContent-Length: {}\r\n\r\n",
+                res.body.len()));
+    stream.write_str(res.body[])
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        422 => "Unprocessable Entity",
+        429 => "Too Many Requests",
+        500 => "Internal Server Error",
+        _ => "",
+    }
+}
+
+fn ok(body: Json) -> Response {
+    Response { status: 200, body: body.to_string() }
+}
+
+fn error(status: u16, msg: &str) -> Response {
+    let mut res = TreeMap::new();
+    res.insert("error".to_string(), msg.to_json());
+    Response { status: status, body: json::Object(res).to_string() }
+}
+
+fn pair(insult: &str, retort: &str) -> Json {
+    let mut res = TreeMap::new();
+    res.insert("insult".to_string(), insult.to_json());
+    res.insert("retort".to_string(), retort.to_json());
+    json::Object(res)
+}
+
+fn ranked(rank: uint, standing: &Standing) -> Json {
+    let mut res = match standing.to_json() {
+        json::Object(x) => x,
+        _ => unreachable!(),
+    };
+    res.insert("rank".to_string(), rank.to_json());
+    json::Object(res)
+}
+
+/// The number in the parameter `key`, None if it's missing.
+fn number_param(query: &str, key: &str) -> Result<Option<uint>, ()> {
+    match query_param(query, key) {
+        Some(x) => match from_str::<uint>(x[]) {
+            Some(n) => Ok(Some(n)),
+            None => Err(()),
+        },
+        None => Ok(None),
+    }
+}
+
+/// The decoded value of `key` in a query string.
+pub fn query_param(query: &str, key: &str) -> Option<String> {
+    for param in query.split('&') {
+        let (k, v) = match param.find('=') {
+            Some(i) => (param[..i], param[i + 1..]),
+            None => (param, ""),
+        };
+        if percent_decode(k)[] == key {
+            return Some(percent_decode(v));
+        }
+    }
+    None
+}
+
+/// Decode `%XX` escapes and `+` as space, invalid escapes are kept.
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut res = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => res.push(b' '),
+            b'%' if i + 2 < bytes.len() => {
+                let hex = str::from_utf8(bytes[i + 1..i + 3]).and_then(|x| from_str_radix::<u8>(x, 16));
+                match hex {
+                    Some(x) => {
+                        res.push(x);
+                        i += 2;
+                    },
+                    None => res.push(b'%'),
+                }
+            },
+            x => res.push(x),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(res[]).into_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use serialize::json;
+
+    use insults::Insults;
+    use super::*;
+
+    #[test]
+    fn test_handle() {
+        let server = Server::new(Insults::new("insults.json").into_shared());
+
+        let res = server.handle("GET", "/retort?insult=You+fight+like+a+dairy+farmer%2E", "");
+        assert_eq!(res.status, 200);
+        assert_eq!(res.body[], r#"{"insult":"You fight like a dairy farmer.","retort":"How appropriate. You fight like a cow."}"#);
+        assert_eq!(server.handle("GET", "/retort?insult=You're+lazy!", "").status, 404);
+        assert_eq!(server.handle("GET", "/retort", "").status, 400);
+
+        let random = json::from_str(server.handle("GET", "/random", "").body[]).unwrap();
+        let insult = random.find(&"insult".to_string()).unwrap().as_string().unwrap();
+        assert!(server.insults().retort(insult).is_some());
+
+        let res = server.handle("GET", "/games/sword_master/insults", "");
+        assert!(res.body[].contains("I've got a long, sharp lesson"));
+        assert_eq!(server.handle("GET", "/games/monkey_island9/insults", "").status, 404);
+        let page = json::from_str(server.handle("GET", "/games/sword_master/insults?offset=1&limit=2", "").body[]).unwrap();
+        assert_eq!(page.find(&"insults".to_string()).unwrap().as_list().unwrap().len(), 2);
+        assert_eq!(page.find(&"total".to_string()).unwrap().as_u64(), Some(server.insults().sword_master_insults().len() as u64));
+        assert_eq!(server.handle("GET", "/games/sword_master/insults?limit=all", "").status, 400);
+        assert_eq!(server.handle("POST", "/random", "").status, 405);
+
+        server.leaderboard().lock().record("guybrush", true).unwrap();
+        assert!(server.handle("GET", "/leaderboard?top=1", "").body[].contains(r#""player":"guybrush""#));
+        assert!(server.handle("GET", "/leaderboard/guybrush", "").body[].contains(r#""rank":1"#));
+        assert_eq!(server.handle("GET", "/leaderboard/lechuck", "").status, 404);
+
+        let knowledge = r#"{"learned":["You fight like a dairy farmer."]}"#;
+        assert_eq!(server.handle("PUT", "/profiles/guybrush/knowledge", knowledge).status, 200);
+        assert_eq!(server.handle("GET", "/profiles/guybrush/knowledge", "").body[], knowledge);
+        assert_eq!(server.handle("GET", "/profiles/elaine/knowledge", "").status, 404);
+        assert_eq!(server.handle("DELETE", "/profiles/guybrush/knowledge", "").status, 405);
+    }
+}
@@ -0,0 +1,69 @@
+//! SHA-1, for the WebSocket handshake.
+
+static H: [u32, ..5] = [0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476, 0xc3d2e1f0];
+
+/// The SHA-1 digest of `data`.
+pub fn digest(data: &[u8]) -> [u8, ..20] {
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    let bits = data.len() as u64 * 8;
+    for i in range(0u, 8).rev() {
+        message.push((bits >> (8 * i)) as u8);
+    }
+
+    let mut h = H;
+    for block in message[].chunks(64) {
+        let mut w = [0u32, ..80];
+        for i in range(0u, 16) {
+            w[i] = range(0u, 4).fold(0, |acc, j| acc << 8 | block[4 * i + j] as u32);
+        }
+        for i in range(16u, 80) {
+            w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+        }
+
+        let (mut a, mut b, mut c, mut d, mut e) = (h[0], h[1], h[2], h[3], h[4]);
+        for i in range(0u, 80) {
+            let (f, k) = match i / 20 {
+                0 => ((b & c) | (!b & d), 0x5a827999),
+                1 => (b ^ c ^ d, 0x6ed9eba1),
+                2 => ((b & c) | (b & d) | (c & d), 0x8f1bbcdc),
+                _ => (b ^ c ^ d, 0xca62c1d6),
+            };
+            let t = a.rotate_left(5) + f + e + k + w[i];
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = t;
+        }
+
+        for (x, y) in h.iter_mut().zip([a, b, c, d, e].iter()) {
+            *x = *x + *y;
+        }
+    }
+
+    let mut res = [0u8, ..20];
+    for i in range(0u, 20) {
+        res[i] = (h[i / 4] >> (24 - 8 * (i % 4))) as u8;
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex(data: &[u8]) -> String {
+        data.iter().map(|b| format!("{:02x}", *b)).collect::<Vec<String>>().concat()
+    }
+
+    #[test]
+    fn test_digest() {
+        assert_eq!(hex(digest(b"")[])[], "da39a3ee5e6b4b0d3255bfef95601890afd80709");
+        assert_eq!(hex(digest(b"abc")[])[], "a9993e364706816aba3e25717850c26c9cd0d89d");
+        assert_eq!(hex(digest([b'a', ..1000][])[])[], "291e9a6c66994949b57ba5e650361e98fc36b1ba");
+    }
+}
@@ -0,0 +1,430 @@
+//! Insults stored in SQLite, for services with many packs.
+//!
+//! Every pack lives in the same tables, lookups query the database
+//! instead of loading the packs into memory:
+//!
+//! ```sql
+//! CREATE TABLE insults (pack TEXT, game TEXT, insult TEXT, retort TEXT);
+//! CREATE TABLE failed_retorts (pack TEXT, position INTEGER, retort TEXT);
+//! CREATE TABLE lookup_priority (pack TEXT, position INTEGER, game TEXT);
+//! ```
+//!
+//! Lookups consult the games in the priority of the pack, with the custom
+//! retorts of `Insults::retort`.
+//!
+//! `SqliteLeaderboard` keeps a leaderboard in a `leaderboard` table of the
+//! same or another database.
+
+use libc::{c_char, c_int, c_uchar, c_void, intptr_t};
+use std::c_str::CString;
+use std::ptr;
+use std::vec;
+
+use game::{Game, MonkeyIsland1, SwordMaster, MonkeyIsland3, CaptainRottingham};
+use game::{NOT_FAIR_SWORD_MASTER, NOT_FAIR_ROTTINGHAM};
+use insults::Insults;
+use leaderboard::{LeaderboardStore, Standing};
+use lookup::{Lookup, DEFAULT_PRIORITY};
+use normalize::nfc;
+use schema::{LoadError, Database};
+
+static SCHEMA: &'static str = "
+    CREATE TABLE IF NOT EXISTS insults (
+        pack TEXT NOT NULL,
+        game TEXT NOT NULL,
+        insult TEXT NOT NULL,
+        retort TEXT NOT NULL,
+        PRIMARY KEY (pack, game, insult)
+    );
+    CREATE INDEX IF NOT EXISTS insults_by_insult ON insults (pack, insult);
+    CREATE TABLE IF NOT EXISTS failed_retorts (
+        pack TEXT NOT NULL,
+        position INTEGER NOT NULL,
+        retort TEXT NOT NULL,
+        PRIMARY KEY (pack, position)
+    );
+    CREATE TABLE IF NOT EXISTS lookup_priority (
+        pack TEXT NOT NULL,
+        position INTEGER NOT NULL,
+        game TEXT NOT NULL,
+        PRIMARY KEY (pack, position)
+    );";
+
+/// The pack `pack` in an SQLite database.
+///
+/// Lookups panic if the database fails.
+pub struct SqliteInsults {
+    db: Connection,
+    pack: String,
+}
+
+impl SqliteInsults {
+    /// Open the pack `pack` in the database at `location`, creating the tables if missing.
+    pub fn open(location: &str, pack: &str) -> Result<SqliteInsults, LoadError> {
+        let db = try!(Connection::open(location));
+        try!(db.exec(SCHEMA));
+        Ok(SqliteInsults {
+            db: db,
+            pack: pack.to_string(),
+        })
+    }
+
+    /// Store `insults` as the pack `pack` in the database at `location`, replacing it.
+    ///
+    /// Nothing is changed if storing fails.
+    pub fn import(location: &str, pack: &str, insults: &Insults) -> Result<SqliteInsults, LoadError> {
+        let res = try!(SqliteInsults::open(location, pack));
+        try!(res.db.exec("BEGIN"));
+        match res.store(insults) {
+            Ok(()) => {
+                try!(res.db.exec("COMMIT"));
+                Ok(res)
+            },
+            Err(e) => {
+                // The error of the insert is the one to report
+                res.db.exec("ROLLBACK").ok();
+                Err(e)
+            },
+        }
+    }
+
+    fn store(&self, insults: &Insults) -> Result<(), LoadError> {
+        let pack = self.pack[];
+        for table in ["insults", "failed_retorts", "lookup_priority"].iter() {
+            try!(self.query(format!("DELETE FROM {} WHERE pack = ?", table)[], [pack], |_| {}));
+        }
+
+        for game in Game::all().iter() {
+            for (insult, entry) in insults.entries(*game).into_iter() {
+                try!(self.query("INSERT INTO insults VALUES (?, ?, ?, ?)",
+                                [pack, game.key(), insult[], entry.retort[]], |_| {}));
+            }
+        }
+        for (i, retort) in insults.failed_retorts().iter().enumerate() {
+            try!(self.query("INSERT INTO failed_retorts VALUES (?, ?, ?)",
+                            [pack, i.to_string()[], retort[]], |_| {}));
+        }
+        for (i, game) in insults.game_priority().iter().enumerate() {
+            try!(self.query("INSERT INTO lookup_priority VALUES (?, ?, ?)",
+                            [pack, i.to_string()[], game.key()], |_| {}));
+        }
+        Ok(())
+    }
+
+    /// The name of the pack.
+    pub fn pack<'a>(&'a self) -> &'a str {
+        self.pack[]
+    }
+
+    fn query(&self, sql: &str, params: &[&str], row: |&Statement|) -> Result<(), LoadError> {
+        self.db.query(sql, params, row)
+    }
+
+    /// The first column of every row, panicking on database errors.
+    fn strings(&self, sql: &str, params: &[&str]) -> Vec<String> {
+        let mut res = Vec::new();
+        let status = self.query(sql, params, |row| res.push(row.text(0)));
+        if let Err(e) = status {
+            panic!("{}", e);
+        }
+        res
+    }
+
+    /// The games consulted by `lookup`, in order, the default ones for packs without.
+    pub fn game_priority(&self) -> Vec<Game> {
+        let keys = self.strings("SELECT game FROM lookup_priority WHERE pack = ? ORDER BY position",
+                                [self.pack[]]);
+        if keys.is_empty() {
+            return DEFAULT_PRIORITY[].to_vec();
+        }
+        keys.iter().filter_map(|x| Game::from_key(x[])).collect()
+    }
+
+    /// The retort to `insult` in the table of `game`.
+    fn table_retort(&self, game: Game, insult: &str) -> Option<String> {
+        self.strings("SELECT retort FROM insults WHERE pack = ? AND game = ? AND insult = ?",
+                     [self.pack[], game.key(), nfc(insult)[]]).into_iter().next()
+    }
+}
+
+impl Lookup for SqliteInsults {
+    fn lookup(&self, insult: &str) -> Option<String> {
+        self.game_priority().into_iter().filter_map(|game| self.lookup_in(game, insult)).next()
+    }
+
+    fn lookup_in(&self, game: Game, insult: &str) -> Option<String> {
+        match game {
+            MonkeyIsland1 if self.table_retort(SwordMaster, insult).is_some() =>
+                Some(NOT_FAIR_SWORD_MASTER.to_string()),
+            MonkeyIsland3 if self.table_retort(CaptainRottingham, insult).is_some() =>
+                Some(NOT_FAIR_ROTTINGHAM.to_string()),
+            _ => self.table_retort(game, insult),
+        }
+    }
+
+    fn insults_of(&self, game: Game) -> Vec<String> {
+        self.strings("SELECT insult FROM insults WHERE pack = ? AND game = ? ORDER BY insult",
+                     [self.pack[], game.key()])
+    }
+
+    fn failed(&self) -> Vec<String> {
+        self.strings("SELECT retort FROM failed_retorts WHERE pack = ? ORDER BY position",
+                     [self.pack[]])
+    }
+}
+
+static LEADERBOARD_SCHEMA: &'static str = "
+    CREATE TABLE IF NOT EXISTS leaderboard (
+        player TEXT PRIMARY KEY,
+        wins INTEGER NOT NULL,
+        losses INTEGER NOT NULL,
+        streak INTEGER NOT NULL,
+        best_streak INTEGER NOT NULL
+    );";
+
+/// Leaderboard standings in an SQLite database.
+///
+/// The database is opened for every operation, so the store can be sent
+/// to the tasks of a server.
+pub struct SqliteLeaderboard {
+    location: String,
+}
+
+impl SqliteLeaderboard {
+    /// Open the leaderboard in the database at `location`, creating the table if missing.
+    pub fn open(location: &str) -> Result<SqliteLeaderboard, LoadError> {
+        let db = try!(Connection::open(location));
+        try!(db.exec(LEADERBOARD_SCHEMA));
+        Ok(SqliteLeaderboard { location: location.to_string() })
+    }
+
+    fn standings(&self, sql: &str, params: &[&str]) -> Result<Vec<Standing>, LoadError> {
+        let db = try!(Connection::open(self.location[]));
+        let mut res = Vec::new();
+        try!(db.query(sql, params, |row| {
+            let number = |i| from_str::<uint>(row.text(i)[]).unwrap_or(0);
+            res.push(Standing {
+                player: row.text(0),
+                wins: number(1),
+                losses: number(2),
+                streak: number(3),
+                best_streak: number(4),
+            });
+        }));
+        Ok(res)
+    }
+}
+
+impl LeaderboardStore for SqliteLeaderboard {
+    fn get(&self, player: &str) -> Result<Option<Standing>, LoadError> {
+        let res = try!(self.standings("SELECT * FROM leaderboard WHERE player = ?", [player]));
+        Ok(res.into_iter().next())
+    }
+
+    fn put(&mut self, standing: &Standing) -> Result<(), LoadError> {
+        let db = try!(Connection::open(self.location[]));
+        db.query("INSERT OR REPLACE INTO leaderboard VALUES (?, ?, ?, ?, ?)",
+                 [standing.player[], standing.wins.to_string()[], standing.losses.to_string()[],
+                  standing.streak.to_string()[], standing.best_streak.to_string()[]], |_| {})
+    }
+
+    fn all(&self) -> Result<Vec<Standing>, LoadError> {
+        self.standings("SELECT * FROM leaderboard", [])
+    }
+}
+
+#[allow(non_camel_case_types)]
+enum sqlite3 {}
+#[allow(non_camel_case_types)]
+enum sqlite3_stmt {}
+
+const SQLITE_OK: c_int = 0;
+const SQLITE_ROW: c_int = 100;
+const SQLITE_DONE: c_int = 101;
+// Makes SQLite copy bound values
+const SQLITE_TRANSIENT: intptr_t = -1;
+
+#[link(name = "sqlite3")]
+extern {
+    fn sqlite3_open(filename: *const c_char, db: *mut *mut sqlite3) -> c_int;
+    fn sqlite3_close(db: *mut sqlite3) -> c_int;
+    fn sqlite3_errmsg(db: *mut sqlite3) -> *const c_char;
+    fn sqlite3_exec(db: *mut sqlite3, sql: *const c_char, callback: *const c_void,
+                    arg: *mut c_void, errmsg: *mut *mut c_char) -> c_int;
+    fn sqlite3_prepare_v2(db: *mut sqlite3, sql: *const c_char, len: c_int,
+                          stmt: *mut *mut sqlite3_stmt, tail: *mut *const c_char) -> c_int;
+    fn sqlite3_bind_text(stmt: *mut sqlite3_stmt, i: c_int, text: *const c_char, len: c_int,
+                         destructor: intptr_t) -> c_int;
+    fn sqlite3_step(stmt: *mut sqlite3_stmt) -> c_int;
+    fn sqlite3_column_text(stmt: *mut sqlite3_stmt, i: c_int) -> *const c_uchar;
+    fn sqlite3_column_bytes(stmt: *mut sqlite3_stmt, i: c_int) -> c_int;
+    fn sqlite3_finalize(stmt: *mut sqlite3_stmt) -> c_int;
+}
+
+struct Connection {
+    db: *mut sqlite3,
+}
+
+impl Connection {
+    fn open(location: &str) -> Result<Connection, LoadError> {
+        let mut db = ptr::null_mut();
+        let rc = location.with_c_str(|p| unsafe { sqlite3_open(p, &mut db) });
+        let res = Connection { db: db };
+        if rc != SQLITE_OK {
+            return Err(res.error());
+        }
+        Ok(res)
+    }
+
+    fn error(&self) -> LoadError {
+        let msg = unsafe { CString::new(sqlite3_errmsg(self.db), false) };
+        Database(msg.as_str().unwrap_or("unknown error").to_string())
+    }
+
+    fn exec(&self, sql: &str) -> Result<(), LoadError> {
+        let rc = sql.with_c_str(|p| unsafe {
+            sqlite3_exec(self.db, p, ptr::null(), ptr::null_mut(), ptr::null_mut())
+        });
+        if rc != SQLITE_OK {
+            return Err(self.error());
+        }
+        Ok(())
+    }
+
+    fn prepare<'a>(&'a self, sql: &str) -> Result<Statement<'a>, LoadError> {
+        let mut stmt = ptr::null_mut();
+        let rc = sql.with_c_str(|p| unsafe {
+            sqlite3_prepare_v2(self.db, p, -1, &mut stmt, ptr::null_mut())
+        });
+        if rc != SQLITE_OK {
+            return Err(self.error());
+        }
+        Ok(Statement { stmt: stmt, db: self })
+    }
+
+    /// Run `sql` with `params`, calling `row` for every resulting row.
+    fn query(&self, sql: &str, params: &[&str], row: |&Statement|) -> Result<(), LoadError> {
+        let stmt = try!(self.prepare(sql));
+        for (i, param) in params.iter().enumerate() {
+            try!(stmt.bind(i + 1, *param));
+        }
+        while try!(stmt.step()) {
+            row(&stmt);
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        unsafe { sqlite3_close(self.db); }
+    }
+}
+
+struct Statement<'a> {
+    stmt: *mut sqlite3_stmt,
+    db: &'a Connection,
+}
+
+impl<'a> Statement<'a> {
+    fn bind(&self, i: uint, text: &str) -> Result<(), LoadError> {
+        let rc = unsafe {
+            sqlite3_bind_text(self.stmt, i as c_int, text.as_ptr() as *const c_char,
+                              text.len() as c_int, SQLITE_TRANSIENT)
+        };
+        if rc != SQLITE_OK {
+            return Err(self.db.error());
+        }
+        Ok(())
+    }
+
+    /// Advance to the next row, false when there are no more.
+    fn step(&self) -> Result<bool, LoadError> {
+        match unsafe { sqlite3_step(self.stmt) } {
+            SQLITE_ROW => Ok(true),
+            SQLITE_DONE => Ok(false),
+            _ => Err(self.db.error()),
+        }
+    }
+
+    fn text(&self, i: uint) -> String {
+        let bytes = unsafe {
+            let p = sqlite3_column_text(self.stmt, i as c_int);
+            let len = sqlite3_column_bytes(self.stmt, i as c_int);
+            vec::raw::from_buf(p, len as uint)
+        };
+        // Only valid UTF-8 is ever inserted
+        String::from_utf8(bytes).unwrap()
+    }
+}
+
+#[unsafe_destructor]
+impl<'a> Drop for Statement<'a> {
+    fn drop(&mut self) {
+        unsafe { sqlite3_finalize(self.stmt); }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::TempDir;
+
+    use game::{Game, MonkeyIsland1, SwordMaster};
+    use insults::{Insults, Entry};
+    use leaderboard::{LeaderboardStore, Standing};
+    use lookup::{Lookup, LookupPolicy};
+    use super::*;
+    use super::Connection;
+
+    #[test]
+    fn test_sqlite() {
+        let tmp = TempDir::new("mi_insults").unwrap();
+        let path = tmp.path().join("packs.db");
+        let location = path.as_str().unwrap();
+
+        let mi = Insults::new("insults.json");
+        SqliteInsults::import(location, "classic", &mi).unwrap();
+
+        let db = SqliteInsults::open(location, "classic").unwrap();
+        assert_eq!(db.lookup("You fight like a dairy farmer."), mi.lookup("You fight like a dairy farmer."));
+        assert_eq!(db.lookup("You fight like a cow."), None);
+        assert_eq!(db.failed(), mi.failed());
+        for game in Game::all().iter() {
+            assert_eq!(db.insults_of(*game), mi.insults_of(*game));
+        }
+
+        for insult in mi.insults().into_iter() {
+            assert_eq!(db.lookup(insult[]), mi.lookup(insult[]));
+        }
+        assert_eq!(db.game_priority(), mi.game_priority().to_vec());
+
+        let other = SqliteInsults::open(location, "other").unwrap();
+        assert_eq!(other.lookup("You fight like a dairy farmer."), None);
+
+        // A pack consulting Monkey Island 1 first answers with its custom retort
+        let policy = Insults::new("insults.json").with_lookup_policy(LookupPolicy::new([MonkeyIsland1, SwordMaster]));
+        let db = SqliteInsults::import(location, "mi1-first", &policy).unwrap();
+        let insult = "I will milk every drop of blood from your body!";
+        assert_eq!(db.lookup(insult), policy.lookup(insult));
+        assert_eq!(db.lookup("Would you like to be buried, or cremated?"), None);
+
+        // A failing import leaves the pack as it was
+        Connection::open(location).unwrap().exec("
+            CREATE TRIGGER no_failed_retorts BEFORE INSERT ON failed_retorts
+            BEGIN SELECT RAISE(ABORT, 'no failed retorts'); END;").unwrap();
+        let mut changed = mi.clone();
+        changed.insert(MonkeyIsland1, "You fight like a dairy farmer.", Entry::new("Moo."));
+        assert!(SqliteInsults::import(location, "classic", &changed).is_err());
+        let db = SqliteInsults::open(location, "classic").unwrap();
+        assert_eq!(db.lookup("You fight like a dairy farmer."), mi.lookup("You fight like a dairy farmer."));
+        assert_eq!(db.failed(), mi.failed());
+
+        let mut board = SqliteLeaderboard::open(location).unwrap();
+        let mut guybrush = Standing::new("guybrush");
+        guybrush.record(true);
+        board.put(&guybrush).unwrap();
+        board.put(&Standing::new("carla")).unwrap();
+        assert_eq!(board.get("guybrush").unwrap(), Some(guybrush));
+        assert_eq!(board.all().unwrap().len(), 2);
+    }
+}
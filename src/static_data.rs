@@ -0,0 +1,101 @@
+//! The built-in dataset compiled into a perfect hash table.
+//!
+//! `insults.json` is turned into static tables at build time, lookups
+//! hash the insult once and compare a single entry without allocating.
+
+#[cfg(not(feature = "std"))]
+use core::prelude::*;
+
+use game::{Game, MonkeyIsland1, SwordMaster, MonkeyIsland3, CaptainRottingham, MonkeyIsland4};
+use game::{NOT_FAIR_SWORD_MASTER, NOT_FAIR_ROTTINGHAM};
+#[cfg(feature = "std")]
+use lookup::Lookup;
+use static_hash::hash;
+
+include!(concat!(env!("OUT_DIR"), "/static_data.rs"))
+
+/// Games searched by `retort`, like the default lookup priority.
+static PRIORITY: [Game, ..5] = [SwordMaster, MonkeyIsland1, MonkeyIsland3, CaptainRottingham, MonkeyIsland4];
+
+/// The retort to `insult` in the table of `game`.
+pub fn retort_in(game: Game, insult: &str) -> Option<&'static str> {
+    let key = game.key();
+    let d = DISPLACEMENTS[(hash(0, key, insult) % DISPLACEMENTS.len() as u64) as uint];
+    let (g, i, r) = ENTRIES[(hash(d, key, insult) % ENTRIES.len() as u64) as uint];
+    if g == key && i == insult { Some(r) } else { None }
+}
+
+/// The retort to `insult` from `game`, with the custom retorts of `Insults::mi1_retort`
+/// and `Insults::mi3_retort`.
+fn game_retort(game: Game, insult: &str) -> Option<&'static str> {
+    match game {
+        MonkeyIsland1 if retort_in(SwordMaster, insult).is_some() => Some(NOT_FAIR_SWORD_MASTER),
+        MonkeyIsland3 if retort_in(CaptainRottingham, insult).is_some() => Some(NOT_FAIR_ROTTINGHAM),
+        _ => retort_in(game, insult),
+    }
+}
+
+/// The retort to `insult` from any game, searched like `Insults::retort`.
+pub fn retort(insult: &str) -> Option<&'static str> {
+    PRIORITY.iter().filter_map(|game| game_retort(*game, insult)).next()
+}
+
+/// The built-in failed retorts.
+pub fn failed_retorts() -> &'static [&'static str] {
+    FAILED_RETORTS[]
+}
+
+/// The built-in dataset as a `Lookup`.
+pub struct StaticInsults;
+
+#[cfg(feature = "std")]
+impl Lookup for StaticInsults {
+    fn lookup(&self, insult: &str) -> Option<String> {
+        retort(insult).map(|x| x.to_string())
+    }
+
+    fn lookup_in(&self, game: Game, insult: &str) -> Option<String> {
+        game_retort(game, insult).map(|x| x.to_string())
+    }
+
+    fn insults_of(&self, game: Game) -> Vec<String> {
+        let mut res: Vec<String> = ENTRIES.iter()
+            .filter(|&&(g, _, _)| g == game.key())
+            .map(|&(_, i, _)| i.to_string())
+            .collect();
+        res.sort();
+        res
+    }
+
+    fn failed(&self) -> Vec<String> {
+        FAILED_RETORTS.iter().map(|x| x.to_string()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use game::{Game, MonkeyIsland1};
+    use insults::Insults;
+    use lookup::Lookup;
+    use super::*;
+
+    #[test]
+    fn test_static_data() {
+        let mi = Insults::new("insults.json");
+        for game in Game::all().iter() {
+            assert_eq!(StaticInsults.insults_of(*game), mi.insults_of(*game));
+            for insult in mi.insults_of(*game).iter() {
+                assert_eq!(StaticInsults.lookup_in(*game, insult[]), mi.lookup_in(*game, insult[]));
+            }
+        }
+        assert_eq!(retort("You fight like a dairy farmer."),
+            Some("How appropriate. You fight like a cow."));
+        assert_eq!(retort("You fight like a cow."), None);
+        for insult in mi.captain_rottingham_insults().iter() {
+            assert_eq!(retort(insult[]), mi.retort(insult[]));
+        }
+        assert_eq!(StaticInsults.lookup_in(MonkeyIsland1, mi.sword_master_insults()[0][]),
+            Some("That's not fair, you're using the Sword Master's insults!".to_string()));
+        assert_eq!(StaticInsults.failed(), mi.failed());
+    }
+}
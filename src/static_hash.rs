@@ -0,0 +1,14 @@
+//! The hash of the built-in perfect hash table, shared with the build script.
+
+#[cfg(not(feature = "std"))]
+use core::prelude::*;
+
+/// FNV-1a of `game` and `insult`, varied by `seed`.
+pub fn hash(seed: u64, game: &str, insult: &str) -> u64 {
+    let mut h = 0xcbf29ce484222325u64 ^ seed;
+    for b in game.bytes().chain(Some(0u8).into_iter()).chain(insult.bytes()) {
+        h ^= b as u64;
+        h *= 0x100000001b3;
+    }
+    h
+}
@@ -0,0 +1,150 @@
+//! Accuracy and response times per insult, for progress dashboards.
+//!
+//! Record every answer given in a duel or quiz, then ask which insults are
+//! the hardest or how well each game is known. `Stats` encodes to JSON for
+//! front-ends.
+
+use std::collections::TreeMap;
+use std::time::Duration;
+
+use game::Game;
+use insults::Insults;
+
+/// Answers to one insult.
+#[deriving(Clone, PartialEq, Show, Encodable, Decodable)]
+pub struct InsultStats {
+    pub attempts: u32,
+    pub successes: u32,
+    /// Sum of the response times in milliseconds.
+    pub total_ms: u64,
+}
+
+impl InsultStats {
+    pub fn new() -> InsultStats {
+        InsultStats { attempts: 0, successes: 0, total_ms: 0 }
+    }
+
+    /// Share of correct answers, 0 without attempts.
+    pub fn accuracy(&self) -> f64 {
+        if self.attempts == 0 {
+            0.0
+        } else {
+            self.successes as f64 / self.attempts as f64
+        }
+    }
+
+    /// Mean response time, zero without attempts.
+    pub fn mean_time(&self) -> Duration {
+        if self.attempts == 0 {
+            Duration::zero()
+        } else {
+            Duration::milliseconds((self.total_ms / self.attempts as u64) as i64)
+        }
+    }
+}
+
+/// Statistics of all insults answered, keyed by insult.
+#[deriving(Clone, PartialEq, Show, Encodable, Decodable)]
+pub struct Stats {
+    pub insults: TreeMap<String, InsultStats>,
+}
+
+impl Stats {
+    pub fn new() -> Stats {
+        Stats { insults: TreeMap::new() }
+    }
+
+    /// Record an answer to `insult` given after `time`.
+    pub fn record(&mut self, insult: &str, correct: bool, time: Duration) {
+        let key = insult.to_string();
+        if !self.insults.contains_key(&key) {
+            self.insults.insert(key.clone(), InsultStats::new());
+        }
+        let stats = self.insults.get_mut(&key).unwrap();
+        stats.attempts += 1;
+        if correct {
+            stats.successes += 1;
+        }
+        stats.total_ms += time.num_milliseconds() as u64;
+    }
+
+    pub fn get<'a>(&'a self, insult: &str) -> Option<&'a InsultStats> {
+        self.insults.get(&insult.to_string())
+    }
+
+    /// All answers together.
+    pub fn total(&self) -> InsultStats {
+        let mut res = InsultStats::new();
+        for x in self.insults.values() {
+            res.attempts += x.attempts;
+            res.successes += x.successes;
+            res.total_ms += x.total_ms;
+        }
+        res
+    }
+
+    /// The `n` insults with the lowest accuracy, slower answers first on ties.
+    pub fn hardest_insults<'a>(&'a self, n: uint) -> Vec<(&'a str, &'a InsultStats)> {
+        let mut res: Vec<(&str, &InsultStats)> = self.insults.iter().map(|(k, v)| (k[], v)).collect();
+        res.sort_by(|&(_, a), &(_, b)| {
+            match a.accuracy().partial_cmp(&b.accuracy()) {
+                Some(Equal) | None => b.mean_time().cmp(&a.mean_time()),
+                Some(x) => x,
+            }
+        });
+        res.truncate(n);
+        res
+    }
+
+    /// Accuracy over the insults of each game in `insults`, for the games
+    /// with any attempts.
+    pub fn accuracy_by_game(&self, insults: &Insults) -> Vec<(Game, f64)> {
+        let mut res = Vec::new();
+        for game in Game::all().iter() {
+            let mut total = InsultStats::new();
+            for (insult, _) in insults.entries(*game).into_iter() {
+                if let Some(x) = self.insults.get(insult) {
+                    total.attempts += x.attempts;
+                    total.successes += x.successes;
+                }
+            }
+            if total.attempts > 0 {
+                res.push((*game, total.accuracy()));
+            }
+        }
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use serialize::json;
+
+    use game::MonkeyIsland1;
+    use insults::Insults;
+    use super::*;
+
+    #[test]
+    fn test_stats() {
+        let mi = Insults::new("insults.json");
+        let mut stats = Stats::new();
+        let easy = "You fight like a dairy farmer.";
+        let hard = "This is the END for you, you gutter-crawling cur!";
+        stats.record(easy, true, Duration::seconds(1));
+        stats.record(easy, true, Duration::seconds(3));
+        stats.record(hard, false, Duration::seconds(5));
+        stats.record(hard, true, Duration::seconds(5));
+
+        assert_eq!(stats.get(easy).unwrap().mean_time(), Duration::seconds(2));
+        let hardest = stats.hardest_insults(1);
+        assert_eq!(hardest.len(), 1);
+        let (insult, x) = hardest[0];
+        assert_eq!((insult, x.accuracy()), (hard, 0.5));
+        assert_eq!(stats.accuracy_by_game(&mi), vec![(MonkeyIsland1, 0.75)]);
+        assert_eq!(stats.total().attempts, 4);
+
+        let decoded: Stats = json::decode(json::encode(&stats)[]).unwrap();
+        assert_eq!(decoded, stats);
+    }
+}
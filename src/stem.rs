@@ -0,0 +1,171 @@
+//! Matching insults by the stems of their words.
+//!
+//! Between normalized and fuzzy matching: "Have you stopped wearing diaper
+//! yet" has the same stems as "Have you stopped wearing diapers yet?". The
+//! `Stemmer` is pluggable, `stemmer_for` picks the one of a locale.
+
+use insults::Insults;
+use normalize::{normalize, fold, folds_accents};
+
+/// Reduces words to their stems.
+pub trait Stemmer {
+    /// The stem of the lower case `word`.
+    fn stem(&self, word: &str) -> String;
+}
+
+/// Strips the common English suffixes, like plurals and "-ing".
+#[deriving(Clone, PartialEq, Show, Default)]
+pub struct English;
+
+impl Stemmer for English {
+    fn stem(&self, word: &str) -> String {
+        let mut res = word.to_string();
+        if res.len() > 4 && res[].ends_with("ies") {
+            let n = res.len() - 3;
+            res.truncate(n);
+            res.push('y');
+        } else if res.len() > 3 && res[].ends_with("s") && !res[].ends_with("ss") && !res[].ends_with("us") {
+            let n = res.len() - 1;
+            res.truncate(n);
+        }
+        for suffix in ["ing", "ed", "ly"].iter() {
+            if res.len() > suffix.len() + 2 && res[].ends_with(*suffix) {
+                let n = res.len() - suffix.len();
+                res.truncate(n);
+                undouble(&mut res);
+                break;
+            }
+        }
+        res
+    }
+}
+
+/// Drop a doubled final consonant, "stopp" is "stop".
+fn undouble(word: &mut String) {
+    let n = word.len();
+    if n < 3 {
+        return;
+    }
+    let (last, before) = (word.as_bytes()[n - 1], word.as_bytes()[n - 2]);
+    if last == before && !"aeioulsz".contains_char(last as char) {
+        word.truncate(n - 1);
+    }
+}
+
+/// Strips Spanish, French and Portuguese plurals.
+#[deriving(Clone, PartialEq, Show, Default)]
+pub struct Romance;
+
+impl Stemmer for Romance {
+    fn stem(&self, word: &str) -> String {
+        let n = word.len();
+        if n > 4 && (word.ends_with("es") || word.ends_with("os") || word.ends_with("as")) {
+            word[..n - 1].to_string()
+        } else if n > 3 && (word.ends_with("s") || word.ends_with("x")) {
+            word[..n - 1].to_string()
+        } else {
+            word.to_string()
+        }
+    }
+}
+
+/// The stemmer for `locale`, None for languages without one.
+pub fn stemmer_for(locale: &str) -> Option<Box<Stemmer + Send>> {
+    match locale.split(|c: char| c == '-' || c == '_').next().unwrap_or("") {
+        "en" => Some(box English as Box<Stemmer + Send>),
+        "es" | "fr" | "pt" | "ca" | "it" => Some(box Romance as Box<Stemmer + Send>),
+        _ => None,
+    }
+}
+
+/// `s` normalized with every word stemmed and punctuation dropped.
+pub fn stem_words(stemmer: &Stemmer, s: &str, fold_accents: bool) -> String {
+    let normalized = if fold_accents { fold(s) } else { normalize(s) };
+    let words: Vec<String> = normalized[].words()
+        .map(|w| w.chars().filter(|c| c.is_alphanumeric() || *c == '\'').collect::<String>())
+        .filter(|w| !w.is_empty())
+        .map(|w| stemmer.stem(w[]))
+        .collect();
+    words.connect(" ")
+}
+
+/// Insults indexed by the stems of their words.
+pub struct StemmedIndex<'a> {
+    stemmer: Box<Stemmer + Send>,
+    fold: bool,
+    /// Stemmed insults with the insult they came from, sorted.
+    keys: Vec<(String, &'a str)>,
+}
+
+impl<'a> StemmedIndex<'a> {
+    /// Index the insults of `insults` and its fallbacks with `stemmer`.
+    pub fn new(insults: &'a Insults, stemmer: Box<Stemmer + Send>) -> StemmedIndex<'a> {
+        let fold = folds_accents(insults.locale());
+        let mut keys = Vec::new();
+        let mut current = Some(insults);
+        loop {
+            match current {
+                Some(x) => {
+                    keys.extend(x.insults().into_iter().map(|s| (stem_words(&*stemmer, s[], fold), s[])));
+                    current = x.fallback();
+                },
+                None => break,
+            }
+        }
+        keys.sort();
+
+        // Insults with the same stems are matched by the first
+        let mut unique: Vec<(String, &'a str)> = Vec::new();
+        for (key, insult) in keys.into_iter() {
+            if unique.last().map_or(true, |&(ref last, _)| *last != key) {
+                unique.push((key, insult));
+            }
+        }
+        StemmedIndex { stemmer: stemmer, fold: fold, keys: unique }
+    }
+
+    /// The insult `input` has the stems of.
+    pub fn insult(&self, input: &str) -> Option<&'a str> {
+        let input = stem_words(&*self.stemmer, input, self.fold);
+        let (mut lo, mut hi) = (0, self.keys.len());
+        while lo < hi {
+            let mid = (lo + hi) / 2;
+            let (ref key, insult) = self.keys[mid];
+            match key[].cmp(input[]) {
+                Less => lo = mid + 1,
+                Greater => hi = mid,
+                Equal => return Some(insult),
+            }
+        }
+        None
+    }
+}
+
+impl Insults {
+    /// An index matching insults by their stems, with the stemmer of the locale.
+    pub fn stemmed_index<'a>(&'a self) -> Option<StemmedIndex<'a>> {
+        stemmer_for(self.locale()).map(|x| StemmedIndex::new(self, x))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use insults::Insults;
+    use super::*;
+
+    #[test]
+    fn test_stemmed_index() {
+        assert_eq!(English.stem("stopped"), "stop".to_string());
+        assert_eq!(English.stem("diapers"), "diaper".to_string());
+        assert_eq!(English.stem("flies"), "fly".to_string());
+        assert_eq!(Romance.stem("granjeros"), "granjero".to_string());
+
+        let mi = Insults::new("insults.json");
+        let index = mi.stemmed_index().unwrap();
+        let insult = "Have you stopped wearing diapers yet?";
+        assert_eq!(index.insult("have you stopped wearing diaper yet"), Some(insult));
+        assert_eq!(mi.normalized_index().with_stemming().retort("Have you stop wearing diaper yet").map(|x| x.retort),
+                   mi.retort(insult));
+        assert_eq!(index.insult("You fight like a cow"), None);
+    }
+}
@@ -0,0 +1,156 @@
+//! Subtitles of a duel, for subtitled clips of a replay.
+//!
+//! The events of a duel, collected through a channel observer, become one
+//! `Cue` per spoken line, timed by their length. `Subtitles` writes them as
+//! SRT or WebVTT, naming the speaker of each line.
+
+use std::cmp;
+use std::time::Duration;
+
+use duel::{DuelEvent, InsultThrown, RetortSucceeded, RetortFailed, GaveUp, Side, Player, Opponent};
+
+/// How long lines stay on screen.
+#[deriving(Clone, PartialEq, Show)]
+pub struct Timing {
+    /// Shortest time a line shows.
+    pub min: Duration,
+    /// Time per character, so longer lines show longer.
+    pub per_char: Duration,
+    /// Pause between two lines.
+    pub gap: Duration,
+}
+
+impl Timing {
+    fn line(&self, text: &str) -> Duration {
+        cmp::max(self.min, self.per_char * text.chars().count() as i32)
+    }
+}
+
+impl Default for Timing {
+    fn default() -> Timing {
+        Timing {
+            min: Duration::milliseconds(1500),
+            per_char: Duration::milliseconds(60),
+            gap: Duration::milliseconds(500),
+        }
+    }
+}
+
+/// A line on screen.
+#[deriving(Clone, PartialEq, Show)]
+pub struct Cue {
+    pub start: Duration,
+    pub end: Duration,
+    pub side: Side,
+    pub text: String,
+}
+
+/// Subtitles of the lines said in a duel.
+#[deriving(Clone, PartialEq, Show)]
+pub struct Subtitles {
+    cues: Vec<Cue>,
+    player: String,
+    opponent: String,
+}
+
+impl Subtitles {
+    /// Time the insults and retorts of `events`, in the order they happened.
+    pub fn new(events: &[DuelEvent], timing: &Timing) -> Subtitles {
+        let mut cues = Vec::new();
+        let mut start = Duration::zero();
+        for event in events.iter() {
+            let (side, text) = match *event {
+                InsultThrown(side, ref x) | RetortSucceeded(side, ref x) | RetortFailed(side, ref x) =>
+                    (side, x.clone()),
+                GaveUp(side) => (side, "I give up, you win!".to_string()),
+                _ => continue,
+            };
+            let end = start + timing.line(text[]);
+            cues.push(Cue { start: start, end: end, side: side, text: text });
+            start = end + timing.gap;
+        }
+        Subtitles { cues: cues, player: "Guybrush".to_string(), opponent: "Pirate".to_string() }
+    }
+
+    /// Name the speakers something else than Guybrush and Pirate.
+    pub fn with_names(mut self, player: &str, opponent: &str) -> Subtitles {
+        self.player = player.to_string();
+        self.opponent = opponent.to_string();
+        self
+    }
+
+    pub fn cues<'a>(&'a self) -> &'a [Cue] {
+        self.cues[]
+    }
+
+    fn name<'a>(&'a self, side: Side) -> &'a str {
+        match side {
+            Player => self.player[],
+            Opponent => self.opponent[],
+        }
+    }
+
+    /// The subtitles as a SubRip file.
+    pub fn to_srt(&self) -> String {
+        let mut res = String::new();
+        for (i, cue) in self.cues.iter().enumerate() {
+            res.push_str(format!("{}\n{} --> {}\n{}: {}\n\n", i + 1, timestamp(cue.start, ','),
+                                 timestamp(cue.end, ','), self.name(cue.side), cue.text)[]);
+        }
+        res
+    }
+
+    /// The subtitles as a WebVTT file, with voice spans for the speakers.
+    pub fn to_webvtt(&self) -> String {
+        let mut res = "WEBVTT\n\n".to_string();
+        for cue in self.cues.iter() {
+            res.push_str(format!("{} --> {}\n<v {}>{}\n\n", timestamp(cue.start, '.'),
+                                 timestamp(cue.end, '.'), self.name(cue.side), escape(cue.text[]))[]);
+        }
+        res
+    }
+}
+
+/// `time` as hours:minutes:seconds with milliseconds after `separator`.
+fn timestamp(time: Duration, separator: char) -> String {
+    let ms = time.num_milliseconds();
+    format!("{:02}:{:02}:{:02}{}{:03}", ms / 3600000, ms / 60000 % 60, ms / 1000 % 60, separator, ms % 1000)
+}
+
+/// Escape the characters WebVTT cue text doesn't allow.
+fn escape(text: &str) -> String {
+    text.replace("&", "&amp;").replace("<", "&lt;").replace(">", "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use duel::{Duel, Rules, Player, Opponent};
+    use insults::Insults;
+    use super::*;
+
+    #[test]
+    fn test_subtitles() {
+        let mi = Insults::new("insults.json");
+        let (tx, rx) = channel();
+        let mut duel = Duel::new(&mi, Rules::first_to(1));
+        duel.observe(box tx);
+        let insult = mi.sword_master_insults()[0].clone();
+        duel.exchange(insult[], mi.retort(insult[]).unwrap());
+        drop(duel);
+        let events: Vec<_> = rx.iter().collect();
+
+        let timing = Timing { min: Duration::seconds(2), per_char: Duration::zero(), gap: Duration::seconds(1) };
+        let subtitles = Subtitles::new(events[], &timing).with_names("Guybrush", "Sword Master");
+        let sides: Vec<_> = subtitles.cues().iter().map(|x| x.side).collect();
+        assert_eq!(sides, vec![Player, Opponent]);
+        assert_eq!(subtitles.cues()[1].start, Duration::seconds(3));
+
+        let srt = subtitles.to_srt();
+        assert!(srt[].starts_with(format!("1\n00:00:00,000 --> 00:00:02,000\nGuybrush: {}\n\n2\n", insult)[]));
+        let vtt = subtitles.to_webvtt();
+        assert!(vtt[].starts_with("WEBVTT\n\n00:00:00.000 --> 00:00:02.000\n<v Guybrush>"));
+        assert!(vtt[].contains("00:00:03.000 --> 00:00:05.000\n<v Sword Master>"));
+    }
+}
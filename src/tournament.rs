@@ -0,0 +1,105 @@
+//! A series of duels, each round with its own rules.
+
+use duel::{Duel, Rules, Side, Player, Opponent};
+use insults::Insults;
+
+/// Rounds fought in order, the side winning most rounds takes the tournament.
+pub struct Tournament {
+    rounds: Vec<Rules>,
+    results: Vec<Side>,
+}
+
+impl Tournament {
+    /// Create a tournament where round `i` is fought by `rounds[i]`.
+    pub fn new(rounds: Vec<Rules>) -> Tournament {
+        Tournament {
+            rounds: rounds,
+            results: Vec::new(),
+        }
+    }
+
+    pub fn rounds(&self) -> &[Rules] {
+        self.rounds[]
+    }
+
+    /// Winners of the rounds played so far.
+    pub fn results(&self) -> &[Side] {
+        self.results[]
+    }
+
+    /// Index of the round to be played next, if any.
+    pub fn current_round(&self) -> Option<uint> {
+        if self.results.len() < self.rounds.len() {
+            Some(self.results.len())
+        } else {
+            None
+        }
+    }
+
+    /// Start the duel for the next round.
+    pub fn next_duel<'a>(&self, insults: &'a Insults) -> Option<Duel<'a>> {
+        self.current_round().map(|i| Duel::new(insults, self.rounds[i].clone()))
+    }
+
+    /// Record the winner of the current round.
+    pub fn record(&mut self, winner: Side) {
+        assert!(self.current_round().is_some(), "all rounds have been played");
+        self.results.push(winner);
+    }
+
+    pub fn rounds_won(&self, side: Side) -> uint {
+        self.results.iter().filter(|&s| *s == side).count()
+    }
+
+    /// The tournament winner, once a side can no longer be caught.
+    pub fn winner(&self) -> Option<Side> {
+        let remaining = self.rounds.len() - self.results.len();
+        let player = self.rounds_won(Player);
+        let opponent = self.rounds_won(Opponent);
+
+        if player > opponent + remaining {
+            Some(Player)
+        } else if opponent > player + remaining {
+            Some(Opponent)
+        } else {
+            None
+        }
+    }
+
+    pub fn is_over(&self) -> bool {
+        self.current_round().is_none() || self.winner().is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use duel::{Rules, Player, Opponent};
+    use insults::Insults;
+    use super::*;
+
+    #[test]
+    fn test_mixed_formats() {
+        let mi = Insults::new("insults.json");
+        let mut t = Tournament::new(vec![Rules::first_to(1),
+                                         Rules::timed(Duration::seconds(30)),
+                                         Rules::sudden_death()]);
+
+        {
+            let mut duel = t.next_duel(&mi).unwrap();
+            duel.exchange("You fight like a dairy farmer.", "Oh yeah?");
+            t.record(duel.winner().unwrap());
+        }
+        assert_eq!(t.winner(), None);
+
+        {
+            let duel = t.next_duel(&mi).unwrap();
+            assert!(duel.rules() == &Rules::timed(Duration::seconds(30)));
+        }
+        t.record(Opponent);
+        t.record(Player);
+        assert_eq!(t.winner(), Some(Player));
+        assert!(t.is_over());
+    }
+}
@@ -0,0 +1,236 @@
+//! Spaced repetition of insults, scheduled like SM-2.
+//!
+//! Every insult is a card asked as a quiz question. Answering correctly
+//! pushes the card further into the future, a wrong answer brings it back
+//! the next day:
+//!
+//! ```no_run
+//! use mi_insults::Insults;
+//! use mi_insults::trainer::{Schedule, Trainer};
+//!
+//! let mi = Insults::new("insults.json");
+//! let schedule = Schedule::open(&Path::new("schedule.json")).unwrap_or(Schedule::new());
+//! let mut trainer = Trainer::new(&mi, schedule);
+//! if let Some(q) = trainer.next_due() {
+//!     let correct = q.check_answer("How appropriate. You fight like a cow.");
+//!     trainer.record_answer(correct);
+//! }
+//! trainer.schedule().save(&Path::new("schedule.json")).unwrap();
+//! ```
+
+use std::collections::TreeMap;
+use std::io::{File, IoResult};
+use std::rand::{mod, Rng};
+use serialize::json;
+use time;
+
+use insults::Insults;
+use profile::Knowledge;
+use quiz::{Quiz, Question};
+use schema::{LoadError, Io, Schema};
+
+const DAY: i64 = 24 * 60 * 60;
+const MIN_EASE: f64 = 1.3;
+
+/// Review state of an insult, times are seconds since the epoch.
+#[deriving(Clone, PartialEq, Show, Encodable, Decodable)]
+pub struct Card {
+    pub ease: f64,
+    /// Days until the next review.
+    pub interval: u32,
+    /// Correct answers in a row.
+    pub repetitions: u32,
+    pub due: i64,
+}
+
+impl Card {
+    pub fn new(now: i64) -> Card {
+        Card { ease: 2.5, interval: 0, repetitions: 0, due: now }
+    }
+
+    /// Schedule the next review after an answer at `now`.
+    ///
+    /// A correct answer grades the recall 4 of 5 and a wrong one 1, SM-2
+    /// has more grades but a quiz answer is either right or wrong.
+    pub fn review(&mut self, correct: bool, now: i64) {
+        let quality = if correct { 4.0 } else { 1.0 };
+        if correct {
+            self.repetitions += 1;
+            self.interval = match self.repetitions {
+                1 => 1,
+                2 => 6,
+                _ => (self.interval as f64 * self.ease).round() as u32,
+            };
+        } else {
+            self.repetitions = 0;
+            self.interval = 1;
+        }
+        let ease = self.ease + 0.1 - (5.0 - quality) * (0.08 + (5.0 - quality) * 0.02);
+        self.ease = if ease < MIN_EASE { MIN_EASE } else { ease };
+        self.due = now + self.interval as i64 * DAY;
+    }
+
+    pub fn is_due(&self, now: i64) -> bool {
+        self.due <= now
+    }
+}
+
+/// Cards of all insults seen so far, keyed by insult.
+#[deriving(Clone, PartialEq, Show, Encodable, Decodable)]
+pub struct Schedule {
+    pub cards: TreeMap<String, Card>,
+}
+
+impl Schedule {
+    pub fn new() -> Schedule {
+        Schedule { cards: TreeMap::new() }
+    }
+
+    /// Read a schedule saved with `save`.
+    pub fn open(path: &Path) -> Result<Schedule, LoadError> {
+        let data = match File::open(path).read_to_string() {
+            Ok(x) => x,
+            Err(e) => return Err(Io(e)),
+        };
+        match json::decode(data[]) {
+            Ok(x) => Ok(x),
+            Err(e) => Err(Schema(vec![path.display().to_string()], e.to_string())),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> IoResult<()> {
+        File::create(path).write_str(json::encode(self)[])
+    }
+}
+
+/// Asks insults when they're due.
+pub struct Trainer<'a> {
+    insults: &'a Insults,
+    /// The insults trained on, in order.
+    candidates: Vec<&'a String>,
+    quiz: Quiz<'a>,
+    schedule: Schedule,
+    current: Option<String>,
+}
+
+impl<'a> Trainer<'a> {
+    /// Train on `insults`, continuing `schedule`.
+    pub fn new(insults: &'a Insults, schedule: Schedule) -> Trainer<'a> {
+        let mut candidates = insults.insults();
+        candidates.sort();
+        Trainer {
+            insults: insults,
+            candidates: candidates,
+            quiz: Quiz::new(insults, 3),
+            schedule: schedule,
+            current: None,
+        }
+    }
+
+    /// Only train on the insults learned in `knowledge`.
+    pub fn with_knowledge(mut self, knowledge: Knowledge) -> Trainer<'a> {
+        self.candidates = knowledge.drill_insults(self.insults);
+        self
+    }
+
+    pub fn schedule(&self) -> &Schedule {
+        &self.schedule
+    }
+
+    /// The question of the card due the longest, or of a new insult when
+    /// nothing is due. None when every insult is scheduled for later.
+    ///
+    /// Insults the quiz can't ask, having no retort, are skipped.
+    pub fn next_due(&mut self) -> Option<Question<'a>> {
+        self.next_due_at(time::get_time().sec, &mut rand::task_rng())
+    }
+
+    /// Like `next_due` at the time `now`, shuffling the choices with `rng`.
+    pub fn next_due_at<R: Rng>(&mut self, now: i64, rng: &mut R) -> Option<Question<'a>> {
+        let mut due: Vec<(i64, &'a String)> = self.candidates.iter()
+            .filter_map(|x| self.schedule.cards.get(*x).map(|card| (card.due, *x)))
+            .filter(|&(due, _)| due <= now)
+            .collect();
+        due.sort();
+        let mut order: Vec<&'a String> = due.into_iter().map(|(_, x)| x).collect();
+        order.extend(self.candidates.iter().filter(|x| !self.schedule.cards.contains_key(**x)).map(|x| *x));
+
+        self.current = None;
+        for insult in order.into_iter() {
+            if let Some(q) = self.quiz.question(insult[], rng) {
+                self.current = Some(insult.clone());
+                return Some(q);
+            }
+        }
+        None
+    }
+
+    /// Record the answer to the question of the last `next_due`.
+    pub fn record_answer(&mut self, correct: bool) {
+        self.record_answer_at(correct, time::get_time().sec)
+    }
+
+    /// Like `record_answer` at the time `now`.
+    pub fn record_answer_at(&mut self, correct: bool, now: i64) {
+        let insult = self.current.take().expect("no question to answer");
+        if !self.schedule.cards.contains_key(&insult) {
+            self.schedule.cards.insert(insult.clone(), Card::new(now));
+        }
+        self.schedule.cards.get_mut(&insult).unwrap().review(correct, now);
+    }
+
+    /// Cards due at `now`, not counting insults never asked.
+    pub fn due_count(&self, now: i64) -> uint {
+        self.schedule.cards.values().filter(|x| x.is_due(now)).count()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rand;
+
+    use insults::Insults;
+    use profile::Knowledge;
+    use super::*;
+
+    #[test]
+    fn test_trainer() {
+        let mut card = Card::new(0);
+        card.review(true, 0);
+        card.review(true, 0);
+        card.review(true, 0);
+        assert_eq!(card.interval, 15);
+        card.review(false, 0);
+        assert_eq!((card.interval, card.repetitions), (1, 0));
+        assert!(card.ease >= 1.3 && card.ease < 2.5);
+
+        let mi = Insults::new("insults.json");
+        let mut trainer = Trainer::new(&mi, Schedule::new());
+        let mut rng = rand::task_rng();
+        let first = trainer.next_due_at(0, &mut rng).unwrap().insult.to_string();
+        trainer.record_answer_at(false, 0);
+        let second = trainer.next_due_at(0, &mut rng).unwrap().insult.to_string();
+        assert!(first != second);
+        trainer.record_answer_at(true, 1);
+
+        // The wrong answer comes back the next day
+        assert_eq!(trainer.due_count(super::DAY), 1);
+        assert_eq!(trainer.next_due_at(super::DAY, &mut rng).unwrap().insult, first[]);
+
+        let json = ::serialize::json::encode(trainer.schedule());
+        let schedule: Schedule = ::serialize::json::decode(json[]).unwrap();
+        assert_eq!(&schedule, trainer.schedule());
+    }
+
+    #[test]
+    fn test_with_knowledge() {
+        let mi = Insults::new("insults.json");
+        let mut knowledge = Knowledge::new();
+        knowledge.learn("You fight like a dairy farmer.");
+        let mut trainer = Trainer::new(&mi, Schedule::new()).with_knowledge(knowledge);
+        let mut rng = rand::task_rng();
+        assert_eq!(trainer.next_due_at(0, &mut rng).unwrap().insult, "You fight like a dairy farmer.");
+        trainer.record_answer_at(true, 0);
+        assert!(trainer.next_due_at(0, &mut rng).is_none());
+    }
+}
@@ -0,0 +1,161 @@
+//! Readable transcripts of a duel, for posting results to chat or forums.
+//!
+//! The events of a finished duel or a replay become the lines said, named
+//! after their speaker, with notes for the points, serves and the winner.
+//! `Transcript` writes them as plain text, Markdown or HTML.
+
+use duel::{DuelEvent, Side, Player, Opponent};
+use duel::{InsultThrown, RetortSucceeded, RetortFailed, GaveUp, HintGiven};
+use duel::{RoundWon, ServeTaken, SuddenDeathStarted, DuelWon, Narrated};
+
+/// A line of a transcript.
+#[deriving(Clone, PartialEq, Show)]
+pub enum Item {
+    /// The side said the text, the retort was wrong if `wrong`.
+    Said(Side, String, bool),
+    /// What happened, like a point being scored.
+    Note(String),
+}
+
+/// The lines said in a duel and how it went.
+#[deriving(Clone, PartialEq, Show)]
+pub struct Transcript {
+    events: Vec<DuelEvent>,
+    player: String,
+    opponent: String,
+}
+
+impl Transcript {
+    /// The transcript of `events`, in the order they happened.
+    pub fn new(events: &[DuelEvent]) -> Transcript {
+        Transcript { events: events.to_vec(), player: "Guybrush".to_string(), opponent: "Pirate".to_string() }
+    }
+
+    /// Name the speakers something else than Guybrush and Pirate.
+    pub fn with_names(mut self, player: &str, opponent: &str) -> Transcript {
+        self.player = player.to_string();
+        self.opponent = opponent.to_string();
+        self
+    }
+
+    fn name<'a>(&'a self, side: Side) -> &'a str {
+        match side {
+            Player => self.player[],
+            Opponent => self.opponent[],
+        }
+    }
+
+    /// The lines and notes, with the score after every point.
+    pub fn items(&self) -> Vec<Item> {
+        let (mut player, mut opponent) = (0u, 0u);
+        let mut res = Vec::new();
+        for event in self.events.iter() {
+            res.push(match *event {
+                InsultThrown(side, ref x) | RetortSucceeded(side, ref x) => Said(side, x.clone(), false),
+                RetortFailed(side, ref x) => Said(side, x.clone(), true),
+                GaveUp(side) => Said(side, "I give up, you win!".to_string(), false),
+                HintGiven(ref x) => Note(format!("Hint: {}", x)),
+                RoundWon(side) => {
+                    match side {
+                        Player => player += 1,
+                        Opponent => opponent += 1,
+                    }
+                    Note(format!("Point to {} ({}–{})", self.name(side), player, opponent))
+                },
+                ServeTaken(side) => Note(format!("{} takes the serve", self.name(side))),
+                SuddenDeathStarted => Note("Sudden death".to_string()),
+                DuelWon(side) => Note(format!("{} wins the duel", self.name(side))),
+                Narrated(ref x) => Note(x.clone()),
+            });
+        }
+        res
+    }
+
+    /// The transcript as plain text, notes indented.
+    pub fn to_text(&self) -> String {
+        let mut res = String::new();
+        for item in self.items().iter() {
+            match *item {
+                Said(side, ref text, wrong) =>
+                    res.push_str(format!("{}: {}{}\n", self.name(side), text, if wrong { " (wrong)" } else { "" })[]),
+                Note(ref text) => res.push_str(format!("    -- {}\n", text)[]),
+            }
+        }
+        res
+    }
+
+    /// The transcript as Markdown, speakers in bold and notes in italics.
+    pub fn to_markdown(&self) -> String {
+        let mut res = String::new();
+        for item in self.items().iter() {
+            match *item {
+                Said(side, ref text, wrong) =>
+                    res.push_str(format!("**{}:** {}{}\n\n", escape_markdown(self.name(side)), escape_markdown(text[]),
+                                         if wrong { " *(wrong)*" } else { "" })[]),
+                Note(ref text) => res.push_str(format!("*{}*\n\n", escape_markdown(text[]))[]),
+            }
+        }
+        res
+    }
+
+    /// The transcript as HTML, classes `player`, `opponent`, `wrong` and `note` for styling.
+    pub fn to_html(&self) -> String {
+        let mut res = "<div class=\"transcript\">\n".to_string();
+        for item in self.items().iter() {
+            match *item {
+                Said(side, ref text, wrong) => {
+                    let class = match side { Player => "player", Opponent => "opponent" };
+                    res.push_str(format!("<p class=\"{}{}\"><b>{}:</b> {}</p>\n", class, if wrong { " wrong" } else { "" },
+                                         escape_html(self.name(side)), escape_html(text[]))[]);
+                },
+                Note(ref text) => res.push_str(format!("<p class=\"note\">{}</p>\n", escape_html(text[]))[]),
+            }
+        }
+        res.push_str("</div>\n");
+        res
+    }
+}
+
+/// Escape the characters Markdown would take for formatting.
+fn escape_markdown(text: &str) -> String {
+    let mut res = String::new();
+    for c in text.chars() {
+        if "\\`*_[]#<>~".contains_char(c) {
+            res.push('\\');
+        }
+        res.push(c);
+    }
+    res
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace("&", "&amp;").replace("<", "&lt;").replace(">", "&gt;").replace("\"", "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use duel::{Duel, Rules};
+    use insults::Insults;
+    use super::*;
+
+    #[test]
+    fn test_transcript() {
+        let mi = Insults::new("insults.json");
+        let (tx, rx) = channel();
+        let mut duel = Duel::new(&mi, Rules::first_to(1));
+        duel.observe(box tx);
+        let insult = mi.sword_master_insults()[0].clone();
+        duel.exchange(insult[], mi.retort(insult[]).unwrap());
+        drop(duel);
+        let events: Vec<_> = rx.iter().collect();
+
+        let transcript = Transcript::new(events[]).with_names("Guybrush", "Sword Master");
+        let text = transcript.to_text();
+        assert!(text[].starts_with(format!("Guybrush: {}\nSword Master: ", insult)[]));
+        assert!(text[].contains("    -- Sword Master wins the duel\n"));
+        assert!(transcript.to_markdown()[].starts_with("**Guybrush:** "));
+        let html = transcript.to_html();
+        assert!(html[].contains("<p class=\"opponent\"><b>Sword Master:</b> "));
+        assert!(html[].contains("<p class=\"note\">Point to Sword Master (0–1)</p>"));
+    }
+}
@@ -0,0 +1,88 @@
+//! Retort across languages, insult in one language and get the retort in another.
+
+use std::collections::HashMap;
+
+use insults::Insults;
+
+/// Localized datasets joined on the canonical ids of their insults.
+pub struct Translations {
+    packs: HashMap<String, Insults>,
+    /// Canonical id to the translated insult, per language.
+    ids: HashMap<String, HashMap<String, String>>,
+}
+
+impl Translations {
+    /// Load the English dataset at `location` together with the translations to `locales`.
+    pub fn load(location: &str, locales: &[&str]) -> Translations {
+        let mut packs = vec![Insults::new(location)];
+        for locale in locales.iter() {
+            packs.push(Insults::with_locale(location, *locale));
+        }
+        Translations::new(packs)
+    }
+
+    /// Build the mapping table from already loaded datasets.
+    pub fn new(packs: Vec<Insults>) -> Translations {
+        let mut ids = HashMap::new();
+        for pack in packs.iter() {
+            for &(insult, id) in pack.translated_insults().iter() {
+                if !ids.contains_key(id) {
+                    ids.insert(id.clone(), HashMap::new());
+                }
+                ids.get_mut(id).unwrap().insert(pack.locale().to_string(), insult.clone());
+            }
+        }
+
+        let packs = packs.into_iter().map(|p| (p.locale().to_string(), p)).collect();
+        Translations { packs: packs, ids: ids }
+    }
+
+    pub fn get<'a>(&'a self, lang: &str) -> Option<&'a Insults> {
+        self.packs.get(&lang.to_string())
+    }
+
+    /// The insult with canonical id `id` in `lang`, English if it's not translated.
+    pub fn insult_in<'a>(&'a self, id: &'a str, lang: &str) -> &'a str {
+        match self.ids.get(&id.to_string()).and_then(|x| x.get(&lang.to_string())) {
+            Some(x) => x[],
+            None => id,
+        }
+    }
+
+    /// Retort to `insult` in `from_lang` with the retort in `to_lang`.
+    pub fn retort_translated<'a>(&'a self, insult: &'a str, from_lang: &str, to_lang: &str)
+            -> Option<&'a str> {
+        let from = match self.get(from_lang) {
+            Some(x) => x,
+            None => return None,
+        };
+        let to = match self.get(to_lang) {
+            Some(x) => x,
+            None => return None,
+        };
+
+        from.canonical_id(insult).and_then(|id| to.retort(self.insult_in(id, to_lang)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_retort_translated() {
+        let t = Translations::load("insults.json", &["de"]);
+
+        assert_eq!(t.retort_translated("Du kämpfst wie ein dummer Bauer.", "de", "en"),
+            Some("How appropriate. You fight like a cow."));
+        assert_eq!(t.retort_translated("You fight like a dairy farmer.", "en", "de"),
+            Some("Wie passend. Du kämpfst wie eine Kuh."));
+
+        // Untranslated insults retort in English
+        assert_eq!(t.retort_translated("Hey, look over there!", "en", "de"),
+            Some("Yeah, yeah I know: it's a three headed monkey."));
+
+        assert_eq!(t.retort_translated("You're lazy!", "en", "de"), None);
+        assert_eq!(t.retort_translated("You fight like a dairy farmer.", "en", "xx"), None);
+    }
+}
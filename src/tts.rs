@@ -0,0 +1,162 @@
+//! Duels read aloud through a text-to-speech engine.
+//!
+//! Every line becomes an SSML snippet in the voice of who says it: the
+//! `speaker` of the entry when the dataset has one, otherwise the opponent
+//! of the insult's game, like the Sword Master. Applications implement
+//! `Speaker` for their engine and observe a duel with `ReadAloud`.
+
+use duel::{DuelEvent, DuelObserver, InsultThrown, RetortSucceeded, RetortFailed, Side, Player, Opponent};
+use game::{Game, SwordMaster, CaptainRottingham};
+use insults::Insults;
+
+/// How a character sounds, as SSML voice and prosody attributes.
+#[deriving(Clone, PartialEq, Show)]
+pub struct Voice {
+    /// Voice name for the engine to map to one of its voices.
+    pub name: String,
+    pub pitch: &'static str,
+    pub rate: &'static str,
+}
+
+impl Voice {
+    /// The voice of `speaker`, like `Captain Rottingham`.
+    pub fn of(speaker: &str) -> Voice {
+        let name: String = speaker.trim().chars()
+            .map(|c| if c.is_alphanumeric() { c.to_lowercase() } else { '_' })
+            .collect();
+        let (pitch, rate) = match name[] {
+            "guybrush" => ("+0%", "105%"),
+            "sword_master" | "carla" => ("+15%", "100%"),
+            "captain_rottingham" => ("-20%", "90%"),
+            _ => ("-10%", "100%"),
+        };
+        Voice { name: name, pitch: pitch, rate: rate }
+    }
+
+    /// The voice of the opponent throwing the insults of `game`.
+    pub fn of_game(game: Game) -> Voice {
+        match game {
+            SwordMaster => Voice::of("Sword Master"),
+            CaptainRottingham => Voice::of("Captain Rottingham"),
+            _ => Voice::of("Pirate"),
+        }
+    }
+}
+
+/// Escape `text` for XML.
+pub fn escape(text: &str) -> String {
+    text.replace("&", "&amp;").replace("<", "&lt;").replace(">", "&gt;")
+        .replace("\"", "&quot;").replace("'", "&apos;")
+}
+
+/// `text` as SSML spoken by `voice`.
+pub fn ssml(text: &str, voice: &Voice) -> String {
+    format!("<speak><voice name=\"{}\"><prosody pitch=\"{}\" rate=\"{}\">{}</prosody></voice></speak>",
+            escape(voice.name[]), voice.pitch, voice.rate, escape(text))
+}
+
+/// The voice of whoever throws `insult`, None if it isn't in the dataset.
+pub fn insult_voice(insults: &Insults, insult: &str) -> Option<Voice> {
+    for game in Game::all().iter() {
+        if let Some(entry) = insults.entry(*game, insult) {
+            return Some(match entry.speaker {
+                Some(ref x) => Voice::of(x[]),
+                None => Voice::of_game(*game),
+            });
+        }
+    }
+    insults.fallback().and_then(|f| insult_voice(f, insult))
+}
+
+/// A text-to-speech engine.
+pub trait Speaker {
+    /// Say the SSML snippet `ssml`.
+    fn speak(&mut self, ssml: &str);
+}
+
+/// Collects the snippets, for tests and for engines reading them later.
+impl Speaker for Vec<String> {
+    fn speak(&mut self, ssml: &str) {
+        self.push(ssml.to_string());
+    }
+}
+
+/// Reads the insults and retorts of a duel aloud.
+///
+/// The player speaks as Guybrush, the opponent in the voice of its insults.
+pub struct ReadAloud<'a, S> {
+    insults: &'a Insults,
+    speaker: S,
+    player: Voice,
+    opponent: Voice,
+}
+
+impl<'a, S: Speaker> ReadAloud<'a, S> {
+    pub fn new(insults: &'a Insults, speaker: S) -> ReadAloud<'a, S> {
+        ReadAloud {
+            insults: insults,
+            speaker: speaker,
+            player: Voice::of("Guybrush"),
+            opponent: Voice::of("Pirate"),
+        }
+    }
+
+    /// Give the player `voice` instead of Guybrush's.
+    pub fn with_player_voice(mut self, voice: Voice) -> ReadAloud<'a, S> {
+        self.player = voice;
+        self
+    }
+
+    pub fn speaker(&mut self) -> &mut S {
+        &mut self.speaker
+    }
+
+    fn say(&mut self, side: Side, text: &str) {
+        let line = match side {
+            Player => ssml(text, &self.player),
+            Opponent => ssml(text, &self.opponent),
+        };
+        self.speaker.speak(line[]);
+    }
+}
+
+impl<'a, S: Speaker> DuelObserver for ReadAloud<'a, S> {
+    fn on_event(&mut self, event: &DuelEvent) {
+        match *event {
+            InsultThrown(side, ref insult) => {
+                if side == Opponent {
+                    if let Some(voice) = insult_voice(self.insults, insult[]) {
+                        self.opponent = voice;
+                    }
+                }
+                self.say(side, insult[]);
+            },
+            RetortSucceeded(side, ref retort) | RetortFailed(side, ref retort) => self.say(side, retort[]),
+            _ => (),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use duel::{DuelObserver, InsultThrown, RetortSucceeded, Player, Opponent};
+    use insults::Insults;
+    use super::*;
+
+    #[test]
+    fn test_read_aloud() {
+        let mi = Insults::new("insults.json");
+        assert_eq!(ssml("Arr & <b>", &Voice::of("Captain Rottingham")),
+                   "<speak><voice name=\"captain_rottingham\"><prosody pitch=\"-20%\" rate=\"90%\">\
+                    Arr &amp; &lt;b&gt;</prosody></voice></speak>".to_string());
+
+        let lines: Vec<String> = Vec::new();
+        let mut reader = ReadAloud::new(&mi, lines);
+        let insult = mi.sword_master_insults()[0].clone();
+        reader.on_event(&InsultThrown(Opponent, insult.clone()));
+        reader.on_event(&RetortSucceeded(Player, "Oh yeah?".to_string()));
+        let lines = reader.speaker().clone();
+        assert!(lines[0][].starts_with("<speak><voice name=\"sword_master\""));
+        assert!(lines[1][].starts_with("<speak><voice name=\"guybrush\""));
+    }
+}
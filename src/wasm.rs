@@ -0,0 +1,205 @@
+//! Bindings for JavaScript, for fan games running in the browser.
+//!
+//! Compile the crate with Emscripten and the `wasm` feature, then load
+//! `js/mi_insults.js` which wraps these functions in a `JsInsults` class:
+//!
+//! ```js
+//! var mi = new JsInsults(json);
+//! mi.retort("You fight like a dairy farmer.");
+//! mi.startDuel(3);
+//! mi.exchange(mi.randInsult(), "How appropriate. You fight like a cow.");
+//! mi.free();
+//! ```
+//!
+//! Strings returned to JavaScript stay valid until the next call on the
+//! same `JsInsults`.
+
+use libc::{c_char, c_int, c_uint};
+use std::c_str::CString;
+use std::mem;
+use std::ptr;
+
+use duel::{Duel, Rules, Side, Player, Opponent, Exchange, Point, Serve, Retry};
+use insults::Insults;
+
+/// A dataset with the duel played on it.
+pub struct JsInsults {
+    // Borrows `insults`, declared first to be dropped first
+    duel: Option<Duel<'static>>,
+    insults: Insults,
+    result: Option<CString>,
+}
+
+impl JsInsults {
+    /// Keep `s` alive for JavaScript to read.
+    fn result(&mut self, s: Option<&str>) -> *const c_char {
+        self.result = s.map(|x| x.to_c_str());
+        match self.result {
+            Some(ref x) => x.as_ptr(),
+            None => ptr::null(),
+        }
+    }
+
+    fn exchange_result(&mut self, exchange: Exchange) -> c_int {
+        match exchange {
+            Point(side) => { self.result(None); side_code(side) },
+            Serve(side) => { self.result(None); 2 + side_code(side) },
+            Retry(hint) => { self.result(Some(hint[])); 4 },
+        }
+    }
+}
+
+fn side_code(side: Side) -> c_int {
+    match side {
+        Player => 0,
+        Opponent => 1,
+    }
+}
+
+unsafe fn to_str<'a>(s: &'a *const c_char) -> Option<&'a str> {
+    if s.is_null() {
+        return None;
+    }
+    // Strings from JavaScript are UTF-8
+    mem::transmute(CString::new(*s, false).as_str())
+}
+
+/// Load a dataset from JSON, null if the JSON is invalid.
+#[no_mangle]
+pub unsafe extern "C" fn js_insults_from_json(json: *const c_char) -> *mut JsInsults {
+    let insults = match to_str(&json).map(|x| Insults::try_from_str(x)) {
+        Some(Ok(x)) => x,
+        _ => return ptr::null_mut(),
+    };
+    mem::transmute(box JsInsults { duel: None, insults: insults, result: None })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn js_insults_free(mi: *mut JsInsults) {
+    if !mi.is_null() {
+        let _: Box<JsInsults> = mem::transmute(mi);
+    }
+}
+
+/// The retort to `insult`, null if there is none.
+#[no_mangle]
+pub unsafe extern "C" fn js_insults_retort(mi: *mut JsInsults, insult: *const c_char) -> *const c_char {
+    let mi = &mut *mi;
+    let retort = to_str(&insult).and_then(|x| mi.insults.retort(x)).map(|x| x.to_string());
+    mi.result(retort.as_ref().map(|x| x[]))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn js_insults_rand_insult(mi: *mut JsInsults) -> *const c_char {
+    let mi = &mut *mi;
+    let insult = mi.insults.rand_insult_default().clone();
+    mi.result(Some(insult[]))
+}
+
+/// The last string returned, or the hint of a retry.
+#[no_mangle]
+pub unsafe extern "C" fn js_insults_result(mi: *mut JsInsults) -> *const c_char {
+    match (*mi).result {
+        Some(ref x) => x.as_ptr(),
+        None => ptr::null(),
+    }
+}
+
+/// Start a duel won by the first to `first_to` points, replacing any duel in progress.
+#[no_mangle]
+pub unsafe extern "C" fn js_insults_start_duel(mi: *mut JsInsults, first_to: c_uint) {
+    let mi = &mut *mi;
+    // The duel is dropped before the insults it borrows
+    let insults: &'static Insults = mem::transmute(&mi.insults);
+    mi.duel = Some(Duel::new(insults, Rules::first_to(first_to as uint)));
+}
+
+/// Play an exchange of the duel.
+///
+/// 0 and 1 are points to the player and the opponent, 2 and 3 their serve,
+/// 4 a retry with the hint as the result. -1 if no duel is being played.
+#[no_mangle]
+pub unsafe extern "C" fn js_insults_exchange(mi: *mut JsInsults, insult: *const c_char,
+                                             retort: *const c_char) -> c_int {
+    let mi = &mut *mi;
+    let exchange = {
+        let duel = match mi.duel {
+            Some(ref mut x) if !x.is_over() && x.retrying().is_none() => x,
+            _ => return -1,
+        };
+        match (to_str(&insult), to_str(&retort)) {
+            (Some(insult), Some(retort)) => duel.exchange(insult, retort),
+            _ => return -1,
+        }
+    };
+    mi.exchange_result(exchange)
+}
+
+/// Retry the last exchange after a hint, see `js_insults_exchange`.
+#[no_mangle]
+pub unsafe extern "C" fn js_insults_retry(mi: *mut JsInsults, retort: *const c_char) -> c_int {
+    let mi = &mut *mi;
+    let exchange = {
+        let duel = match mi.duel {
+            Some(ref mut x) if x.retrying().is_some() => x,
+            _ => return -1,
+        };
+        match to_str(&retort) {
+            Some(retort) => duel.retry(retort),
+            None => return -1,
+        }
+    };
+    mi.exchange_result(exchange)
+}
+
+/// Points of the player (0) or the opponent (1).
+#[no_mangle]
+pub unsafe extern "C" fn js_insults_score(mi: *mut JsInsults, side: c_int) -> c_uint {
+    let side = if side == 0 { Player } else { Opponent };
+    match (*mi).duel {
+        Some(ref duel) => duel.score().of(side) as c_uint,
+        None => 0,
+    }
+}
+
+/// The winner of the duel, 0 for the player, 1 for the opponent and -1 if undecided.
+#[no_mangle]
+pub unsafe extern "C" fn js_insults_winner(mi: *mut JsInsults) -> c_int {
+    match (*mi).duel.as_ref().and_then(|x| x.winner()) {
+        Some(side) => side_code(side),
+        None => -1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::c_str::CString;
+    use std::ptr;
+
+    use insults::read_file;
+    use super::*;
+
+    #[test]
+    fn test_js_insults() {
+        unsafe {
+            let json = read_file("insults.json").to_c_str();
+            let mi = js_insults_from_json(json.as_ptr());
+            assert!(!mi.is_null());
+
+            let insult = "You fight like a dairy farmer.".to_c_str();
+            let retort = CString::new(js_insults_retort(mi, insult.as_ptr()), false);
+            assert_eq!(retort.as_str(), Some("How appropriate. You fight like a cow."));
+
+            assert_eq!(js_insults_exchange(mi, insult.as_ptr(), insult.as_ptr()), -1);
+            js_insults_start_duel(mi, 1);
+            let correct = "How appropriate. You fight like a cow.".to_c_str();
+            assert_eq!(js_insults_exchange(mi, insult.as_ptr(), correct.as_ptr()), 1);
+            assert_eq!(js_insults_score(mi, 1), 1);
+            assert_eq!(js_insults_winner(mi), 1);
+
+            js_insults_free(mi);
+            assert!(js_insults_from_json("{".to_c_str().as_ptr()).is_null());
+            assert!(js_insults_from_json(ptr::null()).is_null());
+        }
+    }
+}
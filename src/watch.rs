@@ -0,0 +1,138 @@
+//! Reload a dataset when its file changes, for long-running bots.
+//!
+//! ```no_run
+//! use mi_insults::Insults;
+//!
+//! let watcher = Insults::watch("insults.json");
+//! // Always the latest version of the file
+//! let insults = watcher.get();
+//! ```
+
+use std::comm::Disconnected;
+use std::io::fs;
+use std::io::timer;
+use std::sync::{Arc, Mutex, RWLock};
+use std::time::Duration;
+
+use insults::Insults;
+use schema::LoadError;
+
+/// A dataset reloaded when its file changes.
+///
+/// The file is polled in the background until the watcher is dropped.
+pub struct Watcher {
+    current: Arc<RWLock<Arc<Insults>>>,
+    error: Arc<Mutex<Option<LoadError>>>,
+    // Dropping it stops the polling
+    _stop: Sender<()>,
+}
+
+impl Watcher {
+    /// The dataset as of the last successful load.
+    ///
+    /// Holding on to it keeps that version alive across reloads.
+    pub fn get(&self) -> Arc<Insults> {
+        self.current.read().clone()
+    }
+
+    /// Why the last reload failed, `None` if it succeeded.
+    ///
+    /// The previous dataset is kept when a reload fails.
+    pub fn error(&self) -> Option<LoadError> {
+        self.error.lock().clone()
+    }
+}
+
+impl Insults {
+    /// Load the dataset at `location` and reload it when the file changes.
+    ///
+    /// The file is checked every second.
+    pub fn watch(location: &str) -> Watcher {
+        Insults::watch_every(location, Duration::seconds(1))
+    }
+
+    /// Like `watch`, checking the file every `interval`.
+    pub fn watch_every(location: &str, interval: Duration) -> Watcher {
+        let current = Arc::new(RWLock::new(Arc::new(Insults::from_path(location))));
+        let error = Arc::new(Mutex::new(None));
+        let (stop, stopped) = channel();
+
+        let path = Path::new(location);
+        let location = location.to_string();
+        let (c, e) = (current.clone(), error.clone());
+        spawn(proc() {
+            let mut modified = mtime(&path);
+            loop {
+                timer::sleep(interval);
+                if let Err(Disconnected) = stopped.try_recv() {
+                    break;
+                }
+
+                let m = mtime(&path);
+                if m == modified {
+                    continue;
+                }
+                modified = m;
+
+                match Insults::try_from_path(location[]) {
+                    Ok(x) => {
+                        *c.write() = Arc::new(x);
+                        *e.lock() = None;
+                    },
+                    Err(err) => *e.lock() = Some(err),
+                }
+            }
+        });
+
+        Watcher {
+            current: current,
+            error: error,
+            _stop: stop,
+        }
+    }
+}
+
+fn mtime(path: &Path) -> Option<u64> {
+    fs::stat(path).ok().map(|s| s.modified)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{File, TempDir};
+    use std::io::timer;
+    use std::time::Duration;
+
+    use insults::Insults;
+
+    #[test]
+    fn test_watch() {
+        let tmp = TempDir::new("mi_insults").unwrap();
+        let path = tmp.path().join("insults.json");
+        let pack = |retort: &str| format!(r#"{{
+            "failed_retorts": [],
+            "monkey_island1": {{ "You fight like a dairy farmer.": "{}" }},
+            "sword_master": {{}},
+            "monkey_island3": {{}},
+            "captain_rottingham": {{}},
+            "monkey_island4": {{}}
+        }}"#, retort);
+        File::create(&path).write_str(pack("How appropriate. You fight like a cow.")[]).unwrap();
+
+        let watcher = Insults::watch_every(path.as_str().unwrap(), Duration::milliseconds(10));
+        let old = watcher.get();
+
+        // Make sure the modification time changes
+        timer::sleep(Duration::milliseconds(1100));
+        File::create(&path).write_str(pack("Moo.")[]).unwrap();
+        timer::sleep(Duration::milliseconds(200));
+        assert_eq!(watcher.get().retort("You fight like a dairy farmer."), Some("Moo."));
+        assert_eq!(old.retort("You fight like a dairy farmer."),
+            Some("How appropriate. You fight like a cow."));
+
+        // Broken saves keep the previous version
+        File::create(&path).write_str("{").unwrap();
+        timer::sleep(Duration::milliseconds(1200));
+        assert!(watcher.error().is_some());
+        assert_eq!(watcher.get().retort("You fight like a dairy farmer."), Some("Moo."));
+    }
+}
@@ -0,0 +1,451 @@
+//! Live duels over WebSocket, for web front-ends.
+//!
+//! A `serve` server accepts WebSocket connections on `/duel`, the duel is
+//! played with JSON text messages. The client sends
+//!
+//! * `{"type": "start", "first_to": 3}` to start a duel, the player serves first
+//! * `{"type": "insult", "insult": ...}` on the player's serve
+//! * `{"type": "retort", "retort": ...}` to answer the opponent's insult
+//!
+//! and the server answers with
+//!
+//! * `{"type": "started", "first_to": 3}`
+//! * `{"type": "exchange", "insult": ..., "retort": ..., "point": "player", "player": 1, "opponent": 0}`,
+//!   `point` is null when nobody scored
+//! * `{"type": "insult", "insult": ...}` when the opponent serves
+//! * `{"type": "over", "winner": "opponent"}`
+//! * `{"type": "error", "message": ...}` for messages out of turn
+//!
+//! `DuelSession` plays the protocol without a connection and `DuelClient`
+//! plays it against a server. Fragmented messages aren't supported.
+
+use std::collections::TreeMap;
+use std::io::{IoError, IoResult, InvalidInput, EndOfFile, TcpStream};
+use std::rand::{mod, Rng};
+use std::str;
+use std::sync::Arc;
+use serialize::base64::{ToBase64, STANDARD};
+use serialize::json::{mod, Json, ToJson};
+
+use duel::{Ai, Duel, Rules, Score, Side, Player, Opponent, Point};
+use insults::Insults;
+use profile::Response;
+use serve::{Request, write_response};
+use sha1;
+
+const GUID: &'static str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+/// Largest frame accepted, messages are short.
+const MAX_FRAME: u64 = 64 * 1024;
+
+pub const TEXT: u8 = 0x1;
+pub const CLOSE: u8 = 0x8;
+pub const PING: u8 = 0x9;
+pub const PONG: u8 = 0xa;
+
+/// A message from the client.
+#[deriving(Clone, PartialEq, Show)]
+pub enum ClientMessage {
+    /// Start a duel won by the first to this many points.
+    StartDuel(uint),
+    ThrowInsult(String),
+    SendRetort(String),
+}
+
+/// The outcome of an exchange.
+#[deriving(Clone, PartialEq, Show)]
+pub struct Report {
+    pub insult: String,
+    pub retort: String,
+    /// Who scored, if anyone.
+    pub point: Option<Side>,
+    pub score: Score,
+}
+
+/// A message from the server.
+#[deriving(Clone, PartialEq, Show)]
+pub enum ServerMessage {
+    DuelStarted(uint),
+    Exchanged(Report),
+    /// The opponent's insult, waiting for a retort.
+    OpponentInsult(String),
+    DuelOver(Side),
+    ProtocolError(String),
+}
+
+fn side_name(side: Side) -> &'static str {
+    match side {
+        Player => "player",
+        Opponent => "opponent",
+    }
+}
+
+fn parse_side(name: &str) -> Option<Side> {
+    match name {
+        "player" => Some(Player),
+        "opponent" => Some(Opponent),
+        _ => None,
+    }
+}
+
+fn message(kind: &str, fields: Vec<(&str, Json)>) -> Json {
+    let mut res = TreeMap::new();
+    res.insert("type".to_string(), kind.to_json());
+    for (k, v) in fields.into_iter() {
+        res.insert(k.to_string(), v);
+    }
+    json::Object(res)
+}
+
+fn string<'a>(data: &'a Json, key: &str) -> Option<&'a str> {
+    data.find(&key.to_string()).and_then(|x| x.as_string())
+}
+
+fn number(data: &Json, key: &str) -> Option<uint> {
+    data.find(&key.to_string()).and_then(|x| x.as_u64()).map(|x| x as uint)
+}
+
+impl ToJson for ClientMessage {
+    fn to_json(&self) -> Json {
+        match *self {
+            StartDuel(n) => message("start", vec![("first_to", n.to_json())]),
+            ThrowInsult(ref x) => message("insult", vec![("insult", x.to_json())]),
+            SendRetort(ref x) => message("retort", vec![("retort", x.to_json())]),
+        }
+    }
+}
+
+impl ClientMessage {
+    pub fn from_json(data: &Json) -> Option<ClientMessage> {
+        match string(data, "type") {
+            Some("start") => number(data, "first_to").map(StartDuel),
+            Some("insult") => string(data, "insult").map(|x| ThrowInsult(x.to_string())),
+            Some("retort") => string(data, "retort").map(|x| SendRetort(x.to_string())),
+            _ => None,
+        }
+    }
+}
+
+impl ToJson for ServerMessage {
+    fn to_json(&self) -> Json {
+        match *self {
+            DuelStarted(n) => message("started", vec![("first_to", n.to_json())]),
+            Exchanged(ref r) => message("exchange", vec![
+                ("insult", r.insult.to_json()),
+                ("retort", r.retort.to_json()),
+                ("point", r.point.map(|x| side_name(x).to_string()).to_json()),
+                ("player", r.score.player.to_json()),
+                ("opponent", r.score.opponent.to_json()),
+            ]),
+            OpponentInsult(ref x) => message("insult", vec![("insult", x.to_json())]),
+            DuelOver(side) => message("over", vec![("winner", side_name(side).to_json())]),
+            ProtocolError(ref x) => message("error", vec![("message", x.to_json())]),
+        }
+    }
+}
+
+impl ServerMessage {
+    pub fn from_json(data: &Json) -> Option<ServerMessage> {
+        match string(data, "type") {
+            Some("started") => number(data, "first_to").map(DuelStarted),
+            Some("exchange") => {
+                let (insult, retort) = match (string(data, "insult"), string(data, "retort")) {
+                    (Some(i), Some(r)) => (i.to_string(), r.to_string()),
+                    _ => return None,
+                };
+                let score = match (number(data, "player"), number(data, "opponent")) {
+                    (Some(p), Some(o)) => Score { player: p, opponent: o },
+                    _ => return None,
+                };
+                let point = string(data, "point").and_then(parse_side);
+                Some(Exchanged(Report { insult: insult, retort: retort, point: point, score: score }))
+            },
+            Some("insult") => string(data, "insult").map(|x| OpponentInsult(x.to_string())),
+            Some("over") => string(data, "winner").and_then(parse_side).map(DuelOver),
+            Some("error") => string(data, "message").map(|x| ProtocolError(x.to_string())),
+            _ => None,
+        }
+    }
+}
+
+fn protocol_error(msg: &str) -> Vec<ServerMessage> {
+    vec![ProtocolError(msg.to_string())]
+}
+
+/// The server side of the protocol, a duel against the computer.
+pub struct DuelSession {
+    insults: Arc<Insults>,
+    ai: Ai,
+    duel: Option<Duel<'static>>,
+    /// The opponent's insult waiting for the player's retort.
+    pending: Option<String>,
+}
+
+impl DuelSession {
+    pub fn new(insults: Arc<Insults>, ai: Ai) -> DuelSession {
+        DuelSession { insults: insults, ai: ai, duel: None, pending: None }
+    }
+
+    /// Play a message from the client, the replies are sent in order.
+    pub fn handle<R: Rng>(&mut self, msg: ClientMessage, rng: &mut R) -> Vec<ServerMessage> {
+        match msg {
+            StartDuel(0) => protocol_error("first_to must be positive"),
+            StartDuel(n) => {
+                self.duel = Some(Duel::shared(self.insults.clone(), Rules::first_to(n)));
+                self.pending = None;
+                vec![DuelStarted(n)]
+            },
+            ThrowInsult(insult) => {
+                if self.insults.retort(insult[]).is_none() {
+                    return protocol_error("not an insult");
+                }
+                let retort = match self.duel {
+                    Some(ref d) if !d.is_over() && d.attacker() == Player =>
+                        self.ai.retort(&*self.insults, insult[], rng).to_string(),
+                    _ => return protocol_error("not your serve"),
+                };
+                self.play(insult, retort, rng)
+            },
+            SendRetort(retort) => match self.pending.take() {
+                Some(insult) => self.play(insult, retort, rng),
+                None => protocol_error("no insult to retort"),
+            },
+        }
+    }
+
+    /// Play a JSON text message, replying with JSON text.
+    pub fn handle_text<R: Rng>(&mut self, text: &str, rng: &mut R) -> Vec<String> {
+        let replies = match json::from_str(text).ok().and_then(|x| ClientMessage::from_json(&x)) {
+            Some(msg) => self.handle(msg, rng),
+            None => protocol_error("invalid message"),
+        };
+        replies.iter().map(|x| x.to_json().to_string()).collect()
+    }
+
+    fn play<R: Rng>(&mut self, insult: String, retort: String, rng: &mut R) -> Vec<ServerMessage> {
+        let (exchange, score, winner, attacker) = {
+            let duel = self.duel.as_mut().unwrap();
+            let exchange = duel.exchange(insult[], retort[]);
+            (exchange, duel.score().clone(), duel.winner(), duel.attacker())
+        };
+        let point = match exchange {
+            Point(side) => Some(side),
+            _ => None,
+        };
+
+        let mut res = vec![Exchanged(Report {
+            insult: insult,
+            retort: retort,
+            point: point,
+            score: score,
+        })];
+        match winner {
+            Some(side) => res.push(DuelOver(side)),
+            None if attacker == Opponent => {
+                let insult = self.ai.insult(&*self.insults, rng).to_string();
+                self.pending = Some(insult.clone());
+                res.push(OpponentInsult(insult));
+            },
+            None => (),
+        }
+        res
+    }
+}
+
+fn invalid(desc: &'static str) -> IoError {
+    IoError { kind: InvalidInput, desc: desc, detail: None }
+}
+
+/// `Sec-WebSocket-Accept` for the client's `Sec-WebSocket-Key`.
+pub fn accept_key(key: &str) -> String {
+    sha1::digest(format!("{}{}", key, GUID).as_bytes())[].to_base64(STANDARD)
+}
+
+/// A frame of a message.
+#[deriving(Clone, PartialEq, Show)]
+pub struct Frame {
+    pub opcode: u8,
+    pub payload: Vec<u8>,
+}
+
+/// Read a frame, unmasking the payload.
+pub fn read_frame<R: Reader>(r: &mut R) -> IoResult<Frame> {
+    let b0 = try!(r.read_u8());
+    let b1 = try!(r.read_u8());
+    let len = match b1 & 0x7f {
+        126 => try!(r.read_be_u16()) as u64,
+        127 => try!(r.read_be_u64()),
+        n => n as u64,
+    };
+    if len > MAX_FRAME {
+        return Err(invalid("frame too large"));
+    }
+    let mask = if b1 & 0x80 != 0 { Some(try!(r.read_exact(4))) } else { None };
+    let mut payload = try!(r.read_exact(len as uint));
+    if let Some(mask) = mask {
+        for (i, b) in payload.iter_mut().enumerate() {
+            *b = *b ^ mask[i % 4];
+        }
+    }
+    Ok(Frame { opcode: b0 & 0x0f, payload: payload })
+}
+
+/// Write a final frame, masked if `mask` is given as clients must.
+pub fn write_frame<W: Writer>(w: &mut W, opcode: u8, payload: &[u8],
+                              mask: Option<[u8, ..4]>) -> IoResult<()> {
+    try!(w.write_u8(0x80 | opcode));
+    let mask_bit = if mask.is_some() { 0x80 } else { 0 };
+    if payload.len() < 126 {
+        try!(w.write_u8(mask_bit | payload.len() as u8));
+    } else if payload.len() <= 0xffff {
+        try!(w.write_u8(mask_bit | 126));
+        try!(w.write_be_u16(payload.len() as u16));
+    } else {
+        try!(w.write_u8(mask_bit | 127));
+        try!(w.write_be_u64(payload.len() as u64));
+    }
+    match mask {
+        Some(mask) => {
+            try!(w.write(mask[]));
+            let masked: Vec<u8> = payload.iter().enumerate().map(|(i, b)| *b ^ mask[i % 4]).collect();
+            w.write(masked[])
+        },
+        None => w.write(payload),
+    }
+}
+
+/// Accept the WebSocket upgrade in `req` and play duels until the client hangs up.
+pub fn serve_duel(mut stream: TcpStream, req: &Request, insults: Arc<Insults>) -> IoResult<()> {
+    let key = match req.header("Sec-WebSocket-Key") {
+        Some(x) => x,
+        None => {
+            let res = Response { status: 400, body: r#"{"error":"missing Sec-WebSocket-Key"}"#.to_string() };
+            return write_response(&mut stream, &res);
+        },
+    };
+    try!(write!(&mut stream, "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\n\
+                              Connection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+                accept_key(key)));
+
+    let mut session = DuelSession::new(insults, Ai::default());
+    let mut rng = rand::task_rng();
+    loop {
+        let frame = try!(read_frame(&mut stream));
+        match frame.opcode {
+            TEXT => {
+                let text = String::from_utf8_lossy(frame.payload[]).into_string();
+                for reply in session.handle_text(text[], &mut rng).iter() {
+                    try!(write_frame(&mut stream, TEXT, reply.as_bytes(), None));
+                }
+            },
+            PING => try!(write_frame(&mut stream, PONG, frame.payload[], None)),
+            CLOSE => return write_frame(&mut stream, CLOSE, [], None),
+            _ => (),
+        }
+    }
+}
+
+/// The client side of the protocol.
+pub struct DuelClient {
+    stream: TcpStream,
+}
+
+impl DuelClient {
+    /// Connect to the duel endpoint of a `serve` server.
+    pub fn connect(host: &str, port: u16) -> IoResult<DuelClient> {
+        let mut stream = try!(TcpStream::connect(host, port));
+        let nonce: Vec<u8> = rand::task_rng().gen_iter::<u8>().take(16).collect();
+        let key = nonce[].to_base64(STANDARD);
+        try!(write!(&mut stream, "GET /duel HTTP/1.1\r\nHost: {}:{}\r\nUpgrade: websocket\r\n\
+                                  Connection: Upgrade\r\nSec-WebSocket-Key: {}\r\n\
+                                  Sec-WebSocket-Version: 13\r\n\r\n", host, port, key));
+
+        let mut head = Vec::new();
+        while !head[].ends_with(b"\r\n\r\n") {
+            head.push(try!(stream.read_u8()));
+        }
+        let head = match str::from_utf8(head[]) {
+            Some(x) => x.to_string(),
+            None => return Err(invalid("invalid handshake")),
+        };
+        let accepted = head[].lines().next().map_or(false, |x| x.contains(" 101 ")) &&
+            head[].contains(accept_key(key[])[]);
+        if !accepted {
+            return Err(invalid("server refused the WebSocket upgrade"));
+        }
+        Ok(DuelClient { stream: stream })
+    }
+
+    pub fn send(&mut self, msg: &ClientMessage) -> IoResult<()> {
+        let mut mask = [0u8, ..4];
+        rand::task_rng().fill_bytes(mask[mut]);
+        write_frame(&mut self.stream, TEXT, msg.to_json().to_string().as_bytes(), Some(mask))
+    }
+
+    /// The next message from the server.
+    pub fn recv(&mut self) -> IoResult<ServerMessage> {
+        loop {
+            let frame = try!(read_frame(&mut self.stream));
+            match frame.opcode {
+                TEXT => {
+                    let msg = str::from_utf8(frame.payload[])
+                        .and_then(|x| json::from_str(x).ok())
+                        .and_then(|x| ServerMessage::from_json(&x));
+                    return match msg {
+                        Some(x) => Ok(x),
+                        None => Err(invalid("invalid message")),
+                    };
+                },
+                PING => try!(write_frame(&mut self.stream, PONG, frame.payload[], Some([0, 0, 0, 0]))),
+                CLOSE => return Err(IoError { kind: EndOfFile, desc: "closed", detail: None }),
+                _ => (),
+            }
+        }
+    }
+
+    pub fn close(mut self) -> IoResult<()> {
+        write_frame(&mut self.stream, CLOSE, [], Some([0, 0, 0, 0]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{MemReader, MemWriter};
+    use std::rand;
+
+    use duel::{Ai, Score, Opponent};
+    use insults::Insults;
+    use super::*;
+
+    #[test]
+    fn test_session() {
+        let mut session = DuelSession::new(Insults::new("insults.json").into_shared(), Ai::new(1.0));
+        let mut rng = rand::task_rng();
+        let insult = "You fight like a dairy farmer.";
+        let retort = "How appropriate. You fight like a cow.";
+
+        assert_eq!(session.handle(SendRetort(retort.to_string()), &mut rng),
+                   vec![ProtocolError("no insult to retort".to_string())]);
+        assert_eq!(session.handle_text(r#"{"type": "start", "first_to": 1}"#, &mut rng),
+                   vec![r#"{"first_to":1,"type":"started"}"#.to_string()]);
+        assert_eq!(session.handle(ThrowInsult("Arr!".to_string()), &mut rng),
+                   vec![ProtocolError("not an insult".to_string())]);
+        assert_eq!(session.handle(ThrowInsult(insult.to_string()), &mut rng), vec![
+            Exchanged(Report {
+                insult: insult.to_string(),
+                retort: retort.to_string(),
+                point: Some(Opponent),
+                score: Score { player: 0, opponent: 1 },
+            }),
+            DuelOver(Opponent),
+        ]);
+
+        let msg = DuelOver(Opponent);
+        assert_eq!(ServerMessage::from_json(&msg.to_json()), Some(msg));
+        assert_eq!(accept_key("dGhlIHNhbXBsZSBub25jZQ==")[], "s3pPLMBiTxaQ9kYGzzhZRbK+xOo=");
+
+        let mut w = MemWriter::new();
+        write_frame(&mut w, TEXT, b"En garde!", Some([1, 2, 3, 4])).unwrap();
+        let frame = read_frame(&mut MemReader::new(w.unwrap())).unwrap();
+        assert_eq!(frame, Frame { opcode: TEXT, payload: b"En garde!".to_vec() });
+    }
+}